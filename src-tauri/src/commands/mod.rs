@@ -315,27 +315,19 @@ pub async fn save_config(
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
     config: AppConfig,
 ) -> Result<(), String> {
+    // 校验失败时不写盘 (按环境变量覆盖后的实际运行值校验)
+    let mut effective = config.proxy.clone();
+    effective.apply_env_overrides(std::env::vars());
+    crate::commands::proxy::validate_proxy_config(&effective)?;
+
     modules::save_app_config(&config)?;
 
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
-    // 热更新正在运行的服务
-    let instance_lock = proxy_state.instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        // 更新模型映射
-        instance.axum_server.update_mapping(&config.proxy).await;
-        // 更新上游代理
-        instance
-            .axum_server
-            .update_proxy(config.proxy.upstream_proxy.clone())
-            .await;
-        // 更新安全策略 (auth)
-        instance.axum_server.update_security(&config.proxy).await;
-        // 更新 z.ai 配置
-        instance.axum_server.update_zai(&config.proxy).await;
-        // 更新实验性配置
-        instance.axum_server.update_experimental(&config.proxy).await;
+    // 热更新正在运行的服务 (与 update_proxy_config 同一路径；需重启的字段在下次启动时生效)
+    crate::commands::proxy::apply_hot_proxy_config(&proxy_state, config.proxy.clone()).await?;
+    if let Some(instance) = proxy_state.instance.read().await.as_ref() {
         // 更新熔断配置
         instance.token_manager.update_circuit_breaker_config(config.circuit_breaker.clone()).await;
         tracing::debug!("已同步热更新反代服务配置");
@@ -462,6 +454,96 @@ pub async fn import_custom_db(app: tauri::AppHandle, path: String) -> Result<Acc
     Ok(account)
 }
 
+/// 导入账号时验证凭据使用的上游客户端 (反代服务运行中时复用其客户端)
+async fn import_upstream_client(
+    proxy_state: &crate::commands::proxy::ProxyServiceState,
+) -> Result<std::sync::Arc<crate::proxy::upstream::client::UpstreamClient>, String> {
+    if let Some(instance) = proxy_state.instance.read().await.as_ref() {
+        return Ok(instance.token_manager.upstream_client());
+    }
+    let config = modules::config::load_app_config()?;
+    Ok(std::sync::Arc::new(crate::proxy::upstream::client::UpstreamClient::new(Some(
+        config.proxy.upstream_proxy,
+    ))))
+}
+
+/// 从 CSV 批量导入账号 (列: email,access_token,refresh_token,project_id)
+#[tauri::command]
+pub async fn import_tokens_from_csv(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    file_path: String,
+) -> Result<modules::migration::ImportReport, String> {
+    let client = import_upstream_client(&proxy_state).await?;
+    let report = modules::migration::import_from_csv(&file_path, &client).await?;
+    modules::logger::log_info(&format!(
+        "CSV 导入完成: 新增 {}，更新 {}，失败 {}",
+        report.imported,
+        report.updated,
+        report.failures.len()
+    ));
+
+    // 同步到运行中的反代服务（如果已启动）
+    let instance_lock = proxy_state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        let _ = instance.token_manager.reload_all_accounts().await;
+    }
+
+    Ok(report)
+}
+
+/// 导出账号池到加密备份文件 (Argon2id + AES-256-GCM)
+/// 反代服务运行中时导出内存中的账号池，否则从磁盘加载
+#[tauri::command]
+pub async fn export_tokens(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    output_path: String,
+    passphrase: String,
+) -> Result<(), String> {
+    let running = proxy_state.instance.read().await.as_ref().map(|i| i.token_manager.clone());
+    let token_manager = match running {
+        Some(tm) => tm,
+        None => {
+            let tm = std::sync::Arc::new(crate::proxy::TokenManager::new(modules::account::get_data_dir()?));
+            tm.load_accounts().await?;
+            tm
+        }
+    };
+    let tokens: Vec<modules::token_backup::ExportedToken> =
+        token_manager.snapshot_tokens().iter().map(Into::into).collect();
+    let count = tokens.len();
+
+    tokio::task::spawn_blocking(move || modules::token_backup::export_to_file(&output_path, &tokens, &passphrase))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))??;
+    modules::logger::log_info(&format!("已导出 {} 个账号到加密备份", count));
+    Ok(())
+}
+
+/// 从加密备份文件导入账号
+#[tauri::command]
+pub async fn import_tokens_from_encrypted_json(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    path: String,
+    passphrase: String,
+) -> Result<modules::migration::ImportReport, String> {
+    let client = import_upstream_client(&proxy_state).await?;
+    let report = modules::token_backup::import_from_file(&path, &passphrase, &client).await?;
+    modules::logger::log_info(&format!(
+        "加密备份导入完成: 新增 {}，更新 {}，失败 {}",
+        report.imported,
+        report.updated,
+        report.failures.len()
+    ));
+
+    // 同步到运行中的反代服务（如果已启动）
+    let instance_lock = proxy_state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        let _ = instance.token_manager.reload_all_accounts().await;
+    }
+
+    Ok(report)
+}
+
 #[tauri::command]
 pub async fn sync_account_from_db(app: tauri::AppHandle) -> Result<Option<Account>, String> {
     // 1. 获取 DB 中的 Refresh Token
@@ -122,11 +122,13 @@ pub async fn internal_start_proxy_service(
     
     let token_manager = Arc::new(TokenManager::new(accounts_dir));
     token_manager.start_auto_cleanup();
+    token_manager.start_budget_reset_task();
     token_manager.update_sticky_config(config.scheduling.clone()).await;
     
     // [NEW] 加载熔断配置 (从主配置加载)
     let app_config = crate::modules::config::load_app_config().unwrap_or_else(|_| crate::models::AppConfig::new());
     token_manager.update_circuit_breaker_config(app_config.circuit_breaker).await;
+    token_manager.update_token_circuit_breaker_config(config.token_circuit_breaker.clone());
 
     // 🆕 [FIX #820] 恢复固定账号模式设置
     if let Some(ref account_id) = config.preferred_account_id {
@@ -211,28 +213,52 @@ pub async fn ensure_admin_server(
     // 默认空 TokenManager 用于管理界面
     let app_data_dir = crate::modules::account::get_data_dir()?;
     let token_manager = Arc::new(TokenManager::new(app_data_dir));
+    token_manager.update_token_circuit_breaker_config(config.token_circuit_breaker.clone());
     // [NEW] 加载账号数据，否则管理界面统计为 0
     let _ = token_manager.load_accounts().await;
 
+    // 审计日志 (仅在配置了 log_file 时启用)
+    let audit_logger = config.log_file.clone().map(|path| {
+        tracing::info!("审计日志已启用: {}", path.display());
+        Arc::new(crate::proxy::audit_log::AuditLogger::new(path, config.log_max_bytes))
+    });
+
+    // 审计数据库 (仅在配置了 db_path 时启用，初始化失败不影响服务启动)
+    let audit_db = config.db_path.clone().and_then(|path| {
+        match crate::proxy::audit_db::AuditDb::new(path.clone()) {
+            Ok(db) => {
+                tracing::info!("审计数据库已启用: {}", path.display());
+                Some(Arc::new(db))
+            }
+            Err(e) => {
+                tracing::error!("审计数据库初始化失败 ({}): {}", path.display(), e);
+                None
+            }
+        }
+    });
+
     let (axum_server, server_handle) =
         match crate::proxy::AxumServer::start(
-            config.get_bind_address().to_string(),
-            config.port,
+            &config,
             token_manager,
-            config.custom_mapping.clone(),
-            config.request_timeout,
-            config.upstream_proxy.clone(),
-            crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
-            config.zai.clone(),
             monitor,
-            config.experimental.clone(),
             integration.clone(),
             cloudflared_state,
+            audit_logger,
+            audit_db,
         ).await {
             Ok((server, handle)) => (server, handle),
+            Err(crate::proxy::server::StartError::InvalidConfig(errors)) => {
+                return Err(crate::proxy::config::describe_config_errors(&errors))
+            }
             Err(e) => return Err(format!("启动管理服务器失败: {}", e)),
         };
 
+    // 当前配置方案名称 (健康检查展示)
+    if let Ok(app_config) = crate::modules::config::load_app_config() {
+        axum_server.set_active_profile(app_config.active_profile_name()).await;
+    }
+
     *admin_lock = Some(AdminServerInstance {
         axum_server,
         server_handle,
@@ -241,6 +267,15 @@ pub async fn ensure_admin_server(
     Ok(())
 }
 
+/// 应用退出时停止管理服务器，等待在途请求排空 (最长 shutdown_timeout_secs)
+pub async fn shutdown_admin_server(state: &ProxyServiceState) {
+    let admin = state.admin_server.write().await.take();
+    if let Some(admin) = admin {
+        admin.axum_server.stop();
+        let _ = admin.server_handle.await;
+    }
+}
+
 /// 停止反代服务
 #[tauri::command]
 pub async fn stop_proxy_service(
@@ -321,6 +356,18 @@ pub async fn get_proxy_stats(
     }
 }
 
+/// 获取仪表盘实时统计 (与 GET /v1/stats 相同，直接读取服务器共享计数器)
+#[tauri::command]
+pub async fn get_proxy_dashboard_stats(
+    state: State<'_, ProxyServiceState>,
+) -> Result<crate::proxy::stats::DashboardStats, String> {
+    let instance_lock = state.instance.read().await;
+    match instance_lock.as_ref() {
+        Some(instance) => Ok(instance.axum_server.dashboard_stats().await),
+        None => Ok(Default::default()),
+    }
+}
+
 /// 获取反代请求日志
 #[tauri::command]
 pub async fn get_proxy_logs(
@@ -450,6 +497,178 @@ pub fn generate_api_key() -> String {
     format!("sk-{}", uuid::Uuid::new_v4().simple())
 }
 
+/// 轮换 API Key：仅持久化哈希，并立即对运行中的服务生效
+/// 未指定 new_key 时自动生成；返回新 key 明文，这是前端唯一一次能拿到它的机会
+#[tauri::command]
+pub async fn rotate_api_key(
+    state: State<'_, ProxyServiceState>,
+    new_key: Option<String>,
+) -> Result<String, String> {
+    let (new_key, config) = persist_rotated_api_key(new_key)?;
+
+    if let Some(instance) = state.instance.read().await.as_ref() {
+        instance.axum_server.update_security(&config.proxy).await;
+    }
+    if let Some(admin) = state.admin_server.read().await.as_ref() {
+        admin.axum_server.update_security(&config.proxy).await;
+    }
+    tracing::info!("API Key 已轮换");
+    Ok(new_key)
+}
+
+/// 校验 (或生成) 新 key，把哈希写入配置文件，返回明文与保存后的配置
+pub(crate) fn persist_rotated_api_key(
+    new_key: Option<String>,
+) -> Result<(String, crate::models::AppConfig), String> {
+    let new_key = match new_key {
+        Some(key) => key.trim().to_string(),
+        None => generate_api_key(),
+    };
+    if new_key.len() < 4 {
+        return Err("API Key 长度至少为 4 个字符".to_string());
+    }
+
+    let mut config = crate::modules::config::load_app_config()?;
+    config.proxy.api_key_hash = crate::proxy::api_key_hash::hash_api_key(&new_key);
+    config.proxy.api_key.clear();
+    crate::modules::config::save_app_config(&config)?;
+    Ok((new_key, config))
+}
+
+/// 热更新反代配置
+/// 服务运行中修改了需重启的字段 (如 port、tls_cert_path) 时返回错误且不做任何修改；
+/// 其余字段立即生效并持久化
+#[tauri::command]
+pub async fn update_proxy_config(
+    state: State<'_, ProxyServiceState>,
+    new_config: ProxyConfig,
+) -> Result<(), String> {
+    apply_proxy_config(&state, new_config.clone()).await?;
+
+    let mut app_config = crate::modules::config::load_app_config()?;
+    app_config.proxy = new_config;
+    crate::modules::config::save_app_config(&app_config)?;
+    tracing::info!("反代配置已热更新");
+    Ok(())
+}
+
+/// 切换到指定的配置方案并设为启动默认方案
+/// 可热更新的字段立即生效；需重启的字段 (如 port) 写入配置，在下次启动反代服务时生效
+#[tauri::command]
+pub async fn switch_profile(
+    state: State<'_, ProxyServiceState>,
+    name: String,
+) -> Result<(), String> {
+    let mut app_config = crate::modules::config::load_app_config()?;
+    app_config.select_profile(&name)?;
+    apply_hot_proxy_config(&state, app_config.proxy.clone()).await?;
+
+    crate::modules::config::save_app_config(&app_config)?;
+    set_active_profile(&state, Some(name.clone())).await;
+    tracing::info!("已切换到配置方案: {}", name);
+    Ok(())
+}
+
+/// 更新运行中服务记录的配置方案名称
+pub async fn set_active_profile(state: &ProxyServiceState, profile: Option<String>) {
+    if let Some(admin) = state.admin_server.read().await.as_ref() {
+        admin.axum_server.set_active_profile(profile).await;
+    }
+}
+
+/// 校验配置，将全部错误合并为一条可读的错误信息
+pub(crate) fn validate_proxy_config(config: &ProxyConfig) -> Result<(), String> {
+    config
+        .validate()
+        .map_err(|errors| crate::proxy::config::describe_config_errors(&errors))
+}
+
+/// 应用配置中可热更新的字段 (不写盘)；需重启的字段只记录警告，下次启动反代服务时生效
+pub async fn apply_hot_proxy_config(state: &ProxyServiceState, mut config: ProxyConfig) -> Result<(), String> {
+    config.apply_env_overrides(std::env::vars());
+    let running = state.instance.read().await.as_ref().map(|i| i.config.clone());
+    let applied = match running {
+        Some(running) => {
+            let blocked = running.restart_required_changes(&config);
+            if !blocked.is_empty() {
+                tracing::warn!("以下配置需重启反代服务后生效: {}", blocked.join(", "));
+            }
+            running.with_hot_fields_from(&config)
+        }
+        None => config,
+    };
+    apply_proxy_config(state, applied).await
+}
+
+/// 将配置应用到运行中的服务 (不写盘)，供 update_proxy_config、switch_profile、save_config 与配置文件监听共用
+/// 环境变量覆盖先于比较生效 (与运行中的配置一致)；配置校验失败时拒绝应用，运行中的服务保持不变
+pub async fn apply_proxy_config(state: &ProxyServiceState, mut new_config: ProxyConfig) -> Result<(), String> {
+    new_config.apply_env_overrides(std::env::vars());
+    validate_proxy_config(&new_config)?;
+
+    let mut instance_lock = state.instance.write().await;
+    if let Some(instance) = instance_lock.as_mut() {
+        let blocked = instance.config.restart_required_changes(&new_config);
+        if !blocked.is_empty() {
+            return Err(format!("以下配置需重启反代服务后生效: {}", blocked.join(", ")));
+        }
+
+        instance.axum_server.apply_config(&new_config).await;
+        instance
+            .token_manager
+            .update_sticky_config(new_config.scheduling.clone())
+            .await;
+        instance
+            .token_manager
+            .update_token_circuit_breaker_config(new_config.token_circuit_breaker.clone());
+        instance
+            .token_manager
+            .set_preferred_account(new_config.preferred_account_id.clone())
+            .await;
+        if let Some(monitor) = state.monitor.read().await.as_ref() {
+            if monitor.is_enabled() != new_config.enable_logging {
+                monitor.set_enabled(new_config.enable_logging);
+            }
+        }
+        instance.config = new_config.clone();
+    }
+    if let Some(admin) = state.admin_server.read().await.as_ref() {
+        admin.axum_server.apply_config(&new_config).await;
+    }
+    Ok(())
+}
+
+/// 预估请求 Token 数 (调用上游 countTokens，需反代服务运行中)
+#[tauri::command]
+pub async fn count_request_tokens(
+    state: State<'_, ProxyServiceState>,
+    request: crate::proxy::mappers::openai::OpenAIRequest,
+) -> Result<u32, String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+
+    let mut request = request;
+    // 与请求处理器相同：先解析别名再映射，保证使用与实际请求一致的模型
+    request.model = crate::proxy::common::model_mapping::resolve_request_model(
+        &request.model,
+        &instance.config.model_aliases,
+        &instance.config.custom_mapping,
+    )?;
+
+    let (access_token, project_id, _email, _wait_ms) = instance
+        .token_manager
+        .get_token("text", false, None, &request.model)
+        .await
+        .map_err(|e| format!("获取 Token 失败: {}", e))?;
+
+    instance
+        .axum_server
+        .upstream
+        .count_tokens(&request, &access_token, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// 重新加载账号（当主应用添加/删除账号时调用）
 #[tauri::command]
 pub async fn reload_proxy_accounts(
@@ -724,3 +943,21 @@ pub async fn clear_all_proxy_rate_limits(
         Err("服务未运行".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_proxy_config_rejects_invalid_config() {
+        let state = ProxyServiceState::new();
+        let config = ProxyConfig {
+            port: 80,
+            ..ProxyConfig::default()
+        };
+        let err = apply_proxy_config(&state, config).await.unwrap_err();
+        assert!(err.starts_with("配置无效"), "{}", err);
+
+        assert!(apply_proxy_config(&state, ProxyConfig::default()).await.is_ok());
+    }
+}
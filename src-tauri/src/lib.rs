@@ -104,7 +104,11 @@ pub fn run() {
                     info!("--------------------------------------------------");
                     info!("🚀 Headless mode proxy service starting...");
                     info!("📍 Port: {}", config.proxy.port);
-                    info!("🔑 Current API Key: {}", config.proxy.api_key);
+                    if config.proxy.api_key.is_empty() {
+                        info!("🔑 API Key: (stored as hash; set ABV_API_KEY to override)");
+                    } else {
+                        info!("🔑 Current API Key: {}", config.proxy.api_key);
+                    }
                     if let Some(ref pwd) = config.proxy.admin_password {
                         info!("🔐 Web UI Password: {}", pwd);
                     } else {
@@ -137,9 +141,10 @@ pub fn run() {
                 }
             }
             
-            // Wait for Ctrl-C
-            tokio::signal::ctrl_c().await.ok();
-            info!("Headless mode shutting down");
+            // 收到 SIGINT / SIGTERM 后停止服务器，排空在途请求再退出
+            let signal = crate::proxy::inflight::shutdown_signal().await;
+            info!("Received {}, headless mode shutting down", signal);
+            commands::proxy::shutdown_admin_server(&proxy_state).await;
         });
         return;
     }
@@ -233,6 +238,17 @@ pub fn run() {
             // Start smart scheduler
             let scheduler_state = app.handle().state::<commands::proxy::ProxyServiceState>();
             modules::scheduler::start_scheduler(Some(app.handle().clone()), scheduler_state.inner().clone());
+
+            // 监听配置文件，外部修改后自动热更新
+            modules::config::start_config_watcher(scheduler_state.inner().clone());
+
+            // 收到 SIGINT / SIGTERM 时走正常退出流程 (RunEvent::Exit 中排空在途请求)
+            let exit_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let signal = crate::proxy::inflight::shutdown_signal().await;
+                info!("Received {}, exiting", signal);
+                exit_handle.exit(0);
+            });
             
             // [PHASE 1] 已整合至 Axum 端口 (8045)，不再单独启动 19527 端口
             info!("Management API integrated into main proxy server (port 8045)");
@@ -287,6 +303,9 @@ pub fn run() {
             commands::import_v1_accounts,
             commands::import_from_db,
             commands::import_custom_db,
+            commands::import_tokens_from_csv,
+            commands::export_tokens,
+            commands::import_tokens_from_encrypted_json,
             commands::sync_account_from_db,
             commands::save_text_file,
             commands::read_text_file,
@@ -308,6 +327,7 @@ pub fn run() {
             commands::proxy::stop_proxy_service,
             commands::proxy::get_proxy_status,
             commands::proxy::get_proxy_stats,
+            commands::proxy::get_proxy_dashboard_stats,
             commands::proxy::get_proxy_logs,
             commands::proxy::get_proxy_logs_paginated,
             commands::proxy::get_proxy_log_detail,
@@ -319,8 +339,12 @@ pub fn run() {
             commands::proxy::set_proxy_monitor_enabled,
             commands::proxy::clear_proxy_logs,
             commands::proxy::generate_api_key,
+            commands::proxy::rotate_api_key,
+            commands::proxy::count_request_tokens,
             commands::proxy::reload_proxy_accounts,
             commands::proxy::update_model_mapping,
+            commands::proxy::update_proxy_config,
+            commands::proxy::switch_profile,
             commands::proxy::fetch_zai_models,
             commands::proxy::get_proxy_scheduling_config,
             commands::proxy::update_proxy_scheduling_config,
@@ -363,6 +387,12 @@ pub fn run() {
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app_handle, event| {
+            // 应用退出时优雅停止管理服务器
+            if matches!(event, tauri::RunEvent::Exit) {
+                let state = app_handle.state::<commands::proxy::ProxyServiceState>();
+                tauri::async_runtime::block_on(commands::proxy::shutdown_admin_server(&state));
+            }
+
             // Handle macOS dock icon click to reopen window
             #[cfg(target_os = "macos")]
             if let tauri::RunEvent::Reopen { .. } = event {
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use crate::proxy::ProxyConfig;
 
 /// Application configuration
@@ -25,6 +26,10 @@ pub struct AppConfig {
     pub pinned_quota_models: PinnedQuotaModelsConfig, // [NEW] Pinned quota models list
     #[serde(default)]
     pub circuit_breaker: CircuitBreakerConfig, // [NEW] Circuit breaker configuration
+    #[serde(default)]
+    pub profiles: HashMap<String, ProxyConfig>, // [NEW] Named proxy config profiles
+    #[serde(default)]
+    pub default_profile: String, // [NEW] Profile used at startup (empty = flat `proxy` config)
 }
 
 /// Scheduled warmup configuration
@@ -181,6 +186,50 @@ impl AppConfig {
             quota_protection: QuotaProtectionConfig::default(),
             pinned_quota_models: PinnedQuotaModelsConfig::default(),
             circuit_breaker: CircuitBreakerConfig::default(),
+            profiles: HashMap::new(),
+            default_profile: String::new(),
+        }
+    }
+
+    /// Name of the profile currently backing `proxy` (None = flat config)
+    pub fn active_profile_name(&self) -> Option<String> {
+        if self.profiles.contains_key(&self.default_profile) {
+            Some(self.default_profile.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Load `default_profile` into `proxy`; keeps the flat config when it is unset or unknown.
+    /// Returns false only when a profile is named but missing
+    pub fn apply_default_profile(&mut self) -> bool {
+        if self.default_profile.is_empty() {
+            return true;
+        }
+        match self.profiles.get(&self.default_profile) {
+            Some(profile) => {
+                self.proxy = profile.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switch to the named profile, making it the startup default
+    pub fn select_profile(&mut self, name: &str) -> Result<(), String> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| format!("profile_not_found: {}", name))?;
+        self.proxy = profile.clone();
+        self.default_profile = name.to_string();
+        Ok(())
+    }
+
+    /// Write edits made to `proxy` back into the active profile so they survive the next load
+    pub fn sync_active_profile(&mut self) {
+        if let Some(profile) = self.profiles.get_mut(&self.default_profile) {
+            *profile = self.proxy.clone();
         }
     }
 }
@@ -190,3 +239,66 @@ impl Default for AppConfig {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_profile_selection() {
+        let staging = ProxyConfig {
+            port: 9100,
+            ..ProxyConfig::default()
+        };
+        let json = serde_json::json!({
+            "language": "en",
+            "theme": "system",
+            "auto_refresh": true,
+            "refresh_interval": 15,
+            "auto_sync": false,
+            "sync_interval": 5,
+            "default_export_path": null,
+            "antigravity_executable": null,
+            "antigravity_args": null,
+            "profiles": { "staging": staging },
+            "default_profile": "staging",
+        });
+        let mut config: AppConfig = serde_json::from_value(json).unwrap();
+        assert!(config.apply_default_profile());
+        assert_eq!(config.proxy.port, 9100);
+        assert_eq!(config.active_profile_name().as_deref(), Some("staging"));
+
+        // Unknown profile falls back to the flat config
+        config.default_profile = "missing".to_string();
+        config.proxy = ProxyConfig::default();
+        assert!(!config.apply_default_profile());
+        assert_eq!(config.proxy.port, ProxyConfig::default().port);
+        assert_eq!(config.active_profile_name(), None);
+    }
+
+    #[test]
+    fn test_flat_config_without_profiles() {
+        let config: AppConfig = serde_json::from_value(serde_json::to_value(AppConfig::new()).unwrap()).unwrap();
+        assert!(config.profiles.is_empty());
+        assert_eq!(config.active_profile_name(), None);
+    }
+
+    #[test]
+    fn test_select_profile_and_sync() {
+        let mut config = AppConfig::new();
+        let prod = ProxyConfig {
+            port: 9200,
+            ..ProxyConfig::default()
+        };
+        config.profiles.insert("prod".to_string(), prod);
+
+        assert!(config.select_profile("dev").is_err());
+        config.select_profile("prod").unwrap();
+        assert_eq!(config.proxy.port, 9200);
+        assert_eq!(config.default_profile, "prod");
+
+        config.proxy.model_aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+        config.sync_active_profile();
+        assert_eq!(config.profiles["prod"].model_aliases.len(), 1);
+    }
+}
@@ -20,7 +20,7 @@ impl AccountService {
         let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
 
         // 3. 获取项目 ID (尝试)
-        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token)
+        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token, &configured_user_agent())
             .await
             .ok();
 
@@ -109,7 +109,7 @@ impl AccountService {
         })?;
 
         let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
-        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token)
+        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_res.access_token, &configured_user_agent())
             .await
             .ok();
 
@@ -134,3 +134,10 @@ impl AccountService {
         Ok(account)
     }
 }
+
+/// 配置的上游 User-Agent (读取失败时为默认值)
+fn configured_user_agent() -> String {
+    modules::config::load_app_config()
+        .map(|config| config.proxy.user_agent)
+        .unwrap_or_else(|_| crate::proxy::config::DEFAULT_USER_AGENT.to_string())
+}
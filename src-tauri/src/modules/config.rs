@@ -1,4 +1,6 @@
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use serde_json;
 
 use crate::models::AppConfig;
@@ -6,16 +8,36 @@ use super::account::get_data_dir;
 
 const CONFIG_FILE: &str = "gui_config.json";
 
-/// Load application configuration
+/// 配置文件修改事件的防抖间隔
+const CONFIG_WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// 配置文件路径
+pub fn config_file_path() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join(CONFIG_FILE))
+}
+
+/// Load application configuration (file contents plus environment overrides)
 pub fn load_app_config() -> Result<AppConfig, String> {
-    let data_dir = get_data_dir()?;
-    let config_path = data_dir.join(CONFIG_FILE);
-    
+    let mut config = read_config_file(&config_file_path()?)?;
+
+    // 环境变量覆盖 (ANTIGRAVITY_<字段名>) 在文件加载之后合并，仅启动时打印一次
+    // 覆盖只作用于运行时副本，save_app_config 写盘前会还原
+    let overridden = config.proxy.apply_env_overrides(std::env::vars());
+    if !overridden.is_empty() {
+        static LOGGED: std::sync::Once = std::sync::Once::new();
+        LOGGED.call_once(|| tracing::info!("以下配置由环境变量覆盖: {}", overridden.join(", ")));
+    }
+
+    Ok(config)
+}
+
+/// 读取配置文件 (含迁移、API Key 封存与配置方案选择，不含环境变量覆盖)
+fn read_config_file(config_path: &Path) -> Result<AppConfig, String> {
     if !config_path.exists() {
         return Ok(AppConfig::new());
     }
     
-    let content = fs::read_to_string(&config_path)
+    let content = fs::read_to_string(config_path)
         .map_err(|e| format!("failed_to_read_config_file: {}", e))?;
     
     let mut v: serde_json::Value = serde_json::from_str(&content)
@@ -64,12 +86,27 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         }
     }
 
-    let config: AppConfig = serde_json::from_value(v)
+    let mut config: AppConfig = serde_json::from_value(v)
         .map_err(|e| format!("failed_to_convert_config_after_migration: {}", e))?;
+
+    // Migrate plaintext API key to hash (plaintext must never stay on disk)
+    if config.proxy.seal_api_key() {
+        modified = true;
+    }
+    for profile in config.profiles.values_mut() {
+        if profile.seal_api_key() {
+            modified = true;
+        }
+    }
     
     // If migration occurred, auto-save once to clean up the file
     if modified {
-        let _ = save_app_config(&config);
+        let _ = write_config_file_at(config_path, &config);
+    }
+
+    // 指定了配置方案时以方案替换平铺的 proxy 配置 (未找到则保留平铺配置)
+    if !config.apply_default_profile() {
+        tracing::warn!("配置方案 {} 不存在，使用平铺的 proxy 配置", config.default_profile);
     }
 
     Ok(config)
@@ -77,12 +114,179 @@ pub fn load_app_config() -> Result<AppConfig, String> {
 
 /// Save application configuration
 pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
-    let data_dir = get_data_dir()?;
-    let config_path = data_dir.join(CONFIG_FILE);
-    
-    let content = serde_json::to_string_pretty(config)
+    // 环境变量覆盖的值不写入文件，保留文件中原有的值
+    let mut config = config.clone();
+    // 文件不可读时以默认值为准
+    let persisted = config_file_path()
+        .and_then(|path| read_config_file(&path))
+        .unwrap_or_else(|_| AppConfig::new());
+    config.proxy.strip_env_overrides(&persisted.proxy, std::env::vars());
+    write_config_file(&config)
+}
+
+fn write_config_file(config: &AppConfig) -> Result<(), String> {
+    write_config_file_at(&config_file_path()?, config)
+}
+
+fn write_config_file_at(config_path: &Path, config: &AppConfig) -> Result<(), String> {
+    // Only the API key hash is persisted
+    let mut config = config.clone();
+    config.sync_active_profile();
+    config.proxy.seal_api_key();
+    for profile in config.profiles.values_mut() {
+        profile.seal_api_key();
+    }
+
+    let content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
     
-    fs::write(&config_path, content)
+    fs::write(config_path, content)
         .map_err(|e| format!("failed_to_save_config: {}", e))
 }
+
+/// 监听配置文件 (notify 文件系统事件 + 防抖)，内容变化且解析成功时回调
+/// 文件无效时记录错误并保留旧配置，直到下一次修改
+pub async fn watch_config_file<F, Fut>(path: PathBuf, debounce: Duration, mut on_change: F)
+where
+    F: FnMut(AppConfig) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        tracing::error!("配置文件路径无效，跳过监听: {}", path.display());
+        return;
+    };
+    let file_name = file_name.to_os_string();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str())) {
+            let _ = tx.send(());
+        }
+    });
+    // 监听所在目录：编辑器常以"写临时文件 + 重命名"方式保存，直接监听文件会在替换后丢失事件
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            tracing::error!("创建配置文件监听失败: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+        tracing::error!("监听配置目录失败 ({}): {}", dir.display(), e);
+        return;
+    }
+    let mut last_content = fs::read_to_string(&path).ok();
+
+    while rx.recv().await.is_some() {
+        // 防抖：一次保存通常产生多个事件，等事件静默 debounce 后再读取
+        loop {
+            match tokio::time::timeout(debounce, rx.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        // 本程序保存配置同样会触发修改事件，内容未变时跳过
+        if last_content.as_deref() == Some(content.as_str()) {
+            continue;
+        }
+
+        // 与启动时相同的加载流程：明文 API Key 封存后写回文件
+        match read_config_file(&path) {
+            Ok(mut config) => {
+                last_content = fs::read_to_string(&path).ok();
+                config.proxy.apply_env_overrides(std::env::vars());
+                on_change(config).await
+            }
+            Err(e) => {
+                last_content = Some(content);
+                tracing::error!("配置文件解析失败，保留当前配置 ({}): {}", path.display(), e)
+            }
+        }
+    }
+}
+
+/// 启动配置文件监听：外部编辑器修改后自动热更新反代配置 (规则同 update_proxy_config)
+pub fn start_config_watcher(proxy_state: crate::commands::proxy::ProxyServiceState) {
+    let path = match config_file_path() {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::error!("无法定位配置文件，跳过监听: {}", e);
+            return;
+        }
+    };
+    tauri::async_runtime::spawn(async move {
+        tracing::info!("配置文件监听已启动: {}", path.display());
+        watch_config_file(path, CONFIG_WATCH_DEBOUNCE, |config| {
+            let proxy_state = proxy_state.clone();
+            async move {
+                let profile = config.active_profile_name();
+                match crate::commands::proxy::apply_proxy_config(&proxy_state, config.proxy).await {
+                    Ok(()) => {
+                        crate::commands::proxy::set_active_profile(&proxy_state, profile).await;
+                        tracing::info!("检测到配置文件修改，已热更新反代配置");
+                    }
+                    Err(e) => tracing::warn!("配置文件修改未生效: {}", e),
+                }
+            }
+        })
+        .await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(path: &PathBuf, config: &AppConfig) {
+        fs::write(path, serde_json::to_string_pretty(config).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_config_file_modification_reloads_within_500ms() {
+        let path = std::env::temp_dir().join(format!("config_watch_test_{}.json", uuid::Uuid::new_v4()));
+        let mut config = AppConfig::new();
+        write_config(&path, &config);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = tokio::spawn(watch_config_file(path.clone(), Duration::from_millis(50), move |config| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(config);
+            }
+        }));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // 无效内容不会触发回调
+        fs::write(&path, "{ not json").unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(300), rx.recv()).await.is_err());
+
+        config.proxy.model_aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+        config.proxy.api_key = "sk-watch-plaintext".to_string();
+        write_config(&path, &config);
+        let reloaded = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .expect("config change not detected within 500ms")
+            .unwrap();
+        assert_eq!(reloaded.proxy.model_aliases.get("fast").map(String::as_str), Some("gemini-2.5-flash"));
+
+        // 手动写入的明文 API Key 与启动加载一样被封存并写回文件
+        assert!(reloaded.proxy.api_key.is_empty());
+        assert!(!reloaded.proxy.api_key_hash.is_empty());
+        assert!(!fs::read_to_string(&path).unwrap().contains("sk-watch-plaintext"));
+        // 写回文件不会再次触发回调
+        assert!(tokio::time::timeout(Duration::from_millis(300), rx.recv()).await.is_err());
+
+        watcher.abort();
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -63,7 +63,9 @@ pub fn init_logger() {
         .unwrap_or_else(|_| EnvFilter::new("info"));
 
     // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
+    // OpenTelemetry layer starts empty; the proxy server installs an exporter when configured
     let _ = tracing_subscriber::registry()
+        .with(crate::proxy::otel::reload_layer())
         .with(filter_layer)
         .with(console_layer)
         .with(file_layer)
@@ -1,9 +1,13 @@
 use std::fs;
 use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use base64::{Engine as _, engine::general_purpose};
 use crate::models::{TokenData, Account};
 use crate::modules::{account, db};
+use crate::proxy::error::ProxyError;
+use crate::proxy::upstream::client::UpstreamClient;
+use futures::StreamExt;
 use crate::utils::protobuf;
 
 /// Scan and import V1 data
@@ -271,3 +275,254 @@ pub fn get_refresh_token_from_db() -> Result<String, String> {
     let db_path = db::get_db_path()?;
     extract_refresh_token_from_file(&db_path)
 }
+
+/// CSV 中的一行账号凭据 (列: email,access_token,refresh_token,project_id；除 email / refresh_token 外均可省略，其余列忽略)
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvTokenRow {
+    pub email: String,
+    #[serde(default)]
+    pub access_token: Option<String>,
+    pub refresh_token: String,
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+/// CSV 批量导入结果
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// 新增账号数
+    pub imported: usize,
+    /// 邮箱已存在、更新凭据的账号数
+    pub updated: usize,
+    /// (行号, 错误原因)，行号从 1 开始并包含表头
+    pub failures: Vec<(usize, String)>,
+}
+
+fn validate_csv_row(row: &CsvTokenRow) -> Result<(), String> {
+    if !row.email.contains('@') {
+        return Err(format!("invalid email: {}", row.email));
+    }
+    if row.refresh_token.is_empty() {
+        return Err("refresh_token is empty".to_string());
+    }
+    Ok(())
+}
+
+/// CSV 解析结果：(行号, 有效行) 与 (行号, 错误原因)
+pub type ParsedTokenCsv = (Vec<(usize, CsvTokenRow)>, Vec<(usize, String)>);
+
+/// 解析账号 CSV：返回 (行号, 有效行) 与解析失败的行
+/// 同一文件中邮箱重复时以最后一行为准
+pub fn parse_token_csv<R: std::io::Read>(reader: R) -> ParsedTokenCsv {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut rows: Vec<(usize, CsvTokenRow)> = Vec::new();
+    let mut failures = Vec::new();
+    let headers = match csv_reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return (rows, vec![(1, e.to_string())]),
+    };
+    for result in csv_reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line() as usize).unwrap_or(0);
+                failures.push((line, e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line() as usize).unwrap_or(0);
+        match record
+            .deserialize::<CsvTokenRow>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|row| validate_csv_row(&row).map(|_| row))
+        {
+            Ok(row) => {
+                rows.retain(|(_, existing)| existing.email != row.email);
+                rows.push((line, row));
+            }
+            Err(e) => failures.push((line, e)),
+        }
+    }
+    (rows, failures)
+}
+
+/// 导入时并发验证的账号数
+const IMPORT_VALIDATE_CONCURRENCY: usize = 4;
+
+/// 导入前向上游验证凭据；access_token 缺失或已过期时先用 refresh_token 换取新 token
+/// 401/403 视为无效凭据，限流、网络错误等无法判定的结果仍允许导入 (与 TokenManager::add_token 一致)
+pub async fn validate_import_token(client: &UpstreamClient, mut token: TokenData) -> Result<TokenData, String> {
+    if token.access_token.is_empty() || token.expiry_timestamp <= chrono::Utc::now().timestamp() {
+        let refreshed = crate::modules::oauth::refresh_access_token(&token.refresh_token)
+            .await
+            .map_err(|e| format!("refresh_token rejected: {}", e))?;
+        token = TokenData::new(
+            refreshed.access_token,
+            refreshed.refresh_token.unwrap_or(token.refresh_token),
+            refreshed.expires_in,
+            token.email,
+            token.project_id,
+            token.session_id,
+        );
+    }
+
+    match client.validate_access_token(&token.access_token).await {
+        Ok(()) => Ok(token),
+        Err(ProxyError::Unauthorized) => Err("token rejected by upstream (401/403)".to_string()),
+        Err(e) => {
+            crate::modules::logger::log_warn(&format!(
+                "Token probe inconclusive for {}: {}",
+                token.email.as_deref().unwrap_or_default(),
+                e
+            ));
+            Ok(token)
+        }
+    }
+}
+
+/// 验证后写入导入的账号 ((序号, 凭据))；邮箱已存在时更新其凭据，失败记录到 report.failures
+pub(crate) async fn import_validated(
+    client: &UpstreamClient,
+    entries: Vec<(usize, TokenData)>,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let existing: std::collections::HashMap<String, Option<String>> = account::load_account_index()?
+        .accounts
+        .into_iter()
+        .map(|s| (s.email, s.name))
+        .collect();
+
+    let validated: Vec<(usize, Result<TokenData, String>)> = futures::stream::iter(entries)
+        .map(|(line, token)| async move { (line, validate_import_token(client, token).await) })
+        .buffered(IMPORT_VALIDATE_CONCURRENCY)
+        .collect()
+        .await;
+
+    for (line, result) in validated {
+        let token = match result {
+            Ok(token) => token,
+            Err(e) => {
+                report.failures.push((line, e));
+                continue;
+            }
+        };
+        let email = token.email.clone().unwrap_or_default();
+        let name = existing.get(&email).cloned().flatten();
+        match account::upsert_account(email.clone(), name, token) {
+            Ok(_) if existing.contains_key(&email) => report.updated += 1,
+            Ok(_) => report.imported += 1,
+            Err(e) => report.failures.push((line, e)),
+        }
+    }
+    report.failures.sort_by_key(|(line, _)| *line);
+    Ok(())
+}
+
+/// 从 CSV 文件批量导入账号；邮箱已存在时更新其凭据而非重复添加
+/// 每行凭据先经上游验证，无效行记录到 failures，不影响其余行的导入
+pub async fn import_from_csv(file_path: &str, client: &UpstreamClient) -> Result<ImportReport, String> {
+    let file = fs::File::open(file_path)
+        .map_err(|e| format!("failed_to_open_csv: {}", e))?;
+    let (rows, failures) = parse_token_csv(file);
+
+    let entries = rows
+        .into_iter()
+        .map(|(line, row)| {
+            let token = TokenData::new(
+                row.access_token.unwrap_or_default(),
+                row.refresh_token,
+                0, // 过期时间未知，验证前先刷新
+                Some(row.email),
+                row.project_id,
+                None,
+            );
+            (line, token)
+        })
+        .collect();
+
+    let mut report = ImportReport { failures, ..Default::default() };
+    import_validated(client, entries, &mut report).await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token_csv_partial_failure() {
+        let csv = "\
+email,access_token,refresh_token,project_id,weight
+a@example.com,ya29.a,1//refresh-a,proj-a,1.5
+not-an-email,,1//refresh-b,,
+c@example.com,,1//refresh-c,,
+d@example.com,ya29.d,,proj-d,
+e@example.com,,1//refresh-e,,heavy
+a@example.com,ya29.a2,1//refresh-a2,,
+";
+        let (rows, failures) = parse_token_csv(csv.as_bytes());
+
+        // 重复邮箱以最后一行为准；未知列 (weight) 忽略
+        let emails: Vec<&str> = rows.iter().map(|(_, r)| r.email.as_str()).collect();
+        assert_eq!(emails, vec!["c@example.com", "e@example.com", "a@example.com"]);
+        assert_eq!(rows[2].1.refresh_token, "1//refresh-a2");
+        assert_eq!(rows[0].1.access_token, None);
+
+        let lines: Vec<usize> = failures.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 5]);
+        assert!(failures[0].1.contains("invalid email"));
+        assert!(failures[1].1.contains("refresh_token"));
+    }
+
+    #[test]
+    fn test_parse_token_csv_optional_columns() {
+        let csv = "email,refresh_token\nx@example.com,1//refresh-x\n";
+        let (rows, failures) = parse_token_csv(csv.as_bytes());
+        assert!(failures.is_empty());
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(rows[0].1.project_id, None);
+    }
+
+    /// 本地 mock countTokens 端点，按 access_token 返回不同状态码
+    async fn probe_client() -> UpstreamClient {
+        use axum::{http::{HeaderMap, StatusCode}, routing::post, Json};
+
+        let router = axum::Router::new().route(
+            "/v1internal:countTokens",
+            post(|headers: HeaderMap| async move {
+                match headers["authorization"].to_str().unwrap() {
+                    "Bearer ya29.bad" => (StatusCode::UNAUTHORIZED, Json(serde_json::json!({}))),
+                    "Bearer ya29.busy" => (StatusCode::TOO_MANY_REQUESTS, Json(serde_json::json!({}))),
+                    _ => (StatusCode::OK, Json(serde_json::json!({"totalTokens": 1}))),
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_upstream(router).await;
+        UpstreamClient::new(None).with_base_urls(vec![base])
+    }
+
+    fn unexpired(access_token: &str) -> TokenData {
+        TokenData::new(
+            access_token.to_string(),
+            "1//refresh".to_string(),
+            3600,
+            Some("x@example.com".to_string()),
+            None,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_validate_import_token() {
+        let client = probe_client().await;
+        assert!(validate_import_token(&client, unexpired("ya29.good")).await.is_ok());
+        // 限流无法判定凭据是否有效，仍允许导入
+        assert!(validate_import_token(&client, unexpired("ya29.busy")).await.is_ok());
+        let err = validate_import_token(&client, unexpired("ya29.bad")).await.unwrap_err();
+        assert!(err.contains("rejected"));
+    }
+}
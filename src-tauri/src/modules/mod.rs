@@ -18,6 +18,7 @@ pub mod cloudflared;
 pub mod integration;
 pub mod account_service;
 pub mod http_api;
+pub mod token_backup;
 
 use crate::models;
 
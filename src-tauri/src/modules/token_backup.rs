@@ -0,0 +1,289 @@
+// 账号池加密备份 - Argon2id 由口令派生密钥，AES-256-GCM (ring) 加密 JSON
+// 文件格式: { version, kdf: { m_cost, t_cost, p_cost, salt }, nonce, ciphertext } (base64)
+// version 与 kdf 作为 AAD 参与认证，篡改文件头同样会导致解密失败
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::models::TokenData;
+use crate::modules::migration::{self, ImportReport};
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::token_manager::ProxyToken;
+
+/// 当前备份文件格式版本 (格式变化时递增并在 decrypt_tokens 中迁移)
+/// - 1: 不带 AAD
+/// - 2: 文件头 (version + kdf) 作为 AAD
+pub const BACKUP_FORMAT_VERSION: u32 = 2;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// 导入时 KDF 参数上限，防止构造的备份文件让派生密钥占用过多内存或时间
+const MAX_M_COST_KIB: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 10;
+const MAX_P_COST: u32 = 4;
+
+/// 备份中的单个账号凭据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedToken {
+    pub account_id: String,
+    pub email: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+    pub timestamp: i64,
+    pub project_id: Option<String>,
+    pub subscription_tier: Option<String>,
+}
+
+impl From<&ProxyToken> for ExportedToken {
+    fn from(token: &ProxyToken) -> Self {
+        Self {
+            account_id: token.account_id.clone(),
+            email: token.email.clone(),
+            access_token: token.access_token.clone(),
+            refresh_token: token.refresh_token.clone(),
+            expires_in: token.expires_in,
+            timestamp: token.timestamp,
+            project_id: token.project_id.clone(),
+            subscription_tier: token.subscription_tier.clone(),
+        }
+    }
+}
+
+/// Argon2id 参数 (随文件保存，调整默认值后旧备份仍可解密)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+}
+
+/// 加密备份文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedTokenBackup {
+    pub version: u32,
+    pub kdf: KdfParams,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<LessSafeKey, String> {
+    let salt = general_purpose::STANDARD
+        .decode(&kdf.salt)
+        .map_err(|e| format!("invalid_salt: {}", e))?;
+    let params = Params::new(
+        kdf.m_cost.min(MAX_M_COST_KIB),
+        kdf.t_cost.min(MAX_T_COST),
+        kdf.p_cost.min(MAX_P_COST),
+        Some(KEY_LEN),
+    )
+    .map_err(|e| format!("invalid_kdf_params: {}", e))?;
+
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| format!("key_derivation_failed: {}", e))?;
+
+    let unbound = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "invalid_key".to_string())?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+/// 参与认证的文件头 (version + kdf)
+fn header_aad(version: u32, kdf: &KdfParams) -> Result<Vec<u8>, String> {
+    serde_json::to_vec(&(version, kdf)).map_err(|e| format!("failed_to_serialize_header: {}", e))
+}
+
+/// 加密账号列表
+pub fn encrypt_tokens(tokens: &[ExportedToken], passphrase: &str) -> Result<EncryptedTokenBackup, String> {
+    if passphrase.is_empty() {
+        return Err("passphrase_required".to_string());
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let defaults = Params::default();
+    let kdf = KdfParams {
+        m_cost: defaults.m_cost(),
+        t_cost: defaults.t_cost(),
+        p_cost: defaults.p_cost(),
+        salt: general_purpose::STANDARD.encode(salt),
+    };
+    let key = derive_key(passphrase, &kdf)?;
+
+    let aad = header_aad(BACKUP_FORMAT_VERSION, &kdf)?;
+    let mut in_out = serde_json::to_vec(tokens).map_err(|e| format!("failed_to_serialize_tokens: {}", e))?;
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(&aad), &mut in_out)
+        .map_err(|_| "encryption_failed".to_string())?;
+
+    Ok(EncryptedTokenBackup {
+        version: BACKUP_FORMAT_VERSION,
+        kdf,
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(in_out),
+    })
+}
+
+/// 解密账号列表；口令错误或内容被篡改时返回错误
+pub fn decrypt_tokens(backup: &EncryptedTokenBackup, passphrase: &str) -> Result<Vec<ExportedToken>, String> {
+    // 版本 1 的文件没有 AAD，仍可解密
+    let aad = match backup.version {
+        1 => Vec::new(),
+        BACKUP_FORMAT_VERSION => header_aad(backup.version, &backup.kdf)?,
+        other => return Err(format!("unsupported_backup_version: {}", other)),
+    };
+
+    let nonce: [u8; NONCE_LEN] = general_purpose::STANDARD
+        .decode(&backup.nonce)
+        .ok()
+        .and_then(|n| n.try_into().ok())
+        .ok_or("invalid_nonce")?;
+    let mut in_out = general_purpose::STANDARD
+        .decode(&backup.ciphertext)
+        .map_err(|e| format!("invalid_ciphertext: {}", e))?;
+
+    let key = derive_key(passphrase, &backup.kdf)?;
+    let plaintext = key
+        .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(&aad), &mut in_out)
+        .map_err(|_| "decryption_failed: wrong passphrase or corrupted file".to_string())?;
+
+    serde_json::from_slice(plaintext).map_err(|e| format!("failed_to_parse_tokens: {}", e))
+}
+
+/// 加密并写入备份文件
+pub fn export_to_file(output_path: &str, tokens: &[ExportedToken], passphrase: &str) -> Result<(), String> {
+    let backup = encrypt_tokens(tokens, passphrase)?;
+    let content = serde_json::to_string_pretty(&backup).map_err(|e| format!("failed_to_serialize_backup: {}", e))?;
+    fs::write(output_path, content).map_err(|e| format!("failed_to_write_backup: {}", e))
+}
+
+/// 读取备份文件并导入；邮箱已存在时更新其凭据
+/// 每个账号的凭据先经上游验证，failures 中的序号为账号在备份中的位置 (从 1 开始)
+pub async fn import_from_file(path: &str, passphrase: &str, client: &UpstreamClient) -> Result<ImportReport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed_to_read_backup: {}", e))?;
+    let backup: EncryptedTokenBackup =
+        serde_json::from_str(&content).map_err(|e| format!("failed_to_parse_backup: {}", e))?;
+    // Argon2 派生密钥耗时较长，放到阻塞线程池执行
+    let passphrase = passphrase.to_string();
+    let tokens = tokio::task::spawn_blocking(move || decrypt_tokens(&backup, &passphrase))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))??;
+
+    let entries = tokens
+        .into_iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let token_data = TokenData {
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                expires_in: token.expires_in,
+                expiry_timestamp: token.timestamp,
+                token_type: "Bearer".to_string(),
+                email: Some(token.email),
+                project_id: token.project_id,
+                session_id: None,
+            };
+            (i + 1, token_data)
+        })
+        .collect();
+
+    let mut report = ImportReport::default();
+    migration::import_validated(client, entries, &mut report).await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_tokens() -> Vec<ExportedToken> {
+        (0..3)
+            .map(|i| ExportedToken {
+                account_id: format!("acc-{}", i),
+                email: format!("user{}@example.com", i),
+                access_token: format!("ya29.access-{}", i),
+                refresh_token: format!("1//refresh-{}", i),
+                expires_in: 3599,
+                timestamp: 1_700_000_000 + i,
+                project_id: Some(format!("proj-{}", i)),
+                subscription_tier: if i == 0 { Some("PRO".to_string()) } else { None },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_through_file() {
+        let path = std::env::temp_dir().join(format!("token_backup_{}.json", uuid::Uuid::new_v4()));
+        let tokens = sample_tokens();
+        export_to_file(path.to_str().unwrap(), &tokens, "correct horse battery staple").unwrap();
+
+        // 明文凭据不会出现在文件中
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("1//refresh-0"));
+
+        let backup: EncryptedTokenBackup = serde_json::from_str(&content).unwrap();
+        assert_eq!(backup.version, BACKUP_FORMAT_VERSION);
+        assert_eq!(decrypt_tokens(&backup, "correct horse battery staple").unwrap(), tokens);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_wrong_passphrase_or_tampering_rejected() {
+        let backup = encrypt_tokens(&sample_tokens(), "secret").unwrap();
+        assert!(decrypt_tokens(&backup, "not-secret").unwrap_err().starts_with("decryption_failed"));
+
+        let mut tampered = backup.clone();
+        let mut bytes = general_purpose::STANDARD.decode(&tampered.ciphertext).unwrap();
+        bytes[0] ^= 0x01;
+        tampered.ciphertext = general_purpose::STANDARD.encode(bytes);
+        assert!(decrypt_tokens(&tampered, "secret").is_err());
+
+        let mut future = backup;
+        future.version = BACKUP_FORMAT_VERSION + 1;
+        assert!(decrypt_tokens(&future, "secret").unwrap_err().starts_with("unsupported_backup_version"));
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let backup = encrypt_tokens(&sample_tokens(), "secret").unwrap();
+
+        // 改动 KDF 参数 (即使派生出的密钥相同) 也无法通过认证；过大的参数按上限截断，不会长时间阻塞
+        let mut tampered = backup.clone();
+        tampered.kdf.t_cost = u32::MAX;
+        assert!(decrypt_tokens(&tampered, "secret").unwrap_err().starts_with("decryption_failed"));
+
+        // 降级为无 AAD 的版本 1 同样被拒绝
+        let mut downgraded = backup;
+        downgraded.version = 1;
+        assert!(decrypt_tokens(&downgraded, "secret").unwrap_err().starts_with("decryption_failed"));
+    }
+
+    #[test]
+    fn test_version_1_backup_still_decrypts() {
+        let kdf = KdfParams {
+            m_cost: 8,
+            t_cost: 1,
+            p_cost: 1,
+            salt: general_purpose::STANDARD.encode([7u8; SALT_LEN]),
+        };
+        let nonce = [9u8; NONCE_LEN];
+        let mut in_out = serde_json::to_vec(&sample_tokens()).unwrap();
+        derive_key("secret", &kdf)
+            .unwrap()
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out)
+            .unwrap();
+        let backup = EncryptedTokenBackup {
+            version: 1,
+            kdf,
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(in_out),
+        };
+        assert_eq!(decrypt_tokens(&backup, "secret").unwrap(), sample_tokens());
+    }
+}
@@ -0,0 +1,140 @@
+// 告警 Webhook - 所有账号连续失败达到阈值时向外部地址推送通知
+// 推送在独立任务中执行，失败只记日志，不影响请求处理
+use serde_json::json;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct AlertWebhook {
+    http: reqwest::Client,
+    url: String,
+    threshold: u32,
+}
+
+impl AlertWebhook {
+    pub fn new(url: String, threshold: u32) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self { http, url, threshold }
+    }
+
+    /// 连续失败次数恰好达到阈值时推送一次 (同一轮连续失败不重复告警)
+    pub fn on_failure(&self, failure_count: u32, last_error: &str) {
+        if self.threshold == 0 || failure_count != self.threshold {
+            return;
+        }
+        let payload = json!({
+            "event": "all_tokens_exhausted",
+            "timestamp": chrono::Utc::now().timestamp(),
+            "failure_count": failure_count,
+            "last_error": last_error,
+        });
+        let http = self.http.clone();
+        let url = self.url.clone();
+        tracing::warn!("连续 {} 次请求失败，推送告警: {}", failure_count, url);
+        tokio::spawn(async move {
+            match http.post(&url).json(&payload).send().await {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => tracing::warn!("告警 Webhook 返回 {}", resp.status()),
+                Err(e) => tracing::warn!("告警 Webhook 推送失败: {}", e),
+            }
+        });
+    }
+}
+
+/// 跨账号的连续失败计数 (任一请求成功即清零)
+#[derive(Clone, Default)]
+pub struct FailureTracker {
+    consecutive_failures: Arc<AtomicU32>,
+    webhook: Arc<std::sync::RwLock<Option<Arc<AlertWebhook>>>>,
+}
+
+impl FailureTracker {
+    pub fn set_webhook(&self, webhook: Option<AlertWebhook>) {
+        *self.webhook.write().unwrap() = webhook.map(Arc::new);
+    }
+
+    pub fn counter(&self) -> Arc<AtomicU32> {
+        self.consecutive_failures.clone()
+    }
+
+    pub fn record(&self, error: Option<&str>) {
+        let Some(error) = error else {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        };
+        let count = self.consecutive_failures.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+        if let Some(webhook) = self.webhook.read().unwrap().as_ref() {
+            webhook.on_failure(count, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    async fn spawn_webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(body);
+                    "ok"
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(app).await;
+        (format!("{}/hook", base), rx)
+    }
+
+    #[tokio::test]
+    async fn test_webhook_fires_at_threshold() {
+        let (url, mut rx) = spawn_webhook().await;
+        let tracker = FailureTracker::default();
+        tracker.set_webhook(Some(AlertWebhook::new(url, 3)));
+
+        tracker.record(Some("429"));
+        tracker.record(Some("429"));
+        tracker.record(Some("503 Service Unavailable"));
+
+        let payload = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("webhook not called")
+            .unwrap();
+        assert_eq!(payload["event"], "all_tokens_exhausted");
+        assert_eq!(payload["failure_count"], 3);
+        assert_eq!(payload["last_error"], "503 Service Unavailable");
+        assert!(payload["timestamp"].as_i64().unwrap() > 0);
+
+        // 同一轮连续失败不重复推送
+        tracker.record(Some("429"));
+        assert!(tokio::time::timeout(Duration::from_millis(200), rx.recv()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_counter() {
+        let (url, mut rx) = spawn_webhook().await;
+        let tracker = FailureTracker::default();
+        tracker.set_webhook(Some(AlertWebhook::new(url, 3)));
+
+        tracker.record(Some("429"));
+        tracker.record(Some("429"));
+        tracker.record(None);
+        assert_eq!(tracker.counter().load(Ordering::Relaxed), 0);
+        tracker.record(Some("429"));
+        tracker.record(Some("429"));
+
+        assert!(tokio::time::timeout(Duration::from_millis(200), rx.recv()).await.is_err());
+        assert_eq!(tracker.counter().load(Ordering::Relaxed), 2);
+    }
+}
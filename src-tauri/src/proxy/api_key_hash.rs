@@ -0,0 +1,179 @@
+// API Key 哈希存储 - Argon2id，配置文件中只保存 PHC 格式哈希
+// 编码格式: $argon2id$v=19$m=...,t=...,p=...$<salt>$<hash>
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+
+const SALT_LEN: usize = 16;
+
+/// 验证缓存的最大条目数 (正常只有当前 key 一条，超出时整体清空)
+const MAX_VERIFIED_KEYS: usize = 16;
+
+/// 已验证通过的 (哈希, key) 组合，以 SHA-256 摘要为键
+/// Argon2 每次验证需要数十毫秒，缓存后同一 key 的后续请求只需一次摘要计算与查表
+static VERIFIED_KEYS: Lazy<DashMap<[u8; 32], ()>> = Lazy::new(DashMap::new);
+
+/// 明文 key 的哈希缓存 (SHA-256(key) -> PHC 哈希)，同一明文只做一次 Argon2 哈希
+static DERIVED_HASHES: Lazy<DashMap<[u8; 32], String>> = Lazy::new(DashMap::new);
+
+/// 同时进行的 Argon2 验证数上限 (每次验证占用约 19MB 内存与数十毫秒 CPU)
+const MAX_CONCURRENT_VERIFIES: usize = 2;
+
+static VERIFY_PERMITS: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(MAX_CONCURRENT_VERIFIES));
+
+/// 对明文 key 生成带随机盐的哈希
+pub fn hash_api_key(key: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).expect("salt length is valid");
+    Argon2::default()
+        .hash_password(key.as_bytes(), &salt)
+        .expect("argon2 hashing with default params cannot fail")
+        .to_string()
+}
+
+/// 内存中明文 key 对应的哈希：同一明文复用首次生成的哈希，避免每次都换盐重算
+pub fn hash_api_key_once(key: &str) -> String {
+    let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+    if let Some(hash) = DERIVED_HASHES.get(&digest) {
+        return hash.clone();
+    }
+    let hash = hash_api_key(key);
+    if DERIVED_HASHES.len() >= MAX_VERIFIED_KEYS {
+        DERIVED_HASHES.clear();
+    }
+    DERIVED_HASHES.insert(digest, hash.clone());
+    hash
+}
+
+/// 校验明文 key 与哈希是否匹配 (参数取自哈希本身)
+pub fn verify_api_key(key: &str, encoded: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(encoded) else {
+        return false;
+    };
+    if is_verified(key, encoded) {
+        return true;
+    }
+
+    let ok = Argon2::default()
+        .verify_password(key.as_bytes(), &parsed)
+        .is_ok();
+    if ok {
+        if VERIFIED_KEYS.len() >= MAX_VERIFIED_KEYS {
+            VERIFIED_KEYS.clear();
+        }
+        VERIFIED_KEYS.insert(verified_key_digest(key, encoded), ());
+    }
+    ok
+}
+
+/// 请求路径上的校验：命中缓存直接返回，否则在阻塞线程池中执行 Argon2，并限制同时验证的数量
+pub async fn verify_api_key_async(key: &str, encoded: &str) -> bool {
+    if is_verified(key, encoded) {
+        return true;
+    }
+    let Ok(_permit) = VERIFY_PERMITS.acquire().await else {
+        return false;
+    };
+    let (key, encoded) = (key.to_string(), encoded.to_string());
+    tokio::task::spawn_blocking(move || verify_api_key(&key, &encoded))
+        .await
+        .unwrap_or(false)
+}
+
+/// 验证缓存的键：SHA-256(哈希 || 0 || key)
+fn verified_key_digest(key: &str, encoded: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(encoded.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(key.as_bytes());
+    hasher.finalize().into()
+}
+
+/// 该 key 是否已针对此哈希验证通过 (不执行 Argon2，不占用验证名额)
+pub fn is_verified(key: &str, encoded: &str) -> bool {
+    VERIFIED_KEYS.contains_key(&verified_key_digest(key, encoded))
+}
+
+/// 清空验证缓存 (API Key 轮换或安全配置更新时调用)
+pub fn clear_verified_keys() {
+    VERIFIED_KEYS.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_api_key("sk-test-key");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_api_key("sk-test-key", &hash));
+        // 命中缓存后仍能拒绝错误 key
+        assert!(verify_api_key("sk-test-key", &hash));
+        assert!(!verify_api_key("sk-wrong-key", &hash));
+    }
+
+    #[test]
+    fn test_salt_makes_hashes_unique() {
+        assert_ne!(hash_api_key("same"), hash_api_key("same"));
+    }
+
+    #[test]
+    fn test_verify_uses_params_from_hash() {
+        let salt = SaltString::encode_b64(b"0123456789abcdef").unwrap();
+        let params = argon2::Params::new(8, 1, 1, None).unwrap();
+        let hash = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password(b"password", &salt)
+            .unwrap()
+            .to_string();
+        assert!(hash.contains("m=8,t=1,p=1"));
+        assert!(verify_api_key("password", &hash));
+        assert!(!verify_api_key("passw0rd", &hash));
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let hashes: Vec<String> = (0..MAX_VERIFIED_KEYS + 1)
+            .map(|i| {
+                let salt = SaltString::encode_b64(format!("salt-{:011}", i).as_bytes()).unwrap();
+                let params = argon2::Params::new(8, 1, 1, None).unwrap();
+                Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password(b"k", &salt)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        for hash in &hashes {
+            assert!(verify_api_key("k", hash));
+        }
+        assert!(VERIFIED_KEYS.len() <= MAX_VERIFIED_KEYS);
+    }
+
+    #[test]
+    fn test_hash_api_key_once_reuses_hash() {
+        let hash = hash_api_key_once("sk-once");
+        assert_eq!(hash_api_key_once("sk-once"), hash);
+        assert_ne!(hash_api_key_once("sk-other"), hash);
+        assert!(verify_api_key("sk-once", &hash));
+    }
+
+    #[tokio::test]
+    async fn test_verify_async() {
+        let hash = hash_api_key("sk-async");
+        assert!(!verify_api_key_async("sk-wrong", &hash).await);
+        assert!(verify_api_key_async("sk-async", &hash).await);
+        assert!(verify_api_key_async("sk-async", &hash).await);
+    }
+
+    #[test]
+    fn test_rejects_malformed_hash() {
+        assert!(!verify_api_key("sk-test", ""));
+        assert!(!verify_api_key("sk-test", "sk-test"));
+        assert!(!verify_api_key("sk-test", "$argon2id$v=19$m=8,t=1,p=1$!!$AAAA"));
+    }
+}
@@ -0,0 +1,294 @@
+// 审计数据库 - 每个完成的请求写入一行到 SQLite，便于按条件查询历史
+use crate::proxy::audit_log::AuditRecord;
+use crate::proxy::token_manager::AccountUsage;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// 通道容量：写库落后太多时直接丢弃，绝不阻塞请求处理
+const AUDIT_DB_CHANNEL_CAPACITY: usize = 1024;
+/// 单个事务最多合并写入的记录数
+const AUDIT_DB_BATCH_SIZE: usize = 100;
+
+/// 审计数据库 (后台线程批量写入)
+pub struct AuditDb {
+    path: PathBuf,
+    tx: mpsc::Sender<AuditRecord>,
+}
+
+impl AuditDb {
+    /// 建表并启动后台写库任务
+    pub fn new(path: PathBuf) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let conn = open_connection(&path)?;
+        init_schema(&conn)?;
+
+        let (tx, mut rx) = mpsc::channel::<AuditRecord>(AUDIT_DB_CHANNEL_CAPACITY);
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn;
+            let mut batch = Vec::with_capacity(AUDIT_DB_BATCH_SIZE);
+            while let Some(record) = rx.blocking_recv() {
+                batch.push(record);
+                // 尽量把已排队的记录合并到同一事务
+                while batch.len() < AUDIT_DB_BATCH_SIZE {
+                    match rx.try_recv() {
+                        Ok(record) => batch.push(record),
+                        Err(_) => break,
+                    }
+                }
+                if let Err(e) = insert_batch(&mut conn, &batch) {
+                    tracing::warn!("[AuditDb] Failed to write {} record(s): {}", batch.len(), e);
+                }
+                batch.clear();
+            }
+        });
+
+        Ok(Self { path, tx })
+    }
+
+    /// 提交一条记录 (非阻塞，通道已满时丢弃)
+    pub fn log(&self, record: AuditRecord) {
+        if let Err(e) = self.tx.try_send(record) {
+            tracing::debug!("[AuditDb] Dropping record: {}", e);
+        }
+    }
+
+    /// 查询最近 N 条记录 (按时间倒序)
+    pub fn recent(&self, limit: usize) -> Result<Vec<AuditRecord>, String> {
+        let conn = open_connection(&self.path)?;
+        query_recent(&conn, limit)
+    }
+
+    /// 读取持久化的账号累计用量 (启动时恢复)
+    pub fn load_account_usage(&self) -> Result<Vec<AccountUsage>, String> {
+        let conn = open_connection(&self.path)?;
+        query_account_usage(&conn)
+    }
+
+    /// 保存账号累计用量快照 (定期 / 停止服务时调用)
+    pub fn save_account_usage(&self, usage: &[AccountUsage]) -> Result<(), String> {
+        let mut conn = open_connection(&self.path)?;
+        upsert_account_usage(&mut conn, usage)
+    }
+}
+
+fn open_connection(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL").map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_records (
+            id TEXT PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            account_email TEXT,
+            model TEXT,
+            prompt_tokens_estimate INTEGER,
+            completion_tokens_estimate INTEGER,
+            finish_reason TEXT,
+            latency_ms INTEGER NOT NULL,
+            http_status INTEGER NOT NULL,
+            error_type TEXT
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_records (timestamp DESC)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS account_usage (
+            email TEXT PRIMARY KEY,
+            prompt_tokens INTEGER NOT NULL,
+            completion_tokens INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn insert_batch(conn: &mut Connection, records: &[AuditRecord]) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR REPLACE INTO audit_records (id, timestamp, account_email, model, prompt_tokens_estimate, completion_tokens_estimate, finish_reason, latency_ms, http_status, error_type)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )
+            .map_err(|e| e.to_string())?;
+        for r in records {
+            stmt.execute(params![
+                r.request_id,
+                r.timestamp,
+                r.account_email,
+                r.model,
+                r.prompt_tokens,
+                r.completion_tokens,
+                r.finish_reason,
+                r.latency_ms as i64,
+                r.status,
+                r.error_type,
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn query_recent(conn: &Connection, limit: usize) -> Result<Vec<AuditRecord>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, timestamp, account_email, model, prompt_tokens_estimate, completion_tokens_estimate, finish_reason, latency_ms, http_status, error_type
+             FROM audit_records
+             ORDER BY timestamp DESC
+             LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([limit as i64], |row| {
+            let model: Option<String> = row.get(3)?;
+            let prompt_tokens: Option<u32> = row.get(4)?;
+            let completion_tokens: Option<u32> = row.get(5)?;
+            // 费用不落库，读取时按当前单价估算
+            let estimated_cost_usd = model.as_deref().zip(prompt_tokens).and_then(|(model, prompt)| {
+                crate::proxy::pricing::estimate_cost_usd(model, prompt as u64, completion_tokens.unwrap_or(0) as u64)
+            });
+            Ok(AuditRecord {
+                request_id: row.get(0)?,
+                timestamp: row.get(1)?,
+                account_email: row.get(2)?,
+                model,
+                prompt_tokens,
+                completion_tokens,
+                finish_reason: row.get(6)?,
+                latency_ms: row.get::<_, i64>(7)? as u64,
+                status: row.get(8)?,
+                error_type: row.get(9)?,
+                estimated_cost_usd,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+fn upsert_account_usage(conn: &mut Connection, usage: &[AccountUsage]) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR REPLACE INTO account_usage (email, prompt_tokens, completion_tokens, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(|e| e.to_string())?;
+        for u in usage {
+            stmt.execute(params![
+                u.email,
+                u.prompt_tokens as i64,
+                u.completion_tokens as i64,
+                now,
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn query_account_usage(conn: &Connection) -> Result<Vec<AccountUsage>, String> {
+    let mut stmt = conn
+        .prepare("SELECT email, prompt_tokens, completion_tokens FROM account_usage ORDER BY email")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let prompt_tokens = row.get::<_, i64>(1)? as u64;
+            let completion_tokens = row.get::<_, i64>(2)? as u64;
+            Ok(AccountUsage {
+                email: row.get(0)?,
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(i: i64, status: u16) -> AuditRecord {
+        AuditRecord {
+            timestamp: 1_700_000_000_000 + i,
+            request_id: format!("req-{}", i),
+            account_email: Some("test@example.com".to_string()),
+            model: Some("gemini-2.5-flash".to_string()),
+            prompt_tokens: Some(10),
+            completion_tokens: Some(20),
+            finish_reason: Some("stop".to_string()),
+            latency_ms: 150,
+            status,
+            error_type: (status >= 400).then(|| "rate_limited".to_string()),
+            estimated_cost_usd: None,
+        }
+    }
+
+    #[test]
+    fn test_insert_and_query_recent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        // 重复建表应为幂等
+        init_schema(&conn).unwrap();
+
+        let records: Vec<_> = (0..5).map(|i| record(i, if i == 4 { 429 } else { 200 })).collect();
+        insert_batch(&mut conn, &records).unwrap();
+
+        let recent = query_recent(&conn, 3).unwrap();
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].request_id, "req-4");
+        assert_eq!(recent[0].status, 429);
+        assert_eq!(recent[0].error_type.as_deref(), Some("rate_limited"));
+        assert_eq!(recent[1].completion_tokens, Some(20));
+        assert_eq!(
+            recent[1].estimated_cost_usd,
+            crate::proxy::pricing::estimate_cost_usd("gemini-2.5-flash", 10, 20)
+        );
+        assert_eq!(recent[2].request_id, "req-2");
+    }
+
+    #[test]
+    fn test_account_usage_round_trip() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+
+        let usage = |prompt, completion| AccountUsage {
+            email: "test@example.com".to_string(),
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        };
+        upsert_account_usage(&mut conn, &[usage(100, 40)]).unwrap();
+        // 再次保存覆盖旧值
+        upsert_account_usage(&mut conn, &[usage(150, 60)]).unwrap();
+
+        let loaded = query_account_usage(&conn).unwrap();
+        assert_eq!(loaded, vec![usage(150, 60)]);
+    }
+}
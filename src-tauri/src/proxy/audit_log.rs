@@ -0,0 +1,203 @@
+// 审计日志 - 每个完成的请求写入一行 JSON 到轮转日志文件
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// 通道容量：写盘落后太多时直接丢弃，绝不阻塞请求处理
+const AUDIT_CHANNEL_CAPACITY: usize = 1024;
+
+/// 单条审计记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: i64,
+    pub request_id: String,
+    pub account_email: Option<String>,
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u32>,
+    #[serde(default)]
+    pub completion_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+    pub latency_ms: u64,
+    pub status: u16,
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// 按模型单价估算的费用 (USD，未知模型为 None)
+    #[serde(default)]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// 审计日志记录器 (后台任务写盘)
+pub struct AuditLogger {
+    tx: mpsc::Sender<AuditRecord>,
+}
+
+impl AuditLogger {
+    /// 启动后台写盘任务并返回记录器句柄
+    pub fn new(path: PathBuf, max_bytes: u64) -> Self {
+        let (tx, mut rx) = mpsc::channel::<AuditRecord>(AUDIT_CHANNEL_CAPACITY);
+
+        tokio::spawn(async move {
+            let mut writer = RotatingWriter::new(path, max_bytes);
+            while let Some(record) = rx.recv().await {
+                if let Err(e) = writer.write_record(&record) {
+                    tracing::warn!("[AuditLog] Failed to write record: {}", e);
+                }
+            }
+        });
+
+        Self { tx }
+    }
+
+    /// 提交一条记录 (非阻塞，通道已满时丢弃)
+    pub fn log(&self, record: AuditRecord) {
+        if let Err(e) = self.tx.try_send(record) {
+            tracing::debug!("[AuditLog] Dropping record: {}", e);
+        }
+    }
+}
+
+/// 简单轮转写入器：超过 max_bytes 时重命名为 `<name>.1` 并新建文件 (仅保留一个备份)
+struct RotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Option<File>,
+    written: u64,
+}
+
+impl RotatingWriter {
+    fn new(path: PathBuf, max_bytes: u64) -> Self {
+        let written = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        Self {
+            path,
+            max_bytes,
+            file: None,
+            written,
+        }
+    }
+
+    fn backup_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        fs::rename(&self.path, Self::backup_path(&self.path))?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &AuditRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        if self.max_bytes > 0 && self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(&line)?;
+            self.written += line.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+/// 从 OpenAI / Claude / Gemini 响应 JSON 中提取结束原因
+pub fn extract_finish_reason(json: &Value) -> Option<String> {
+    json.get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("finish_reason"))
+        .or_else(|| json.get("stop_reason"))
+        .or_else(|| json.get("delta").and_then(|d| d.get("stop_reason")))
+        .or_else(|| {
+            json.get("candidates")
+                .or_else(|| json.get("response").and_then(|r| r.get("candidates")))
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("finishReason"))
+        })
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// 粗略估算提示词 token 数 (约 4 字符 / token)
+pub fn estimate_prompt_tokens(request_body: &str) -> u32 {
+    (request_body.len() as u32).div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_record(i: usize) -> AuditRecord {
+        AuditRecord {
+            timestamp: 1_700_000_000_000 + i as i64,
+            request_id: format!("req-{:03}", i),
+            account_email: Some("test@example.com".to_string()),
+            model: Some("gemini-2.5-flash".to_string()),
+            prompt_tokens: Some(42),
+            completion_tokens: Some(7),
+            finish_reason: Some("stop".to_string()),
+            latency_ms: 120,
+            status: 200,
+            error_type: None,
+            estimated_cost_usd: None,
+        }
+    }
+
+    #[test]
+    fn test_rotation_after_100_entries() {
+        let dir = std::env::temp_dir().join(format!("audit_log_test_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("audit.log");
+        let line_len = serde_json::to_vec(&sample_record(0)).unwrap().len() as u64 + 1;
+        // 约 30 条记录触发一次轮转
+        let max_bytes = line_len * 30;
+
+        let mut writer = RotatingWriter::new(path.clone(), max_bytes);
+        for i in 0..100 {
+            writer.write_record(&sample_record(i)).unwrap();
+        }
+
+        let backup = RotatingWriter::backup_path(&path);
+        assert!(backup.exists(), "rotation should have produced a .1 backup");
+        assert!(fs::metadata(&path).unwrap().len() <= max_bytes);
+        assert!(fs::metadata(&backup).unwrap().len() <= max_bytes);
+
+        // 当前文件应包含最后一条记录，且每行都是合法 JSON
+        let current = fs::read_to_string(&path).unwrap();
+        let records: Vec<AuditRecord> = current
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.last().unwrap().request_id, "req-099");
+        assert_eq!(records.len(), 100 % 30);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_finish_reason() {
+        let openai = json!({"choices": [{"finish_reason": "stop"}]});
+        assert_eq!(extract_finish_reason(&openai).as_deref(), Some("stop"));
+
+        let claude = json!({"stop_reason": "end_turn"});
+        assert_eq!(extract_finish_reason(&claude).as_deref(), Some("end_turn"));
+
+        let gemini = json!({"response": {"candidates": [{"finishReason": "STOP"}]}});
+        assert_eq!(extract_finish_reason(&gemini).as_deref(), Some("STOP"));
+
+        assert_eq!(extract_finish_reason(&json!({})), None);
+    }
+}
@@ -66,7 +66,11 @@ pub fn map_claude_model_to_gemini(input: &str) -> String {
     }
 
     // 2. Pass-through known prefixes (gemini-, -thinking) to support dynamic suffixes
-    if input.starts_with("gemini-") || input.contains("thinking") {
+    // [FIX] 向量模型原样透传，不落入对话模型的默认映射
+    if input.starts_with("gemini-")
+        || input.contains("thinking")
+        || crate::proxy::mappers::openai::is_embedding_model(input)
+    {
         return input.to_string();
     }
 
@@ -89,6 +93,12 @@ pub fn get_supported_models() -> Vec<String> {
 pub async fn get_all_dynamic_models(
     custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
 ) -> Vec<String> {
+    let mapping = custom_mapping.read().await;
+    collect_model_ids(&mapping)
+}
+
+/// 同步版本的模型列表构建 (供已持有映射表快照的调用方使用)
+pub fn collect_model_ids(custom_mapping: &HashMap<String, String>) -> Vec<String> {
     use std::collections::HashSet;
     let mut model_ids = HashSet::new();
 
@@ -98,11 +108,8 @@ pub async fn get_all_dynamic_models(
     }
 
     // 2. 获取所有自定义映射模型 (Custom)
-    {
-        let mapping = custom_mapping.read().await;
-        for key in mapping.keys() {
-            model_ids.insert(key.clone());
-        }
+    for key in custom_mapping.keys() {
+        model_ids.insert(key.clone());
     }
 
     // 5. 确保包含常用的 Gemini/画画模型 ID
@@ -135,6 +142,85 @@ pub async fn get_all_dynamic_models(
     sorted_ids
 }
 
+/// 解析模型别名 (model_aliases)
+/// - 未命中别名: 原样返回
+/// - 命中别名且目标为已知模型: 返回目标模型名
+/// - 命中别名但目标模型不存在: 返回描述性错误 (由调用方转换为 400)
+pub fn resolve_model_alias(
+    model: &str,
+    aliases: &HashMap<String, String>,
+    custom_mapping: &HashMap<String, String>,
+) -> Result<String, String> {
+    let Some(target) = aliases.get(model) else {
+        return Ok(model.to_string());
+    };
+
+    if collect_model_ids(custom_mapping).iter().any(|id| id == target) {
+        crate::modules::logger::log_info(&format!("[Router] 别名解析: {} -> {}", model, target));
+        Ok(target.clone())
+    } else {
+        Err(format!(
+            "Model alias '{}' points to unknown model '{}'",
+            model, target
+        ))
+    }
+}
+
+/// 请求模型的完整解析：先解析别名 (model_aliases)，再按路由规则映射 (custom_mapping / 默认映射)
+pub fn resolve_request_model(
+    model: &str,
+    aliases: &HashMap<String, String>,
+    custom_mapping: &HashMap<String, String>,
+) -> Result<String, String> {
+    let model = resolve_model_alias(model, aliases, custom_mapping)?;
+    Ok(resolve_model_route(&model, custom_mapping))
+}
+
+/// OpenAI 模型对象统一使用的 created 时间戳
+const OPENAI_MODEL_CREATED: i64 = 1706745600;
+
+/// 构建 OpenAI 格式的模型对象列表 (/v1/models 与 /v1/models/:model_id 共用，保证两者一致)
+/// - 真实模型附带 `aliases` 数组
+/// - 别名本身也作为条目返回，`root` 指向真实模型
+pub fn build_openai_model_objects(
+    model_ids: Vec<String>,
+    aliases: &HashMap<String, String>,
+) -> Vec<serde_json::Value> {
+    let mut data: Vec<_> = model_ids
+        .into_iter()
+        .filter(|id| !aliases.contains_key(id))
+        .map(|id| {
+            let mut model_aliases: Vec<&String> = aliases
+                .iter()
+                .filter(|(_, target)| **target == id)
+                .map(|(alias, _)| alias)
+                .collect();
+            model_aliases.sort();
+            serde_json::json!({
+                "id": id,
+                "object": "model",
+                "created": OPENAI_MODEL_CREATED,
+                "owned_by": "antigravity",
+                "aliases": model_aliases
+            })
+        })
+        .collect();
+
+    let mut alias_entries: Vec<_> = aliases.iter().collect();
+    alias_entries.sort();
+    for (alias, target) in alias_entries {
+        data.push(serde_json::json!({
+            "id": alias,
+            "object": "model",
+            "created": OPENAI_MODEL_CREATED,
+            "owned_by": "antigravity",
+            "root": target
+        }));
+    }
+
+    data
+}
+
 /// Wildcard matching - supports multiple wildcards
 ///
 /// **Note**: Matching is **case-sensitive**. Pattern `GPT-4*` will NOT match `gpt-4-turbo`.
@@ -257,10 +343,72 @@ pub fn normalize_to_standard_id(model_name: &str) -> Option<String> {
     }
 }
 
+/// 模型能力 (用于按能力改道)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModelCapability {
+    TextGeneration,
+    ImageGeneration,
+    CodeExecution,
+    Thinking,
+    Embedding,
+}
+
+/// 上游物理模型 -> 能力列表
+pub static MODEL_CAPABILITIES: Lazy<HashMap<&'static str, Vec<ModelCapability>>> = Lazy::new(|| {
+    use ModelCapability::*;
+    let mut m = HashMap::new();
+
+    m.insert("gemini-2.5-flash", vec![TextGeneration, CodeExecution]);
+    m.insert("gemini-2.5-flash-thinking", vec![TextGeneration, CodeExecution, Thinking]);
+    m.insert("gemini-2.5-pro", vec![TextGeneration, CodeExecution, Thinking]);
+    m.insert("gemini-3-flash", vec![TextGeneration, CodeExecution, Thinking]);
+    m.insert("gemini-3-pro-preview", vec![TextGeneration, CodeExecution, Thinking]);
+    m.insert("gemini-3-pro-image", vec![TextGeneration, ImageGeneration]);
+    m.insert("claude-sonnet-4-5", vec![TextGeneration]);
+    m.insert("claude-sonnet-4-5-thinking", vec![TextGeneration, Thinking]);
+    m.insert("claude-opus-4-5-thinking", vec![TextGeneration, Thinking]);
+    m.insert("text-embedding-004", vec![Embedding]);
+    m.insert("gemini-embedding-001", vec![Embedding]);
+
+    m
+});
+
+/// 具备 `model` 全部能力的其他模型，按多余能力数 (越接近越优先)、名称排序
+/// 未登记能力的模型返回空列表
+pub fn capability_alternatives(model: &str) -> Vec<&'static str> {
+    let Some(required) = MODEL_CAPABILITIES.get(model) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<(&'static str, usize)> = MODEL_CAPABILITIES
+        .iter()
+        .filter(|(name, caps)| **name != model && required.iter().all(|c| caps.contains(c)))
+        .map(|(name, caps)| (*name, caps.len() - required.len()))
+        .collect();
+    candidates.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+    candidates.into_iter().map(|(name, _)| name).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_capability_alternatives() {
+        assert_eq!(
+            capability_alternatives("claude-sonnet-4-5-thinking"),
+            vec![
+                "claude-opus-4-5-thinking",
+                "gemini-2.5-flash-thinking",
+                "gemini-2.5-pro",
+                "gemini-3-flash",
+                "gemini-3-pro-preview",
+            ]
+        );
+        assert_eq!(capability_alternatives("gemini-3-pro-image"), Vec::<&str>::new());
+        assert_eq!(capability_alternatives("text-embedding-004"), vec!["gemini-embedding-001"]);
+        assert!(capability_alternatives("unknown-model").is_empty());
+    }
+
     #[test]
     fn test_model_mapping() {
         assert_eq!(
@@ -276,6 +424,10 @@ mod tests {
             map_claude_model_to_gemini("gemini-2.5-flash-mini-test"),
             "gemini-2.5-flash-mini-test"
         );
+        assert_eq!(
+            map_claude_model_to_gemini("text-embedding-004"),
+            "text-embedding-004"
+        );
         assert_eq!(
             map_claude_model_to_gemini("unknown-model"),
             "claude-sonnet-4-5"
@@ -340,4 +492,55 @@ mod tests {
         // Multi-wildcard: "a*b*c" (3)
         assert_eq!(resolve_model_route("a-test-b-foo-c", &custom), "multi-wild");
     }
+
+    #[test]
+    fn test_model_alias_resolution() {
+        let custom = HashMap::new();
+        let mut aliases = HashMap::new();
+        aliases.insert("my-fast".to_string(), "gemini-2.5-flash".to_string());
+        aliases.insert("broken".to_string(), "does-not-exist".to_string());
+
+        assert_eq!(resolve_model_alias("my-fast", &aliases, &custom).unwrap(), "gemini-2.5-flash");
+        assert_eq!(resolve_model_alias("gpt-4o", &aliases, &custom).unwrap(), "gpt-4o");
+        assert!(resolve_model_alias("broken", &aliases, &custom)
+            .unwrap_err()
+            .contains("does-not-exist"));
+    }
+
+    #[test]
+    fn test_resolve_request_model_applies_alias_then_route() {
+        let custom_mapping = HashMap::from([("gpt-4o".to_string(), "gemini-3-flash".to_string())]);
+        let aliases = HashMap::from([("fast".to_string(), "gpt-4o".to_string())]);
+        assert_eq!(
+            resolve_request_model("fast", &aliases, &custom_mapping).unwrap(),
+            "gemini-3-flash"
+        );
+        assert_eq!(
+            resolve_request_model("gemini-2.5-flash", &aliases, &custom_mapping).unwrap(),
+            "gemini-2.5-flash"
+        );
+        let broken = HashMap::from([("bad".to_string(), "no-such-model".to_string())]);
+        assert!(resolve_request_model("bad", &broken, &custom_mapping).is_err());
+    }
+
+    #[test]
+    fn test_build_openai_model_objects() {
+        let mut aliases = HashMap::new();
+        aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+
+        let objects = build_openai_model_objects(
+            vec!["gemini-2.5-flash".to_string(), "gemini-3-flash".to_string()],
+            &aliases,
+        );
+        assert_eq!(objects.len(), 3);
+
+        let flash = objects.iter().find(|o| o["id"] == "gemini-2.5-flash").unwrap();
+        assert_eq!(flash["object"], "model");
+        assert_eq!(flash["aliases"][0], "fast");
+
+        let alias = objects.iter().find(|o| o["id"] == "fast").unwrap();
+        assert_eq!(alias["root"], "gemini-2.5-flash");
+
+        assert!(!objects.iter().any(|o| o["id"] == "missing-model"));
+    }
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
-// use std::path::PathBuf;
+use std::path::PathBuf;
 use std::collections::HashMap;
+use thiserror::Error;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -17,6 +18,17 @@ impl Default for ProxyAuthMode {
     }
 }
 
+impl ProxyAuthMode {
+    /// 实际生效的鉴权模式：Auto 在允许局域网访问时等同 AllExceptHealth，否则为 Off
+    pub fn resolve(&self, allow_lan_access: bool) -> ProxyAuthMode {
+        match self {
+            ProxyAuthMode::Auto if allow_lan_access => ProxyAuthMode::AllExceptHealth,
+            ProxyAuthMode::Auto => ProxyAuthMode::Off,
+            other => other.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ZaiDispatchMode {
@@ -148,6 +160,20 @@ pub struct ExperimentalConfig {
     /// 上下文压缩阈值 L3 (Fork + Summary)
     #[serde(default = "default_threshold_l3")]
     pub context_compression_threshold_l3: f32,
+
+    /// 流式响应最小 chunk 字节数 (合并过碎的文本增量，最多等待 200ms)
+    /// - 0: 禁用 (默认)
+    #[serde(default)]
+    pub min_chunk_bytes: usize,
+
+    /// 启用 Prompt 缓存 (Gemini CachedContent)
+    /// 较长的 system prompt 在各账号下缓存一次，后续请求只发送缓存引用
+    #[serde(default = "default_false")]
+    pub enable_prompt_cache: bool,
+
+    /// Prompt 缓存有效期 (秒)
+    #[serde(default = "default_prompt_cache_ttl_secs")]
+    pub prompt_cache_ttl_secs: u64,
 }
 
 impl Default for ExperimentalConfig {
@@ -160,14 +186,153 @@ impl Default for ExperimentalConfig {
             context_compression_threshold_l1: 0.4,
             context_compression_threshold_l2: 0.55,
             context_compression_threshold_l3: 0.7,
+            min_chunk_bytes: 0,
+            enable_prompt_cache: false,
+            prompt_cache_ttl_secs: default_prompt_cache_ttl_secs(),
         }
     }
 }
 
+fn default_prompt_cache_ttl_secs() -> u64 { 3600 }
 fn default_threshold_l1() -> f32 { 0.4 }
 fn default_threshold_l2() -> f32 { 0.55 }
 fn default_threshold_l3() -> f32 { 0.7 }
 
+/// 重试退避策略 (请求失败后切换账号前的等待)
+/// 等待时间 = min(base_delay_ms * 2^attempt, max_delay_ms) + [0, base_delay_ms) 的随机抖动
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// 首次请求之外的最大重试次数
+    #[serde(default = "default_max_retries")]
+    pub max_retries: usize,
+
+    /// 退避基准时间 (毫秒)
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+
+    /// 单次退避上限 (毫秒，不含抖动)
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+
+    /// 是否附加随机抖动，避免多个请求同时重试
+    #[serde(default = "default_true")]
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            base_delay_ms: default_base_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 计算第 attempt 次重试前的等待时间 (attempt 从 0 开始)
+    pub fn delay_for(&self, attempt: usize) -> std::time::Duration {
+        let factor = 1u64.checked_shl(attempt as u32).unwrap_or(u64::MAX);
+        let backoff = self.base_delay_ms.saturating_mul(factor).min(self.max_delay_ms);
+        let jitter = if self.jitter && self.base_delay_ms > 0 {
+            use rand::Rng;
+            rand::thread_rng().gen_range(0..self.base_delay_ms)
+        } else {
+            0
+        };
+        std::time::Duration::from_millis(backoff + jitter)
+    }
+
+    /// 按策略等待后再进行下一次重试
+    pub async fn backoff(&self, attempt: usize) {
+        let delay = self.delay_for(attempt);
+        tracing::debug!("[RetryPolicy] Backing off {}ms before retry #{}", delay.as_millis(), attempt + 1);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// 流量拆分 (A/B 测试)：请求模型匹配 `model_pattern` (正则) 时按百分比随机改用某个变体模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficSplit {
+    pub model_pattern: String,
+
+    /// (模型, 百分比)，百分比之和必须为 100
+    pub variants: Vec<(String, u8)>,
+}
+
+impl TrafficSplit {
+    /// 按累计百分比选取变体，`roll` 取值 [0, 100)
+    pub fn pick(&self, roll: u8) -> Option<&str> {
+        let mut cumulative = 0u32;
+        for (model, percent) in &self.variants {
+            cumulative += *percent as u32;
+            if (roll as u32) < cumulative {
+                return Some(model);
+            }
+        }
+        None
+    }
+
+    /// 校验正则与百分比之和，返回问题描述
+    fn problem(&self) -> Option<String> {
+        if let Err(e) = regex::Regex::new(&self.model_pattern) {
+            return Some(format!("invalid model_pattern: {}", e));
+        }
+        let total: u32 = self.variants.iter().map(|(_, p)| *p as u32).sum();
+        if total != 100 {
+            return Some(format!("variant percentages sum to {}, expected 100", total));
+        }
+        None
+    }
+}
+
+/// 账号级熔断配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenCircuitBreakerConfig {
+    /// 是否启用
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// 连续失败多少次后熔断
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// 熔断后多久进入半开状态 (秒)
+    #[serde(default = "default_recovery_timeout_secs")]
+    pub recovery_timeout_secs: u64,
+}
+
+impl Default for TokenCircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: default_failure_threshold(),
+            recovery_timeout_secs: default_recovery_timeout_secs(),
+        }
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_recovery_timeout_secs() -> u64 {
+    60
+}
+
+fn default_max_retries() -> usize {
+    2
+}
+
+fn default_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_delay_ms() -> u64 {
+    8000
+}
+
 fn default_true() -> bool {
     true
 }
@@ -199,12 +364,26 @@ pub struct ProxyConfig {
     /// 监听端口
     pub port: u16,
 
-    /// API 密钥
+    /// API 密钥 (明文，仅在内存中短暂存在；保存配置时会被哈希并清空)
+    #[serde(default)]
     pub api_key: String,
+
+    /// API 密钥哈希 (Argon2id PHC 格式，配置文件中实际保存的值)
+    #[serde(default)]
+    pub api_key_hash: String,
     
     /// Web UI 管理后台密码 (可选，如未设置则使用 api_key)
     pub admin_password: Option<String>,
 
+    /// /admin 运行时 token 管理接口的独立密钥 (明文，仅在内存中短暂存在；保存配置时会被哈希并清空)
+    #[serde(default)]
+    pub admin_api_key: Option<String>,
+
+    /// /admin 密钥哈希 (Argon2id PHC 格式，与 api_key_hash 相同)
+    /// - 与 admin_api_key 均未配置时不开放 /admin 接口
+    #[serde(default)]
+    pub admin_api_key_hash: Option<String>,
+
     /// 是否自动启动
     pub auto_start: bool,
 
@@ -212,6 +391,16 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub custom_mapping: std::collections::HashMap<String, String>,
 
+    /// 模型别名表 (key: 客户端使用的别名, value: 已存在的模型名)
+    /// 与 custom_mapping 不同，别名必须指向已知模型，否则请求返回 400
+    #[serde(default)]
+    pub model_aliases: std::collections::HashMap<String, String>,
+
+    /// 模型降级链 (key: 主模型, value: 按顺序尝试的备选模型)
+    /// 主模型返回非配额类错误时自动改用下一个备选模型，响应头 X-Model-Used 标明实际使用的模型
+    #[serde(default)]
+    pub model_fallbacks: std::collections::HashMap<String, Vec<String>>,
+
     /// API 请求超时时间(秒)
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
@@ -241,6 +430,412 @@ pub struct ProxyConfig {
     /// - Some(account_id): 固定使用指定账号
     #[serde(default)]
     pub preferred_account_id: Option<String>,
+
+    /// 审计日志文件路径 (每个完成的请求写入一行 JSON)
+    /// - None: 不写审计日志
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+
+    /// 审计日志文件大小上限 (字节)，超过后轮转为 `<name>.1`
+    #[serde(default = "default_log_max_bytes")]
+    pub log_max_bytes: u64,
+
+    /// 审计数据库路径 (SQLite，每个请求一行，可通过 /v1/stats/history 查询)
+    /// - None: 不写审计数据库
+    #[serde(default)]
+    pub db_path: Option<PathBuf>,
+
+    /// CORS 允许的来源列表
+    /// - 空列表: 不附加 CORS 头
+    /// - 包含 "*": 允许任意来源 (默认，保持旧行为)
+    #[serde(default = "default_cors_allowed_origins")]
+    pub cors_allowed_origins: Vec<String>,
+
+    /// 是否暴露 GET /metrics (Prometheus 文本格式)
+    #[serde(default)]
+    pub metrics_enabled: bool,
+
+    /// 重试退避策略
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+
+    /// 账号级熔断 (连续失败后暂时跳过该账号)
+    #[serde(default)]
+    pub token_circuit_breaker: TokenCircuitBreakerConfig,
+
+    /// HTTPS 证书路径 (PEM)，与 tls_key_path 同时配置时启用 TLS
+    #[serde(default)]
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// HTTPS 私钥路径 (PEM，PKCS#8 / PKCS#1 / SEC1)
+    #[serde(default)]
+    pub tls_key_path: Option<PathBuf>,
+
+    /// 请求体大小上限 (字节)，超限返回 413
+    /// 环境变量 ABV_MAX_BODY_SIZE 设置时优先
+    #[serde(default = "default_max_request_body_bytes")]
+    pub max_request_body_bytes: usize,
+
+    /// POST /v1/chat/completions/batch 单次允许的最大子请求数
+    #[serde(default = "default_batch_max_requests")]
+    pub batch_max_requests: usize,
+
+    /// Chat Completions 最大并发请求数，超出时立即返回 503 (不排队)
+    /// - 0: 不限制
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// 停止服务时等待在途请求 (含 SSE 流) 完成的最长时间 (秒)
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
+    /// 按模型配置的上游超时 (key: 映射后的模型名或其前缀, value: 秒)
+    /// 例如图片生成模型需要更长时间，flash 模型可更快失败
+    #[serde(default)]
+    pub model_timeouts: std::collections::HashMap<String, u64>,
+
+    /// 未在 model_timeouts 中配置的模型使用的上游超时 (秒)
+    #[serde(default = "default_timeout_secs")]
+    pub default_timeout_secs: u64,
+
+    /// 请求竞速：同一请求同时发往多个账号，采用最先返回的结果，其余取消
+    /// 以额外配额换取更低的首字延迟，默认关闭
+    #[serde(default)]
+    pub racing_enabled: bool,
+
+    /// 参与竞速的账号数 (2-3，超出按 3 处理)
+    #[serde(default = "default_racing_tokens")]
+    pub racing_tokens: usize,
+
+    /// 上游 HTTP 连接池：每个主机保留的最大空闲连接数
+    #[serde(default = "default_http_pool_max_idle_per_host")]
+    pub http_pool_max_idle_per_host: usize,
+
+    /// 上游 HTTP 连接池：空闲连接保持时间 (秒)
+    #[serde(default = "default_http_pool_idle_timeout_secs")]
+    pub http_pool_idle_timeout_secs: u64,
+
+    /// 发往上游的 User-Agent (上游版本检测变化时可调整)
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+
+    /// 透传到上游的客户端请求头前缀 (如链路追踪头)，未设置时为 "x-custom-"
+    /// - 空字符串: 不透传
+    #[serde(default)]
+    pub forwarded_header_prefix: Option<String>,
+
+    /// 响应图片上传地址：设置后响应中的 base64 图片会 POST 到该地址，
+    /// Markdown 中改为引用返回的 URL (响应为 {"url": "..."} 或纯文本 URL)
+    #[serde(default)]
+    pub image_upload_url: Option<String>,
+
+    /// 内联 base64 图片大小上限 (字节，按解码后估算)
+    /// - 请求中超限的图片返回 400
+    /// - 响应中超限的图片替换为提示文本
+    #[serde(default = "default_max_inline_image_bytes")]
+    pub max_inline_image_bytes: usize,
+
+    /// OpenAI SSE 流的心跳间隔 (秒)，空闲时发送 `: keepalive` 注释，防止反向代理/负载均衡断开空闲连接
+    #[serde(default = "default_sse_keepalive_interval_secs")]
+    pub sse_keepalive_interval_secs: u64,
+
+    /// SSE 断线续传：流结束后事件保留的时间 (秒)，客户端可携带 Last-Event-ID 重连补发
+    /// - 0: 关闭
+    #[serde(default = "default_sse_replay_window_secs")]
+    pub sse_replay_window_secs: u64,
+
+    /// SSE 断线续传：客户端断开后等待重连的宽限期 (秒)，超时仍未重连则中止上游
+    /// 仅对携带 `x-sse-resume: true` 的请求生效，其余请求断开即中止上游
+    #[serde(default = "default_sse_reconnect_grace_secs")]
+    pub sse_reconnect_grace_secs: u64,
+
+    /// 会话账号亲和的空闲过期时间 (秒)
+    /// 请求携带 x-session-id (或 body.session_id) 时同一会话固定使用同一账号
+    #[serde(default = "default_session_ttl_secs")]
+    pub session_ttl_secs: u64,
+
+    /// 按能力改道：主模型 (及降级链) 失败且所有账号对其不可用时，
+    /// 改用具备相同能力且仍有可用账号的模型 (见 model_mapping::MODEL_CAPABILITIES)
+    #[serde(default)]
+    pub capability_routing: bool,
+
+    /// 按模型拆分流量 (A/B 测试)，按顺序匹配第一条规则
+    #[serde(default)]
+    pub traffic_splits: Vec<TrafficSplit>,
+
+    /// 影子模式：把请求镜像到该 v1internal 端点 (如 https://daily-cloudcode-pa.googleapis.com/v1internal)，
+    /// 响应丢弃，仅记录耗时与错误；None 表示关闭
+    #[serde(default)]
+    pub shadow_endpoint: Option<String>,
+
+    /// 镜像到影子端点的请求比例 (0-100)
+    #[serde(default = "default_shadow_percentage")]
+    pub shadow_percentage: u8,
+
+    /// 客户端 IP 白名单 (CIDR，如 "10.0.0.0/8")，不在其中的请求返回 403；为空时不限制
+    #[serde(default)]
+    pub ip_allowlist: Vec<ipnetwork::IpNetwork>,
+
+    /// 受信任的反向代理地址：仅当对端为这些地址时才采信 X-Forwarded-For
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
+
+    /// 上游 429 返回 Retry-After 时重试前的最长等待 (秒)
+    #[serde(default = "default_max_retry_delay_secs")]
+    pub max_retry_delay_secs: u64,
+
+    /// 以 gzip 压缩发往上游的请求体 (多图对话等大请求可显著减小体积)
+    #[serde(default)]
+    pub compress_requests: bool,
+
+    /// 按客户端 Accept-Encoding 以 gzip/zstd 压缩响应体 (SSE 流式响应除外)
+    #[serde(default)]
+    pub compress_responses: bool,
+
+    /// OpenTelemetry 链路追踪导出 (OTLP/HTTP)；None 表示关闭
+    #[serde(default)]
+    pub otel: Option<OtelConfig>,
+
+    /// 告警 Webhook：所有账号连续失败达到阈值时 POST JSON 通知；None 表示关闭
+    #[serde(default)]
+    pub alert_webhook_url: Option<String>,
+
+    /// 触发告警的连续失败次数 (跨账号统计，任一请求成功即清零)；0 表示关闭
+    #[serde(default = "default_alert_on_consecutive_failures")]
+    pub alert_on_consecutive_failures: u32,
+
+    /// 400 错误体匹配任一正则时视为可重试 (重试一次)，默认为内置的 Thinking 签名错误规则
+    #[serde(default = "default_retry_on_patterns")]
+    pub retry_on_patterns: Vec<String>,
+
+    /// 非流式响应附带 x-estimated-cost 响应头 (按模型单价估算的费用，USD)
+    #[serde(default)]
+    pub include_cost_in_response: bool,
+
+    /// 对话超出上下文窗口时截断 (默认) 或生成摘要
+    #[serde(default)]
+    pub context_overflow_strategy: ContextOverflowStrategy,
+
+    /// 思考模型的思考内容输出方式 (passthrough / strip / separate_field)
+    #[serde(default)]
+    pub thinking_mode: ThinkingMode,
+
+    /// 严格校验: /v1/chat/completions 请求体含未识别字段时返回 400 (默认忽略)
+    #[serde(default)]
+    pub strict_request_validation: bool,
+}
+
+/// 竞速账号数上限，避免一次请求消耗过多配额
+pub const MAX_RACING_TOKENS: usize = 3;
+
+/// 默认上游 User-Agent
+pub const DEFAULT_USER_AGENT: &str = "antigravity/1.11.9 windows/amd64";
+
+/// 默认透传请求头前缀
+pub const DEFAULT_FORWARDED_HEADER_PREFIX: &str = "x-custom-";
+
+/// 服务运行中可热更新的字段：即 AxumServer::apply_config 与 commands::proxy::apply_proxy_config 实际更新的字段
+/// 新增字段时须在此处或 RESTART_REQUIRED_FIELDS 中登记 (见 test_every_field_is_classified)
+pub const HOT_RELOADABLE_FIELDS: &[&str] = &[
+    "enabled",
+    "auto_start",
+    "auth_mode",
+    "api_key",
+    "api_key_hash",
+    "admin_password",
+    "admin_api_key",
+    "admin_api_key_hash",
+    "custom_mapping",
+    "model_aliases",
+    "model_fallbacks",
+    "enable_logging",
+    "upstream_proxy",
+    "zai",
+    "scheduling",
+    "experimental",
+    "preferred_account_id",
+    "token_circuit_breaker",
+    "max_retry_delay_secs",
+    "thinking_mode",
+    "strict_request_validation",
+    "retry_policy",
+    "max_concurrent_requests",
+    "model_timeouts",
+    "default_timeout_secs",
+    "traffic_splits",
+    "retry_on_patterns",
+    "include_cost_in_response",
+    "context_overflow_strategy",
+];
+
+/// 启动时固化的字段 (监听地址 / TLS / 连接池 / 启动时构建的组件等)，修改后需重启
+pub const RESTART_REQUIRED_FIELDS: &[&str] = &[
+    "allow_lan_access",
+    "port",
+    "request_timeout",
+    "log_file",
+    "log_max_bytes",
+    "db_path",
+    "cors_allowed_origins",
+    "metrics_enabled",
+    "tls_cert_path",
+    "tls_key_path",
+    "max_request_body_bytes",
+    "batch_max_requests",
+    "shutdown_timeout_secs",
+    "racing_enabled",
+    "racing_tokens",
+    "http_pool_max_idle_per_host",
+    "http_pool_idle_timeout_secs",
+    "user_agent",
+    "forwarded_header_prefix",
+    "image_upload_url",
+    "max_inline_image_bytes",
+    "sse_keepalive_interval_secs",
+    "sse_replay_window_secs",
+    "sse_reconnect_grace_secs",
+    "session_ttl_secs",
+    "capability_routing",
+    "shadow_endpoint",
+    "shadow_percentage",
+    "ip_allowlist",
+    "trusted_proxies",
+    "compress_requests",
+    "compress_responses",
+    "otel",
+    "alert_webhook_url",
+    "alert_on_consecutive_failures",
+];
+
+/// 配置校验错误 (启动前一次性返回全部问题)
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigError {
+    #[error("port {0} is out of range, expected 1024-65535")]
+    InvalidPort(u16),
+
+    #[error("auth is enabled but no API key is configured")]
+    MissingApiKey,
+
+    #[error("TLS requires both tls_cert_path and tls_key_path")]
+    IncompleteTls,
+
+    #[error("TLS file not found: {0}")]
+    TlsFileNotFound(PathBuf),
+
+    #[error("model alias cycle detected: {0}")]
+    AliasCycle(String),
+
+    #[error("{0} must be greater than 0")]
+    NonPositive(String),
+
+    #[error("traffic_splits[{0}]: {1}")]
+    InvalidTrafficSplit(usize, String),
+
+    #[error("{0} must be between 0 and 100")]
+    InvalidPercentage(String),
+
+    #[error("retry_on_patterns[{0}]: {1}")]
+    InvalidRetryPattern(usize, String),
+}
+
+/// 将全部校验错误合并为一条可读的错误信息
+pub fn describe_config_errors(errors: &[ConfigError]) -> String {
+    let details: Vec<String> = errors.iter().map(|e| format!("- {}", e)).collect();
+    format!("配置无效:\n{}", details.join("\n"))
+}
+
+/// 环境变量覆盖前缀，如 ANTIGRAVITY_PORT=9000
+pub const ENV_OVERRIDE_PREFIX: &str = "ANTIGRAVITY_";
+
+/// 日志中需脱敏的字段
+const SECRET_FIELDS: &[&str] = &["api_key", "api_key_hash", "admin_password", "admin_api_key", "admin_api_key_hash"];
+
+/// 按字段当前值的类型解析环境变量：布尔/数字/对象/数组按 JSON 解析，字符串原样使用
+fn parse_env_value(current: &serde_json::Value, raw: &str) -> Option<serde_json::Value> {
+    use serde_json::Value;
+    match current {
+        Value::String(_) => Some(Value::String(raw.to_string())),
+        Value::Bool(_) => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(Value::Bool(true)),
+            "0" | "false" | "no" | "off" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        Value::Null => Some(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))),
+        _ => serde_json::from_str(raw).ok(),
+    }
+}
+
+/// 查找别名循环 (a -> b -> a)，返回形如 "a -> b -> a" 的路径
+fn find_alias_cycle(aliases: &HashMap<String, String>) -> Option<String> {
+    let mut starts: Vec<&String> = aliases.keys().collect();
+    starts.sort();
+    for start in starts {
+        let mut path = vec![start.as_str()];
+        let mut current = start.as_str();
+        while let Some(next) = aliases.get(current) {
+            if let Some(pos) = path.iter().position(|p| *p == next.as_str()) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.push(next.as_str());
+                return Some(cycle.join(" -> "));
+            }
+            path.push(next.as_str());
+            current = next.as_str();
+        }
+    }
+    None
+}
+
+/// 默认内联图片大小上限 (解码后, 5MB)
+pub const DEFAULT_MAX_INLINE_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+fn default_retry_on_patterns() -> Vec<String> {
+    crate::proxy::handlers::common::BUILT_IN_RETRY_PATTERNS
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn default_alert_on_consecutive_failures() -> u32 {
+    10
+}
+
+/// 对话超出模型上下文窗口时的处理策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflowStrategy {
+    /// 丢弃最早的历史消息
+    #[default]
+    Truncate,
+    /// 将最早一半的历史消息交给 summarise_model 生成摘要后替换 (失败时退回截断)
+    Summarise { summarise_model: String },
+}
+
+/// 思考模型 (thought: true) 内容的输出方式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingMode {
+    /// 以 reasoning_content 字段返回 (默认)
+    #[default]
+    Passthrough,
+    /// 丢弃思考内容
+    Strip,
+    /// 从响应体移出，非流式响应改由 x-thinking-tokens 响应头返回 (流式响应等同 Strip)
+    #[serde(alias = "seperate_field")]
+    SeparateField,
+}
+
+/// OpenTelemetry 导出配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OtelConfig {
+    /// OTLP/HTTP 接收地址 (如 http://localhost:4318/v1/traces)
+    pub endpoint: String,
+    /// 上报的 service.name
+    #[serde(default = "default_otel_service_name")]
+    pub service_name: String,
+}
+
+fn default_otel_service_name() -> String {
+    "antigravity-proxy".to_string()
 }
 
 /// 上游代理配置
@@ -252,6 +847,249 @@ pub struct UpstreamProxyConfig {
     pub url: String,
 }
 
+impl ProxyConfig {
+    /// 将明文 api_key / admin_api_key 哈希到对应的 *_hash 字段并清空明文 (写盘前调用)
+    /// 返回是否发生了变化
+    pub fn seal_api_key(&mut self) -> bool {
+        let mut changed = false;
+        if !self.api_key.is_empty() {
+            self.api_key_hash = crate::proxy::api_key_hash::hash_api_key(&self.api_key);
+            self.api_key.clear();
+            changed = true;
+        }
+        if let Some(key) = self.admin_api_key.take() {
+            if !key.is_empty() {
+                self.admin_api_key_hash = Some(crate::proxy::api_key_hash::hash_api_key(&key));
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// 用于鉴权的哈希：内存中仍有明文 (如刚生成 / 环境变量注入) 时以明文为准
+    /// 明文的哈希只计算一次，之后的调用复用同一结果
+    pub fn effective_api_key_hash(&self) -> String {
+        if self.api_key.is_empty() {
+            self.api_key_hash.clone()
+        } else {
+            crate::proxy::api_key_hash::hash_api_key_once(&self.api_key)
+        }
+    }
+
+    /// /admin 密钥的鉴权哈希 (规则同 effective_api_key_hash)，None 表示不开放 /admin 接口
+    pub fn effective_admin_api_key_hash(&self) -> Option<String> {
+        match self.admin_api_key.as_deref() {
+            Some(key) if !key.is_empty() => Some(crate::proxy::api_key_hash::hash_api_key_once(key)),
+            _ => self.admin_api_key_hash.clone().filter(|hash| !hash.is_empty()),
+        }
+    }
+
+    /// 启动前校验配置，返回全部错误而非第一个
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if self.port < 1024 {
+            errors.push(ConfigError::InvalidPort(self.port));
+        }
+
+        let auth_enabled = !matches!(self.auth_mode.resolve(self.allow_lan_access), ProxyAuthMode::Off);
+        if auth_enabled && self.api_key.trim().is_empty() && self.api_key_hash.is_empty() {
+            errors.push(ConfigError::MissingApiKey);
+        }
+
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (None, None) => {}
+            (Some(cert), Some(key)) => {
+                for path in [cert, key] {
+                    if !path.exists() {
+                        errors.push(ConfigError::TlsFileNotFound(path.clone()));
+                    }
+                }
+            }
+            _ => errors.push(ConfigError::IncompleteTls),
+        }
+
+        if let Some(cycle) = find_alias_cycle(&self.model_aliases) {
+            errors.push(ConfigError::AliasCycle(cycle));
+        }
+
+        // max_concurrent_requests 为 0 表示不限制并发，因此不在此检查
+        for (field, value) in [
+            ("default_timeout_secs", self.default_timeout_secs),
+            ("batch_max_requests", self.batch_max_requests as u64),
+            ("session_ttl_secs", self.session_ttl_secs),
+        ] {
+            if value == 0 {
+                errors.push(ConfigError::NonPositive(field.to_string()));
+            }
+        }
+        let mut zero_timeouts: Vec<&String> = self
+            .model_timeouts
+            .iter()
+            .filter(|(_, secs)| **secs == 0)
+            .map(|(model, _)| model)
+            .collect();
+        zero_timeouts.sort();
+        for model in zero_timeouts {
+            errors.push(ConfigError::NonPositive(format!("model_timeouts[{}]", model)));
+        }
+
+        if self.shadow_percentage > 100 {
+            errors.push(ConfigError::InvalidPercentage("shadow_percentage".to_string()));
+        }
+
+        for (i, split) in self.traffic_splits.iter().enumerate() {
+            if let Some(problem) = split.problem() {
+                errors.push(ConfigError::InvalidTrafficSplit(i, problem));
+            }
+        }
+
+        for (i, pattern) in self.retry_on_patterns.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                errors.push(ConfigError::InvalidRetryPattern(i, e.to_string()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 应用 `ANTIGRAVITY_<字段名>` 环境变量覆盖，返回 `字段=值` 列表 (敏感字段脱敏)
+    /// 无法解析或类型不符的变量记录警告后忽略
+    pub fn apply_env_overrides<I>(&mut self, vars: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let Ok(mut current) = serde_json::to_value(&*self) else {
+            return Vec::new();
+        };
+        let mut applied = Vec::new();
+
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_OVERRIDE_PREFIX))
+            .collect();
+        vars.sort();
+        for (name, raw) in vars {
+            let field = name[ENV_OVERRIDE_PREFIX.len()..].to_ascii_lowercase();
+            let Some(existing) = current.get(&field).cloned() else {
+                continue;
+            };
+            let Some(value) = parse_env_value(&existing, &raw) else {
+                tracing::warn!("环境变量 {} 的值无法解析，已忽略", name);
+                continue;
+            };
+
+            current[&field] = value;
+            match serde_json::from_value::<ProxyConfig>(current.clone()) {
+                Ok(config) => {
+                    *self = config;
+                    let shown = if SECRET_FIELDS.contains(&field.as_str()) { "***" } else { raw.as_str() };
+                    applied.push(format!("{}={}", field, shown));
+                }
+                Err(e) => {
+                    tracing::warn!("环境变量 {} 类型不符，已忽略: {}", name, e);
+                    current[&field] = existing;
+                }
+            }
+        }
+        applied
+    }
+
+    /// 撤销环境变量覆盖 (写盘前调用)：仍等于覆盖值的字段还原为 `persisted` (文件中) 的值，
+    /// 运行期间被显式改为其他值的字段保留新值
+    pub fn strip_env_overrides<I>(&mut self, persisted: &ProxyConfig, vars: I)
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_OVERRIDE_PREFIX))
+            .collect();
+        if vars.is_empty() {
+            return;
+        }
+        let mut overridden = persisted.clone();
+        overridden.apply_env_overrides(vars.iter().cloned());
+
+        let (
+            Ok(serde_json::Value::Object(mut current)),
+            Ok(serde_json::Value::Object(file)),
+            Ok(serde_json::Value::Object(env)),
+        ) = (
+            serde_json::to_value(&*self),
+            serde_json::to_value(persisted),
+            serde_json::to_value(&overridden),
+        )
+        else {
+            return;
+        };
+        for (name, _) in &vars {
+            let field = name[ENV_OVERRIDE_PREFIX.len()..].to_ascii_lowercase();
+            let (Some(env_value), Some(file_value)) = (env.get(&field), file.get(&field)) else {
+                continue;
+            };
+            if current.get(&field) == Some(env_value) {
+                current.insert(field, file_value.clone());
+            }
+        }
+        if let Ok(config) = serde_json::from_value(serde_json::Value::Object(current)) {
+            *self = config;
+        }
+    }
+
+    /// 相对当前配置发生变化、且需重启服务才能生效的字段 (按字段名排序)
+    pub fn restart_required_changes(&self, new: &ProxyConfig) -> Vec<String> {
+        let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+            (serde_json::to_value(self), serde_json::to_value(new))
+        else {
+            return Vec::new();
+        };
+        let mut changed: Vec<String> = RESTART_REQUIRED_FIELDS
+            .iter()
+            .filter(|field| old.get(**field) != new.get(**field))
+            .map(|field| field.to_string())
+            .collect();
+        changed.sort();
+        changed
+    }
+
+    /// 以当前配置为基础，仅取 `other` 中可热更新的字段 (需重启的字段保持不变)
+    pub fn with_hot_fields_from(&self, other: &ProxyConfig) -> ProxyConfig {
+        let (Ok(serde_json::Value::Object(mut merged)), Ok(serde_json::Value::Object(other_map))) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        else {
+            return self.clone();
+        };
+        for field in HOT_RELOADABLE_FIELDS {
+            match other_map.get(*field) {
+                Some(value) => merged.insert(field.to_string(), value.clone()),
+                None => merged.remove(*field),
+            };
+        }
+        serde_json::from_value(serde_json::Value::Object(merged)).unwrap_or_else(|_| self.clone())
+    }
+
+    /// 实际生效的透传请求头前缀 (小写)，空字符串表示不透传
+    pub fn effective_forwarded_header_prefix(&self) -> String {
+        self.forwarded_header_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_FORWARDED_HEADER_PREFIX)
+            .to_ascii_lowercase()
+    }
+
+    /// 实际生效的竞速账号数：未启用或少于 2 个时返回 0，最多 MAX_RACING_TOKENS 个
+    pub fn effective_racing_tokens(&self) -> usize {
+        if !self.racing_enabled || self.racing_tokens < 2 {
+            return 0;
+        }
+        self.racing_tokens.min(MAX_RACING_TOKENS)
+    }
+}
+
 impl Default for ProxyConfig {
     fn default() -> Self {
         Self {
@@ -260,9 +1098,14 @@ impl Default for ProxyConfig {
             auth_mode: ProxyAuthMode::default(),
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
+            api_key_hash: String::new(),
             admin_password: None,
+            admin_api_key: None,
+            admin_api_key_hash: None,
             auto_start: false,
             custom_mapping: std::collections::HashMap::new(),
+            model_aliases: std::collections::HashMap::new(),
+            model_fallbacks: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
             enable_logging: true, // 默认开启，支持 token 统计功能
             upstream_proxy: UpstreamProxyConfig::default(),
@@ -270,6 +1113,50 @@ impl Default for ProxyConfig {
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
             preferred_account_id: None, // 默认使用轮询模式
+            log_file: None,
+            log_max_bytes: default_log_max_bytes(),
+            db_path: None,
+            cors_allowed_origins: default_cors_allowed_origins(),
+            metrics_enabled: false,
+            retry_policy: RetryPolicy::default(),
+            token_circuit_breaker: TokenCircuitBreakerConfig::default(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            max_request_body_bytes: default_max_request_body_bytes(),
+            batch_max_requests: default_batch_max_requests(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            model_timeouts: std::collections::HashMap::new(),
+            default_timeout_secs: default_timeout_secs(),
+            racing_enabled: false,
+            racing_tokens: default_racing_tokens(),
+            http_pool_max_idle_per_host: default_http_pool_max_idle_per_host(),
+            http_pool_idle_timeout_secs: default_http_pool_idle_timeout_secs(),
+            user_agent: default_user_agent(),
+            forwarded_header_prefix: None,
+            image_upload_url: None,
+            max_inline_image_bytes: default_max_inline_image_bytes(),
+            sse_keepalive_interval_secs: default_sse_keepalive_interval_secs(),
+            sse_replay_window_secs: default_sse_replay_window_secs(),
+            sse_reconnect_grace_secs: default_sse_reconnect_grace_secs(),
+            session_ttl_secs: default_session_ttl_secs(),
+            capability_routing: false,
+            traffic_splits: Vec::new(),
+            shadow_endpoint: None,
+            shadow_percentage: default_shadow_percentage(),
+            ip_allowlist: Vec::new(),
+            trusted_proxies: Vec::new(),
+            max_retry_delay_secs: default_max_retry_delay_secs(),
+            compress_requests: false,
+            compress_responses: false,
+            otel: None,
+            alert_webhook_url: None,
+            alert_on_consecutive_failures: default_alert_on_consecutive_failures(),
+            retry_on_patterns: default_retry_on_patterns(),
+            include_cost_in_response: false,
+            context_overflow_strategy: ContextOverflowStrategy::default(),
+            thinking_mode: ThinkingMode::default(),
+            strict_request_validation: false,
         }
     }
 }
@@ -278,6 +1165,94 @@ fn default_request_timeout() -> u64 {
     120 // 默认 120 秒,原来 60 秒太短
 }
 
+fn default_log_max_bytes() -> u64 {
+    10 * 1024 * 1024 // 默认 10MB
+}
+
+fn default_max_request_body_bytes() -> usize {
+    10 * 1024 * 1024 // 默认 10MB
+}
+
+fn default_batch_max_requests() -> usize {
+    20
+}
+
+fn default_max_concurrent_requests() -> usize {
+    64
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_timeout_secs() -> u64 {
+    600 // 与 UpstreamClient 的整体超时保持一致
+}
+
+fn default_racing_tokens() -> usize {
+    2
+}
+
+fn default_http_pool_max_idle_per_host() -> usize {
+    20
+}
+
+fn default_http_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_user_agent() -> String {
+    DEFAULT_USER_AGENT.to_string()
+}
+
+fn default_max_inline_image_bytes() -> usize {
+    DEFAULT_MAX_INLINE_IMAGE_BYTES
+}
+
+fn default_sse_keepalive_interval_secs() -> u64 {
+    30
+}
+
+fn default_sse_replay_window_secs() -> u64 {
+    60
+}
+
+fn default_sse_reconnect_grace_secs() -> u64 {
+    10
+}
+
+fn default_max_retry_delay_secs() -> u64 {
+    30
+}
+
+fn default_shadow_percentage() -> u8 {
+    100
+}
+
+fn default_session_ttl_secs() -> u64 {
+    3600
+}
+
+/// 查询模型的上游超时：精确匹配优先，其次最长前缀，最后回落到默认值
+pub fn resolve_model_timeout(
+    model_timeouts: &std::collections::HashMap<String, u64>,
+    default_secs: u64,
+    model: &str,
+) -> std::time::Duration {
+    let secs = model_timeouts.get(model).copied().or_else(|| {
+        model_timeouts
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, secs)| *secs)
+    });
+    std::time::Duration::from_secs(secs.unwrap_or(default_secs).max(1))
+}
+
+fn default_cors_allowed_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
 fn default_zai_base_url() -> String {
     "https://api.z.ai/api/anthropic".to_string()
 }
@@ -306,3 +1281,346 @@ impl ProxyConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy_delay_bounds() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+            jitter: true,
+        };
+        for attempt in 0..8 {
+            let expected = (100u64 << attempt).min(1000);
+            let delay = policy.delay_for(attempt).as_millis() as u64;
+            assert!(delay >= expected && delay < expected + 100, "attempt {} -> {}ms", attempt, delay);
+        }
+
+        let no_jitter = RetryPolicy { jitter: false, ..policy };
+        assert_eq!(no_jitter.delay_for(0), Duration::from_millis(100));
+        assert_eq!(no_jitter.delay_for(3), Duration::from_millis(800));
+        assert_eq!(no_jitter.delay_for(64), Duration::from_millis(1000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_policy_backoff_sleeps_within_bounds() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 500,
+            jitter: true,
+        };
+        for (attempt, expected) in [(0usize, 200u64), (1, 400), (2, 500)] {
+            let start = tokio::time::Instant::now();
+            policy.backoff(attempt).await;
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(expected));
+            assert!(elapsed < Duration::from_millis(expected + 200));
+        }
+    }
+
+    #[test]
+    fn test_resolve_model_timeout() {
+        let timeouts = std::collections::HashMap::from([
+            ("gemini-3-pro-image".to_string(), 300u64),
+            ("gemini-2.5-flash".to_string(), 30u64),
+            ("gemini-2.5-flash-lite".to_string(), 15u64),
+        ]);
+        assert_eq!(resolve_model_timeout(&timeouts, 120, "gemini-3-pro-image"), Duration::from_secs(300));
+        assert_eq!(resolve_model_timeout(&timeouts, 120, "gemini-2.5-flash"), Duration::from_secs(30));
+        // 最长前缀优先
+        assert_eq!(resolve_model_timeout(&timeouts, 120, "gemini-2.5-flash-lite-preview"), Duration::from_secs(15));
+        assert_eq!(resolve_model_timeout(&timeouts, 120, "claude-sonnet-4-5"), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_effective_racing_tokens() {
+        let mut config = ProxyConfig::default();
+        assert_eq!(config.effective_racing_tokens(), 0);
+        config.racing_enabled = true;
+        assert_eq!(config.effective_racing_tokens(), 2);
+        config.racing_tokens = 8;
+        assert_eq!(config.effective_racing_tokens(), MAX_RACING_TOKENS);
+        config.racing_tokens = 1;
+        assert_eq!(config.effective_racing_tokens(), 0);
+    }
+
+    #[test]
+    fn test_seal_api_key_never_serializes_plaintext() {
+        let mut config = ProxyConfig::default();
+        let plaintext = config.api_key.clone();
+        assert!(config.seal_api_key());
+        assert!(config.api_key.is_empty());
+        assert!(crate::proxy::api_key_hash::verify_api_key(&plaintext, &config.api_key_hash));
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains(&plaintext));
+        // 已密封的配置再次调用不会改变哈希
+        let hash = config.api_key_hash.clone();
+        assert!(!config.seal_api_key());
+        assert_eq!(config.api_key_hash, hash);
+
+        // admin_api_key 同样只保存哈希
+        config.admin_api_key = Some("adm-secret".to_string());
+        assert!(config.seal_api_key());
+        assert!(config.admin_api_key.is_none());
+        let admin_hash = config.effective_admin_api_key_hash().unwrap();
+        assert!(crate::proxy::api_key_hash::verify_api_key("adm-secret", &admin_hash));
+        assert!(!serde_json::to_string(&config).unwrap().contains("adm-secret"));
+    }
+
+    #[test]
+    fn test_restart_required_changes() {
+        let running = ProxyConfig::default();
+
+        // 热更新字段 (模型别名 / 鉴权 / 上游代理) 不需要重启
+        let mut hot = running.clone();
+        hot.model_aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+        hot.auth_mode = ProxyAuthMode::Strict;
+        hot.upstream_proxy.enabled = true;
+        assert!(running.restart_required_changes(&hot).is_empty());
+
+        let mut cold = hot.clone();
+        cold.port = 9000;
+        cold.tls_cert_path = Some(std::path::PathBuf::from("/tmp/cert.pem"));
+        assert_eq!(running.restart_required_changes(&cold), vec!["port", "tls_cert_path"]);
+    }
+
+    #[test]
+    fn test_every_field_is_classified() {
+        // 每个字段必须且只能属于热更新或需重启之一，防止新增字段既不生效也不被拦截
+        let serde_json::Value::Object(fields) = serde_json::to_value(ProxyConfig::default()).unwrap() else {
+            panic!("ProxyConfig must serialize to an object");
+        };
+        for key in fields.keys() {
+            let hot = HOT_RELOADABLE_FIELDS.contains(&key.as_str());
+            let cold = RESTART_REQUIRED_FIELDS.contains(&key.as_str());
+            assert!(hot ^ cold, "field {} must be listed in exactly one of HOT_RELOADABLE_FIELDS / RESTART_REQUIRED_FIELDS", key);
+        }
+        assert_eq!(fields.len(), HOT_RELOADABLE_FIELDS.len() + RESTART_REQUIRED_FIELDS.len());
+
+        let running = ProxyConfig::default();
+        let mut hot = running.clone();
+        hot.max_retry_delay_secs += 1;
+        hot.thinking_mode = ThinkingMode::Strip;
+        hot.strict_request_validation = true;
+        hot.max_concurrent_requests = 1;
+        hot.retry_policy.max_retries += 1;
+        hot.model_timeouts.insert("gemini-3-pro".to_string(), 30);
+        hot.retry_on_patterns.push("FAILED_PRECONDITION".to_string());
+        hot.include_cost_in_response = true;
+        assert!(running.restart_required_changes(&hot).is_empty());
+    }
+
+    #[test]
+    fn test_with_hot_fields_from() {
+        let running = ProxyConfig::default();
+        let mut profile = running.clone();
+        profile.port = 9000;
+        profile.model_aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+
+        let merged = running.with_hot_fields_from(&profile);
+        assert_eq!(merged.port, running.port);
+        assert_eq!(merged.model_aliases, profile.model_aliases);
+        assert!(running.restart_required_changes(&merged).is_empty());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = [
+            ("ANTIGRAVITY_PORT", "9000"),
+            ("ANTIGRAVITY_API_KEY", "sk-from-env"),
+            ("ANTIGRAVITY_ALLOW_LAN_ACCESS", "true"),
+            ("ANTIGRAVITY_AUTH_MODE", "strict"),
+            ("ANTIGRAVITY_IMAGE_UPLOAD_URL", "https://upload.example.com"),
+            ("ANTIGRAVITY_MODEL_ALIASES", r#"{"fast":"gemini-2.5-flash"}"#),
+            ("ANTIGRAVITY_MAX_CONCURRENT_REQUESTS", "not-a-number"),
+            ("ANTIGRAVITY_UNKNOWN_FIELD", "1"),
+            ("PORT", "1234"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let mut config = ProxyConfig::default();
+        let applied = config.apply_env_overrides(vars);
+
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.api_key, "sk-from-env");
+        assert!(config.allow_lan_access);
+        assert!(matches!(config.auth_mode, ProxyAuthMode::Strict));
+        assert_eq!(config.image_upload_url.as_deref(), Some("https://upload.example.com"));
+        assert_eq!(config.model_aliases.get("fast").map(String::as_str), Some("gemini-2.5-flash"));
+        assert_eq!(config.max_concurrent_requests, ProxyConfig::default().max_concurrent_requests);
+
+        assert!(applied.contains(&"api_key=***".to_string()));
+        assert!(applied.contains(&"port=9000".to_string()));
+        assert_eq!(applied.len(), 6);
+    }
+
+    #[test]
+    fn test_strip_env_overrides_keeps_file_values() {
+        let vars = [
+            ("ANTIGRAVITY_PORT", "9000"),
+            ("ANTIGRAVITY_API_KEY", "sk-from-env"),
+            ("ANTIGRAVITY_ALLOW_LAN_ACCESS", "true"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+
+        let persisted = ProxyConfig {
+            port: 8100,
+            api_key: String::new(),
+            api_key_hash: "file-hash".to_string(),
+            ..ProxyConfig::default()
+        };
+
+        let mut runtime = persisted.clone();
+        runtime.apply_env_overrides(vars.clone());
+        // 运行期间显式修改了被覆盖的字段，以及未被覆盖的字段
+        runtime.allow_lan_access = false;
+        runtime.request_timeout = 42;
+
+        runtime.strip_env_overrides(&persisted, vars);
+        assert_eq!(runtime.port, 8100);
+        assert!(runtime.api_key.is_empty());
+        assert_eq!(runtime.api_key_hash, "file-hash");
+        assert!(!runtime.allow_lan_access);
+        assert_eq!(runtime.request_timeout, 42);
+    }
+
+    #[test]
+    fn test_validate_default_config() {
+        assert_eq!(ProxyConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_port_range() {
+        let config = ProxyConfig {
+            port: 80,
+            ..ProxyConfig::default()
+        };
+        assert_eq!(config.validate(), Err(vec![ConfigError::InvalidPort(80)]));
+    }
+
+    #[test]
+    fn test_validate_api_key_required_when_auth_enabled() {
+        let mut config = ProxyConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            ..ProxyConfig::default()
+        };
+        config.api_key.clear();
+        assert_eq!(config.validate(), Err(vec![ConfigError::MissingApiKey]));
+
+        config.auth_mode = ProxyAuthMode::Off;
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_tls_paths() {
+        let mut config = ProxyConfig {
+            tls_cert_path: Some(PathBuf::from("/nonexistent/cert.pem")),
+            ..ProxyConfig::default()
+        };
+        assert_eq!(config.validate(), Err(vec![ConfigError::IncompleteTls]));
+
+        config.tls_key_path = Some(PathBuf::from("/nonexistent/key.pem"));
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::TlsFileNotFound(PathBuf::from("/nonexistent/cert.pem")),
+                ConfigError::TlsFileNotFound(PathBuf::from("/nonexistent/key.pem")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_alias_cycle() {
+        let mut config = ProxyConfig::default();
+        config.model_aliases.insert("fast".to_string(), "gemini-2.5-flash".to_string());
+        assert_eq!(config.validate(), Ok(()));
+
+        config.model_aliases.insert("a".to_string(), "b".to_string());
+        config.model_aliases.insert("b".to_string(), "a".to_string());
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::AliasCycle("a -> b -> a".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_validate_positive_limits() {
+        let mut config = ProxyConfig {
+            default_timeout_secs: 0,
+            ..ProxyConfig::default()
+        };
+        config.model_timeouts.insert("gemini-2.5-pro".to_string(), 0);
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::NonPositive("default_timeout_secs".to_string()),
+                ConfigError::NonPositive("model_timeouts[gemini-2.5-pro]".to_string()),
+            ])
+        );
+
+        // max_concurrent_requests 为 0 表示不限制，不视为错误
+        let unlimited = ProxyConfig {
+            max_concurrent_requests: 0,
+            ..ProxyConfig::default()
+        };
+        assert_eq!(unlimited.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_traffic_splits() {
+        let config = ProxyConfig {
+            traffic_splits: vec![
+                TrafficSplit {
+                    model_pattern: "^gpt-4o$".to_string(),
+                    variants: vec![("gemini-2.5-flash".to_string(), 70), ("gemini-3-flash".to_string(), 30)],
+                },
+                TrafficSplit {
+                    model_pattern: "^gpt".to_string(),
+                    variants: vec![("gemini-2.5-flash".to_string(), 50), ("gemini-3-flash".to_string(), 40)],
+                },
+                TrafficSplit {
+                    model_pattern: "(".to_string(),
+                    variants: vec![("gemini-2.5-flash".to_string(), 100)],
+                },
+            ],
+            ..ProxyConfig::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0],
+            ConfigError::InvalidTrafficSplit(1, "variant percentages sum to 90, expected 100".to_string())
+        );
+        assert!(matches!(&errors[1], ConfigError::InvalidTrafficSplit(2, msg) if msg.starts_with("invalid model_pattern")));
+    }
+
+    #[test]
+    fn test_validate_retry_on_patterns() {
+        let mut config = ProxyConfig::default();
+        assert_eq!(config.retry_on_patterns.len(), 4);
+        config.retry_on_patterns.push("RESOURCE_EXHAUSTED: \\d+".to_string());
+        config.retry_on_patterns.push("[unclosed".to_string());
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ConfigError::InvalidRetryPattern(5, _)));
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let config = ProxyConfig {
+            port: 0,
+            batch_max_requests: 0,
+            ..ProxyConfig::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "port 0 is out of range, expected 1024-65535");
+    }
+}
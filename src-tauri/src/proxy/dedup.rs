@@ -0,0 +1,229 @@
+// 在途请求去重 - 相同的非流式请求同时到达时只向上游发送一次，其余请求等待并共享结果
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// 请求体哈希 -> 在途请求
+pub type InFlightRequests = DashMap<u64, InFlightRequest>;
+
+/// 在途请求：保存完整请求体，哈希冲突时不会把不同请求当作重复
+pub struct InFlightRequest {
+    body: Arc<str>,
+    sender: Arc<broadcast::Sender<Value>>,
+}
+
+/// 去重键：规范化后的请求体及其 xxh3 哈希
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestKey {
+    hash: u64,
+    body: Arc<str>,
+}
+
+impl RequestKey {
+    /// serde_json 对象键有序，序列化结果稳定
+    pub fn new(body: &Value) -> Self {
+        let body = body.to_string();
+        Self {
+            hash: xxh3_64(body.as_bytes()),
+            body: body.into(),
+        }
+    }
+}
+
+/// 加入去重表后的角色
+pub enum DedupRole {
+    /// 首个请求：负责调用上游并广播结果
+    Leader(DedupLeader),
+    /// 重复请求：等待首个请求的结果
+    Follower(broadcast::Receiver<Value>),
+}
+
+/// 以 `key` 加入去重表；已有相同请求在途时订阅其结果
+pub fn join(map: &Arc<InFlightRequests>, key: RequestKey) -> DedupRole {
+    let (tx, _) = broadcast::channel(1);
+    let sender = Arc::new(tx);
+    match map.entry(key.hash) {
+        Entry::Occupied(entry) if *entry.get().body == *key.body => {
+            DedupRole::Follower(entry.get().sender.subscribe())
+        }
+        // 哈希冲突：按普通请求处理，不登记到去重表
+        Entry::Occupied(_) => {
+            tracing::debug!("Dedup hash collision on {:016x}, skipping deduplication", key.hash);
+            DedupRole::Leader(DedupLeader {
+                map: map.clone(),
+                hash: key.hash,
+                sender,
+            })
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(InFlightRequest {
+                body: key.body,
+                sender: sender.clone(),
+            });
+            DedupRole::Leader(DedupLeader {
+                map: map.clone(),
+                hash: key.hash,
+                sender,
+            })
+        }
+    }
+}
+
+/// 首个请求的句柄；未调用 complete 就被 drop 时 (如上游失败)，
+/// 等待方会收到 Closed 并自行发起请求
+pub struct DedupLeader {
+    map: Arc<InFlightRequests>,
+    hash: u64,
+    sender: Arc<broadcast::Sender<Value>>,
+}
+
+impl DedupLeader {
+    /// 广播结果给所有等待方
+    pub fn complete(self, value: Value) {
+        // 先移出表再发送：之后到达的相同请求将作为新的首个请求，不会错过结果
+        self.remove();
+        let _ = self.sender.send(value);
+    }
+
+    fn remove(&self) {
+        self.map
+            .remove_if(&self.hash, |_, entry| Arc::ptr_eq(&entry.sender, &self.sender));
+    }
+}
+
+impl Drop for DedupLeader {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::server::AppState;
+    use crate::proxy::tests::support::mock_app_state;
+    use axum::response::IntoResponse;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// 慢速 mock 上游 (每次 generateContent 耗时 200ms)，返回 (状态, 上游调用次数)
+    async fn dedup_state() -> (AppState, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:generateContent",
+            axum::routing::post(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    axum::Json(json!({
+                        "response": {
+                            "candidates": [{
+                                "content": {"role": "model", "parts": [{"text": "hello"}]},
+                                "finishReason": "STOP"
+                            }]
+                        }
+                    }))
+                }
+            }),
+        );
+        (mock_app_state("dedup@example.com", router).await, calls)
+    }
+
+    async fn chat(state: &AppState, content: &str) -> axum::response::Response {
+        let body = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": content}]
+        });
+        crate::proxy::handlers::openai::handle_chat_completions(
+            axum::extract::State(state.clone()),
+            axum::http::HeaderMap::new(),
+            axum::Json(body),
+        )
+        .await
+        .map(IntoResponse::into_response)
+        .unwrap_or_else(IntoResponse::into_response)
+    }
+
+    #[test]
+    fn test_request_key_is_key_order_independent() {
+        let a = json!({"model": "gemini-2.5-flash", "messages": [{"role": "user", "content": "hi"}]});
+        let b: Value =
+            serde_json::from_str(r#"{"messages":[{"content":"hi","role":"user"}],"model":"gemini-2.5-flash"}"#)
+                .unwrap();
+        assert_eq!(RequestKey::new(&a), RequestKey::new(&b));
+        assert_ne!(RequestKey::new(&a), RequestKey::new(&json!({"model": "gemini-2.5-flash"})));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_make_one_upstream_call() {
+        let (state, calls) = dedup_state().await;
+
+        let (a, b) = tokio::join!(chat(&state, "hi"), chat(&state, "hi"));
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(a.status(), axum::http::StatusCode::OK);
+        assert_eq!(b.status(), axum::http::StatusCode::OK);
+        let deduplicated = [&a, &b]
+            .iter()
+            .filter(|r| r.headers().get("x-deduplicated").is_some())
+            .count();
+        assert_eq!(deduplicated, 1);
+        assert!(state.in_flight_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_different_requests_are_not_deduplicated() {
+        let (state, calls) = dedup_state().await;
+
+        let (a, b) = tokio::join!(chat(&state, "a"), chat(&state, "b"));
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(a.headers().get("x-deduplicated").is_none());
+        assert!(b.headers().get("x-deduplicated").is_none());
+    }
+
+    #[test]
+    fn test_hash_collision_is_not_deduplicated() {
+        let map = Arc::new(InFlightRequests::new());
+        let a = RequestKey { hash: 42, body: r#"{"a":1}"#.into() };
+        let b = RequestKey { hash: 42, body: r#"{"b":1}"#.into() };
+
+        let _leader = match join(&map, a.clone()) {
+            DedupRole::Leader(leader) => leader,
+            DedupRole::Follower(_) => panic!("first request should lead"),
+        };
+        let collided = match join(&map, b) {
+            DedupRole::Leader(leader) => leader,
+            DedupRole::Follower(_) => panic!("different body must not follow"),
+        };
+        assert!(matches!(join(&map, a), DedupRole::Follower(_)));
+
+        // 冲突请求结束时不影响已登记的在途请求
+        drop(collided);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_follower_notified_when_leader_fails() {
+        let map = Arc::new(InFlightRequests::new());
+        let key = RequestKey::new(&json!({"messages": [{"content": "a"}]}));
+        let leader = match join(&map, key.clone()) {
+            DedupRole::Leader(leader) => leader,
+            DedupRole::Follower(_) => panic!("first request should lead"),
+        };
+        let mut rx = match join(&map, key) {
+            DedupRole::Follower(rx) => rx,
+            DedupRole::Leader(_) => panic!("second request should follow"),
+        };
+
+        drop(leader);
+        assert!(rx.recv().await.is_err());
+        assert!(map.is_empty());
+    }
+}
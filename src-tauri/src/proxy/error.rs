@@ -0,0 +1,214 @@
+// 反代上游错误类型 - 按状态码与错误体分类，调用方按变体决定重试 / 轮换账号，不再匹配错误字符串
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error, PartialEq, Serialize)]
+#[serde(tag = "type", content = "detail", rename_all = "snake_case")]
+pub enum ProxyError {
+    /// 429 限流；`retry_after` 为上游建议的等待时间 (毫秒)
+    #[error("rate limited ({status}), retry after {retry_after:?}ms")]
+    RateLimited { status: u16, retry_after: Option<u64> },
+
+    /// 401 / 403 凭据无效或无权限
+    #[error("unauthorized")]
+    Unauthorized,
+
+    /// 429 配额耗尽 (QUOTA_EXHAUSTED)，短时间内重试无意义
+    #[error("quota exhausted")]
+    QuotaExhausted,
+
+    #[error("network error: {0}")]
+    NetworkError(String),
+
+    #[error("parse error: {0}")]
+    ParseError(String),
+
+    #[error("upstream request timed out")]
+    Timeout,
+
+    #[error("upstream returned {status}: {body}")]
+    ApiError { status: u16, body: String },
+}
+
+impl ProxyError {
+    /// 按上游状态码与错误体分类
+    pub fn from_status(status: u16, body: &str) -> Self {
+        Self::from_status_with_retry_after(status, None, body)
+    }
+
+    /// 同 from_status，429 时优先使用 Retry-After 响应头，其次是错误体中的 RetryInfo
+    pub fn from_status_with_retry_after(status: u16, retry_after: Option<&str>, body: &str) -> Self {
+        use crate::proxy::upstream::retry::{parse_retry_after_header, parse_retry_delay};
+        match status {
+            401 | 403 => Self::Unauthorized,
+            429 if body.contains("QUOTA_EXHAUSTED") => Self::QuotaExhausted,
+            429 => Self::RateLimited {
+                status,
+                retry_after: retry_after
+                    .and_then(parse_retry_after_header)
+                    .or_else(|| parse_retry_delay(body)),
+            },
+            _ => Self::ApiError {
+                status,
+                body: body.to_string(),
+            },
+        }
+    }
+
+    /// 读取非 2xx 响应的错误体并分类
+    pub async fn from_response(resp: reqwest::Response) -> Self {
+        let status = resp.status().as_u16();
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = resp.text().await.unwrap_or_default();
+        Self::from_status_with_retry_after(status, retry_after.as_deref(), &body)
+    }
+
+    /// 对应的 HTTP 状态码 (网络 / 解析错误没有状态码)
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::RateLimited { status, .. } | Self::ApiError { status, .. } => Some(*status),
+            Self::Unauthorized => Some(401),
+            Self::QuotaExhausted => Some(429),
+            Self::Timeout => Some(504),
+            Self::NetworkError(_) | Self::ParseError(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProxyError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_decode() {
+            Self::ParseError(e.to_string())
+        } else {
+            Self::NetworkError(e.to_string())
+        }
+    }
+}
+
+/// OpenAI 兼容错误对象 (https://platform.openai.com/docs/guides/error-codes)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub param: Option<String>,
+    pub code: Option<String>,
+}
+
+/// 响应体外层：`{"error": {...}}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIErrorBody {
+    pub error: OpenAIError,
+}
+
+impl OpenAIError {
+    pub fn new(message: impl Into<String>, type_: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            type_: type_.into(),
+            param: None,
+            code: None,
+        }
+    }
+
+    /// 按 HTTP 状态码选择错误类型
+    pub fn for_status(status: StatusCode, message: impl Into<String>) -> Self {
+        let type_ = match status.as_u16() {
+            401 => "authentication_error",
+            403 => "permission_error",
+            404 => "not_found_error",
+            429 => "rate_limit_error",
+            400..=499 => "invalid_request_error",
+            _ => "server_error",
+        };
+        Self::new(message, type_)
+    }
+
+    pub fn with_param(mut self, param: impl Into<String>) -> Self {
+        self.param = Some(param.into());
+        self
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// 组装为 axum 响应 `(status, {"error": {...}})`
+    pub fn response(self, status: StatusCode) -> (StatusCode, Json<OpenAIErrorBody>) {
+        (status, Json(OpenAIErrorBody { error: self }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_status_classification() {
+        assert_eq!(ProxyError::from_status(401, ""), ProxyError::Unauthorized);
+        assert_eq!(ProxyError::from_status(403, "PERMISSION_DENIED"), ProxyError::Unauthorized);
+        assert_eq!(
+            ProxyError::from_status(429, r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#),
+            ProxyError::QuotaExhausted
+        );
+        assert_eq!(
+            ProxyError::from_status(429, r#"{"error":{"details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"1.5s"}]}}"#),
+            ProxyError::RateLimited { status: 429, retry_after: Some(1500) }
+        );
+        assert_eq!(
+            ProxyError::from_status(503, "overloaded"),
+            ProxyError::ApiError { status: 503, body: "overloaded".to_string() }
+        );
+        assert_eq!(ProxyError::Timeout.status(), Some(504));
+        assert_eq!(ProxyError::NetworkError("reset".to_string()).status(), None);
+    }
+
+    #[test]
+    fn test_openai_error_schema() {
+        let (status, Json(body)) = OpenAIError::for_status(StatusCode::BAD_GATEWAY, "upstream failed")
+            .with_code("upstream_error")
+            .response(StatusCode::BAD_GATEWAY);
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            serde_json::json!({
+                "error": {
+                    "message": "upstream failed",
+                    "type": "server_error",
+                    "param": null,
+                    "code": "upstream_error"
+                }
+            })
+        );
+
+        let not_found = OpenAIError::for_status(StatusCode::NOT_FOUND, "model not found").with_param("model");
+        assert_eq!(not_found.type_, "not_found_error");
+        assert_eq!(not_found.param.as_deref(), Some("model"));
+        assert_eq!(OpenAIError::for_status(StatusCode::UNPROCESSABLE_ENTITY, "").type_, "invalid_request_error");
+    }
+
+    #[tokio::test]
+    async fn test_from_response_reads_retry_after_header() {
+        use axum::{http::StatusCode, routing::get, Router};
+
+        let app = Router::new().route(
+            "/",
+            get(|| async { (StatusCode::TOO_MANY_REQUESTS, [("Retry-After", "2")], "slow down") }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(app).await;
+
+        let resp = reqwest::get(format!("{}/", base)).await.unwrap();
+        assert_eq!(
+            ProxyError::from_response(resp).await,
+            ProxyError::RateLimited { status: 429, retry_after: Some(2000) }
+        );
+    }
+}
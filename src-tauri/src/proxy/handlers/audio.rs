@@ -10,6 +10,7 @@ use uuid::Uuid;
 
 use crate::proxy::{
     audio::AudioProcessor,
+    error::OpenAIError,
     server::AppState,
 };
 
@@ -99,7 +100,7 @@ pub async fn handle_audio_transcription(
     });
 
     // 6. 获取 Token 和上游客户端
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let (access_token, project_id, email, _wait_ms) = token_manager
         .get_token("text", false, None, &model)
         .await
@@ -120,7 +121,7 @@ pub async fn handle_audio_transcription(
     // 8. 发送请求到 Gemini
     let upstream = state.upstream.clone();
     let response = upstream
-        .call_v1_internal("generateContent", &access_token, wrapped_body, None)
+        .call_v1_internal("generateContent", &access_token, wrapped_body, None, Some(state.timeout_for_model(&model)))
         .await
         .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
 
@@ -160,3 +161,31 @@ pub async fn handle_audio_transcription(
         }))
     ).into_response())
 }
+
+/// 语音合成 (OpenAI TTS API) 暂不支持：返回 501 与可操作的提示，而不是令人困惑的 404
+pub async fn handle_audio_speech() -> impl IntoResponse {
+    OpenAIError::new(
+        "Text-to-speech is not supported by this proxy. Use the official Gemini Text-to-Speech API directly.",
+        "not_implemented",
+    )
+    .response(StatusCode::NOT_IMPLEMENTED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audio_speech_not_implemented() {
+        let resp = handle_audio_speech().await.into_response();
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(resp.headers().get("content-type").unwrap(), "application/json");
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "not_implemented");
+        assert!(body["error"]["message"].as_str().unwrap().contains("Text-to-speech"));
+        assert!(body["error"]["param"].is_null());
+        assert!(body["error"]["code"].is_null());
+    }
+}
@@ -24,8 +24,6 @@ use crate::proxy::mappers::estimation_calibrator::get_calibrator;
 use axum::http::HeaderMap;
 use std::sync::{atomic::Ordering, Arc};
 
-const MAX_RETRY_ATTEMPTS: usize = 3;
-
 // ===== Model Constants for Background Tasks =====
 // These can be adjusted for performance/cost optimization or overridden by custom_mapping
 const INTERNAL_BACKGROUND_TASK: &str = "internal-background-task";  // Unified virtual ID for all background tasks
@@ -103,6 +101,7 @@ The structure MUST be as follows:
 // ===== 统一退避策略模块 =====
 // 移除本地重复定义，使用 common 中的统一实现
 use super::common::{determine_retry_strategy, apply_retry_strategy, should_rotate_account, RetryStrategy};
+use crate::proxy::error::ProxyError;
 
 // ===== 退避策略模块结束 =====
 
@@ -353,12 +352,12 @@ pub async fn handle_messages(
     
     // 3. 准备闭包
     let mut request_for_body = request.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries (e.g. stripping signatures)
     // even if the user has only 1 account.
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size.saturating_add(1)).max(2);
+    let max_attempts = state.live_config.retry_policy().max_retries.saturating_add(1).min(pool_size.saturating_add(1)).max(2);
 
     let mut last_error = String::new();
     let retried_without_thinking = false;
@@ -674,12 +673,14 @@ pub async fn handle_messages(
         }
 
         // 5. 上游调用
+        let attempt_start = std::time::Instant::now();
         let response = match upstream
-            .call_v1_internal_with_headers(method, &access_token, gemini_body, query, extra_headers.clone())
+            .call_v1_internal_with_headers(method, &access_token, gemini_body, query, extra_headers.clone(), Some(state.timeout_for_model(&request_with_mapped.model)))
             .await {
             Ok(r) => r,
             Err(e) => {
-                last_error = e.clone();
+                token_manager.record_request(&email, &request_with_mapped.model, attempt_start.elapsed().as_millis() as u64, Some(&e));
+                last_error = e.to_string();
                 debug!("Request failed on attempt {}/{}: {}", attempt + 1, max_attempts, e);
                 continue;
             }
@@ -692,6 +693,7 @@ pub async fn handle_messages(
         if status.is_success() {
             // [智能限流] 请求成功，重置该账号的连续失败计数
             token_manager.mark_account_success(&email);
+            token_manager.record_request(&email, &request_with_mapped.model, attempt_start.elapsed().as_millis() as u64, None);
             
                 // Determine context limit based on model
                 let context_limit = crate::proxy::mappers::claude::utils::get_context_limit_for_model(&request_with_mapped.model);
@@ -893,6 +895,8 @@ pub async fn handle_messages(
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status));
         last_error = format!("HTTP {}: {}", status_code, error_text);
         debug!("[{}] Upstream Error Response: {}", trace_id, error_text);
+        let upstream_error = ProxyError::from_status_with_retry_after(status_code, retry_after.as_deref(), &error_text);
+        token_manager.record_request(&email, &request_with_mapped.model, attempt_start.elapsed().as_millis() as u64, Some(&upstream_error));
         
         // 3. 标记限流状态(用于 UI 显示) - 使用异步版本以支持实时配额刷新
         // 🆕 传入实际使用的模型,实现模型级别限流,避免不同模型配额互相影响
@@ -997,6 +1001,7 @@ pub async fn handle_messages(
             // 不要使用 determine_retry_strategy，因为它会因为 retried_without_thinking=true 而返回 NoRetry
             if apply_retry_strategy(
                 RetryStrategy::FixedDelay(Duration::from_millis(200)), 
+                &state.live_config.retry_policy(),
                 attempt, 
                 max_attempts,
                 status_code, 
@@ -1012,12 +1017,17 @@ pub async fn handle_messages(
         
         
         // 确定重试策略
-        let strategy = determine_retry_strategy(status_code, &error_text, retried_without_thinking);
+        let strategy = determine_retry_strategy(
+            &upstream_error,
+            retried_without_thinking,
+            state.live_config.max_retry_delay(),
+            &state.live_config.retry_patterns(),
+        );
         
         // 执行退避
-        if apply_retry_strategy(strategy, attempt, max_attempts, status_code, &trace_id).await {
+        if apply_retry_strategy(strategy, &state.live_config.retry_policy(), attempt, max_attempts, status_code, &trace_id).await {
             // 判断是否需要轮换账号
-            if !should_rotate_account(status_code) {
+            if !should_rotate_account(&upstream_error) {
                 debug!("[{}] Keeping same account for status {} (server-side issue)", trace_id, status_code);
             }
             continue;
@@ -2,8 +2,31 @@ use tokio::time::{sleep, Duration};
 use tracing::{debug, info};
 use axum::{http::StatusCode, response::{IntoResponse, Response}, Json, extract::State};
 use serde_json::{json, Value};
+use crate::proxy::config::RetryPolicy;
+use crate::proxy::error::ProxyError;
 use crate::proxy::server::AppState;
 
+/// 提取需要透传到上游的请求头 (名称以 `prefix` 开头，原样保留名称与值)
+/// `prefix` 为空时不透传任何请求头
+pub fn collect_forwarded_headers(
+    headers: &axum::http::HeaderMap,
+    prefix: &str,
+) -> std::collections::HashMap<String, String> {
+    if prefix.is_empty() {
+        return std::collections::HashMap::new();
+    }
+    headers
+        .iter()
+        .filter(|(name, _)| name.as_str().starts_with(prefix))
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|v| (name.as_str().to_string(), v.to_string()))
+        })
+        .collect()
+}
+
 // ===== 统一重试与退避策略 =====
 
 /// 重试策略枚举
@@ -13,58 +36,70 @@ pub enum RetryStrategy {
     NoRetry,
     /// 固定延迟
     FixedDelay(Duration),
-    /// 线性退避：base_ms * (attempt + 1)
-    LinearBackoff { base_ms: u64 },
-    /// 指数退避：base_ms * 2^attempt，上限 max_ms
-    ExponentialBackoff { base_ms: u64, max_ms: u64 },
+    /// 按配置的 RetryPolicy 退避 (指数退避 + 抖动)
+    Backoff,
+}
+
+/// 内置的 400 可重试错误匹配规则 (Thinking 签名失效)，也是 retry_on_patterns 的默认值
+pub const BUILT_IN_RETRY_PATTERNS: &[&str] = &[
+    r"Invalid `signature`",
+    r"thinking\.signature",
+    r"thinking\.thinking",
+    r"Corrupted thought signature",
+];
+
+/// 编译 retry_on_patterns；非法规则跳过 (配置校验阶段已报告)，列表为空时回退到内置规则
+pub fn compile_retry_patterns(patterns: &[String]) -> Vec<regex::Regex> {
+    if patterns.is_empty() {
+        return BUILT_IN_RETRY_PATTERNS
+            .iter()
+            .filter_map(|p| regex::Regex::new(p).ok())
+            .collect();
+    }
+    patterns
+        .iter()
+        .filter_map(|p| match regex::Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("忽略非法的重试匹配规则 {:?}: {}", p, e);
+                None
+            }
+        })
+        .collect()
 }
 
-/// 根据错误状态码和错误信息确定重试策略
+/// 根据上游错误类型确定重试策略
+/// 服务端建议的等待时间 (Retry-After / RetryInfo) 最长为 `max_delay`，400 错误体匹配 `retry_patterns` 任一规则时重试一次
 pub fn determine_retry_strategy(
-    status_code: u16,
-    error_text: &str,
+    error: &ProxyError,
     retried_without_thinking: bool,
+    max_delay: Duration,
+    retry_patterns: &[regex::Regex],
 ) -> RetryStrategy {
-    match status_code {
-        // 400 错误：仅在特定 Thinking 签名失败时重试一次
-        400 if !retried_without_thinking
-            && (error_text.contains("Invalid `signature`")
-                || error_text.contains("thinking.signature")
-                || error_text.contains("thinking.thinking")
-                || error_text.contains("Corrupted thought signature")) =>
+    match error {
+        // 400 错误：仅在匹配可重试规则 (默认为 Thinking 签名失败) 时重试一次
+        ProxyError::ApiError { status: 400, body }
+            if !retried_without_thinking && retry_patterns.iter().any(|re| re.is_match(body)) =>
         {
             RetryStrategy::FixedDelay(Duration::from_millis(200))
         }
 
-        // 429 限流错误
-        429 => {
-            // 优先使用服务端返回的 Retry-After
-            if let Some(delay_ms) = crate::proxy::upstream::retry::parse_retry_delay(error_text) {
-                let actual_delay = delay_ms.saturating_add(200).min(30_000); // 上限上调至 30s
-                RetryStrategy::FixedDelay(Duration::from_millis(actual_delay))
-            } else {
-                // 否则使用线性退避：起始 5s，逐步增加
-                RetryStrategy::LinearBackoff { base_ms: 5000 }
-            }
+        // 429 限流错误：优先使用服务端返回的 Retry-After
+        ProxyError::RateLimited { retry_after: Some(delay_ms), .. } => {
+            let actual_delay = delay_ms.saturating_add(200).min(max_delay.as_millis() as u64);
+            RetryStrategy::FixedDelay(Duration::from_millis(actual_delay))
         }
 
-        // 503 服务不可用 / 529 服务器过载
-        503 | 529 => {
-            // 指数退避：起始 10s，上限 60s (针对 Google 边缘节点过载)
-            RetryStrategy::ExponentialBackoff {
-                base_ms: 10000,
-                max_ms: 60000,
-            }
+        // 否则按 RetryPolicy 退避 (配额耗尽时同样退避并轮换账号)
+        ProxyError::RateLimited { retry_after: None, .. } | ProxyError::QuotaExhausted => {
+            RetryStrategy::Backoff
         }
 
-        // 500 服务器内部错误
-        500 => {
-            // 线性退避：起始 3s
-            RetryStrategy::LinearBackoff { base_ms: 3000 }
-        }
+        // 500 服务器内部错误 / 503 服务不可用 / 529 服务器过载
+        ProxyError::ApiError { status: 500 | 503 | 529, .. } => RetryStrategy::Backoff,
 
         // 401/403 认证/权限错误：切换账号前给予极短缓冲
-        401 | 403 => RetryStrategy::FixedDelay(Duration::from_millis(200)),
+        ProxyError::Unauthorized => RetryStrategy::FixedDelay(Duration::from_millis(200)),
 
         // 其他错误：不重试
         _ => RetryStrategy::NoRetry,
@@ -74,6 +109,7 @@ pub fn determine_retry_strategy(
 /// 执行退避策略并返回是否应该继续重试
 pub async fn apply_retry_strategy(
     strategy: RetryStrategy,
+    policy: &RetryPolicy,
     attempt: usize,
     max_attempts: usize,
     status_code: u16,
@@ -99,43 +135,31 @@ pub async fn apply_retry_strategy(
             true
         }
 
-        RetryStrategy::LinearBackoff { base_ms } => {
-            let calculated_ms = base_ms * (attempt as u64 + 1);
+        RetryStrategy::Backoff => {
+            let delay = policy.delay_for(attempt);
             info!(
-                "[{}] ⏱️ Retry with linear backoff: status={}, attempt={}/{}, delay={}ms",
+                "[{}] ⏱️ Retry with backoff: status={}, attempt={}/{}, delay={}ms",
                 trace_id,
                 status_code,
                 attempt + 1,
                 max_attempts,
-                calculated_ms
+                delay.as_millis()
             );
-            sleep(Duration::from_millis(calculated_ms)).await;
-            true
-        }
-
-        RetryStrategy::ExponentialBackoff { base_ms, max_ms } => {
-            let calculated_ms = (base_ms * 2_u64.pow(attempt as u32)).min(max_ms);
-            info!(
-                "[{}] ⏱️ Retry with exponential backoff: status={}, attempt={}/{}, delay={}ms",
-                trace_id,
-                status_code,
-                attempt + 1,
-                max_attempts,
-                calculated_ms
-            );
-            sleep(Duration::from_millis(calculated_ms)).await;
+            sleep(delay).await;
             true
         }
     }
 }
 
 /// 判断是否应该轮换账号
-pub fn should_rotate_account(status_code: u16) -> bool {
-    match status_code {
+pub fn should_rotate_account(error: &ProxyError) -> bool {
+    match error {
         // 这些错误是账号级别或特定节点配额的，需要轮换
-        429 | 401 | 403 | 500 => true,
+        ProxyError::RateLimited { .. }
+        | ProxyError::QuotaExhausted
+        | ProxyError::Unauthorized
+        | ProxyError::ApiError { status: 500, .. } => true,
         // 这些错误通常是协议或服务端全局性、甚至参数错误的，轮换账号通常无意义
-        400 | 503 | 529 => false,
         _ => false,
     }
 }
@@ -186,3 +210,122 @@ pub async fn handle_detect_model(
 
     Json(response).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, HeaderValue};
+
+    const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    fn built_in_strategy(error: &ProxyError, retried_without_thinking: bool) -> RetryStrategy {
+        determine_retry_strategy(error, retried_without_thinking, MAX_RETRY_DELAY, &compile_retry_patterns(&[]))
+    }
+
+    #[test]
+    fn test_collect_forwarded_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-custom-b3-traceid", HeaderValue::from_static("abc123"));
+        headers.insert("X-Custom-Tenant", HeaderValue::from_static("acme"));
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        headers.insert("x-request-id", HeaderValue::from_static("req-1"));
+
+        let forwarded = collect_forwarded_headers(&headers, "x-custom-");
+        assert_eq!(forwarded.len(), 2);
+        assert_eq!(forwarded["x-custom-b3-traceid"], "abc123");
+        assert_eq!(forwarded["x-custom-tenant"], "acme");
+
+        assert!(collect_forwarded_headers(&headers, "").is_empty());
+    }
+
+    #[test]
+    fn test_retry_strategy_by_error_variant() {
+        let signature = ProxyError::from_status(400, "Invalid `signature` in thinking block");
+        assert!(matches!(built_in_strategy(&signature, false), RetryStrategy::FixedDelay(_)));
+        assert!(matches!(built_in_strategy(&signature, true), RetryStrategy::NoRetry));
+
+        let limited = ProxyError::RateLimited { status: 429, retry_after: Some(60_000) };
+        assert!(matches!(
+            built_in_strategy(&limited, false),
+            RetryStrategy::FixedDelay(d) if d == Duration::from_millis(30_000)
+        ));
+        assert!(matches!(
+            built_in_strategy(&ProxyError::QuotaExhausted, false),
+            RetryStrategy::Backoff
+        ));
+        assert!(matches!(
+            built_in_strategy(&ProxyError::from_status(529, ""), false),
+            RetryStrategy::Backoff
+        ));
+        assert!(matches!(built_in_strategy(&ProxyError::Timeout, false), RetryStrategy::NoRetry));
+
+        assert!(should_rotate_account(&ProxyError::Unauthorized));
+        assert!(should_rotate_account(&limited));
+        assert!(!should_rotate_account(&ProxyError::from_status(503, "")));
+    }
+
+    #[test]
+    fn test_custom_retry_patterns() {
+        let built_in = compile_retry_patterns(&[]);
+        assert_eq!(built_in.len(), BUILT_IN_RETRY_PATTERNS.len());
+        for body in ["Invalid `signature` in block", "bad thinking.signature", "Corrupted thought signature"] {
+            let err = ProxyError::from_status(400, body);
+            assert!(matches!(
+                determine_retry_strategy(&err, false, MAX_RETRY_DELAY, &built_in),
+                RetryStrategy::FixedDelay(_)
+            ));
+        }
+        // 内置规则按正则转义，'.' 不匹配任意字符
+        let err = ProxyError::from_status(400, "thinkingXsignature");
+        assert!(matches!(
+            determine_retry_strategy(&err, false, MAX_RETRY_DELAY, &built_in),
+            RetryStrategy::NoRetry
+        ));
+
+        // 运维新增的错误码，非法规则被跳过
+        let custom = compile_retry_patterns(&["FAILED_PRECONDITION: code \\d+".to_string(), "(".to_string()]);
+        assert_eq!(custom.len(), 1);
+        let err = ProxyError::from_status(400, "FAILED_PRECONDITION: code 42");
+        assert!(matches!(
+            determine_retry_strategy(&err, false, MAX_RETRY_DELAY, &custom),
+            RetryStrategy::FixedDelay(_)
+        ));
+        let signature = ProxyError::from_status(400, "Invalid `signature`");
+        assert!(matches!(
+            determine_retry_strategy(&signature, false, MAX_RETRY_DELAY, &custom),
+            RetryStrategy::NoRetry
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after_header_delays_next_attempt() {
+        let error = ProxyError::from_status_with_retry_after(429, Some("2"), "");
+        let strategy = determine_retry_strategy(&error, false, Duration::from_secs(30), &[]);
+        assert!(matches!(strategy, RetryStrategy::FixedDelay(d) if d == Duration::from_millis(2200)));
+
+        let started = tokio::time::Instant::now();
+        assert!(apply_retry_strategy(strategy, &RetryPolicy::default(), 0, 3, 429, "t").await);
+        assert!(started.elapsed() >= Duration::from_secs(2));
+
+        // 超过 max_delay 时截断
+        let long = ProxyError::from_status_with_retry_after(429, Some("120"), "");
+        assert!(matches!(
+            determine_retry_strategy(&long, false, Duration::from_secs(5), &[]),
+            RetryStrategy::FixedDelay(d) if d == Duration::from_secs(5)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff_uses_configured_policy() {
+        let policy = RetryPolicy { max_retries: 3, base_delay_ms: 100, max_delay_ms: 300, jitter: false };
+        let strategy = built_in_strategy(&ProxyError::from_status(503, ""), false);
+
+        let started = tokio::time::Instant::now();
+        assert!(apply_retry_strategy(strategy.clone(), &policy, 1, 4, 503, "t").await);
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+
+        let started = tokio::time::Instant::now();
+        assert!(apply_retry_strategy(strategy, &policy, 3, 4, 503, "t").await);
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+    }
+}
@@ -7,10 +7,9 @@ use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
 use crate::proxy::server::AppState;
 use crate::proxy::session_manager::SessionManager;
 use crate::proxy::handlers::common::{determine_retry_strategy, apply_retry_strategy, should_rotate_account, RetryStrategy};
+use crate::proxy::error::ProxyError;
 use tokio::time::Duration;
  
-const MAX_RETRY_ATTEMPTS: usize = 3;
- 
 /// 处理 generateContent 和 streamGenerateContent
 /// 路径参数: model_name, method (e.g. "gemini-pro", "generateContent")
 pub async fn handle_generate(
@@ -42,9 +41,9 @@ pub async fn handle_generate(
 
     // 2. 获取 UpstreamClient 和 TokenManager
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
+    let max_attempts = state.live_config.retry_policy().max_retries.saturating_add(1).min(pool_size).max(1);
     
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
@@ -99,12 +98,14 @@ pub async fn handle_generate(
         let query_string = if is_stream { Some("alt=sse") } else { None };
         let upstream_method = if is_stream { "streamGenerateContent" } else { "generateContent" };
 
+        let attempt_start = std::time::Instant::now();
         let response = match upstream
-            .call_v1_internal(upstream_method, &access_token, wrapped_body, query_string)
+            .call_v1_internal(upstream_method, &access_token, wrapped_body, query_string, Some(state.timeout_for_model(&mapped_model)))
             .await {
                 Ok(r) => r,
                 Err(e) => {
-                    last_error = e.clone();
+                    token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, Some(&e));
+                    last_error = e.to_string();
                     debug!("Gemini Request failed on attempt {}/{}: {}", attempt + 1, max_attempts, e);
                     continue;
                 }
@@ -153,6 +154,8 @@ pub async fn handle_generate(
                     }
                 }
 
+                let peek_error = retry_gemini.then(|| ProxyError::NetworkError(last_error.clone()));
+                token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, peek_error.as_ref());
                 if retry_gemini {
                     continue;
                 }
@@ -276,6 +279,7 @@ pub async fn handle_generate(
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, None);
 
             // [FIX #765] Extract thoughtSignature from non-streaming response
             let inner_val = if gemini_resp.get("response").is_some() {
@@ -311,13 +315,15 @@ pub async fn handle_generate(
         last_error = format!("HTTP {}: {}", status_code, error_text);
  
         // 确定重试策略
-        let strategy = determine_retry_strategy(status_code, &error_text, false);
+        let upstream_error = ProxyError::from_status_with_retry_after(status_code, retry_after.as_deref(), &error_text);
+        token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, Some(&upstream_error));
+        let strategy = determine_retry_strategy(&upstream_error, false, state.live_config.max_retry_delay(), &state.live_config.retry_patterns());
         let trace_id = format!("gemini_{}", session_id);
 
         // 执行退避
-        if apply_retry_strategy(strategy, attempt, max_attempts, status_code, &trace_id).await {
+        if apply_retry_strategy(strategy, &state.live_config.retry_policy(), attempt, max_attempts, status_code, &trace_id).await {
             // 判断是否需要轮换账号
-            if !should_rotate_account(status_code) {
+            if !should_rotate_account(&upstream_error) {
                 debug!("[{}] Keeping same account for status {} (Gemini server-side issue)", trace_id, status_code);
             }
             continue;
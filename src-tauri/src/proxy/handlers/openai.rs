@@ -1,29 +1,62 @@
 // OpenAI Handler
 use axum::{
-    extract::Json, extract::State, http::StatusCode, response::IntoResponse, response::Response,
+    extract::Json, extract::Path, extract::State, http::StatusCode, response::IntoResponse, response::Response,
 };
 use base64::Engine as _;
 use bytes::Bytes;
 use serde_json::{json, Value};
-use tracing::{debug, error, info}; // Import Engine trait for encode method
+use tracing::{debug, error, info, Instrument}; // Import Engine trait for encode method
 
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIRequest,
+    inline_remote_images, strip_unsupported_penalties, transform_openai_request,
+    transform_openai_response, validate_audio_inputs, validate_inline_image_sizes,
+    validate_safety_settings, validate_sampling_params, OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
 use crate::proxy::server::AppState;
 
-const MAX_RETRY_ATTEMPTS: usize = 3;
 use super::common::{
-    apply_retry_strategy, determine_retry_strategy, should_rotate_account, RetryStrategy,
+    apply_retry_strategy, determine_retry_strategy, should_rotate_account,
+    RetryStrategy,
 };
+use crate::proxy::error::{OpenAIError, OpenAIErrorBody, ProxyError};
 use crate::proxy::session_manager::SessionManager;
 use tokio::time::Duration;
 
+/// 错误响应统一使用 OpenAI 错误格式 `(status, {"error": {...}})`
+type OpenAIErrorResponse = (StatusCode, Json<OpenAIErrorBody>);
+
+fn openai_error(status: StatusCode, message: impl Into<String>) -> OpenAIErrorResponse {
+    OpenAIError::for_status(status, message).response(status)
+}
+
+#[tracing::instrument(name = "chat_completions", skip_all, fields(model = tracing::field::Empty))]
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
     Json(mut body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, OpenAIErrorResponse> {
+    // [NEW] 在途请求计数，流式响应会在 body 结束时才释放
+    let in_flight = crate::proxy::inflight::InFlightGuard::new(&state.in_flight);
+
+    // [NEW] SSE 断线重连：缓冲仍在时补发缺失事件并继续跟随原响应，不再重新请求上游
+    if let Some(last_event_id) = headers.get("last-event-id").and_then(|v| v.to_str().ok()) {
+        if let Some(stream) = crate::proxy::sse_replay::resume(&state.sse_replays, last_event_id) {
+            info!("Resuming SSE stream from Last-Event-ID {}", last_event_id);
+            return Ok(axum::response::Response::builder()
+                .header("Content-Type", "text/event-stream")
+                .header("Cache-Control", "no-cache")
+                .header("Connection", "keep-alive")
+                .header("X-Accel-Buffering", "no")
+                .body(axum::body::Body::from_stream(futures::StreamExt::map(stream, move |chunk| {
+                    let _guard = &in_flight;
+                    chunk
+                })))
+                .unwrap()
+                .into_response());
+        }
+    }
+
     // [NEW] 自动检测并转换 Responses 格式
     // 如果请求包含 instructions 或 input 但没有 messages，则认为是 Responses 格式
     let is_responses_format = !body.get("messages").is_some()
@@ -73,8 +106,23 @@ pub async fn handle_chat_completions(
         }
     }
 
-    let mut openai_req: OpenAIRequest = serde_json::from_value(body)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    let dedup_key = crate::proxy::dedup::RequestKey::new(&body);
+    // [NEW] 客户端显式会话 ID：同一会话固定使用同一账号
+    let explicit_session_id = crate::proxy::session_affinity::explicit_session_id(&headers, &body);
+    let mut openai_req = parse_chat_request(body, state.live_config.strict_request_validation())?;
+
+    // 模型别名解析 (别名指向未知模型时直接返回 400)
+    apply_model_alias(&state, &mut openai_req)
+        .await
+        .map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+    tracing::Span::current().record("model", openai_req.model.as_str());
+    validate_sampling_params(&openai_req).map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+    validate_safety_settings(&openai_req).map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+    // 远程图片 URL 下载后内联为 base64 (上游无法访问任意外部 URL)
+    inline_remote_images(&mut openai_req).await;
+    validate_inline_image_sizes(&openai_req, state.max_inline_image_bytes)
+        .map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+    validate_audio_inputs(&openai_req).map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
 
     // Safety: Ensure messages is not empty
     if openai_req.messages.is_empty() {
@@ -99,12 +147,433 @@ pub async fn handle_chat_completions(
         trace_id, openai_req.model, openai_req.messages.len(), openai_req.stream
     );
 
+    // [NEW] 按前缀透传客户端请求头 (如链路追踪头)
+    let forwarded_headers =
+        super::common::collect_forwarded_headers(&headers, &state.forwarded_header_prefix);
+    // [NEW] 客户端声明续传时断线后保留上游一段时间等待重连
+    let reconnect_grace = crate::proxy::sse_replay::reconnect_grace(
+        &headers,
+        Duration::from_secs(state.sse_reconnect_grace_secs),
+    );
+
+    // [NEW] 相同的非流式请求在途时直接等待其结果，不再重复调用上游
+    // 首个请求失败时等待方会收到 Closed，此时按普通请求继续处理
+    let dedup_leader = if openai_req.stream {
+        None
+    } else {
+        use crate::proxy::dedup::DedupRole;
+        match crate::proxy::dedup::join(&state.in_flight_requests, dedup_key) {
+            DedupRole::Leader(leader) => Some(leader),
+            DedupRole::Follower(mut rx) => match rx.recv().await {
+                Ok(value) => {
+                    info!("[{}] Identical request in flight, reusing its response", trace_id);
+                    return Ok((StatusCode::OK, [("X-Deduplicated", "true")], Json(value)).into_response());
+                }
+                Err(_) => None,
+            },
+        }
+    };
+
+    // [NEW] 并发上限：无可用许可时立即返回 503，避免所有账号被同时打满
+    let _permit = match try_acquire_request_permit(&state.live_config.request_semaphore()) {
+        Ok(permit) => permit,
+        Err(resp) => {
+            tracing::warn!("[{}] Concurrent request limit reached, rejecting with 503", trace_id);
+            return Ok(*resp);
+        }
+    };
+
+    // [NEW] 流量拆分：按配置的百分比改用变体模型 (实际模型经 x-model-used 返回)
+    if let Some(variant) = state.live_config.traffic_splitter().select(&openai_req.model) {
+        info!("[{}] Traffic split: {} -> {}", trace_id, openai_req.model, variant);
+        openai_req.model = variant;
+    }
+
+    // [NEW] 模型降级链：主模型返回非配额类错误时，按顺序改用 model_fallbacks 中的备选模型
+    let fallbacks = state
+        .model_fallbacks
+        .read()
+        .await
+        .get(&openai_req.model)
+        .cloned()
+        .unwrap_or_default();
+
+    // [NEW] 请求竞速：同时发往多个账号，采用最先返回的结果；不可用时回落到常规流程
+    let raced = if state.racing_tokens >= 2 {
+        super::racing::race_chat_request(
+            &state,
+            &openai_req,
+            &forwarded_headers,
+            &trace_id,
+            state.racing_tokens,
+            reconnect_grace,
+        )
+        .await
+    } else {
+        None
+    };
+    let mut response = match raced {
+        Some(response) => response,
+        None => process_chat_request(
+            &state,
+            openai_req.clone(),
+            explicit_session_id.as_deref(),
+            &forwarded_headers,
+            &trace_id,
+            reconnect_grace,
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response),
+    };
+    for fallback in fallbacks {
+        if !should_fallback(response.status()) {
+            break;
+        }
+        tracing::warn!(
+            "[{}] Model {} failed with {}, falling back to {}",
+            trace_id,
+            openai_req.model,
+            response.status(),
+            fallback
+        );
+        openai_req.model = fallback;
+        response = process_chat_request(
+            &state,
+            openai_req.clone(),
+            explicit_session_id.as_deref(),
+            &forwarded_headers,
+            &trace_id,
+            reconnect_grace,
+        )
+        .await
+        .unwrap_or_else(IntoResponse::into_response);
+    }
+
+    // [NEW] 按能力改道：直接重试与降级链均失败且当前模型已无可用账号时，改用具备相同能力的模型
+    if state.capability_routing && !response.status().is_success() {
+        if let Some(alternative) = find_capability_alternative(&state, &openai_req.model).await {
+            tracing::warn!(
+                "[{}] Model {} unavailable on all accounts, rerouting to {} by capability",
+                trace_id,
+                openai_req.model,
+                alternative
+            );
+            openai_req.model = alternative.to_string();
+            response = process_chat_request(
+                &state,
+                openai_req.clone(),
+                explicit_session_id.as_deref(),
+                &forwarded_headers,
+                &trace_id,
+                reconnect_grace,
+            )
+            .await
+            .unwrap_or_else(IntoResponse::into_response);
+        }
+    }
+
+    if let Ok(model_used) = axum::http::HeaderValue::from_str(&openai_req.model) {
+        response.headers_mut().insert(
+            axum::http::HeaderName::from_static("x-model-used"),
+            model_used,
+        );
+    }
+
+    // 非流式成功结果广播给等待中的相同请求
+    if let Some(leader) = dedup_leader {
+        if response.status().is_success() {
+            let (parts, body) = response.into_parts();
+            let bytes = axum::body::to_bytes(body, usize::MAX)
+                .await
+                .map_err(|e| openai_error(StatusCode::BAD_GATEWAY, format!("Read response error: {}", e)))?;
+            if let Ok(value) = serde_json::from_slice::<Value>(&bytes) {
+                leader.complete(value);
+            }
+            response = Response::from_parts(parts, axum::body::Body::from(bytes));
+        }
+    }
+
+    // 将在途守卫绑定到响应体，SSE 流结束 (或客户端断开) 后才计数 -1
+    let (parts, body) = response.into_parts();
+    let guarded_body = futures::StreamExt::map(body.into_data_stream(), move |chunk| {
+        let _guard = &in_flight;
+        chunk
+    });
+    Ok(Response::from_parts(parts, axum::body::Body::from_stream(guarded_body)))
+}
+
+/// [P1 FIX] Enhanced Peek logic to handle heartbeats and slow start
+/// Pre-read until we find meaningful content, skip heartbeats
+/// 返回 Err 时应换号重试
+pub(crate) async fn peek_first_chunk(
+    openai_stream: &mut std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>>,
+) -> Result<Bytes, String> {
+    use futures::StreamExt;
+
+    // Loop to skip heartbeats during peek
+    loop {
+        match tokio::time::timeout(std::time::Duration::from_secs(60), openai_stream.next()).await {
+            Ok(Some(Ok(bytes))) => {
+                if bytes.is_empty() {
+                    continue;
+                }
+
+                let text = String::from_utf8_lossy(&bytes);
+                // Skip SSE comments/pings (heartbeats)
+                if text.trim().starts_with(":") || text.trim().starts_with("data: :") {
+                    tracing::debug!("[OpenAI] Skipping peek heartbeat");
+                    continue;
+                }
+
+                // Check for error events
+                if text.contains("\"error\"") {
+                    tracing::warn!("[OpenAI] Error detected during peek, retrying...");
+                    return Err("Error event during peek".to_string());
+                }
+
+                // We found real data!
+                return Ok(bytes);
+            }
+            Ok(Some(Err(e))) => {
+                tracing::warn!("[OpenAI] Stream error during peek: {}, retrying...", e);
+                return Err(format!("Stream error during peek: {}", e));
+            }
+            Ok(None) => {
+                tracing::warn!("[OpenAI] Stream ended during peek (Empty Response), retrying...");
+                return Err("Empty response stream during peek".to_string());
+            }
+            Err(_) => {
+                tracing::warn!("[OpenAI] Timeout waiting for first data (60s), retrying...");
+                return Err("Timeout waiting for first data".to_string());
+            }
+        }
+    }
+}
+
+/// 将已预读首个 chunk 的 OpenAI SSE 流转为最终响应
+/// - 客户端流式: 返回 SSE (按 min_chunk_bytes 合并碎片)
+/// - 客户端非流式: 收集流数据并聚合为 JSON
+pub(crate) async fn respond_with_openai_stream(
+    state: &AppState,
+    openai_req: &OpenAIRequest,
+    first_chunk: Bytes,
+    openai_stream: std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>>,
+    email: &str,
+    mapped_model: &str,
+    trace_id: &str,
+    reconnect_grace: Duration,
+) -> Response {
+    use axum::body::Body;
+    use futures::StreamExt;
+
+    // Combine first chunk with remaining stream
+    let combined_stream =
+        futures::stream::once(async move { Ok::<Bytes, String>(first_chunk) }).chain(openai_stream);
+
+    if openai_req.stream {
+        // [NEW] 记录分块间隔与流总时长 (Prometheus 直方图)
+        let mut sse_stream: std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>> =
+            Box::pin(crate::proxy::metrics::instrument_stream(mapped_model, combined_stream));
+        // [NEW] 按 thinking_mode 移除思考内容
+        sse_stream = crate::proxy::mappers::openai::thinking::strip_thinking_from_sse(sse_stream, &state.live_config.thinking_mode());
+        // [NEW] 配置了图片上传地址时，将响应中的 base64 图片替换为外链
+        if let Some(upload_url) = &state.image_upload_url {
+            sse_stream = crate::proxy::mappers::openai::media::upload_images_in_sse_stream(
+                sse_stream,
+                state.upstream.http_client().clone(),
+                upload_url.clone(),
+            );
+        }
+
+        // 客户端请求流式，返回 SSE
+        // [NEW] 按 min_chunk_bytes 合并过碎的文本增量 (0 = 不缓冲)
+        let min_chunk_bytes = state.experimental.read().await.min_chunk_bytes;
+        let mut buffered_stream = crate::proxy::mappers::openai::chunk_buffer::buffer_sse_chunks(
+            sse_stream,
+            min_chunk_bytes,
+        );
+        // [NEW] 事件编号并记录，客户端断线后可凭 Last-Event-ID 续传
+        if state.sse_replay_window_secs > 0 {
+            buffered_stream = crate::proxy::sse_replay::record(
+                &state.sse_replays,
+                buffered_stream,
+                Duration::from_secs(state.sse_replay_window_secs),
+                reconnect_grace,
+            );
+        }
+        let body = Body::from_stream(buffered_stream);
+        return Response::builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("Connection", "keep-alive")
+            .header("X-Accel-Buffering", "no")
+            .header("X-Account-Email", email)
+            .header("X-Mapped-Model", mapped_model)
+            .body(body)
+            .unwrap()
+            .into_response();
+    }
+
+    // 客户端请求非流式，但内部强制转为流式
+    use crate::proxy::mappers::openai::collector::collect_stream_to_json;
+
+    match collect_stream_to_json(Box::pin(combined_stream)).await {
+        Ok(mut full_response) => {
+            info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
+            // 非流式响应始终携带 usage，便于 LiteLLM 等客户端统计
+            full_response
+                .usage
+                .get_or_insert_with(crate::proxy::mappers::openai::OpenAIUsage::default);
+            if let Some(upload_url) = &state.image_upload_url {
+                crate::proxy::mappers::openai::media::upload_images_in_response(
+                    &mut full_response,
+                    state.upstream.http_client(),
+                    upload_url,
+                )
+                .await;
+            }
+            if openai_req.is_json_mode() {
+                warn_if_not_json(&full_response, trace_id);
+            }
+            // [NEW] 按 thinking_mode 处理思考内容
+            let thoughts = crate::proxy::mappers::openai::thinking::apply_thinking_mode(
+                &mut full_response,
+                &state.live_config.thinking_mode(),
+            );
+            // [NEW] 按模型单价估算费用
+            let estimated_cost = full_response
+                .usage
+                .as_ref()
+                .filter(|_| state.live_config.include_cost_in_response())
+                .and_then(|u| {
+                    crate::proxy::pricing::estimate_cost_usd(
+                        mapped_model,
+                        u.prompt_tokens as u64,
+                        u.completion_tokens as u64,
+                    )
+                });
+            let mut response = (
+                StatusCode::OK,
+                [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+                Json(full_response),
+            )
+                .into_response();
+            if let Some(cost) = estimated_cost {
+                if let Ok(value) = crate::proxy::pricing::format_cost(cost).parse() {
+                    response.headers_mut().insert("x-estimated-cost", value);
+                }
+            }
+            if !thoughts.is_empty() {
+                let encoded = crate::proxy::mappers::openai::thinking::thinking_header_value(&thoughts);
+                match axum::http::HeaderValue::from_str(&encoded) {
+                    Ok(value) => {
+                        response
+                            .headers_mut()
+                            .insert(crate::proxy::mappers::openai::thinking::THINKING_HEADER, value);
+                    }
+                    Err(e) => tracing::warn!("[{}] Thinking header not set: {}", trace_id, e),
+                }
+            }
+            response
+        }
+        Err(e) => {
+            error!("[{}] Stream collection error: {}", trace_id, e);
+            openai_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Stream collection error: {}", e),
+            )
+            .into_response()
+        }
+    }
+}
+
+/// 获取并发许可；已满时返回带 Retry-After 的 503 响应
+fn try_acquire_request_permit(
+    semaphore: &std::sync::Arc<tokio::sync::Semaphore>,
+) -> Result<tokio::sync::OwnedSemaphorePermit, Box<Response>> {
+    semaphore.clone().try_acquire_owned().map_err(|_| {
+        Box::new(
+            (
+                [(axum::http::header::RETRY_AFTER, "5")],
+                OpenAIError::for_status(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Too many concurrent requests, please retry later",
+                )
+                .with_code("concurrency_limit_exceeded")
+                .response(StatusCode::SERVICE_UNAVAILABLE),
+            )
+                .into_response(),
+        )
+    })
+}
+
+/// JSON 模式下检查各候选结果是否为合法 JSON (仅记录警告，不拦截响应)
+/// 返回不合法的候选下标
+fn warn_if_not_json(response: &crate::proxy::mappers::openai::OpenAIResponse, trace_id: &str) -> Vec<u32> {
+    use crate::proxy::mappers::openai::OpenAIContent;
+
+    let mut invalid = Vec::new();
+    for choice in &response.choices {
+        let Some(OpenAIContent::String(text)) = &choice.message.content else {
+            continue;
+        };
+        if let Err(e) = serde_json::from_str::<Value>(text.trim()) {
+            tracing::warn!(
+                "[{}] JSON mode response (choice {}) is not valid JSON: {}",
+                trace_id,
+                choice.index,
+                e
+            );
+            invalid.push(choice.index);
+        }
+    }
+    invalid
+}
+
+/// 429 (配额/限流) 由账号轮换处理，换模型无意义；其余错误才触发降级
+fn should_fallback(status: StatusCode) -> bool {
+    !status.is_success() && status != StatusCode::TOO_MANY_REQUESTS
+}
+
+/// 当前模型已被熔断 (无可用账号) 时，返回第一个具备相同能力且有可用账号的模型
+async fn find_capability_alternative(state: &AppState, model: &str) -> Option<&'static str> {
+    use crate::proxy::common::model_mapping::{capability_alternatives, resolve_model_route};
+
+    let mapped = resolve_model_route(model, &*state.custom_mapping.read().await);
+    if state.token_manager.is_model_available(&mapped).await {
+        return None;
+    }
+    for candidate in capability_alternatives(&mapped) {
+        if state.token_manager.is_model_available(candidate).await {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// 使用指定模型执行一次完整的补全流程 (含账号轮换与重试)
+#[tracing::instrument(name = "process_request", skip_all, fields(model = %openai_req.model))]
+async fn process_chat_request(
+    state: &AppState,
+    mut openai_req: OpenAIRequest,
+    explicit_session_id: Option<&str>,
+    forwarded_headers: &std::collections::HashMap<String, String>,
+    trace_id: &str,
+    reconnect_grace: Duration,
+) -> Result<Response, OpenAIErrorResponse> {
+    let trace_id = trace_id.to_string();
+
     // 1. 获取 UpstreamClient (Clone handle)
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let retry_policy = state.live_config.retry_policy();
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size.saturating_add(1)).max(2);
+    let max_attempts = retry_policy
+        .max_retries
+        .saturating_add(1)
+        .min(pool_size.saturating_add(1))
+        .max(2);
 
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
@@ -115,12 +584,34 @@ pub async fn handle_chat_completions(
         &*state.custom_mapping.read().await,
     );
 
+    let upstream_timeout = state.timeout_for_model(&mapped_model);
+
+    // [NEW] max_tokens 超出模型最大输出时截断
+    openai_req.max_tokens = crate::proxy::model_registry::cap_max_tokens(&mapped_model, openai_req.max_tokens);
+
+    // 超出上下文窗口时丢弃最早的历史消息，避免上游直接报错
+    let max_output_tokens = openai_req.max_tokens.map_or(0, |v| v.min(i32::MAX as u32) as i32);
+    // [NEW] 摘要策略：先将最早一半历史替换为摘要，仍超出时再截断
+    if let crate::proxy::config::ContextOverflowStrategy::Summarise { summarise_model } =
+        &state.live_config.context_overflow_strategy()
+    {
+        crate::proxy::mappers::openai::summarise_messages_to_context(
+            &mut openai_req.messages,
+            &mapped_model,
+            max_output_tokens,
+            |history| summarise_conversation(state, summarise_model, history),
+        )
+        .await;
+    }
+    crate::proxy::mappers::openai::truncate_messages_to_context(
+        &mut openai_req.messages,
+        &mapped_model,
+        max_output_tokens,
+    );
+
     for attempt in 0..max_attempts {
         // 将 OpenAI 工具转为 Value 数组以便探测联网
-        let tools_val: Option<Vec<Value>> = openai_req
-            .tools
-            .as_ref()
-            .map(|list| list.iter().cloned().collect());
+        let tools_val: Option<Vec<Value>> = openai_req.tools_as_values();
         let config = crate::proxy::mappers::common_utils::resolve_request_config(
             &openai_req.model,
             &mapped_model,
@@ -133,24 +624,38 @@ pub async fn handle_chat_completions(
         let session_id = SessionManager::extract_openai_session_id(&openai_req);
 
         // 4. 获取 Token (使用准确的 request_type)
+        // 显式会话已绑定且账号可用时优先复用；否则 (含重试) 走常规调度并重新绑定
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                attempt > 0,
-                Some(&session_id),
-                &mapped_model,
-            )
-            .await
-        {
+        let affined = match explicit_session_id {
+            Some(sid) if attempt == 0 => match state.session_affinity.get(sid) {
+                Some(bound) if token_manager.is_email_available(&bound).await => {
+                    token_manager.get_token_by_email(&bound).await.ok()
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let token = match affined {
+            Some(token) => Ok(token),
+            None => {
+                token_manager
+                    .get_token(
+                        &config.request_type,
+                        attempt > 0,
+                        Some(&session_id),
+                        &mapped_model,
+                    )
+                    .await
+            }
+        };
+        let (access_token, project_id, email, _wait_ms) = match token {
             Ok(t) => t,
             Err(e) => {
                 // [FIX] Attach headers to error response for logging visibility
                 let headers = [("X-Mapped-Model", mapped_model.as_str())];
                 return Ok((
-                    StatusCode::SERVICE_UNAVAILABLE,
                     headers,
-                    format!("Token error: {}", e),
+                    openai_error(StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)),
                 )
                     .into_response());
             }
@@ -158,9 +663,33 @@ pub async fn handle_chat_completions(
 
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        if let Some(sid) = explicit_session_id {
+            state.session_affinity.bind(sid, &email);
+        }
 
         // 4. 转换请求
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        let mut gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
+
+        // [NEW] 长 system prompt 使用 CachedContent 引用代替重复发送
+        let (prompt_cache_enabled, prompt_cache_ttl) = {
+            let experimental = state.experimental.read().await;
+            (experimental.enable_prompt_cache, experimental.prompt_cache_ttl_secs)
+        };
+        if prompt_cache_enabled {
+            let lookup = crate::proxy::prompt_cache::apply_prompt_cache(
+                &state.prompt_caches,
+                &upstream,
+                &mut gemini_body,
+                &mapped_model,
+                prompt_cache_ttl,
+                &access_token,
+                &project_id,
+            )
+            .await;
+            if let Some(hit) = lookup {
+                state.stats.record_cache_lookup(hit);
+            }
+        }
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试
         if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
@@ -186,19 +715,42 @@ pub async fn handle_chat_completions(
         };
         let query_string = if actual_stream { Some("alt=sse") } else { None };
 
+        // [NEW] 影子模式：首次尝试时按比例镜像到备用上游 (后台执行，响应丢弃)
+        if attempt == 0 {
+            if let Some(shadow) = &state.shadow {
+                shadow.maybe_mirror(&access_token, &gemini_body, upstream_timeout, &trace_id);
+            }
+        }
+
+        let attempt_start = std::time::Instant::now();
         let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+            .call_v1_internal_with_headers(
+                method,
+                &access_token,
+                gemini_body,
+                query_string,
+                forwarded_headers.clone(),
+                Some(upstream_timeout),
+            )
+            .instrument(tracing::info_span!(
+                "attempt",
+                attempt_number = attempt + 1,
+                token_email = %email,
+                model = %mapped_model
+            ))
             .await
         {
             Ok(r) => r,
             Err(e) => {
-                last_error = e.clone();
+                token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, Some(&e));
+                last_error = e.to_string();
                 debug!(
                     "OpenAI Request failed on attempt {}/{}: {}",
                     attempt + 1,
                     max_attempts,
                     e
                 );
+                retry_policy.backoff(attempt).await;
                 continue;
             }
         };
@@ -208,137 +760,64 @@ pub async fn handle_chat_completions(
             // 5. 处理流式 vs 非流式
             if actual_stream {
                 use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
-                use axum::body::Body;
-                use axum::response::Response;
-                use futures::StreamExt;
 
                 let gemini_stream = response.bytes_stream();
 
-                // [P1 FIX] Enhanced Peek logic to handle heartbeats and slow start
-                // Pre-read until we find meaningful content, skip heartbeats
-                let mut openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
-
-                let mut first_data_chunk = None;
-                let mut retry_this_account = false;
-
-                // Loop to skip heartbeats during peek
-                loop {
-                    match tokio::time::timeout(
-                        std::time::Duration::from_secs(60),
-                        openai_stream.next(),
-                    )
-                    .await
-                    {
-                        Ok(Some(Ok(bytes))) => {
-                            if bytes.is_empty() {
-                                continue;
-                            }
-
-                            let text = String::from_utf8_lossy(&bytes);
-                            // Skip SSE comments/pings (heartbeats)
-                            if text.trim().starts_with(":") || text.trim().starts_with("data: :") {
-                                tracing::debug!("[OpenAI] Skipping peek heartbeat");
-                                continue;
-                            }
-
-                            // Check for error events
-                            if text.contains("\"error\"") {
-                                tracing::warn!("[OpenAI] Error detected during peek, retrying...");
-                                last_error = "Error event during peek".to_string();
-                                retry_this_account = true;
-                                break;
-                            }
+                let mut openai_stream = create_openai_sse_stream(
+                    Box::pin(gemini_stream),
+                    openai_req.model.clone(),
+                    client_wants_stream && openai_req.include_usage(),
+                    state.max_inline_image_bytes,
+                    state.sse_keepalive_interval(),
+                );
 
-                            // We found real data!
-                            first_data_chunk = Some(bytes);
-                            break;
-                        }
-                        Ok(Some(Err(e))) => {
-                            tracing::warn!("[OpenAI] Stream error during peek: {}, retrying...", e);
-                            last_error = format!("Stream error during peek: {}", e);
-                            retry_this_account = true;
-                            break;
-                        }
-                        Ok(None) => {
-                            tracing::warn!(
-                                "[OpenAI] Stream ended during peek (Empty Response), retrying..."
-                            );
-                            last_error = "Empty response stream during peek".to_string();
-                            retry_this_account = true;
-                            break;
-                        }
-                        Err(_) => {
-                            tracing::warn!(
-                                "[OpenAI] Timeout waiting for first data (60s), retrying..."
-                            );
-                            last_error = "Timeout waiting for first data".to_string();
-                            retry_this_account = true;
-                            break;
-                        }
+                let first_data_chunk = match peek_first_chunk(&mut openai_stream).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        token_manager.record_request(
+                            &email,
+                            &mapped_model,
+                            attempt_start.elapsed().as_millis() as u64,
+                            Some(&ProxyError::NetworkError(e.clone())),
+                        );
+                        last_error = e;
+                        retry_policy.backoff(attempt).await;
+                        continue; // Rotate to next account
                     }
-                }
+                };
 
-                if retry_this_account {
-                    continue; // Rotate to next account
-                }
-
-                // Combine first chunk with remaining stream
-                let combined_stream =
-                    futures::stream::once(
-                        async move { Ok::<Bytes, String>(first_data_chunk.unwrap()) },
-                    )
-                    .chain(openai_stream);
-
-                if client_wants_stream {
-                    // 客户端请求流式，返回 SSE
-                    let body = Body::from_stream(combined_stream);
-                    return Ok(Response::builder()
-                        .header("Content-Type", "text/event-stream")
-                        .header("Cache-Control", "no-cache")
-                        .header("Connection", "keep-alive")
-                        .header("X-Accel-Buffering", "no")
-                        .header("X-Account-Email", &email)
-                        .header("X-Mapped-Model", &mapped_model)
-                        .body(body)
-                        .unwrap()
-                        .into_response());
-                } else {
-                    // 客户端请求非流式，但内部强制转为流式
-                    // 收集流数据并聚合为 JSON
-                    use crate::proxy::mappers::openai::collector::collect_stream_to_json;
+                token_manager.mark_account_success(&email);
+                token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, None);
+                crate::proxy::metrics::metrics().record_ttfb(&mapped_model, attempt_start.elapsed());
 
-                    match collect_stream_to_json(Box::pin(combined_stream)).await {
-                        Ok(full_response) => {
-                            info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
-                            return Ok((
-                                StatusCode::OK,
-                                [
-                                    ("X-Account-Email", email.as_str()),
-                                    ("X-Mapped-Model", mapped_model.as_str()),
-                                ],
-                                Json(full_response),
-                            )
-                                .into_response());
-                        }
-                        Err(e) => {
-                            error!("[{}] Stream collection error: {}", trace_id, e);
-                            return Ok((
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                format!("Stream collection error: {}", e),
-                            )
-                                .into_response());
-                        }
-                    }
-                }
+                return Ok(respond_with_openai_stream(
+                    state,
+                    &openai_req,
+                    first_data_chunk,
+                    openai_stream,
+                    &email,
+                    &mapped_model,
+                    &trace_id,
+                    reconnect_grace,
+                )
+                .await);
             }
 
             let gemini_resp: Value = response
                 .json()
                 .await
-                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+                .map_err(|e| openai_error(StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, None);
 
-            let openai_response = transform_openai_response(&gemini_resp);
+            let openai_response = crate::proxy::mappers::openai::gemini_response_to_openai_chat_completion(
+                &gemini_resp,
+                &openai_req.model,
+                &trace_id,
+                state.max_inline_image_bytes,
+            );
+            if openai_req.is_json_mode() {
+                warn_if_not_json(&openai_response, &trace_id);
+            }
             return Ok((
                 StatusCode::OK,
                 [
@@ -362,6 +841,9 @@ pub async fn handle_chat_completions(
             .await
             .unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        let upstream_error =
+            ProxyError::from_status_with_retry_after(status_code, _retry_after.as_deref(), &error_text);
+        token_manager.record_request(&email, &mapped_model, attempt_start.elapsed().as_millis() as u64, Some(&upstream_error));
 
         // [New] 打印错误报文日志
         tracing::error!(
@@ -370,8 +852,32 @@ pub async fn handle_chat_completions(
             error_text
         );
 
+        // 限流类错误由 TokenManager::mark_rate_limited_async 统计，这里只记录鉴权类错误
+        if status_code == 401 || status_code == 403 {
+            crate::proxy::metrics::metrics().record_token_error(
+                &email,
+                crate::proxy::metrics::token_error_reason(status_code),
+            );
+            token_manager.record_token_failure(&email);
+        }
+
+        // 部分模型不支持 presence/frequency penalty，移除后重试
+        if status_code == 400 && strip_unsupported_penalties(&mut openai_req, &error_text) {
+            tracing::warn!(
+                "[OpenAI] Upstream rejected penalty parameters on account {}, retrying without them",
+                email
+            );
+            continue;
+        }
+
         // 确定重试策略
-        let strategy = determine_retry_strategy(status_code, &error_text, false);
+        // [NEW] 429 的 Retry-After 等待上限由 max_retry_delay_secs 配置
+        let strategy = determine_retry_strategy(
+            &upstream_error,
+            false,
+            state.live_config.max_retry_delay(),
+            &state.live_config.retry_patterns(),
+        );
 
         // 3. 标记限流状态(用于 UI 显示)
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
@@ -388,9 +894,9 @@ pub async fn handle_chat_completions(
         }
 
         // 执行退避
-        if apply_retry_strategy(strategy, attempt, max_attempts, status_code, &trace_id).await {
+        if apply_retry_strategy(strategy, &state.live_config.retry_policy(), attempt, max_attempts, status_code, &trace_id).await {
             // 判断是否需要轮换账号
-            if !should_rotate_account(status_code) {
+            if !should_rotate_account(&upstream_error) {
                 debug!(
                     "[{}] Keeping same account for status {} (server-side issue)",
                     trace_id, status_code
@@ -463,6 +969,7 @@ pub async fn handle_chat_completions(
         if status_code == 403 || status_code == 401 {
             if apply_retry_strategy(
                 RetryStrategy::FixedDelay(Duration::from_millis(200)),
+                &state.live_config.retry_policy(),
                 attempt,
                 max_attempts,
                 status_code,
@@ -480,12 +987,11 @@ pub async fn handle_chat_completions(
             status_code, email, error_text
         );
         return Ok((
-            status,
             [
                 ("X-Account-Email", email.as_str()),
                 ("X-Mapped-Model", mapped_model.as_str()),
             ],
-            error_text,
+            openai_error(status, error_text),
         )
             .into_response());
     }
@@ -493,21 +999,216 @@ pub async fn handle_chat_completions(
     // 所有尝试均失败
     if let Some(email) = last_email {
         Ok((
-            StatusCode::TOO_MANY_REQUESTS,
             [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
-            format!("All accounts exhausted. Last error: {}", last_error),
+            openai_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("All accounts exhausted. Last error: {}", last_error),
+            ),
         )
             .into_response())
     } else {
         Ok((
-            StatusCode::TOO_MANY_REQUESTS,
             [("X-Mapped-Model", mapped_model)],
-            format!("All accounts exhausted. Last error: {}", last_error),
+            openai_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("All accounts exhausted. Last error: {}", last_error),
+            ),
         )
             .into_response())
     }
 }
 
+/// POST /v1/chat/completions 入口：按 Content-Type 分派
+/// - application/json: 原样交给 handle_chat_completions
+/// - multipart/form-data: `request` 为 JSON 请求体，`image` (或 image1、image[] 等) 为图片文件，
+///   图片以 data URI 形式追加到最后一条 user 消息后再走同一流程
+pub async fn handle_chat_completions_dispatch(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    request: axum::extract::Request,
+) -> Response {
+    use axum::extract::FromRequest;
+
+    let is_multipart = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("multipart/form-data"));
+
+    let body = if is_multipart {
+        let multipart = match axum::extract::Multipart::from_request(request, &state).await {
+            Ok(m) => m,
+            Err(rejection) => return rejection.into_response(),
+        };
+        match parse_multipart_chat_request(multipart).await {
+            Ok(body) => body,
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        match Json::<Value>::from_request(request, &state).await {
+            Ok(Json(body)) => body,
+            Err(rejection) => return rejection.into_response(),
+        }
+    };
+
+    handle_chat_completions(State(state), headers, Json(body))
+        .await
+        .into_response()
+}
+
+/// 解析 multipart chat 请求，返回注入图片后的 JSON 请求体
+async fn parse_multipart_chat_request(
+    mut multipart: axum::extract::Multipart,
+) -> Result<Value, OpenAIErrorResponse> {
+    let mut body: Option<Value> = None;
+    let mut image_urls: Vec<String> = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
+    {
+        let name = field.name().unwrap_or("").to_string();
+        if name == "request" {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Request part read error: {}", e)))?;
+            body = Some(
+                serde_json::from_str(&text)
+                    .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Invalid request part: {}", e)))?,
+            );
+        } else if name.starts_with("image") {
+            // 缺少或为通用类型时按 PNG 处理，上游会按实际内容识别
+            let mime_type = field
+                .content_type()
+                .filter(|ct| ct.starts_with("image/"))
+                .unwrap_or("image/png")
+                .to_string();
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Image read error: {}", e)))?;
+            image_urls.push(format!(
+                "data:{};base64,{}",
+                mime_type,
+                base64::engine::general_purpose::STANDARD.encode(data)
+            ));
+        }
+    }
+
+    let mut body = body.ok_or_else(|| {
+        openai_error(
+            StatusCode::BAD_REQUEST,
+            "Multipart request is missing the `request` JSON part",
+        )
+    })?;
+    inject_image_parts(&mut body, image_urls).map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+    Ok(body)
+}
+
+/// 将图片作为 image_url 内容块追加到最后一条 user 消息 (字符串内容先转为 text 块)
+fn inject_image_parts(body: &mut Value, image_urls: Vec<String>) -> Result<(), String> {
+    if image_urls.is_empty() {
+        return Ok(());
+    }
+    let message = body
+        .get_mut("messages")
+        .and_then(|m| m.as_array_mut())
+        .and_then(|messages| {
+            messages
+                .iter_mut()
+                .rev()
+                .find(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+        })
+        .ok_or("Multipart images require at least one user message")?;
+
+    let mut parts = match message.get_mut("content").map(Value::take) {
+        Some(Value::Array(parts)) => parts,
+        Some(Value::String(text)) if !text.is_empty() => vec![json!({"type": "text", "text": text})],
+        _ => Vec::new(),
+    };
+    parts.extend(
+        image_urls
+            .into_iter()
+            .map(|url| json!({"type": "image_url", "image_url": {"url": url}})),
+    );
+    message["content"] = Value::Array(parts);
+    Ok(())
+}
+
+/// 批量补全: POST /v1/chat/completions/batch
+/// 子请求并发执行 (每个子请求独立走 get_token 轮询，尽量分散到不同账号)，
+/// 单个失败不影响整体，结果按原顺序返回
+pub async fn handle_chat_completions_batch(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(requests): Json<Vec<Value>>,
+) -> Response {
+    if requests.is_empty() {
+        return openai_error(StatusCode::BAD_REQUEST, "Batch must contain at least one request").into_response();
+    }
+    if requests.len() > state.batch_max_requests {
+        return openai_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Batch contains {} requests, maximum is {}",
+                requests.len(),
+                state.batch_max_requests
+            ),
+        )
+            .into_response();
+    }
+
+    info!("[OpenAI-Batch] Dispatching {} requests", requests.len());
+
+    let tasks = requests.into_iter().enumerate().map(|(id, mut body)| {
+        let state = state.clone();
+        let headers = headers.clone();
+        async move {
+            // 批量模式只支持非流式结果
+            if let Some(obj) = body.as_object_mut() {
+                obj.insert("stream".to_string(), Value::Bool(false));
+            }
+            let response = match handle_chat_completions(State(state), headers, Json(body)).await {
+                Ok(resp) => resp.into_response(),
+                Err(err) => err.into_response(),
+            };
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap_or_default();
+            batch_entry(id, status, &bytes)
+        }
+    });
+
+    let results = futures::future::join_all(tasks).await;
+    Json(Value::Array(results)).into_response()
+}
+
+/// 单个子请求的结果: 成功为补全 JSON，失败统一包装为 OpenAI error 对象
+fn batch_entry(id: usize, status: StatusCode, body: &[u8]) -> Value {
+    let parsed = serde_json::from_slice::<Value>(body).ok();
+    let result = if status.is_success() {
+        parsed.unwrap_or(Value::Null)
+    } else {
+        match parsed {
+            Some(v) if v.get("error").is_some() => v,
+            _ => json!({
+                "error": {
+                    "message": String::from_utf8_lossy(body),
+                    "type": "upstream_error",
+                    "code": status.as_u16()
+                }
+            }),
+        }
+    };
+    json!({
+        "id": id,
+        "status": status.as_u16(),
+        "result": result
+    })
+}
+
 /// 处理 Legacy Completions API (/v1/completions)
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
@@ -746,18 +1447,17 @@ pub async fn handle_completions(
         if let Some(obj) = body.as_object_mut() {
             obj.insert("messages".to_string(), json!(messages));
         }
-    } else if let Some(prompt_val) = body.get("prompt") {
+    } else if body.get("prompt").is_some() {
         // Legacy OpenAI Style: prompt -> Chat
-        let prompt_str = match prompt_val {
-            Value::String(s) => s.clone(),
-            Value::Array(arr) => arr
-                .iter()
-                .filter_map(|v| v.as_str())
-                .collect::<Vec<_>>()
-                .join("\n"),
-            _ => prompt_val.to_string(),
-        };
-        let messages = json!([ { "role": "user", "content": prompt_str } ]);
+        let legacy_req: crate::proxy::mappers::openai::OpenAICompletionRequest =
+            match serde_json::from_value(body.clone()) {
+                Ok(req) => req,
+                Err(e) => {
+                    return openai_error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e))
+                        .into_response()
+                }
+            };
+        let messages = json!([legacy_req.to_user_message()]);
         if let Some(obj) = body.as_object_mut() {
             obj.remove("prompt");
             obj.insert("messages".to_string(), messages);
@@ -866,10 +1566,27 @@ pub async fn handle_completions(
     let mut openai_req: OpenAIRequest = match serde_json::from_value(body.clone()) {
         Ok(req) => req,
         Err(e) => {
-            return (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response();
+            return openai_error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response();
         }
     };
 
+    if let Err(e) = apply_model_alias(&state, &mut openai_req).await {
+        return openai_error(StatusCode::BAD_REQUEST, e).into_response();
+    }
+    if let Err(e) = validate_sampling_params(&openai_req) {
+        return openai_error(StatusCode::BAD_REQUEST, e).into_response();
+    }
+    if let Err(e) = validate_safety_settings(&openai_req) {
+        return openai_error(StatusCode::BAD_REQUEST, e).into_response();
+    }
+    inline_remote_images(&mut openai_req).await;
+    if let Err(e) = validate_inline_image_sizes(&openai_req, state.max_inline_image_bytes) {
+        return openai_error(StatusCode::BAD_REQUEST, e).into_response();
+    }
+    if let Err(e) = validate_audio_inputs(&openai_req) {
+        return openai_error(StatusCode::BAD_REQUEST, e).into_response();
+    }
+
     // Safety: Inject empty message if needed
     if openai_req.messages.is_empty() {
         openai_req
@@ -887,10 +1604,16 @@ pub async fn handle_completions(
     }
 
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries
-    let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size.saturating_add(1)).max(2);
+    let max_attempts = state
+        .live_config
+        .retry_policy()
+        .max_retries
+        .saturating_add(1)
+        .min(pool_size.saturating_add(1))
+        .max(2);
 
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
@@ -901,14 +1624,12 @@ pub async fn handle_completions(
         &*state.custom_mapping.read().await,
     );
     let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    let upstream_timeout = state.timeout_for_model(&mapped_model);
 
     for attempt in 0..max_attempts {
         // 3. 模型配置解析
         // 将 OpenAI 工具转为 Value 数组以便探测联网
-        let tools_val: Option<Vec<Value>> = openai_req
-            .tools
-            .as_ref()
-            .map(|list| list.iter().cloned().collect());
+        let tools_val: Option<Vec<Value>> = openai_req.tools_as_values();
         let config = crate::proxy::mappers::common_utils::resolve_request_config(
             &openai_req.model,
             &mapped_model,
@@ -937,9 +1658,8 @@ pub async fn handle_completions(
             Ok(t) => t,
             Err(e) => {
                 return (
-                    StatusCode::SERVICE_UNAVAILABLE,
                     [("X-Mapped-Model", mapped_model)],
-                    format!("Token error: {}", e),
+                    openai_error(StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)),
                 )
                     .into_response()
             }
@@ -973,12 +1693,12 @@ pub async fn handle_completions(
         let query_string = if list_response { Some("alt=sse") } else { None };
 
         let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+            .call_v1_internal(method, &access_token, gemini_body, query_string, Some(upstream_timeout))
             .await
         {
             Ok(r) => r,
             Err(e) => {
-                last_error = e.clone();
+                last_error = e.to_string();
                 debug!(
                     "Codex Request failed on attempt {}/{}: {}",
                     attempt + 1,
@@ -1009,10 +1729,18 @@ pub async fn handle_completions(
                 if client_wants_stream {
                     let mut openai_stream = if is_codex_style {
                         use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone())
+                        create_codex_sse_stream(
+                            Box::pin(gemini_stream),
+                            openai_req.model.clone(),
+                            state.sse_keepalive_interval(),
+                        )
                     } else {
                         use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
-                        create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone())
+                        create_legacy_sse_stream(
+                            Box::pin(gemini_stream),
+                            openai_req.model.clone(),
+                            state.sse_keepalive_interval(),
+                        )
                     };
 
                     // [P1 FIX] Enhanced Peek logic (Reused from above/standard)
@@ -1086,8 +1814,13 @@ pub async fn handle_completions(
                     use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
                     // Note: We use create_openai_sse_stream regardless of is_codex_style here,
                     // because we just want the content aggregation which chat stream does well.
-                    let mut openai_stream =
-                        create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    let mut openai_stream = create_openai_sse_stream(
+                        Box::pin(gemini_stream),
+                        openai_req.model.clone(),
+                        false,
+                        state.max_inline_image_bytes,
+                        state.sse_keepalive_interval(),
+                    );
 
                     // Peek Logic (Repeated for safety/correctness on this stream type)
                     let mut first_data_chunk = None;
@@ -1180,11 +1913,11 @@ pub async fn handle_completions(
                                 .into_response();
                         }
                         Err(e) => {
-                            return (
+                            return openai_error(
                                 StatusCode::INTERNAL_SERVER_ERROR,
                                 format!("Stream collection error: {}", e),
                             )
-                                .into_response();
+                            .into_response();
                         }
                     }
                 }
@@ -1194,15 +1927,14 @@ pub async fn handle_completions(
                 Ok(json) => json,
                 Err(e) => {
                     return (
-                        StatusCode::BAD_GATEWAY,
                         [("X-Mapped-Model", mapped_model.as_str())],
-                        format!("Parse error: {}", e),
+                        openai_error(StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)),
                     )
                         .into_response();
                 }
             };
 
-            let chat_resp = transform_openai_response(&gemini_resp);
+            let chat_resp = transform_openai_response(&gemini_resp, state.max_inline_image_bytes);
 
             // Map Chat Response -> Legacy Completions Response
             let choices = chat_resp.choices.iter().map(|c| {
@@ -1256,6 +1988,15 @@ pub async fn handle_completions(
             error_text
         );
 
+        // 部分模型不支持 presence/frequency penalty，移除后重试
+        if status_code == 400 && strip_unsupported_penalties(&mut openai_req, &error_text) {
+            tracing::warn!(
+                "[Codex] Upstream rejected penalty parameters on account {}, retrying without them",
+                email
+            );
+            continue;
+        }
+
         // 3. 标记限流状态(用于 UI 显示)
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 {
             token_manager
@@ -1270,20 +2011,24 @@ pub async fn handle_completions(
         }
 
         // 确定重试策略
-        let strategy = determine_retry_strategy(status_code, &error_text, false);
+        let strategy = determine_retry_strategy(
+            &ProxyError::from_status(status_code, &error_text),
+            false,
+            state.live_config.max_retry_delay(),
+            &state.live_config.retry_patterns(),
+        );
 
-        if apply_retry_strategy(strategy, attempt, max_attempts, status_code, &trace_id).await {
+        if apply_retry_strategy(strategy, &state.live_config.retry_policy(), attempt, max_attempts, status_code, &trace_id).await {
             // 继续重试 (loop 会增加 attempt, 导致 force_rotate=true)
             continue;
         } else {
             // 不可重试
             return (
-                status,
                 [
                     ("X-Account-Email", email.as_str()),
                     ("X-Mapped-Model", mapped_model.as_str()),
                 ],
-                error_text,
+                openai_error(status, error_text),
             )
                 .into_response();
         }
@@ -1292,81 +2037,258 @@ pub async fn handle_completions(
     // 所有尝试均失败
     if let Some(email) = last_email {
         (
-            StatusCode::TOO_MANY_REQUESTS,
             [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
-            format!("All accounts exhausted. Last error: {}", last_error),
+            openai_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("All accounts exhausted. Last error: {}", last_error),
+            ),
         )
             .into_response()
     } else {
         (
-            StatusCode::TOO_MANY_REQUESTS,
             [("X-Mapped-Model", mapped_model)],
-            format!("All accounts exhausted. Last error: {}", last_error),
+            openai_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("All accounts exhausted. Last error: {}", last_error),
+            ),
         )
             .into_response()
     }
 }
 
-pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
-    use crate::proxy::common::model_mapping::get_all_dynamic_models;
+/// 使用 summarise_model 为一段历史消息生成摘要 (同样经由账号池调度)
+async fn summarise_conversation(
+    state: &AppState,
+    summarise_model: &str,
+    history: Vec<crate::proxy::mappers::openai::OpenAIMessage>,
+) -> Result<String, String> {
+    use crate::proxy::mappers::openai::{OpenAIContent, SUMMARISE_PROMPT};
+
+    let mut messages = vec![json!({"role": "system", "content": SUMMARISE_PROMPT})];
+    for msg in history {
+        messages.push(serde_json::to_value(msg).map_err(|e| e.to_string())?);
+    }
+    messages.push(json!({"role": "user", "content": "Summarise the conversation above."}));
+    let request: OpenAIRequest = serde_json::from_value(json!({
+        "model": summarise_model,
+        "messages": messages,
+        "stream": false,
+    }))
+    .map_err(|e| e.to_string())?;
 
-    let model_ids = get_all_dynamic_models(&state.custom_mapping).await;
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        summarise_model,
+        &*state.custom_mapping.read().await,
+    );
+    let (access_token, project_id, email, _) = state
+        .token_manager
+        .get_token("text", false, None, &mapped_model)
+        .await?;
+    debug!("[Summarise] Using account {} with model {}", email, mapped_model);
+
+    let body = transform_openai_request(&request, &project_id, &mapped_model);
+    let response = state
+        .upstream
+        .call_v1_internal(
+            "generateContent",
+            &access_token,
+            body,
+            None,
+            Some(state.timeout_for_model(&mapped_model)),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}: {}", status, response.text().await.unwrap_or_default()));
+    }
+    let raw: Value = response.json().await.map_err(|e| e.to_string())?;
+    let summary = transform_openai_response(&raw, 0);
+    match summary.choices.into_iter().next().and_then(|c| c.message.content) {
+        Some(OpenAIContent::String(text)) => Ok(text),
+        _ => Err("summary response has no text".to_string()),
+    }
+}
 
-    let data: Vec<_> = model_ids
-        .into_iter()
-        .map(|id| {
-            json!({
-                "id": id,
-                "object": "model",
-                "created": 1706745600,
-                "owned_by": "antigravity"
-            })
-        })
-        .collect();
+/// 将请求中的模型别名替换为真实模型名 (model_aliases)
+async fn apply_model_alias(state: &AppState, openai_req: &mut OpenAIRequest) -> Result<(), String> {
+    let aliases = state.model_aliases.read().await;
+    if aliases.is_empty() {
+        return Ok(());
+    }
+    let custom_mapping = state.custom_mapping.read().await;
+    openai_req.model = crate::proxy::common::model_mapping::resolve_model_alias(
+        &openai_req.model,
+        &aliases,
+        &custom_mapping,
+    )?;
+    Ok(())
+}
+
+/// 当前可用的 OpenAI 模型对象 (含向量模型与别名)
+async fn openai_model_objects(state: &AppState) -> Vec<Value> {
+    use crate::proxy::common::model_mapping::{build_openai_model_objects, get_all_dynamic_models};
+
+    let mut model_ids = get_all_dynamic_models(&state.custom_mapping).await;
+    for embedding_model in crate::proxy::mappers::openai::EMBEDDING_MODELS {
+        if !model_ids.iter().any(|id| id == embedding_model) {
+            model_ids.push(embedding_model.to_string());
+        }
+    }
+    let aliases = state.model_aliases.read().await;
+    build_openai_model_objects(model_ids, &aliases)
+}
 
+pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     Json(json!({
         "object": "list",
-        "data": data
+        "data": openai_model_objects(&state).await
     }))
 }
 
+/// GET /v1/models/:model_id
+/// 部分客户端 (如 openai-python) 会在使用前校验模型是否存在
+pub async fn handle_get_model(
+    State(state): State<AppState>,
+    Path(model_id): Path<String>,
+) -> Response {
+    match openai_model_objects(&state)
+        .await
+        .into_iter()
+        .find(|m| m["id"] == model_id.as_str())
+    {
+        Some(model) => Json(model).into_response(),
+        None => OpenAIError::new("model not found", "invalid_request_error")
+            .with_param("model")
+            .with_code("model_not_found")
+            .response(StatusCode::NOT_FOUND)
+            .into_response(),
+    }
+}
+
+/// OpenAI Embeddings API: POST /v1/embeddings
+/// 经 batchEmbedContents 批量获取向量 (每批最多 MAX_EMBED_BATCH_SIZE 条)，转换为 OpenAI list 格式
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, OpenAIErrorResponse> {
+    use crate::proxy::mappers::openai::{
+        build_batch_embed_contents_request, build_embeddings_response, extract_batch_embedding_values,
+        is_embedding_model, OpenAIEmbeddingRequest, MAX_EMBED_BATCH_SIZE,
+    };
+
+    let mut embed_req: OpenAIEmbeddingRequest = serde_json::from_value(body)
+        .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+
+    // 别名解析 + 模型路由 (与 chat 接口共用 model_aliases / custom_mapping)
+    let mapped_model = {
+        let aliases = state.model_aliases.read().await;
+        let custom_mapping = state.custom_mapping.read().await;
+        if !aliases.is_empty() {
+            embed_req.model = crate::proxy::common::model_mapping::resolve_model_alias(
+                &embed_req.model,
+                &aliases,
+                &custom_mapping,
+            )
+            .map_err(|e| openai_error(StatusCode::BAD_REQUEST, e))?;
+        }
+        crate::proxy::common::model_mapping::resolve_model_route(&embed_req.model, &custom_mapping)
+    };
+
+    if !is_embedding_model(&mapped_model) {
+        return Err(openai_error(
+            StatusCode::BAD_REQUEST,
+            format!("Model '{}' does not support embeddings", embed_req.model),
+        ));
+    }
+
+    let encoding_format = embed_req.encoding_format.clone();
+    let inputs = embed_req.input.into_vec();
+    if inputs.is_empty() {
+        return Err(openai_error(StatusCode::BAD_REQUEST, "Input must not be empty".to_string()));
+    }
+
+    let (access_token, project_id, email, _wait_ms) = state
+        .token_manager
+        .get_token("text", false, None, &mapped_model)
+        .await
+        .map_err(|e| openai_error(StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)))?;
+
+    info!(
+        "✓ Using account: {} for embeddings ({} input(s), model: {})",
+        email,
+        inputs.len(),
+        mapped_model
+    );
+
+    let prompt_tokens: u32 = inputs
+        .iter()
+        .map(|text| crate::proxy::audit_log::estimate_prompt_tokens(text))
+        .sum();
+    let mut embeddings = Vec::with_capacity(inputs.len());
+    for batch in inputs.chunks(MAX_EMBED_BATCH_SIZE) {
+        let gemini_body = build_batch_embed_contents_request(batch, &mapped_model, &project_id);
+
+        let response = state
+            .upstream
+            .batch_embed_contents(&access_token, gemini_body)
+            .await
+            .map_err(|e| openai_error(StatusCode::BAD_GATEWAY, format!("Network error: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let err_text = response.text().await.unwrap_or_default();
+            error!("[Embeddings] Upstream error {}: {}", status, err_text);
+            return Err(openai_error(
+                StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                err_text,
+            ));
+        }
+
+        let gemini_resp: Value = response
+            .json()
+            .await
+            .map_err(|e| openai_error(StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+        let values = extract_batch_embedding_values(&gemini_resp)
+            .filter(|values| values.len() == batch.len())
+            .ok_or_else(|| {
+                openai_error(StatusCode::BAD_GATEWAY, "Upstream response missing embedding values")
+            })?;
+        embeddings.extend(values);
+    }
+
+    let openai_response = build_embeddings_response(
+        embeddings,
+        &embed_req.model,
+        encoding_format.as_deref(),
+        prompt_tokens,
+    );
+
+    Ok((
+        StatusCode::OK,
+        [("X-Account-Email", email.as_str())],
+        Json(openai_response),
+    )
+        .into_response())
+}
+
 /// OpenAI Images API: POST /v1/images/generations
 /// 处理图像生成请求，转换为 Gemini API 格式
 pub async fn handle_images_generations(
     State(state): State<AppState>,
     Json(body): Json<Value>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, OpenAIErrorResponse> {
     // 1. 解析请求参数
-    let prompt = body.get("prompt").and_then(|v| v.as_str()).ok_or((
-        StatusCode::BAD_REQUEST,
-        "Missing 'prompt' field".to_string(),
-    ))?;
-
-    let model = body
-        .get("model")
-        .and_then(|v| v.as_str())
-        .unwrap_or("gemini-3-pro-image");
-
-    let n = body.get("n").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
-
-    let size = body
-        .get("size")
-        .and_then(|v| v.as_str())
-        .unwrap_or("1024x1024");
-
-    let response_format = body
-        .get("response_format")
-        .and_then(|v| v.as_str())
-        .unwrap_or("b64_json");
-
-    let quality = body
-        .get("quality")
-        .and_then(|v| v.as_str())
-        .unwrap_or("standard");
-    let style = body
-        .get("style")
-        .and_then(|v| v.as_str())
-        .unwrap_or("vivid");
+    let request: crate::proxy::mappers::openai::OpenAIImageGenerationRequest =
+        serde_json::from_value(body)
+            .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+    let prompt = request.prompt.as_str();
+    let model = request.model.as_str();
+    let n = request.n.unwrap_or(1) as usize;
+    let size = request.size.as_deref().unwrap_or("1024x1024");
+    let response_format = request.response_format.as_deref().unwrap_or("b64_json");
+    let quality = request.quality.as_deref().unwrap_or("standard");
+    let style = request.style.as_deref().unwrap_or("vivid");
 
     info!(
         "[Images] Received request: model={}, prompt={:.50}..., n={}, size={}, quality={}, style={}",
@@ -1398,6 +2320,7 @@ pub async fn handle_images_generations(
 
     // 4. 获取 Token
     let upstream = state.upstream.clone();
+    let upstream_timeout = state.timeout_for_model("gemini-3-pro-image");
     let token_manager = state.token_manager;
 
     let (access_token, project_id, email, _wait_ms) = match token_manager
@@ -1406,7 +2329,7 @@ pub async fn handle_images_generations(
     {
         Ok(t) => t,
         Err(e) => {
-            return Err((
+            return Err(openai_error(
                 StatusCode::SERVICE_UNAVAILABLE,
                 format!("Token error: {}", e),
             ))
@@ -1455,7 +2378,7 @@ pub async fn handle_images_generations(
             });
 
             match upstream
-                .call_v1_internal("generateContent", &access_token, gemini_body, None)
+                .call_v1_internal("generateContent", &access_token, gemini_body, None, Some(upstream_timeout))
                 .await
             {
                 Ok(response) => {
@@ -1482,36 +2405,11 @@ pub async fn handle_images_generations(
         match task.await {
             Ok(result) => match result {
                 Ok(gemini_resp) => {
-                    let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
-                    if let Some(parts) = raw
-                        .get("candidates")
-                        .and_then(|c| c.get(0))
-                        .and_then(|cand| cand.get("content"))
-                        .and_then(|content| content.get("parts"))
-                        .and_then(|p| p.as_array())
-                    {
-                        for part in parts {
-                            if let Some(img) = part.get("inlineData") {
-                                let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                if !data.is_empty() {
-                                    if response_format == "url" {
-                                        let mime_type = img
-                                            .get("mimeType")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("image/png");
-                                        images.push(json!({
-                                            "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
-                                    } else {
-                                        images.push(json!({
-                                            "b64_json": data
-                                        }));
-                                    }
-                                    tracing::debug!("[Images] Task {} succeeded", idx);
-                                }
-                            }
-                        }
+                    let generated = images_from_gemini_response(&gemini_resp, response_format);
+                    if !generated.is_empty() {
+                        tracing::debug!("[Images] Task {} succeeded", idx);
                     }
+                    images.extend(generated);
                 }
                 Err(e) => {
                     tracing::error!("[Images] Task {} failed: {}", idx, e);
@@ -1533,7 +2431,7 @@ pub async fn handle_images_generations(
             "No images generated".to_string()
         };
         tracing::error!("[Images] All {} requests failed. Errors: {}", n, error_msg);
-        return Err((StatusCode::BAD_GATEWAY, error_msg));
+        return Err(openai_error(StatusCode::BAD_GATEWAY, error_msg));
     }
 
     // 部分成功时记录警告
@@ -1566,10 +2464,43 @@ pub async fn handle_images_generations(
         .into_response())
 }
 
+/// 提取 Gemini 响应中的 inlineData 图片，转为 OpenAI images data 项
+/// response_format 为 "url" 时返回 data URI，否则返回 b64_json
+fn images_from_gemini_response(gemini_resp: &Value, response_format: &str) -> Vec<Value> {
+    let raw = gemini_resp.get("response").unwrap_or(gemini_resp);
+    let Some(parts) = raw
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|cand| cand.get("content"))
+        .and_then(|content| content.get("parts"))
+        .and_then(|p| p.as_array())
+    else {
+        return Vec::new();
+    };
+
+    let mut images = Vec::new();
+    for img in parts.iter().filter_map(|part| part.get("inlineData")) {
+        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
+        if data.is_empty() {
+            continue;
+        }
+        if response_format == "url" {
+            let mime_type = img
+                .get("mimeType")
+                .and_then(|v| v.as_str())
+                .unwrap_or("image/png");
+            images.push(json!({ "url": format!("data:{};base64,{}", mime_type, data) }));
+        } else {
+            images.push(json!({ "b64_json": data }));
+        }
+    }
+    images
+}
+
 pub async fn handle_images_edits(
     State(state): State<AppState>,
     mut multipart: axum::extract::Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, OpenAIErrorResponse> {
     tracing::info!("[Images] Received edit request");
 
     let mut image_data = None;
@@ -1588,7 +2519,7 @@ pub async fn handle_images_edits(
     while let Some(field) = multipart
         .next_field()
         .await
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
+        .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
     {
         let name = field.name().unwrap_or("").to_string();
 
@@ -1596,18 +2527,18 @@ pub async fn handle_images_edits(
             let data = field
                 .bytes()
                 .await
-                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Image read error: {}", e)))?;
+                .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Image read error: {}", e)))?;
             image_data = Some(base64::engine::general_purpose::STANDARD.encode(data));
         } else if name == "mask" {
             let data = field
                 .bytes()
                 .await
-                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Mask read error: {}", e)))?;
+                .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Mask read error: {}", e)))?;
             mask_data = Some(base64::engine::general_purpose::STANDARD.encode(data));
         } else if name.starts_with("image") && name != "image_size" {
             // Support image1, image2, etc.
             let data = field.bytes().await.map_err(|e| {
-                (
+                openai_error(
                     StatusCode::BAD_REQUEST,
                     format!("Reference image read error: {}", e),
                 )
@@ -1617,7 +2548,7 @@ pub async fn handle_images_edits(
             prompt = field
                 .text()
                 .await
-                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Prompt read error: {}", e)))?;
+                .map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Prompt read error: {}", e)))?;
         } else if name == "n" {
             if let Ok(val) = field.text().await {
                 n = val.parse().unwrap_or(1);
@@ -1654,7 +2585,7 @@ pub async fn handle_images_edits(
     // Validation: Require either 'image' (standard edit) OR 'prompt' (generation)
     // If reference images are present, we treat it as generation with image context
     if prompt.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Missing prompt".to_string()));
+        return Err(openai_error(StatusCode::BAD_REQUEST, "Missing prompt".to_string()));
     }
 
     tracing::info!(
@@ -1672,6 +2603,7 @@ pub async fn handle_images_edits(
 
     // 1. Get Upstream & Token
     let upstream = state.upstream.clone();
+    let upstream_timeout = state.timeout_for_model(&model);
     let token_manager = state.token_manager;
     let (access_token, project_id, email, _wait_ms) = match token_manager
         .get_token("image_gen", false, None, "dall-e-3")
@@ -1679,7 +2611,7 @@ pub async fn handle_images_edits(
     {
         Ok(t) => t,
         Err(e) => {
-            return Err((
+            return Err(openai_error(
                 StatusCode::SERVICE_UNAVAILABLE,
                 format!("Token error: {}", e),
             ))
@@ -1790,7 +2722,7 @@ pub async fn handle_images_edits(
 
         tasks.push(tokio::spawn(async move {
             match upstream
-                .call_v1_internal("generateContent", &access_token, body, None)
+                .call_v1_internal("generateContent", &access_token, body, None, Some(upstream_timeout))
                 .await
             {
                 Ok(response) => {
@@ -1872,7 +2804,7 @@ pub async fn handle_images_edits(
             n,
             error_msg
         );
-        return Err((StatusCode::BAD_GATEWAY, error_msg));
+        return Err(openai_error(StatusCode::BAD_GATEWAY, error_msg));
     }
 
     if !errors.is_empty() {
@@ -1902,3 +2834,402 @@ pub async fn handle_images_edits(
     )
         .into_response())
 }
+
+/// 解析 chat 请求体；严格模式下含未识别字段时返回 400 并列出全部字段名
+fn parse_chat_request(body: Value, strict: bool) -> Result<OpenAIRequest, OpenAIErrorResponse> {
+    if strict {
+        let unknown = OpenAIRequest::unknown_fields(&body);
+        if !unknown.is_empty() {
+            return Err(openai_error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid request: unrecognised field(s): {}", unknown.join(", ")),
+            ));
+        }
+    }
+    serde_json::from_value(body).map_err(|e| openai_error(StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::openai::MAX_EMBED_BATCH_SIZE;
+    use std::sync::Arc;
+
+    async fn multipart_from_raw(raw: String) -> axum::extract::Multipart {
+        use axum::extract::FromRequest;
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .header("content-type", "multipart/form-data; boundary=XBOUNDARY")
+            .body(axum::body::Body::from(raw))
+            .unwrap();
+        axum::extract::Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_multipart_chat_request_injects_images() {
+        let request_json = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "What is in these pictures?"}
+            ]
+        });
+        let raw = format!(
+            "--XBOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"request\"\r\n\r\n\
+             {}\r\n\
+             --XBOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"image\"; filename=\"a.jpg\"\r\n\
+             Content-Type: image/jpeg\r\n\r\n\
+             abc\r\n\
+             --XBOUNDARY\r\n\
+             Content-Disposition: form-data; name=\"image2\"; filename=\"b.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n\
+             xyz\r\n\
+             --XBOUNDARY--\r\n",
+            request_json
+        );
+
+        let body = parse_multipart_chat_request(multipart_from_raw(raw).await).await.unwrap();
+        let content = &body["messages"][1]["content"];
+        assert_eq!(content[0], json!({"type": "text", "text": "What is in these pictures?"}));
+        assert_eq!(content[1]["image_url"]["url"], "data:image/jpeg;base64,YWJj");
+        assert_eq!(content[2]["image_url"]["url"], "data:image/png;base64,eHl6");
+        assert_eq!(body["messages"][0]["content"], "be brief");
+
+        // 注入后的请求体可按原流程解析
+        let req: OpenAIRequest = serde_json::from_value(body).unwrap();
+        assert!(matches!(
+            req.messages[1].content,
+            Some(crate::proxy::mappers::openai::OpenAIContent::Array(ref blocks)) if blocks.len() == 3
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_chat_request_requires_request_part() {
+        let raw = "--XBOUNDARY\r\n\
+                   Content-Disposition: form-data; name=\"image\"; filename=\"a.png\"\r\n\r\n\
+                   abc\r\n\
+                   --XBOUNDARY--\r\n"
+            .to_string();
+        let (status, _) = parse_multipart_chat_request(multipart_from_raw(raw).await).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut body = json!({"model": "m", "messages": [{"role": "system", "content": "x"}]});
+        assert!(inject_image_parts(&mut body, vec!["data:image/png;base64,AA".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_unknown_fields() {
+        let body = json!({"model": "gemini-2.5-flash", "messages": [], "unknown_field": 1});
+
+        let (status, Json(error)) = parse_chat_request(body.clone(), true).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.error.message, "Invalid request: unrecognised field(s): unknown_field");
+        assert_eq!(error.error.type_, "invalid_request_error");
+
+        // 非严格模式忽略未知字段
+        assert_eq!(parse_chat_request(body, false).unwrap().model, "gemini-2.5-flash");
+    }
+
+    #[test]
+    fn test_image_generation_request_defaults() {
+        let req: crate::proxy::mappers::openai::OpenAIImageGenerationRequest =
+            serde_json::from_value(json!({ "prompt": "a cat" })).unwrap();
+        assert_eq!(req.model, "gemini-3-pro-image");
+        assert_eq!(req.n, None);
+        assert!(serde_json::from_value::<crate::proxy::mappers::openai::OpenAIImageGenerationRequest>(
+            json!({ "model": "dall-e-3" })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_images_from_gemini_response() {
+        let resp = json!({
+            "response": {
+                "candidates": [{
+                    "content": {
+                        "parts": [
+                            { "text": "here you go" },
+                            { "inlineData": { "mimeType": "image/jpeg", "data": "AAAA" } },
+                            { "inlineData": { "mimeType": "image/png", "data": "" } }
+                        ]
+                    }
+                }]
+            }
+        });
+        assert_eq!(images_from_gemini_response(&resp, "b64_json"), vec![json!({ "b64_json": "AAAA" })]);
+        assert_eq!(
+            images_from_gemini_response(&resp, "url"),
+            vec![json!({ "url": "data:image/jpeg;base64,AAAA" })]
+        );
+        assert!(images_from_gemini_response(&json!({}), "b64_json").is_empty());
+    }
+
+    #[test]
+    fn test_batch_entry_success() {
+        let entry = batch_entry(0, StatusCode::OK, br#"{"id":"chatcmpl-1","object":"chat.completion"}"#);
+        assert_eq!(entry["id"], 0);
+        assert_eq!(entry["status"], 200);
+        assert_eq!(entry["result"]["object"], "chat.completion");
+    }
+
+    #[test]
+    fn test_batch_entry_plain_text_error() {
+        let entry = batch_entry(3, StatusCode::BAD_REQUEST, b"Invalid request: missing field `model`");
+        assert_eq!(entry["id"], 3);
+        assert_eq!(entry["status"], 400);
+        assert_eq!(entry["result"]["error"]["code"], 400);
+        assert!(entry["result"]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("missing field"));
+    }
+
+    #[test]
+    fn test_batch_entry_keeps_structured_error() {
+        let body = br#"{"error":{"message":"quota","type":"rate_limit_error"}}"#;
+        let entry = batch_entry(1, StatusCode::TOO_MANY_REQUESTS, body);
+        assert_eq!(entry["result"]["error"]["type"], "rate_limit_error");
+    }
+
+    #[test]
+    fn test_warn_if_not_json_reports_invalid_choices() {
+        use crate::proxy::mappers::openai::OpenAIResponse;
+
+        let response: OpenAIResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gemini-2.5-flash",
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "{\"city\": \"Paris\"}"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"role": "assistant", "content": "Sure! Here is JSON: {"}, "finish_reason": "stop"}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(warn_if_not_json(&response, "req_test"), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_rejects_extra_request() {
+        const N: usize = 4;
+        let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(N));
+        let (release_tx, _) = tokio::sync::broadcast::channel::<()>(1);
+        let (acquired_tx, mut acquired_rx) = tokio::sync::mpsc::channel::<()>(N);
+
+        // N 个并发请求各自持有许可直到被释放
+        let mut handles = Vec::new();
+        for _ in 0..N {
+            let semaphore = semaphore.clone();
+            let acquired_tx = acquired_tx.clone();
+            let mut release_rx = release_tx.subscribe();
+            handles.push(tokio::spawn(async move {
+                let permit = try_acquire_request_permit(&semaphore);
+                assert!(permit.is_ok());
+                acquired_tx.send(()).await.unwrap();
+                let _ = release_rx.recv().await;
+            }));
+        }
+        for _ in 0..N {
+            acquired_rx.recv().await.unwrap();
+        }
+
+        // 第 N+1 个请求立即得到 503 + Retry-After
+        let resp = try_acquire_request_permit(&semaphore).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()[axum::http::header::RETRY_AFTER], "5");
+
+        // 许可释放后恢复可用
+        release_tx.send(()).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert!(try_acquire_request_permit(&semaphore).is_ok());
+    }
+
+    #[test]
+    fn test_should_fallback_skips_success_and_quota_errors() {
+        assert!(!should_fallback(StatusCode::OK));
+        assert!(!should_fallback(StatusCode::TOO_MANY_REQUESTS));
+        assert!(should_fallback(StatusCode::NOT_FOUND));
+        assert!(should_fallback(StatusCode::BAD_REQUEST));
+        assert!(should_fallback(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    /// batchEmbedContents mock 上游：每条向量为 [文本长度, 0.5]，含 "fail" 文本时返回 429；记录收到的请求体
+    async fn embeddings_state(email: &str) -> (AppState, Arc<std::sync::Mutex<Vec<Value>>>) {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = received.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:batchEmbedContents",
+            axum::routing::post(move |Json(body): Json<Value>| {
+                let log = log.clone();
+                async move {
+                    let texts: Vec<String> = body["request"]["requests"]
+                        .as_array()
+                        .map(|requests| {
+                            requests
+                                .iter()
+                                .map(|r| r["content"]["parts"][0]["text"].as_str().unwrap_or_default().to_string())
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    log.lock().unwrap().push(body);
+                    if texts.iter().any(|t| t == "fail") {
+                        return (StatusCode::TOO_MANY_REQUESTS, "quota exhausted").into_response();
+                    }
+                    let embeddings: Vec<Value> = texts
+                        .iter()
+                        .map(|t| json!({"values": [t.len() as f64, 0.5]}))
+                        .collect();
+                    Json(json!({"response": {"embeddings": embeddings}})).into_response()
+                }
+            }),
+        );
+        (crate::proxy::tests::support::mock_app_state(email, router).await, received)
+    }
+
+    async fn response_json(resp: axum::response::Response) -> Value {
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_against_mock_upstream() {
+        let (state, received) = embeddings_state("embed@example.com").await;
+        let resp = handle_embeddings(
+            State(state),
+            Json(json!({"model": "text-embedding-004", "input": ["hi", "hello"]})),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("X-Account-Email").unwrap(), "embed@example.com");
+        let body = response_json(resp).await;
+        assert_eq!(body["object"], "list");
+        assert_eq!(body["model"], "text-embedding-004");
+        let data = body["data"].as_array().unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0]["index"], 0);
+        assert_eq!(data[0]["embedding"], json!([2.0, 0.5]));
+        assert_eq!(data[1]["index"], 1);
+        assert_eq!(data[1]["embedding"], json!([5.0, 0.5]));
+
+        // 全部输入合并为一次 batchEmbedContents，按输入顺序排列
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["model"], "text-embedding-004");
+        let requests = received[0]["request"]["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["model"], "models/text-embedding-004");
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_splits_large_batches() {
+        let (state, received) = embeddings_state("embed-many@example.com").await;
+        let inputs: Vec<String> = (0..MAX_EMBED_BATCH_SIZE + 1).map(|i| "x".repeat(i % 7 + 1)).collect();
+        let resp = handle_embeddings(
+            State(state),
+            Json(json!({"model": "text-embedding-004", "input": inputs})),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        let body = response_json(resp).await;
+        let data = body["data"].as_array().unwrap();
+        assert_eq!(data.len(), MAX_EMBED_BATCH_SIZE + 1);
+        assert_eq!(data[MAX_EMBED_BATCH_SIZE]["index"], MAX_EMBED_BATCH_SIZE);
+        assert_eq!(data[MAX_EMBED_BATCH_SIZE]["embedding"], json!([(MAX_EMBED_BATCH_SIZE % 7 + 1) as f64, 0.5]));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0]["request"]["requests"].as_array().unwrap().len(), MAX_EMBED_BATCH_SIZE);
+        assert_eq!(received[1]["request"]["requests"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_applies_custom_mapping() {
+        let (state, received) = embeddings_state("embed-map@example.com").await;
+        state
+            .custom_mapping
+            .write()
+            .await
+            .insert("text-embedding-3-small".to_string(), "gemini-embedding-001".to_string());
+        let resp = handle_embeddings(
+            State(state),
+            Json(json!({"model": "text-embedding-3-small", "input": "hi"})),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        // 响应中保留客户端请求的模型名，上游使用映射后的向量模型
+        let body = response_json(resp).await;
+        assert_eq!(body["model"], "text-embedding-3-small");
+        let received = received.lock().unwrap();
+        assert_eq!(received[0]["model"], "gemini-embedding-001");
+        assert_eq!(received[0]["request"]["requests"][0]["model"], "models/gemini-embedding-001");
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_base64_encoding() {
+        let (state, _) = embeddings_state("embed-b64@example.com").await;
+        let resp = handle_embeddings(
+            State(state),
+            Json(json!({
+                "model": "text-embedding-004",
+                "input": "abc",
+                "encoding_format": "base64"
+            })),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        let body = response_json(resp).await;
+        let encoded = body["data"][0]["embedding"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        let expected: Vec<u8> = [3.0f32, 0.5].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(bytes, expected);
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_propagates_upstream_error() {
+        let (state, received) = embeddings_state("embed-err@example.com").await;
+        let (status, Json(body)) = handle_embeddings(
+            State(state),
+            Json(json!({"model": "text-embedding-004", "input": ["fail", "never sent"]})),
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body.error.message, "quota exhausted");
+        assert_eq!(body.error.type_, "rate_limit_error");
+        // 同一批次只请求一次
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_handler_rejects_chat_model() {
+        let (state, received) = embeddings_state("embed-bad@example.com").await;
+        let (status, _) = handle_embeddings(
+            State(state),
+            Json(json!({"model": "gemini-2.5-flash", "input": "hi"})),
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+// 请求竞速 - 同一请求同时发往多个账号，采用最先返回有效数据的结果，其余请求被取消
+use axum::response::Response;
+use bytes::Bytes;
+use futures::future::{select_all, BoxFuture};
+use futures::FutureExt;
+use std::pin::Pin;
+use tracing::{debug, info, warn};
+
+use super::openai::{peek_first_chunk, respond_with_openai_stream};
+use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
+use crate::proxy::mappers::openai::{
+    estimate_prompt_tokens, transform_openai_request, truncate_messages_to_context, OpenAIRequest,
+};
+use crate::proxy::server::AppState;
+
+type OpenAISseStream = Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 等待第一个成功的 future，失败者被跳过；返回 (胜者下标, 结果)
+/// 其余未完成的 future 在返回时被 drop，对应的上游请求随之取消
+/// 全部失败时按完成顺序返回所有错误
+pub async fn race_first_ok<T, E>(
+    futures: Vec<BoxFuture<'static, Result<T, E>>>,
+) -> Result<(usize, T), Vec<E>>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    let mut pending: Vec<BoxFuture<'static, (usize, Result<T, E>)>> = futures
+        .into_iter()
+        .enumerate()
+        .map(|(idx, fut)| fut.map(move |result| (idx, result)).boxed())
+        .collect();
+    let mut errors = Vec::new();
+
+    while !pending.is_empty() {
+        let ((idx, result), _, rest) = select_all(pending).await;
+        match result {
+            Ok(value) => return Ok((idx, value)),
+            Err(e) => {
+                errors.push(e);
+                pending = rest;
+            }
+        }
+    }
+    Err(errors)
+}
+
+/// 使用最多 `racers` 个不同账号竞速执行一次 Chat Completions
+/// 可用账号不足 2 个或所有参赛请求都失败时返回 None，由调用方回落到常规重试流程
+pub async fn race_chat_request(
+    state: &AppState,
+    openai_req: &OpenAIRequest,
+    forwarded_headers: &std::collections::HashMap<String, String>,
+    trace_id: &str,
+    racers: usize,
+    reconnect_grace: std::time::Duration,
+) -> Option<Response> {
+    let mut openai_req = openai_req.clone();
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &openai_req.model,
+        &*state.custom_mapping.read().await,
+    );
+    let upstream_timeout = state.timeout_for_model(&mapped_model);
+
+    openai_req.max_tokens = crate::proxy::model_registry::cap_max_tokens(&mapped_model, openai_req.max_tokens);
+    let max_output_tokens = openai_req.max_tokens.map_or(0, |v| v.min(i32::MAX as u32) as i32);
+    truncate_messages_to_context(&mut openai_req.messages, &mapped_model, max_output_tokens);
+
+    let tools_val = openai_req.tools_as_values();
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        &openai_req.model,
+        &mapped_model,
+        &tools_val,
+        None,
+        None,
+    );
+
+    // 每次强制轮换，取 N 个不同账号
+    let mut accounts: Vec<(String, String, String)> = Vec::new();
+    for i in 0..racers {
+        match state
+            .token_manager
+            .get_token(&config.request_type, i > 0, None, &mapped_model)
+            .await
+        {
+            Ok((access_token, project_id, email, _)) => {
+                if !accounts.iter().any(|(_, _, e)| *e == email) {
+                    accounts.push((access_token, project_id, email));
+                }
+            }
+            Err(e) => {
+                debug!("[{}] Racing: no more tokens available: {}", trace_id, e);
+                break;
+            }
+        }
+    }
+    if accounts.len() < 2 {
+        debug!("[{}] Racing skipped: only {} account(s) available", trace_id, accounts.len());
+        return None;
+    }
+
+    let include_usage = openai_req.stream && openai_req.include_usage();
+    let max_inline_image_bytes = state.max_inline_image_bytes;
+    let keepalive_interval = state.sse_keepalive_interval();
+    let attempts: Vec<BoxFuture<'static, Result<(Bytes, OpenAISseStream), String>>> = accounts
+        .iter()
+        .map(|(access_token, project_id, email)| {
+            let upstream = state.upstream.clone();
+            let gemini_body = transform_openai_request(&openai_req, project_id, &mapped_model);
+            let access_token = access_token.clone();
+            let model = openai_req.model.clone();
+            let email = email.clone();
+            let forwarded_headers = forwarded_headers.clone();
+            async move {
+                let response = upstream
+                    .call_v1_internal_with_headers(
+                        "streamGenerateContent",
+                        &access_token,
+                        gemini_body,
+                        Some("alt=sse"),
+                        forwarded_headers,
+                        Some(upstream_timeout),
+                    )
+                    .await
+                    .map_err(|e| format!("{}: {}", email, e))?;
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(format!("{}: HTTP {}", email, status.as_u16()));
+                }
+                let mut stream = create_openai_sse_stream(
+                    Box::pin(response.bytes_stream()),
+                    model,
+                    include_usage,
+                    max_inline_image_bytes,
+                    keepalive_interval,
+                );
+                let first_chunk = peek_first_chunk(&mut stream)
+                    .await
+                    .map_err(|e| format!("{}: {}", email, e))?;
+                Ok((first_chunk, stream))
+            }
+            .boxed()
+        })
+        .collect();
+
+    info!("[{}] Racing request across {} accounts", trace_id, accounts.len());
+    let race_start = std::time::Instant::now();
+    let (winner, (first_chunk, stream)) = match race_first_ok(attempts).await {
+        Ok(won) => won,
+        Err(errors) => {
+            warn!("[{}] All racing attempts failed: {:?}", trace_id, errors);
+            return None;
+        }
+    };
+
+    // 落败的请求已被取消，但上游可能已计入输入 token，按估算值记账
+    let prompt_tokens = estimate_prompt_tokens(&openai_req.messages) as u64;
+    for (idx, (_, _, email)) in accounts.iter().enumerate() {
+        if idx != winner {
+            state.token_manager.record_usage(email, prompt_tokens, 0);
+        }
+    }
+
+    let email = &accounts[winner].2;
+    info!("[{}] Racing winner: {}", trace_id, email);
+    state.token_manager.mark_account_success(email);
+    crate::proxy::metrics::metrics().record_ttfb(&mapped_model, race_start.elapsed());
+
+    Some(
+        respond_with_openai_stream(
+            state,
+            &openai_req,
+            first_chunk,
+            stream,
+            email,
+            &mapped_model,
+            trace_id,
+            reconnect_grace,
+        )
+        .await,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    type MockResult = Result<&'static str, &'static str>;
+
+    fn mock(delay_ms: u64, result: MockResult) -> BoxFuture<'static, MockResult> {
+        async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            result
+        }
+        .boxed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fastest_mock_wins() {
+        let racers = vec![mock(300, Ok("slow")), mock(50, Ok("fast")), mock(150, Ok("medium"))];
+        assert_eq!(race_first_ok(racers).await, Ok((1, "fast")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_faster_failure_is_skipped() {
+        let racers = vec![mock(200, Ok("slow")), mock(10, Err("429"))];
+        assert_eq!(race_first_ok(racers).await, Ok((0, "slow")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_all_failures_are_reported() {
+        let racers = vec![mock(20, Err("b")), mock(10, Err("a"))];
+        assert_eq!(race_first_ok(racers).await, Err(vec!["a", "b"]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_losers_are_cancelled() {
+        struct DropFlag(Arc<AtomicBool>);
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let slow: BoxFuture<'static, MockResult> = async move {
+            let _flag = flag;
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok("slow")
+        }
+        .boxed();
+
+        assert_eq!(race_first_ok(vec![slow, mock(10, Ok("fast"))]).await, Ok((1, "fast")));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
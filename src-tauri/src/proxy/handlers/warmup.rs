@@ -170,14 +170,14 @@ pub async fn handle_warmup(
 
     let mut result = state
         .upstream
-        .call_v1_internal(method, &access_token, body.clone(), query)
+        .call_v1_internal(method, &access_token, body.clone(), query, None)
         .await;
 
     // 如果流式请求失败，尝试非流式请求
     if result.is_err() && !prefer_non_stream {
         result = state
             .upstream
-            .call_v1_internal("generateContent", &access_token, body, None)
+            .call_v1_internal("generateContent", &access_token, body, None, None)
             .await;
     }
 
@@ -234,7 +234,7 @@ pub async fn handle_warmup(
                 Json(WarmupResponse {
                     success: false,
                     message: "Warmup request failed".to_string(),
-                    error: Some(e),
+                    error: Some(e.to_string()),
                 }),
             ).into_response();
 
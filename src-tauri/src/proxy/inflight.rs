@@ -0,0 +1,146 @@
+// 在途请求计数 - 停止服务时等待进行中的请求 (含 SSE 流) 完成
+// [NEW] 进程收到 SIGINT / SIGTERM 时由顶层 (headless run / Tauri) 调用 AxumServer::stop 排空
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 轮询间隔
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 在途请求守卫：创建时计数 +1，drop 时 -1
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    pub fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 等待在途请求归零，最多等待 `timeout`
+/// 返回 true 表示已全部完成，false 表示超时
+pub async fn wait_for_drain(counter: &AtomicUsize, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let remaining = counter.load(Ordering::SeqCst);
+        if remaining == 0 {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            tracing::warn!("等待在途请求超时 ({:?})，仍有 {} 个请求未完成", timeout, remaining);
+            return false;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+/// 等待进程停止信号 (Ctrl-C / SIGINT，Unix 下还有 SIGTERM)，返回信号名称
+/// 只应在进程入口处监听一次，服务器任务本身不处理信号
+pub async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = term.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                tracing::warn!("无法监听 SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_counts() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let a = InFlightGuard::new(&counter);
+        let b = InFlightGuard::new(&counter);
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+        drop(a);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        drop(b);
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_in_flight_request_completes_before_drain() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let guard = InFlightGuard::new(&counter);
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let finished_clone = finished.clone();
+        let request = tokio::spawn(async move {
+            let _guard = guard;
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            finished_clone.store(true, Ordering::SeqCst);
+        });
+
+        // 停止信号发出后开始等待，请求应在等待期间正常结束
+        assert!(wait_for_drain(&counter, Duration::from_secs(5)).await);
+        assert!(finished.load(Ordering::SeqCst));
+        request.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_times_out() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let _guard = InFlightGuard::new(&counter);
+        let start = tokio::time::Instant::now();
+        assert!(!wait_for_drain(&counter, Duration::from_secs(1)).await);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sigterm_drains_active_request() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let guard = InFlightGuard::new(&counter);
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let finished_clone = finished.clone();
+        let request = tokio::spawn(async move {
+            let _guard = guard;
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            finished_clone.store(true, Ordering::SeqCst);
+        });
+
+        // 与进程入口相同：收到信号后排空在途请求
+        let counter_clone = counter.clone();
+        let shutdown = tokio::spawn(async move {
+            let signal = shutdown_signal().await;
+            let drained = wait_for_drain(&counter_clone, Duration::from_secs(5)).await;
+            (signal, drained)
+        });
+
+        // 等待信号监听注册后再发送 SIGTERM，避免默认处理终止测试进程
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+
+        let (signal, drained) = shutdown.await.unwrap();
+        assert_eq!(signal, "SIGTERM");
+        assert!(drained);
+        assert!(finished.load(Ordering::SeqCst));
+        request.await.unwrap();
+    }
+}
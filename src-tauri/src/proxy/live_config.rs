@@ -0,0 +1,196 @@
+// 运行中的配置 - 按请求读取的选项 (重试/超时/并发上限/流量拆分等) 统一从这里取值
+// AxumServer::apply_config 写入新配置后，下一个请求即按新配置处理，无需重启
+use regex::Regex;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::proxy::config::{ContextOverflowStrategy, ProxyConfig, RetryPolicy, ThinkingMode};
+use crate::proxy::traffic_split::TrafficSplitter;
+
+/// 运行中的 ProxyConfig 及由其派生的状态 (预编译正则、并发信号量)
+pub struct LiveConfig {
+    config: RwLock<ProxyConfig>,
+    retry_patterns: RwLock<Arc<Vec<Regex>>>,
+    traffic_splitter: RwLock<Arc<TrafficSplitter>>,
+    request_semaphore: Arc<Semaphore>,
+    /// 信号量当前对应的并发上限 (许可总数)；调整时持锁，保证多次调整按顺序生效
+    request_limit: Mutex<usize>,
+}
+
+/// 0 表示不限制并发
+fn permits_for(max_concurrent_requests: usize) -> usize {
+    if max_concurrent_requests == 0 {
+        Semaphore::MAX_PERMITS
+    } else {
+        max_concurrent_requests
+    }
+}
+
+impl LiveConfig {
+    pub fn new(config: &ProxyConfig) -> Self {
+        let limit = permits_for(config.max_concurrent_requests);
+        Self {
+            config: RwLock::new(config.clone()),
+            retry_patterns: RwLock::new(Arc::new(crate::proxy::handlers::common::compile_retry_patterns(
+                &config.retry_on_patterns,
+            ))),
+            traffic_splitter: RwLock::new(Arc::new(TrafficSplitter::new(&config.traffic_splits))),
+            request_semaphore: Arc::new(Semaphore::new(limit)),
+            request_limit: Mutex::new(limit),
+        }
+    }
+
+    /// 替换为新配置并重建派生状态
+    pub fn apply(&self, config: &ProxyConfig) {
+        let previous = std::mem::replace(&mut *self.config.write().unwrap(), config.clone());
+        if previous.retry_on_patterns != config.retry_on_patterns {
+            *self.retry_patterns.write().unwrap() = Arc::new(
+                crate::proxy::handlers::common::compile_retry_patterns(&config.retry_on_patterns),
+            );
+        }
+        if previous.traffic_splits != config.traffic_splits {
+            *self.traffic_splitter.write().unwrap() = Arc::new(TrafficSplitter::new(&config.traffic_splits));
+        }
+        self.resize_request_limit(permits_for(config.max_concurrent_requests));
+    }
+
+    /// 原地调整并发上限：扩大时直接补发许可；缩小时先回收空闲许可，
+    /// 仍不足的部分等在途请求释放后回收 (等待期间新请求拿不到许可)
+    fn resize_request_limit(&self, limit: usize) {
+        let mut current = self.request_limit.lock().unwrap();
+        if limit > *current {
+            self.request_semaphore.add_permits(limit - *current);
+        } else if limit < *current {
+            let excess = *current - limit;
+            let forgotten = self.request_semaphore.forget_permits(excess);
+            let pending = excess - forgotten;
+            if pending > 0 {
+                let semaphore = self.request_semaphore.clone();
+                tokio::spawn(async move {
+                    // acquire_many 单次最多 u32::MAX 个许可
+                    let mut pending = pending;
+                    while pending > 0 {
+                        let batch = pending.min(u32::MAX as usize);
+                        match semaphore.clone().acquire_many_owned(batch as u32).await {
+                            Ok(permit) => permit.forget(),
+                            Err(_) => return,
+                        }
+                        pending -= batch;
+                    }
+                });
+            }
+        }
+        *current = limit;
+    }
+
+    /// 读取配置中的任意字段 (不要在闭包中等待)
+    pub fn read<T>(&self, f: impl FnOnce(&ProxyConfig) -> T) -> T {
+        f(&self.config.read().unwrap())
+    }
+
+    /// Chat Completions 并发信号量 (上限随配置调整)
+    pub fn request_semaphore(&self) -> Arc<Semaphore> {
+        self.request_semaphore.clone()
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.read(|c| c.retry_policy.clone())
+    }
+
+    /// 400 可重试错误匹配规则 (内置规则 + retry_on_patterns)
+    pub fn retry_patterns(&self) -> Arc<Vec<Regex>> {
+        self.retry_patterns.read().unwrap().clone()
+    }
+
+    pub fn traffic_splitter(&self) -> Arc<TrafficSplitter> {
+        self.traffic_splitter.read().unwrap().clone()
+    }
+
+    /// Retry-After 等待上限
+    pub fn max_retry_delay(&self) -> Duration {
+        self.read(|c| Duration::from_secs(c.max_retry_delay_secs))
+    }
+
+    /// 模型的上游请求超时
+    pub fn timeout_for_model(&self, model: &str) -> Duration {
+        self.read(|c| crate::proxy::config::resolve_model_timeout(&c.model_timeouts, c.default_timeout_secs, model))
+    }
+
+    pub fn include_cost_in_response(&self) -> bool {
+        self.read(|c| c.include_cost_in_response)
+    }
+
+    pub fn context_overflow_strategy(&self) -> ContextOverflowStrategy {
+        self.read(|c| c.context_overflow_strategy.clone())
+    }
+
+    pub fn thinking_mode(&self) -> ThinkingMode {
+        self.read(|c| c.thinking_mode.clone())
+    }
+
+    pub fn strict_request_validation(&self) -> bool {
+        self.read(|c| c.strict_request_validation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_limit(max_concurrent_requests: usize) -> ProxyConfig {
+        ProxyConfig {
+            max_concurrent_requests,
+            ..ProxyConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_change_applies_without_restart() {
+        let live = LiveConfig::new(&config_with_limit(2));
+        let semaphore = live.request_semaphore();
+        let held: Vec<_> = (0..2).map(|_| semaphore.clone().try_acquire_owned().unwrap()).collect();
+        assert!(semaphore.clone().try_acquire_owned().is_err());
+
+        // 扩大上限：同一个信号量立即多出许可
+        live.apply(&config_with_limit(3));
+        let third = semaphore.clone().try_acquire_owned().unwrap();
+        assert!(semaphore.clone().try_acquire_owned().is_err());
+
+        // 缩小上限：在途请求释放后才回收，之后只允许 1 个并发
+        live.apply(&config_with_limit(1));
+        drop(third);
+        drop(held);
+        tokio::task::yield_now().await;
+        let only = semaphore.clone().try_acquire_owned().unwrap();
+        assert!(semaphore.clone().try_acquire_owned().is_err());
+        drop(only);
+
+        // 0 表示不限制
+        live.apply(&config_with_limit(0));
+        let many: Vec<_> = (0..100).map(|_| semaphore.clone().try_acquire_owned().unwrap()).collect();
+        assert_eq!(many.len(), 100);
+    }
+
+    #[test]
+    fn test_apply_rebuilds_derived_state() {
+        let live = LiveConfig::new(&ProxyConfig::default());
+        let built_in = live.retry_patterns().len();
+        assert_eq!(live.traffic_splitter().select("gpt-4o"), None);
+
+        let mut config = ProxyConfig::default();
+        config.retry_on_patterns.push("FAILED_PRECONDITION".to_string());
+        config.traffic_splits = vec![crate::proxy::config::TrafficSplit {
+            model_pattern: "^gpt-4o".to_string(),
+            variants: vec![("gemini-3-flash".to_string(), 100)],
+        }];
+        config.model_timeouts.insert("gemini-3-pro".to_string(), 42);
+        config.include_cost_in_response = true;
+        live.apply(&config);
+
+        assert_eq!(live.retry_patterns().len(), built_in + 1);
+        assert_eq!(live.traffic_splitter().select("gpt-4o").as_deref(), Some("gemini-3-flash"));
+        assert_eq!(live.timeout_for_model("gemini-3-pro"), Duration::from_secs(42));
+        assert!(live.include_cost_in_response());
+    }
+}
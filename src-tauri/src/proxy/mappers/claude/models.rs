@@ -255,14 +255,16 @@ pub struct Usage {
 // ========== Gemini 数据模型 ==========
 
 /// Gemini Content
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeminiContent {
+    #[serde(default)]
     pub role: String,
+    #[serde(default)]
     pub parts: Vec<GeminiPart>,
 }
 
 /// Gemini Part
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeminiPart {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
@@ -271,7 +273,7 @@ pub struct GeminiPart {
     pub thought: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(rename = "thoughtSignature")]
+    #[serde(rename = "thoughtSignature", alias = "thought_signature")]
     pub thought_signature: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -285,6 +287,21 @@ pub struct GeminiPart {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "inlineData")]
     pub inline_data: Option<InlineData>,
+
+    /// 远程文件引用 (如 http(s) 图片 URL)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fileData")]
+    pub file_data: Option<FileData>,
+
+    /// 代码执行工具产出的代码 ({"language", "code"})
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "executableCode")]
+    pub executable_code: Option<serde_json::Value>,
+
+    /// 代码执行结果 ({"outcome", "output"})
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "codeExecutionResult")]
+    pub code_execution_result: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -311,6 +328,14 @@ pub struct InlineData {
     pub data: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileData {
+    #[serde(rename = "fileUri")]
+    pub file_uri: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+}
+
 /// Gemini 完整响应
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeminiResponse {
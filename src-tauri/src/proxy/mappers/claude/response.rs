@@ -566,6 +566,9 @@ mod tests {
                         function_call: None,
                         function_response: None,
                         inline_data: None,
+                        file_data: None,
+                        executable_code: None,
+                        code_execution_result: None,
                     }],
                 }),
                 finish_reason: Some("STOP".to_string()),
@@ -619,6 +622,9 @@ mod tests {
                             function_call: None,
                             function_response: None,
                             inline_data: None,
+                            file_data: None,
+                            executable_code: None,
+                            code_execution_result: None,
                         },
                         GeminiPart {
                             text: Some("The answer is 42".to_string()),
@@ -627,6 +633,9 @@ mod tests {
                             function_call: None,
                             function_response: None,
                             inline_data: None,
+                            file_data: None,
+                            executable_code: None,
+                            code_execution_result: None,
                         },
                     ],
                 }),
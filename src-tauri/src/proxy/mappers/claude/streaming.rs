@@ -1065,9 +1065,12 @@ mod tests {
             text: None,
             function_call: Some(fc),
             inline_data: None,
+            file_data: None,
             thought: None,
             thought_signature: None,
             function_response: None,
+            executable_code: None,
+            code_execution_result: None,
         };
 
         let chunks = processor.process(&part);
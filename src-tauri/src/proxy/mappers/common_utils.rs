@@ -264,35 +264,40 @@ pub fn inject_google_search_tool(body: &mut Value) {
     if let Some(obj) = body.as_object_mut() {
         let tools_entry = obj.entry("tools").or_insert_with(|| json!([]));
         if let Some(tools_arr) = tools_entry.as_array_mut() {
-            // [安全校验] 如果数组中已经包含 functionDeclarations，严禁注入 googleSearch
-            // 因为 Gemini v1internal 不支持在一次请求中混用 search 和 functions
-            let has_functions = tools_arr.iter().any(|t| {
-                t.as_object()
-                    .map_or(false, |o| o.contains_key("functionDeclarations"))
-            });
-
-            if has_functions {
-                tracing::debug!(
-                    "Skipping googleSearch injection due to existing functionDeclarations"
-                );
-                return;
-            }
+            inject_google_search_tools(tools_arr);
+        }
+    }
+}
 
-            // 首先清理掉已存在的 googleSearch 或 googleSearchRetrieval，以防重复产生冲突
-            tools_arr.retain(|t| {
-                if let Some(o) = t.as_object() {
-                    !(o.contains_key("googleSearch") || o.contains_key("googleSearchRetrieval"))
-                } else {
-                    true
-                }
-            });
+/// 同 inject_google_search_tool，直接作用于 tools 数组
+pub fn inject_google_search_tools(tools_arr: &mut Vec<Value>) {
+    // [安全校验] 如果数组中已经包含 functionDeclarations，严禁注入 googleSearch
+    // 因为 Gemini v1internal 不支持在一次请求中混用 search 和 functions
+    let has_functions = tools_arr.iter().any(|t| {
+        t.as_object()
+            .map_or(false, |o| o.contains_key("functionDeclarations"))
+    });
+
+    if has_functions {
+        tracing::debug!(
+            "Skipping googleSearch injection due to existing functionDeclarations"
+        );
+        return;
+    }
 
-            // 注入统一的 googleSearch (v1internal 规范)
-            tools_arr.push(json!({
-                "googleSearch": {}
-            }));
+    // 首先清理掉已存在的 googleSearch 或 googleSearchRetrieval，以防重复产生冲突
+    tools_arr.retain(|t| {
+        if let Some(o) = t.as_object() {
+            !(o.contains_key("googleSearch") || o.contains_key("googleSearchRetrieval"))
+        } else {
+            true
         }
-    }
+    });
+
+    // 注入统一的 googleSearch (v1internal 规范)
+    tools_arr.push(json!({
+        "googleSearch": {}
+    }));
 }
 
 /// 深度迭代清理客户端发送的 [undefined] 脏字符串，防止 Gemini 接口校验失败
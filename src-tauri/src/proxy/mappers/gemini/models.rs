@@ -1,16 +1,313 @@
 // Gemini v1internal 数据模型
+use crate::proxy::mappers::claude::models::{GeminiContent, GeminiPart, GroundingMetadata};
+
+pub use crate::proxy::mappers::claude::models::UsageMetadata as GeminiUsageMetadata;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[allow(dead_code)]
+/// v1internal generateContent 请求信封
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct V1InternalRequest {
     pub project: String,
     #[serde(rename = "requestId")]
     pub request_id: String,
-    pub request: serde_json::Value,
+    pub request: GeminiRequest,
     pub model: String,
     #[serde(rename = "userAgent")]
     pub user_agent: String,
     #[serde(rename = "requestType")]
     pub request_type: String,
 }
+
+/// v1internal 请求体中仅需 project 与内层请求的场景 (countTokens / createCachedContent)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct V1InternalBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub request: GeminiRequest,
+}
+
+/// 内层 Gemini 请求 (generateContent / countTokens / createCachedContent 共用字段)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiRequest {
+    /// countTokens / createCachedContent 需要 "models/xxx"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiSystemInstruction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GeminiGenerationConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<GeminiToolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<Vec<GeminiSafetySetting>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+    /// CachedContent 存活时间 (如 "3600s")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    /// Anthropic 风格模型 (claude-*) 的思考预算 ({"type": "enabled", "budget_tokens": N})
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeminiSafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+impl GeminiSafetySetting {
+    pub fn new(category: &str, threshold: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            threshold: threshold.to_string(),
+        }
+    }
+}
+
+/// systemInstruction (v1internal 要求 role 为 "user")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiSystemInstruction {
+    pub role: String,
+    pub parts: Vec<GeminiPart>,
+}
+
+impl GeminiSystemInstruction {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            parts: vec![GeminiPart {
+                text: Some(text.into()),
+                ..Default::default()
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<GeminiThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_config: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiThinkingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiToolConfig {
+    pub function_calling_config: GeminiFunctionCallingConfig,
+}
+
+/// mode: "AUTO" | "ANY" | "NONE" | "VALIDATED"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionCallingConfig {
+    pub mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_function_names: Option<Vec<String>>,
+}
+
+/// streamGenerateContent 的单个 SSE 事件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiStreamChunk {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    /// 仅携带 finishReason 的收尾事件可能没有 content
+    #[serde(default)]
+    pub content: GeminiContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GroundingMetadata>,
+}
+
+/// v1internal 将事件包在 {"response": {...}} 中，直连 Gemini API 则没有这层包装
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StreamEvent {
+    Wrapped { response: GeminiStreamChunk },
+    Bare(GeminiStreamChunk),
+}
+
+impl GeminiStreamChunk {
+    /// 解析一行 SSE data (兼容 v1internal 包装)
+    pub fn parse(data: &str) -> serde_json::Result<Self> {
+        Ok(match serde_json::from_str::<StreamEvent>(data)? {
+            StreamEvent::Wrapped { response } => response,
+            StreamEvent::Bare(chunk) => chunk,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_gemini_request_schema() {
+        let request = GeminiRequest {
+            contents: vec![GeminiContent {
+                role: "user".to_string(),
+                parts: vec![GeminiPart {
+                    text: Some("hi".to_string()),
+                    ..Default::default()
+                }],
+            }],
+            system_instruction: Some(GeminiSystemInstruction::text("be brief")),
+            generation_config: Some(GeminiGenerationConfig {
+                max_output_tokens: Some(1024),
+                thinking_config: Some(GeminiThinkingConfig {
+                    include_thoughts: Some(true),
+                    thinking_budget: Some(2048),
+                }),
+                ..Default::default()
+            }),
+            tool_config: Some(GeminiToolConfig {
+                function_calling_config: GeminiFunctionCallingConfig {
+                    mode: "ANY".to_string(),
+                    allowed_function_names: Some(vec!["get_weather".to_string()]),
+                },
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "contents": [{"role": "user", "parts": [{"text": "hi"}]}],
+                "systemInstruction": {"role": "user", "parts": [{"text": "be brief"}]},
+                "generationConfig": {
+                    "maxOutputTokens": 1024,
+                    "thinkingConfig": {"includeThoughts": true, "thinkingBudget": 2048}
+                },
+                "toolConfig": {
+                    "functionCallingConfig": {"mode": "ANY", "allowedFunctionNames": ["get_weather"]}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_envelope_skips_empty_fields() {
+        let body = V1InternalBody {
+            project: None,
+            request: GeminiRequest {
+                model: Some("models/gemini-2.5-flash".to_string()),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({"request": {"model": "models/gemini-2.5-flash"}})
+        );
+
+        // 反序列化后保持一致
+        let parsed: GeminiRequest = serde_json::from_value(json!({
+            "contents": [{"role": "model", "parts": [{"text": "ok", "thought": true}]}],
+            "generationConfig": {"temperature": 0.5}
+        }))
+        .unwrap();
+        assert_eq!(parsed.contents[0].parts[0].thought, Some(true));
+        assert_eq!(parsed.generation_config.unwrap().temperature, Some(0.5));
+    }
+
+    #[test]
+    fn test_parse_stream_chunk_fixtures() {
+        let fixture = include_str!("../../tests/fixtures/gemini_stream_chunks.sse");
+        let chunks: Vec<GeminiStreamChunk> = fixture
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| GeminiStreamChunk::parse(data).unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 4);
+
+        // 思考片段 (v1internal 包装)
+        let thought = &chunks[0].candidates[0].content.parts[0];
+        assert_eq!(chunks[0].candidates[0].content.role, "model");
+        assert_eq!(thought.thought, Some(true));
+        assert_eq!(thought.thought_signature.as_deref(), Some("c2lnbmF0dXJl"));
+
+        // 正文片段 (无包装)
+        assert_eq!(chunks[1].candidates[0].content.parts[0].text.as_deref(), Some("The answer"));
+        assert_eq!(chunks[1].model_version.as_deref(), Some("gemini-2.5-flash"));
+
+        // 工具调用
+        let call = chunks[2].candidates[0].content.parts[0].function_call.as_ref().unwrap();
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(call.args.as_ref().unwrap()["city"], "Paris");
+
+        // 收尾事件：finishReason + usageMetadata
+        let last = &chunks[3];
+        assert_eq!(last.candidates[0].finish_reason.as_deref(), Some("STOP"));
+        let usage = last.usage_metadata.as_ref().unwrap();
+        assert_eq!(usage.prompt_token_count, Some(12));
+        assert_eq!(usage.candidates_token_count, Some(30));
+        assert_eq!(usage.total_token_count, Some(42));
+    }
+
+    #[test]
+    fn test_parse_stream_chunk_without_content() {
+        let chunk = GeminiStreamChunk::parse(
+            r#"{"response": {"candidates": [{"finishReason": "SAFETY", "index": 0}]}, "traceId": "abc"}"#,
+        )
+        .unwrap();
+        assert!(chunk.candidates[0].content.parts.is_empty());
+        assert_eq!(chunk.candidates[0].finish_reason.as_deref(), Some("SAFETY"));
+        assert!(GeminiStreamChunk::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// 流式 chunk 合并 - 将过碎的 content 增量合并后再发送，减少客户端重绘次数
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// 缓冲的最长等待时间，超时后即使不足 min_chunk_bytes 也立即发送
+const MAX_BUFFER_DELAY: Duration = Duration::from_millis(200);
+
+type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 包装 OpenAI SSE 流：连续的纯文本增量会被合并，直到累计 `min_chunk_bytes` 字节、
+/// 收到 finish_reason / 非文本 chunk / [DONE]，或距第一个缓冲 chunk 超过 200ms
+/// `min_chunk_bytes` 为 0 时原样返回
+pub fn buffer_sse_chunks(mut inner: SseStream, min_chunk_bytes: usize) -> SseStream {
+    if min_chunk_bytes == 0 {
+        return inner;
+    }
+
+    let stream = async_stream::stream! {
+        let mut pending: Option<Value> = None;
+        let mut pending_bytes = 0usize;
+        let mut deadline: Option<tokio::time::Instant> = None;
+
+        loop {
+            // None 表示缓冲超时
+            let next = match deadline {
+                Some(d) => tokio::select! {
+                    item = inner.next() => Some(item),
+                    _ = tokio::time::sleep_until(d) => None,
+                },
+                None => Some(inner.next().await),
+            };
+
+            let item = match next {
+                None => {
+                    if let Some(chunk) = pending.take() {
+                        yield Ok(to_frame(&chunk));
+                    }
+                    pending_bytes = 0;
+                    deadline = None;
+                    continue;
+                }
+                Some(item) => item,
+            };
+
+            match item {
+                Some(Ok(bytes)) => match parse_text_delta(&bytes) {
+                    Some((chunk, index, text)) => {
+                        match pending.as_mut() {
+                            Some(buffered) if choice_index(buffered) == Some(index) => {
+                                append_content(buffered, &text);
+                            }
+                            _ => {
+                                if let Some(prev) = pending.replace(chunk) {
+                                    yield Ok(to_frame(&prev));
+                                }
+                                pending_bytes = 0;
+                                deadline = Some(tokio::time::Instant::now() + MAX_BUFFER_DELAY);
+                            }
+                        }
+                        pending_bytes += text.len();
+                        if pending_bytes >= min_chunk_bytes {
+                            if let Some(chunk) = pending.take() {
+                                yield Ok(to_frame(&chunk));
+                            }
+                            pending_bytes = 0;
+                            deadline = None;
+                        }
+                    }
+                    None => {
+                        // finish_reason / tool_calls / 心跳 / [DONE] 等：先冲刷缓冲，保持顺序
+                        if let Some(chunk) = pending.take() {
+                            yield Ok(to_frame(&chunk));
+                        }
+                        pending_bytes = 0;
+                        deadline = None;
+                        yield Ok(bytes);
+                    }
+                },
+                Some(Err(e)) => {
+                    if let Some(chunk) = pending.take() {
+                        yield Ok(to_frame(&chunk));
+                    }
+                    pending_bytes = 0;
+                    deadline = None;
+                    yield Err(e);
+                }
+                None => {
+                    if let Some(chunk) = pending.take() {
+                        yield Ok(to_frame(&chunk));
+                    }
+                    break;
+                }
+            }
+        }
+    };
+
+    Box::pin(stream)
+}
+
+/// 仅当 chunk 是单个 choice、delta 只有 content 文本、且没有 finish_reason / usage 时才可合并
+fn parse_text_delta(bytes: &Bytes) -> Option<(Value, u64, String)> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let data = text.trim().strip_prefix("data:")?.trim();
+    let chunk: Value = serde_json::from_str(data).ok()?;
+
+    if chunk.get("usage").is_some_and(|u| !u.is_null()) {
+        return None;
+    }
+    let choices = chunk.get("choices")?.as_array()?;
+    if choices.len() != 1 {
+        return None;
+    }
+    let choice = &choices[0];
+    if choice.get("finish_reason").is_some_and(|f| !f.is_null()) {
+        return None;
+    }
+    let delta = choice.get("delta")?.as_object()?;
+    if delta.len() != 1 {
+        return None;
+    }
+    let content = delta.get("content")?.as_str()?.to_string();
+    let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+    Some((chunk, index, content))
+}
+
+fn choice_index(chunk: &Value) -> Option<u64> {
+    Some(chunk["choices"][0].get("index").and_then(|i| i.as_u64()).unwrap_or(0))
+}
+
+fn append_content(chunk: &mut Value, text: &str) {
+    let content = &mut chunk["choices"][0]["delta"]["content"];
+    let merged = format!("{}{}", content.as_str().unwrap_or(""), text);
+    *content = Value::String(merged);
+}
+
+fn to_frame(chunk: &Value) -> Bytes {
+    Bytes::from(format!(
+        "data: {}\n\n",
+        serde_json::to_string(chunk).unwrap_or_default()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text_frame(text: &str) -> Result<Bytes, String> {
+        let chunk = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "choices": [{"index": 0, "delta": {"content": text}, "finish_reason": null}]
+        });
+        Ok(to_frame(&chunk))
+    }
+
+    fn finish_frame() -> Result<Bytes, String> {
+        let chunk = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "choices": [{"index": 0, "delta": {"content": ""}, "finish_reason": "stop"}]
+        });
+        Ok(to_frame(&chunk))
+    }
+
+    fn contents(frames: &[Bytes]) -> Vec<String> {
+        frames
+            .iter()
+            .map(|f| {
+                let text = std::str::from_utf8(f).unwrap().trim();
+                let data = text.strip_prefix("data: ").unwrap();
+                if data == "[DONE]" {
+                    return "[DONE]".to_string();
+                }
+                let v: Value = serde_json::from_str(data).unwrap();
+                match v["choices"][0]["finish_reason"].as_str() {
+                    Some(reason) => format!("<{}>", reason),
+                    None => v["choices"][0]["delta"]["content"].as_str().unwrap().to_string(),
+                }
+            })
+            .collect()
+    }
+
+    async fn run(frames: Vec<Result<Bytes, String>>, min: usize) -> Vec<Bytes> {
+        let inner: SseStream = Box::pin(futures::stream::iter(frames));
+        buffer_sse_chunks(inner, min)
+            .map(|r| r.unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_disabled_passes_through() {
+        let out = run(vec![text_frame("a"), text_frame("b")], 0).await;
+        assert_eq!(contents(&out), vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_merges_until_threshold_and_flushes_on_finish() {
+        let frames = vec![
+            text_frame("He"),
+            text_frame("ll"),
+            text_frame("o!"),
+            text_frame(" w"),
+            finish_frame(),
+            Ok(Bytes::from("data: [DONE]\n\n")),
+        ];
+        let out = run(frames, 5).await;
+        assert_eq!(contents(&out), vec!["Hello!", " w", "<stop>", "[DONE]"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_flushes_after_max_delay() {
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, String>>(8);
+        let inner: SseStream = Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx));
+        let mut buffered = buffer_sse_chunks(inner, 1024);
+
+        tx.send(text_frame("slow")).await.unwrap();
+        let first = tokio::time::timeout(Duration::from_secs(1), buffered.next())
+            .await
+            .expect("buffer should flush after 200ms")
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&[first]), vec!["slow"]);
+
+        drop(tx);
+        assert!(buffered.next().await.is_none());
+    }
+}
@@ -20,15 +20,13 @@ where
         object: "chat.completion".to_string(),
         created: chrono::Utc::now().timestamp() as u64,
         model: "unknown".to_string(),
+        system_fingerprint: Some(super::models::SYSTEM_FINGERPRINT.to_string()),
         choices: Vec::new(),
         usage: None,
     };
 
-    let mut role: Option<String> = None;
-    let mut content_parts: Vec<String> = Vec::new();
-    let mut reasoning_parts: Vec<String> = Vec::new();
-    let mut finish_reason: Option<String> = None;
-    let mut tool_calls: Vec<Value> = Vec::new(); // Store as Value to be flexible with partials
+    // 按 choice index 分别聚合 (n > 1 时上游会返回多个候选)
+    let mut choices: std::collections::BTreeMap<u32, ChoiceAccumulator> = std::collections::BTreeMap::new();
 
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
@@ -62,30 +60,39 @@ where
                     }
 
                     // Collect Choices Delta
-                    if let Some(choices) = json.get("choices").and_then(|v| v.as_array()) {
-                        if let Some(choice) = choices.first() {
+                    if let Some(choice_list) = json.get("choices").and_then(|v| v.as_array()) {
+                        for choice in choice_list {
+                            let index = choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                            let acc = choices.entry(index).or_default();
+
                             if let Some(delta) = choice.get("delta") {
                                 // Role
                                 if let Some(r) = delta.get("role").and_then(|v| v.as_str()) {
-                                    role = Some(r.to_string());
+                                    acc.role = Some(r.to_string());
                                 }
-                                
+
                                 // Content
                                 if let Some(c) = delta.get("content").and_then(|v| v.as_str()) {
-                                    content_parts.push(c.to_string());
+                                    acc.content.push_str(c);
                                 }
 
                                 // Reasoning Content
                                 if let Some(rc) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
-                                    reasoning_parts.push(rc.to_string());
+                                    acc.reasoning.push_str(rc);
                                 }
 
-                                // Tool Calls Logic would go here (simplified for now as usually not mixed with non-stream heavy)
-                                // But proper implementation needs to aggregate tool calls by index.
+                                // Tool Calls
+                                if let Some(deltas) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+                                    for raw in deltas {
+                                        if let Ok(tc) = serde_json::from_value::<ToolCallDelta>(raw.clone()) {
+                                            merge_tool_call_delta(&mut acc.tool_calls, tc);
+                                        }
+                                    }
+                                }
                             }
 
                             if let Some(fr) = choice.get("finish_reason").and_then(|v| v.as_str()) {
-                                finish_reason = Some(fr.to_string());
+                                acc.finish_reason = Some(fr.to_string());
                             }
                         }
                     }
@@ -94,28 +101,99 @@ where
         }
     }
 
-    // Construct final message
-    let full_content = content_parts.join("");
-    let full_reasoning = if reasoning_parts.is_empty() {
-        None
-    } else {
-        Some(reasoning_parts.join(""))
-    };
+    // 至少返回一个 choice (空流时保持旧行为)
+    if choices.is_empty() {
+        choices.insert(0, ChoiceAccumulator::default());
+    }
 
-    let message = OpenAIMessage {
-        role: role.unwrap_or("assistant".to_string()),
-        content: Some(OpenAIContent::String(full_content)),
-        reasoning_content: full_reasoning,
-        tool_calls: None, // TODO: Implement tool call aggregation if needed
-        tool_call_id: None,
-        name: None,
-    };
+    for (index, acc) in choices {
+        let message = OpenAIMessage {
+            role: acc.role.unwrap_or("assistant".to_string()),
+            content: Some(OpenAIContent::String(acc.content)),
+            reasoning_content: if acc.reasoning.is_empty() { None } else { Some(acc.reasoning) },
+            tool_calls: if acc.tool_calls.is_empty() { None } else { Some(acc.tool_calls) },
+            tool_call_id: None,
+            name: None,
+        };
 
-    response.choices.push(Choice {
-        index: 0,
-        message,
-        finish_reason: finish_reason.or(Some("stop".to_string())),
-    });
+        response.choices.push(Choice {
+            index,
+            message,
+            logprobs: None,
+            finish_reason: acc.finish_reason.or(Some("stop".to_string())),
+        });
+    }
 
     Ok(response)
 }
+
+/// 单个 choice 的聚合状态
+#[derive(Default)]
+struct ChoiceAccumulator {
+    role: Option<String>,
+    content: String,
+    reasoning: String,
+    finish_reason: Option<String>,
+    // 按 index 聚合 tool_calls 增量 (id/name 取首次出现，arguments 拼接)
+    tool_calls: Vec<ToolCall>,
+}
+
+fn merge_tool_call_delta(tool_calls: &mut Vec<ToolCall>, delta: ToolCallDelta) {
+    let index = delta.index as usize;
+    while tool_calls.len() <= index {
+        tool_calls.push(ToolCall {
+            id: String::new(),
+            r#type: "function".to_string(),
+            function: ToolFunction {
+                name: String::new(),
+                arguments: String::new(),
+            },
+        });
+    }
+
+    let entry = &mut tool_calls[index];
+    if let Some(id) = delta.id {
+        if entry.id.is_empty() {
+            entry.id = id;
+        }
+    }
+    if let Some(t) = delta.r#type {
+        entry.r#type = t;
+    }
+    if let Some(func) = delta.function {
+        if let Some(name) = func.name {
+            if entry.function.name.is_empty() {
+                entry.function.name = name;
+            }
+        }
+        if let Some(args) = func.arguments {
+            entry.function.arguments.push_str(&args);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_multiple_choices() {
+        let chunks = vec![
+            Ok::<Bytes, String>(Bytes::from(
+                "data: {\"id\":\"chatcmpl-1\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"A\"}},{\"index\":1,\"delta\":{\"role\":\"assistant\",\"content\":\"B\"}}]}\n\n",
+            )),
+            Ok(Bytes::from(
+                "data: {\"choices\":[{\"index\":1,\"delta\":{\"content\":\"b\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+            )),
+        ];
+
+        let response = collect_stream_to_json(futures::stream::iter(chunks)).await.unwrap();
+        assert_eq!(response.choices.len(), 2);
+        assert_eq!(response.choices[0].index, 0);
+        assert_eq!(response.choices[1].index, 1);
+        assert_eq!(
+            response.choices[1].message.content,
+            Some(OpenAIContent::String("Bb".to_string()))
+        );
+    }
+}
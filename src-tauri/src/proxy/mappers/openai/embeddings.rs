@@ -0,0 +1,162 @@
+// Embeddings 映射 (OpenAI /v1/embeddings ↔ Gemini batchEmbedContents)
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+
+/// 支持的 Gemini 向量模型 (同时在 /v1/models 中列出)
+pub const EMBEDDING_MODELS: &[&str] = &["text-embedding-004", "gemini-embedding-001"];
+
+pub fn is_embedding_model(model: &str) -> bool {
+    EMBEDDING_MODELS.contains(&model)
+}
+
+/// 单次 batchEmbedContents 请求的文本条数上限 (Gemini 限制)
+pub const MAX_EMBED_BATCH_SIZE: usize = 100;
+
+/// 构建多条文本的 batchEmbedContents 请求体 (v1internal 包装)
+pub fn build_batch_embed_contents_request(texts: &[String], model: &str, project_id: &str) -> Value {
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| {
+            json!({
+                "model": format!("models/{}", model),
+                "content": {
+                    "parts": [{"text": text}]
+                }
+            })
+        })
+        .collect();
+
+    json!({
+        "project": project_id,
+        "requestId": format!("agent-{}", uuid::Uuid::new_v4()),
+        "model": model,
+        "userAgent": "antigravity",
+        "request": {
+            "requests": requests
+        }
+    })
+}
+
+/// 从 batchEmbedContents 响应中按请求顺序提取向量 (兼容 v1internal 的 `response` 包装)
+pub fn extract_batch_embedding_values(gemini_resp: &Value) -> Option<Vec<Vec<f32>>> {
+    let raw = gemini_resp.get("response").unwrap_or(gemini_resp);
+    raw.get("embeddings")?
+        .as_array()?
+        .iter()
+        .map(|embedding| {
+            embedding.get("values").and_then(|v| v.as_array()).map(|values| {
+                values
+                    .iter()
+                    .filter_map(|v| v.as_f64())
+                    .map(|v| v as f32)
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+/// 按 OpenAI 规范将向量编码为 base64 (小端 f32 字节序列)
+fn encode_base64(values: &[f32]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// 组装 OpenAI Embeddings 响应
+pub fn build_embeddings_response(
+    embeddings: Vec<Vec<f32>>,
+    model: &str,
+    encoding_format: Option<&str>,
+    prompt_tokens: u32,
+) -> Value {
+    let use_base64 = encoding_format == Some("base64");
+    let data: Vec<Value> = embeddings
+        .iter()
+        .enumerate()
+        .map(|(index, values)| {
+            let embedding = if use_base64 {
+                json!(encode_base64(values))
+            } else {
+                json!(values)
+            };
+            json!({
+                "object": "embedding",
+                "embedding": embedding,
+                "index": index
+            })
+        })
+        .collect();
+
+    json!({
+        "object": "list",
+        "data": data,
+        "model": model,
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "total_tokens": prompt_tokens
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::openai::{EmbeddingInput, OpenAIEmbeddingRequest};
+
+    #[test]
+    fn test_embedding_input_variants() {
+        let single: OpenAIEmbeddingRequest = serde_json::from_value(json!({
+            "model": "text-embedding-004",
+            "input": "hello"
+        }))
+        .unwrap();
+        assert_eq!(single.input, EmbeddingInput::Single("hello".to_string()));
+
+        let batch: OpenAIEmbeddingRequest = serde_json::from_value(json!({
+            "model": "text-embedding-004",
+            "input": ["a", "b"],
+            "encoding_format": "float"
+        }))
+        .unwrap();
+        assert_eq!(batch.input.into_vec(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_embed_request_and_response_mapping() {
+        let texts = vec!["hello".to_string(), "world".to_string()];
+        let body = build_batch_embed_contents_request(&texts, "text-embedding-004", "test-p");
+        assert_eq!(body["project"], "test-p");
+        let requests = body["request"]["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["model"], "models/text-embedding-004");
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "world");
+
+        // 模拟上游 batchEmbedContents 响应 (带 / 不带 response 包装)
+        let wrapped = json!({"response": {"embeddings": [{"values": [0.1, -0.2, 0.3]}, {"values": [0.5]}]}});
+        let plain = json!({"embeddings": [{"values": [0.5]}]});
+        let mut batch = extract_batch_embedding_values(&wrapped).unwrap();
+        assert_eq!(extract_batch_embedding_values(&plain).unwrap(), vec![vec![0.5]]);
+        assert!(extract_batch_embedding_values(&json!({"error": {}})).is_none());
+        assert!(extract_batch_embedding_values(&json!({"embeddings": [{"values": [1.0]}, {}]})).is_none());
+        let second = batch.pop().unwrap();
+        let first = batch.pop().unwrap();
+        assert_eq!(first.len(), 3);
+
+        let resp = build_embeddings_response(vec![first, second], "text-embedding-004", None, 4);
+        assert_eq!(resp["object"], "list");
+        assert_eq!(resp["model"], "text-embedding-004");
+        assert_eq!(resp["data"][1]["index"], 1);
+        assert_eq!(resp["data"][0]["object"], "embedding");
+        assert_eq!(resp["data"][0]["embedding"].as_array().unwrap().len(), 3);
+        assert_eq!(resp["usage"]["prompt_tokens"], 4);
+        assert_eq!(resp["usage"]["total_tokens"], 4);
+    }
+
+    #[test]
+    fn test_base64_encoding_format() {
+        let resp = build_embeddings_response(vec![vec![1.0]], "text-embedding-004", Some("base64"), 1);
+        let encoded = resp["data"][0]["embedding"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        assert_eq!(bytes, 1.0f32.to_le_bytes().to_vec());
+    }
+}
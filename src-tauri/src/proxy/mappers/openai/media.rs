@@ -0,0 +1,516 @@
+// 远程图片内联：将 http(s) 图片 URL 下载并转换为 data URL (Gemini inlineData)
+// 仅允许公网地址 (含每次重定向)，防止借图片 URL 访问本机或内网服务 (SSRF)
+// 响应图片外链：将响应 Markdown 中的 base64 图片上传到 image_upload_url，改为引用返回的 URL
+use super::models::{OpenAIContent, OpenAIContentBlock, OpenAIRequest, OpenAIResponse};
+use base64::Engine as _;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::time::Duration;
+
+/// 单张远程图片下载超时
+const REMOTE_IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+/// 单张远程图片大小上限 (10MB)
+const REMOTE_IMAGE_MAX_BYTES: usize = 10 * 1024 * 1024;
+/// 下载远程图片时最多跟随的重定向次数
+const REMOTE_IMAGE_MAX_REDIRECTS: usize = 5;
+/// 单张响应图片上传超时
+const IMAGE_UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 响应中内联图片的 Markdown: ![image](data:<mime>;base64,<data>)
+static INLINE_IMAGE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"!\[image\]\(data:([\w.+-]+/[\w.+-]+);base64,([A-Za-z0-9+/=]+)\)").unwrap()
+});
+
+/// 下载请求中所有 http(s) 图片并替换为 data URL
+/// 下载失败 (含指向内网地址) 的图片会被替换为一段说明文字，避免整个请求失败
+pub async fn inline_remote_images(request: &mut OpenAIRequest) {
+    inline_remote_images_with(request, is_public_ip).await
+}
+
+async fn inline_remote_images_with(request: &mut OpenAIRequest, allow_ip: fn(IpAddr) -> bool) {
+    for msg in request.messages.iter_mut() {
+        let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let OpenAIContentBlock::ImageUrl { image_url } = block else {
+                continue;
+            };
+            if !(image_url.url.starts_with("http://") || image_url.url.starts_with("https://")) {
+                continue;
+            }
+
+            match fetch_image_as_data_url(&image_url.url, allow_ip).await {
+                Ok(data_url) => {
+                    tracing::debug!("[OpenAI-Request] Inlined remote image: {}", image_url.url);
+                    image_url.url = data_url;
+                }
+                Err(e) => {
+                    tracing::warn!("[OpenAI-Request] Failed to fetch image {}: {}", image_url.url, e);
+                    *block = OpenAIContentBlock::Text {
+                        text: format!("[Image could not be loaded from {}: {}]", image_url.url, e),
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// 是否为公网地址 (拒绝回环 / 私有 / 链路本地 / CGNAT / 组播等内部地址)
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// 解析目标主机并校验全部地址，返回把该主机固定解析到已校验地址的客户端 (防止 DNS 重绑定)
+/// 客户端不自动跟随重定向、不走系统代理，由调用方逐跳校验
+async fn pinned_client(url: &reqwest::Url, allow_ip: fn(IpAddr) -> bool) -> Result<reqwest::Client, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("unsupported scheme {}", url.scheme()));
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let (domain, addrs): (Option<&str>, Vec<SocketAddr>) = match url.host() {
+        Some(url::Host::Ipv4(ip)) => (None, vec![SocketAddr::new(ip.into(), port)]),
+        Some(url::Host::Ipv6(ip)) => (None, vec![SocketAddr::new(ip.into(), port)]),
+        Some(url::Host::Domain(domain)) => {
+            let addrs = tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(|e| format!("DNS lookup failed: {}", e))?
+                .collect();
+            (Some(domain), addrs)
+        }
+        None => return Err("URL has no host".to_string()),
+    };
+    if addrs.is_empty() {
+        return Err("DNS lookup returned no addresses".to_string());
+    }
+    if let Some(blocked) = addrs.iter().find(|addr| !allow_ip(addr.ip())) {
+        return Err(format!("refusing to fetch from non-public address {}", blocked.ip()));
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy();
+    if let Some(domain) = domain {
+        builder = builder.resolve(domain, addrs[0]);
+    }
+    builder.build().map_err(|e| format!("client build failed: {}", e))
+}
+
+async fn fetch_image_as_data_url(url: &str, allow_ip: fn(IpAddr) -> bool) -> Result<String, String> {
+    let fetch = async {
+        let mut url = reqwest::Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+        let mut redirects = 0;
+        // 手动跟随重定向，每一跳都重新校验目标地址
+        let mut response = loop {
+            let response = pinned_client(&url, allow_ip)
+                .await?
+                .get(url.clone())
+                .send()
+                .await
+                .map_err(|e| format!("request failed: {}", e))?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            if redirects == REMOTE_IMAGE_MAX_REDIRECTS {
+                return Err(format!("too many redirects (max {})", REMOTE_IMAGE_MAX_REDIRECTS));
+            }
+            redirects += 1;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| format!("HTTP {} without Location", response.status()))?;
+            url = url
+                .join(location)
+                .map_err(|e| format!("invalid redirect location: {}", e))?;
+        };
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        if let Some(len) = response.content_length() {
+            if len as usize > REMOTE_IMAGE_MAX_BYTES {
+                return Err(format!(
+                    "image exceeds the {} MB size limit",
+                    REMOTE_IMAGE_MAX_BYTES / 1024 / 1024
+                ));
+            }
+        }
+
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(|v| super::request::normalize_image_mime_type(&v))
+            .unwrap_or("image/jpeg");
+
+        // 分块读取，未声明 Content-Length 时同样受大小限制
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("read failed: {}", e))?
+        {
+            if body.len() + chunk.len() > REMOTE_IMAGE_MAX_BYTES {
+                return Err(format!(
+                    "image exceeds the {} MB size limit",
+                    REMOTE_IMAGE_MAX_BYTES / 1024 / 1024
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let data = base64::engine::general_purpose::STANDARD.encode(&body);
+        Ok(format!("data:{};base64,{}", mime_type, data))
+    };
+
+    tokio::time::timeout(REMOTE_IMAGE_TIMEOUT, fetch)
+        .await
+        .map_err(|_| format!("timed out after {}s", REMOTE_IMAGE_TIMEOUT.as_secs()))?
+}
+
+/// 将 Markdown 中的 base64 图片上传到 `upload_url`，替换为返回的 URL
+/// 上传失败的图片保留原始 data URL
+pub async fn upload_inline_images(markdown: &str, client: &reqwest::Client, upload_url: &str) -> String {
+    if !markdown.contains("](data:") {
+        return markdown.to_string();
+    }
+
+    let mut out = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for caps in INLINE_IMAGE_RE.captures_iter(markdown) {
+        let whole = caps.get(0).unwrap();
+        out.push_str(&markdown[last..whole.start()]);
+        match upload_image(client, upload_url, &caps[1], &caps[2]).await {
+            Ok(url) => out.push_str(&format!("![image]({})", url)),
+            Err(e) => {
+                tracing::warn!("[OpenAI-Response] Image upload failed, keeping inline data: {}", e);
+                out.push_str(whole.as_str());
+            }
+        }
+        last = whole.end();
+    }
+    out.push_str(&markdown[last..]);
+    out
+}
+
+/// POST 图片原始字节 (Content-Type 为图片 MIME)，响应为 {"url": "..."} 或纯文本 URL
+async fn upload_image(
+    client: &reqwest::Client,
+    upload_url: &str,
+    mime_type: &str,
+    b64: &str,
+) -> Result<String, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(b64)
+        .map_err(|e| format!("invalid base64: {}", e))?;
+
+    let response = client
+        .post(upload_url)
+        .header(reqwest::header::CONTENT_TYPE, mime_type)
+        .timeout(IMAGE_UPLOAD_TIMEOUT)
+        .body(bytes)
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+
+    let text = response.text().await.map_err(|e| format!("read failed: {}", e))?;
+    let url = match serde_json::from_str::<Value>(&text) {
+        Ok(json) => json
+            .get("url")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(|| format!("response missing url: {}", text))?,
+        Err(_) => text.trim().to_string(),
+    };
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(format!("invalid url returned: {}", url));
+    }
+    Ok(url)
+}
+
+/// 上传非流式响应中所有 choice 的内联图片
+pub async fn upload_images_in_response(
+    response: &mut OpenAIResponse,
+    client: &reqwest::Client,
+    upload_url: &str,
+) {
+    for choice in response.choices.iter_mut() {
+        if let Some(OpenAIContent::String(content)) = choice.message.content.as_mut() {
+            *content = upload_inline_images(content, client, upload_url).await;
+        }
+    }
+}
+
+type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 包装 OpenAI SSE 流：含内联图片的 chunk 在发送前上传图片并改写 delta.content
+pub fn upload_images_in_sse_stream(inner: SseStream, client: reqwest::Client, upload_url: String) -> SseStream {
+    Box::pin(inner.then(move |item| {
+        let client = client.clone();
+        let upload_url = upload_url.clone();
+        async move {
+            let bytes = item?;
+            if !bytes.windows(7).any(|w| w == b"](data:") {
+                return Ok(bytes);
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            let Some(mut chunk) = text
+                .trim()
+                .strip_prefix("data:")
+                .and_then(|d| serde_json::from_str::<Value>(d.trim()).ok())
+            else {
+                return Ok(bytes);
+            };
+
+            if let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) {
+                for choice in choices {
+                    let content = &mut choice["delta"]["content"];
+                    if let Some(s) = content.as_str() {
+                        *content = Value::String(upload_inline_images(s, &client, &upload_url).await);
+                    }
+                }
+            }
+            Ok(Bytes::from(format!(
+                "data: {}\n\n",
+                serde_json::to_string(&chunk).unwrap_or_default()
+            )))
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::openai::transform_openai_request;
+    use axum::{http::header, routing::get, Router};
+    use serde_json::json;
+
+    /// 测试图片源运行在本机，测试中放行回环地址
+    fn allow_loopback(ip: IpAddr) -> bool {
+        ip.is_loopback() || is_public_ip(ip)
+    }
+
+    /// 启动本地 HTTP 服务模拟图片源
+    async fn spawn_image_server() -> String {
+        let app = Router::new()
+            .route(
+                "/moved",
+                get(|| async { (axum::http::StatusCode::FOUND, [(header::LOCATION, "/cat.png")]) }),
+            )
+            .route(
+                "/metadata",
+                get(|| async {
+                    (
+                        axum::http::StatusCode::FOUND,
+                        [(header::LOCATION, "http://169.254.169.254/latest/meta-data/")],
+                    )
+                }),
+            )
+            .route(
+                "/cat.png",
+                get(|| async { ([(header::CONTENT_TYPE, "image/png")], vec![1u8, 2, 3, 4]) }),
+            )
+            .route(
+                "/huge.jpg",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "image/jpeg")],
+                        vec![0u8; REMOTE_IMAGE_MAX_BYTES + 1],
+                    )
+                }),
+            );
+        crate::proxy::tests::support::spawn_mock_server(app).await
+    }
+
+    fn request_with_image(url: &str) -> OpenAIRequest {
+        serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": url}}
+                ]
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_remote_image_inlined() {
+        let base = spawn_image_server().await;
+        let mut req = request_with_image(&format!("{}/cat.png", base));
+        inline_remote_images_with(&mut req, allow_loopback).await;
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(
+            parts[1]["inlineData"]["data"],
+            base64::engine::general_purpose::STANDARD.encode([1u8, 2, 3, 4])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_image_becomes_error_text() {
+        let base = spawn_image_server().await;
+        let mut req = request_with_image(&format!("{}/huge.jpg", base));
+        inline_remote_images_with(&mut req, allow_loopback).await;
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert!(parts[1].get("inlineData").is_none());
+        assert!(parts[1]["text"].as_str().unwrap().contains("size limit"));
+    }
+
+    #[tokio::test]
+    async fn test_internal_addresses_rejected() {
+        let base = spawn_image_server().await;
+        let image_text = |req: &OpenAIRequest| {
+            let result = transform_openai_request(req, "test-p", "gemini-2.5-flash");
+            result["request"]["contents"][0]["parts"][1]["text"].as_str().unwrap_or_default().to_string()
+        };
+
+        // 默认策略拒绝回环地址
+        let mut req = request_with_image(&format!("{}/cat.png", base));
+        inline_remote_images(&mut req).await;
+        assert!(image_text(&req).contains("non-public address 127.0.0.1"));
+
+        // 重定向到公网以外的地址同样被拒绝
+        let mut req = request_with_image(&format!("{}/metadata", base));
+        inline_remote_images_with(&mut req, allow_loopback).await;
+        assert!(image_text(&req).contains("non-public address 169.254.169.254"));
+
+        // 允许的地址之间正常跟随重定向
+        let mut req = request_with_image(&format!("{}/moved", base));
+        inline_remote_images_with(&mut req, allow_loopback).await;
+        assert!(matches!(
+            &req.messages[0].content,
+            Some(OpenAIContent::Array(blocks))
+                if matches!(&blocks[1], OpenAIContentBlock::ImageUrl { image_url } if image_url.url.starts_with("data:image/png"))
+        ));
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "142.250.72.14", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    /// 启动本地上传服务，返回上传地址与收到的 Content-Type
+    async fn spawn_upload_server(
+        status: axum::http::StatusCode,
+    ) -> (String, std::sync::Arc<tokio::sync::Mutex<Vec<(String, Vec<u8>)>>>) {
+        use axum::{body::Bytes as AxumBytes, http::HeaderMap, routing::post, Json};
+
+        let received = std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        let app = Router::new().route(
+            "/upload",
+            post(move |headers: HeaderMap, body: AxumBytes| {
+                let received = received_clone.clone();
+                async move {
+                    let mime = headers[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                    let mut list = received.lock().await;
+                    list.push((mime, body.to_vec()));
+                    let url = format!("https://cdn.example.com/img/{}.png", list.len());
+                    (status, Json(json!({ "url": url })))
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(app).await;
+        (format!("{}/upload", base), received)
+    }
+
+    #[tokio::test]
+    async fn test_upload_inline_images_uses_returned_url() {
+        let (upload_url, received) = spawn_upload_server(axum::http::StatusCode::OK).await;
+        let client = reqwest::Client::new();
+        let b64 = base64::engine::general_purpose::STANDARD.encode([1u8, 2, 3]);
+        let markdown = format!("Here you go:\n![image](data:image/png;base64,{})\nEnjoy", b64);
+
+        let out = upload_inline_images(&markdown, &client, &upload_url).await;
+        assert_eq!(out, "Here you go:\n![image](https://cdn.example.com/img/1.png)\nEnjoy");
+
+        let received = received.lock().await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "image/png");
+        assert_eq!(received[0].1, vec![1u8, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_failure_keeps_inline_data() {
+        let (upload_url, _) = spawn_upload_server(axum::http::StatusCode::INTERNAL_SERVER_ERROR).await;
+        let client = reqwest::Client::new();
+        let markdown = "![image](data:image/jpeg;base64,AAEC)";
+        assert_eq!(upload_inline_images(markdown, &client, &upload_url).await, markdown);
+    }
+
+    #[tokio::test]
+    async fn test_upload_images_in_sse_stream() {
+        let (upload_url, _) = spawn_upload_server(axum::http::StatusCode::OK).await;
+        let chunk = json!({
+            "object": "chat.completion.chunk",
+            "choices": [{"index": 0, "delta": {"content": "![image](data:image/png;base64,AAEC)"}}]
+        });
+        let frames = vec![
+            Ok(Bytes::from("data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n")),
+            Ok(Bytes::from(format!("data: {}\n\n", chunk))),
+        ];
+        let inner: SseStream = Box::pin(futures::stream::iter(frames));
+        let out: Vec<Bytes> = upload_images_in_sse_stream(inner, reqwest::Client::new(), upload_url)
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+
+        assert!(std::str::from_utf8(&out[0]).unwrap().contains("\"hi\""));
+        let data: Value =
+            serde_json::from_str(std::str::from_utf8(&out[1]).unwrap().trim().strip_prefix("data: ").unwrap())
+                .unwrap();
+        assert_eq!(
+            data["choices"][0]["delta"]["content"],
+            "![image](https://cdn.example.com/img/1.png)"
+        );
+    }
+}
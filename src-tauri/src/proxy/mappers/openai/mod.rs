@@ -5,8 +5,14 @@ pub mod models;
 pub mod request;
 pub mod response;
 pub mod streaming;
+pub mod chunk_buffer;
 pub mod collector; // [NEW]
+pub mod embeddings;
+pub mod media;
+pub mod thinking;
 
 pub use models::*;
 pub use request::*;
 pub use response::*;
+pub use embeddings::*;
+pub use media::inline_remote_images;
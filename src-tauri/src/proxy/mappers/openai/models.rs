@@ -1,8 +1,13 @@
 // OpenAI 数据模型
 
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// 响应中的 system_fingerprint 占位值
+/// Gemini 不提供后端指纹，仅为兼容检查该字段的客户端
+pub const SYSTEM_FINGERPRINT: &str = "gemini-fp-v1";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIRequest {
     pub model: String,
@@ -13,18 +18,29 @@ pub struct OpenAIRequest {
     #[serde(default)]
     pub stream: bool,
     #[serde(default)]
+    pub stream_options: Option<StreamOptions>, // [NEW] include_usage: 流末尾追加 usage chunk
+    #[serde(default)]
     pub n: Option<u32>, // [NEW] 支持多候选结果数量
+    #[serde(default)]
+    pub seed: Option<i64>, // [NEW] 透传到 generationConfig.seed (Gemini 的确定性保证弱于 OpenAI)
     #[serde(rename = "max_tokens")]
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
     #[serde(rename = "top_p")]
     pub top_p: Option<f32>,
-    pub stop: Option<Value>,
+    #[serde(default)]
+    pub top_k: Option<i32>,
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default)]
+    pub stop: Option<StopSequences>,
     pub response_format: Option<ResponseFormat>,
     #[serde(default)]
-    pub tools: Option<Vec<Value>>,
+    pub tools: Option<Vec<ToolDefinition>>,
     #[serde(rename = "tool_choice")]
-    pub tool_choice: Option<Value>,
+    pub tool_choice: Option<ToolChoice>,
     #[serde(rename = "parallel_tool_calls")]
     pub parallel_tool_calls: Option<bool>,
     // Codex proprietary fields
@@ -37,11 +53,206 @@ pub struct OpenAIRequest {
     pub quality: Option<String>,
     #[serde(default, rename = "personGeneration")]
     pub person_generation: Option<String>,
+    // [NEW] 扩展字段：原样透传到 Gemini tools 数组 (如 {"googleSearch": {}} 联网检索)
+    #[serde(default, rename = "x-antigravity-tools", skip_serializing_if = "Option::is_none")]
+    pub x_antigravity_tools: Option<Value>,
+    // [NEW] 扩展字段：启用 Gemini 代码执行工具 (codeExecution)
+    #[serde(default, rename = "x-antigravity-code-execution", skip_serializing_if = "std::ops::Not::not")]
+    pub x_antigravity_code_execution: bool,
+    // [NEW] 扩展字段：自定义 Gemini safetySettings (替换默认的全部 OFF)
+    #[serde(default, rename = "x-antigravity-safety-settings", skip_serializing_if = "Option::is_none")]
+    pub x_antigravity_safety_settings: Option<Vec<SafetySetting>>,
+    // [NEW] 扩展字段：思考预算 (Gemini thinkingConfig.thinkingBudget / Claude thinking.budget_tokens)
+    #[serde(default, rename = "x-antigravity-thinking-budget", skip_serializing_if = "Option::is_none")]
+    pub x_antigravity_thinking_budget: Option<u32>,
 }
 
+/// 严格校验用的影子结构：字段名 (含 rename) 与 OpenAIRequest 一一对应，值不做解析
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)]
+struct StrictOpenAIRequestFields {
+    model: Option<IgnoredAny>,
+    messages: Option<IgnoredAny>,
+    prompt: Option<IgnoredAny>,
+    stream: Option<IgnoredAny>,
+    stream_options: Option<IgnoredAny>,
+    n: Option<IgnoredAny>,
+    seed: Option<IgnoredAny>,
+    max_tokens: Option<IgnoredAny>,
+    temperature: Option<IgnoredAny>,
+    top_p: Option<IgnoredAny>,
+    top_k: Option<IgnoredAny>,
+    presence_penalty: Option<IgnoredAny>,
+    frequency_penalty: Option<IgnoredAny>,
+    stop: Option<IgnoredAny>,
+    response_format: Option<IgnoredAny>,
+    tools: Option<IgnoredAny>,
+    tool_choice: Option<IgnoredAny>,
+    parallel_tool_calls: Option<IgnoredAny>,
+    instructions: Option<IgnoredAny>,
+    input: Option<IgnoredAny>,
+    size: Option<IgnoredAny>,
+    quality: Option<IgnoredAny>,
+    #[serde(rename = "personGeneration")]
+    person_generation: Option<IgnoredAny>,
+    #[serde(rename = "x-antigravity-tools")]
+    x_antigravity_tools: Option<IgnoredAny>,
+    #[serde(rename = "x-antigravity-code-execution")]
+    x_antigravity_code_execution: Option<IgnoredAny>,
+    #[serde(rename = "x-antigravity-safety-settings")]
+    x_antigravity_safety_settings: Option<IgnoredAny>,
+    #[serde(rename = "x-antigravity-thinking-budget")]
+    x_antigravity_thinking_budget: Option<IgnoredAny>,
+    // 以下字段不在 OpenAIRequest 中，由 handler 直接从原始请求体读取
+    session_id: Option<IgnoredAny>, // 会话亲和 (session_affinity::explicit_session_id)
+}
+
+/// Gemini 安全设置项 (`safetySettings[]`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+impl OpenAIRequest {
+    /// 严格模式：返回请求体中 OpenAIRequest 不识别的字段名
+    pub fn unknown_fields(body: &Value) -> Vec<String> {
+        let Some(obj) = body.as_object() else {
+            return Vec::new();
+        };
+        // deny_unknown_fields 只报告第一个未知字段，逐个字段探测以列出全部
+        obj.keys()
+            .filter(|key| {
+                let probe = Value::Object(serde_json::Map::from_iter([((*key).clone(), Value::Null)]));
+                serde_json::from_value::<StrictOpenAIRequestFields>(probe).is_err()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 客户端是否通过 stream_options.include_usage 要求单独的 usage chunk
+    pub fn include_usage(&self) -> bool {
+        self.stream_options.as_ref().is_some_and(|o| o.include_usage)
+    }
+
+    /// 是否为 JSON 输出模式 (response_format 为 json_object / json_schema)
+    pub fn is_json_mode(&self) -> bool {
+        self.response_format.as_ref().is_some_and(ResponseFormat::is_json)
+    }
+
+    /// 将工具定义转为 Value 数组 (供联网探测与 Gemini 转换使用)
+    pub fn tools_as_values(&self) -> Option<Vec<Value>> {
+        self.tools
+            .as_ref()
+            .map(|list| list.iter().map(ToolDefinition::to_value).collect())
+    }
+}
+
+/// OpenAI 工具定义 (`tools[]` 元素)
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResponseFormat {
+pub struct ToolDefinition {
+    #[serde(default = "default_tool_type")]
     pub r#type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<FunctionDefinition>,
+    /// 非标准工具 (Codex 自定义工具、web_search 等) 的其余字段原样保留
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+impl ToolDefinition {
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    /// 缺失名称的工具会在转换阶段被跳过，而不是直接拒绝整个请求
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// OpenAI `stop` 字段：可以是单个字符串或字符串数组，内部统一为 `Vec<String>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct StopSequences(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for StopSequences {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawStop {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        let list = match RawStop::deserialize(deserializer)? {
+            RawStop::One(s) => vec![s],
+            RawStop::Many(v) => v,
+        };
+        Ok(StopSequences(list.into_iter().filter(|s| !s.is_empty()).collect()))
+    }
+}
+
+/// OpenAI `tool_choice`: "none" / "auto" / "required" 或指定函数
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ToolChoice {
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        r#type: String,
+        function: ToolChoiceFunction,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolChoiceFunction {
+    pub name: String,
+}
+
+/// OpenAI `response_format`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+impl ResponseFormat {
+    /// 是否要求模型输出 JSON
+    pub fn is_json(&self) -> bool {
+        !matches!(self, ResponseFormat::Text)
+    }
+}
+
+/// `response_format.json_schema`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonSchemaFormat {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -77,7 +288,8 @@ pub struct AudioUrlContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIMessage {
     pub role: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// 始终序列化 (仅含 tool_calls 的 assistant 消息按 OpenAI 规范为 null)
+    #[serde(default)]
     pub content: Option<OpenAIContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_content: Option<String>,
@@ -102,12 +314,55 @@ pub struct ToolFunction {
     pub arguments: String,
 }
 
+/// 流式响应中的 `delta.tool_calls[]` 元素
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<ToolFunctionDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ToolFunctionDelta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+}
+
+impl ToolCallDelta {
+    /// 由 Gemini functionCall 构造一个完整的 (非增量) tool_call delta
+    pub fn from_complete_call(index: u32, id: String, name: String, arguments: String) -> Self {
+        Self {
+            index,
+            id: Some(id),
+            r#type: Some("function".to_string()),
+            function: Some(ToolFunctionDelta {
+                name: Some(name),
+                arguments: Some(arguments),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIResponse {
     pub id: String,
     pub object: String,
     pub created: u64,
     pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
     pub choices: Vec<Choice>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<OpenAIUsage>,
@@ -117,10 +372,13 @@ pub struct OpenAIResponse {
 pub struct Choice {
     pub index: u32,
     pub message: OpenAIMessage,
+    /// Gemini 不提供 logprobs，固定为 null
+    #[serde(default)]
+    pub logprobs: Option<Value>,
     pub finish_reason: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct OpenAIUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
@@ -142,3 +400,151 @@ pub struct CompletionTokensDetails {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_tokens: Option<u32>,
 }
+
+/// OpenAI Embeddings 请求 (`POST /v1/embeddings`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIEmbeddingRequest {
+    pub model: String,
+    pub input: EmbeddingInput,
+    /// "float" (默认) 或 "base64"
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+}
+
+/// `input` 可以是单个字符串或字符串数组
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+impl EmbeddingInput {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            EmbeddingInput::Single(s) => vec![s],
+            EmbeddingInput::Batch(v) => v,
+        }
+    }
+}
+
+/// OpenAI 图像生成请求 (`POST /v1/images/generations`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIImageGenerationRequest {
+    pub prompt: String,
+    #[serde(default = "default_image_model")]
+    pub model: String,
+    /// 生成张数 (默认 1，每张单独请求上游)
+    #[serde(default)]
+    pub n: Option<u32>,
+    /// "1024x1024" 等尺寸，换算为 aspectRatio / imageSize
+    #[serde(default)]
+    pub size: Option<String>,
+    /// "b64_json" (默认) 或 "url" (data URI)
+    #[serde(default)]
+    pub response_format: Option<String>,
+    /// "standard" (默认) 或 "hd"
+    #[serde(default)]
+    pub quality: Option<String>,
+    /// "vivid" (默认) 或 "natural"
+    #[serde(default)]
+    pub style: Option<String>,
+}
+
+fn default_image_model() -> String {
+    "gemini-3-pro-image".to_string()
+}
+
+/// 旧版文本补全请求 (`POST /v1/completions`)，以 prompt 代替 messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAICompletionRequest {
+    pub model: String,
+    pub prompt: CompletionPrompt,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// `prompt` 可以是单个字符串或字符串数组
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CompletionPrompt {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+impl OpenAICompletionRequest {
+    /// 转为单条 user 消息 (多个 prompt 以换行拼接)
+    pub fn to_user_message(&self) -> OpenAIMessage {
+        let text = match &self.prompt {
+            CompletionPrompt::Single(s) => s.clone(),
+            CompletionPrompt::Batch(v) => v.join("\n"),
+        };
+        OpenAIMessage {
+            role: "user".to_string(),
+            content: Some(OpenAIContent::String(text)),
+            reasoning_content: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unknown_fields_lists_all() {
+        let body = json!({"model": "m", "messages": [], "messaegs": [], "logit_bias": {}, "x-antigravity-tools": []});
+        assert_eq!(OpenAIRequest::unknown_fields(&body), vec!["logit_bias", "messaegs"]);
+        assert!(OpenAIRequest::unknown_fields(&json!({"model": "m", "max_tokens": 8})).is_empty());
+    }
+
+    #[test]
+    fn test_strict_fields_cover_request() {
+        // 影子结构需与 OpenAIRequest 同步：序列化出的每个字段都必须被识别
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "m",
+            "x-antigravity-tools": [],
+            "x-antigravity-code-execution": true,
+            "x-antigravity-safety-settings": [],
+            "x-antigravity-thinking-budget": 1024
+        }))
+        .unwrap();
+        let mut body = serde_json::to_value(&req).unwrap();
+        assert!(body.as_object().unwrap().len() > 20);
+        // handler 直接从请求体读取的字段同样不能被严格模式拒绝
+        body["session_id"] = json!("sess-1");
+        assert!(OpenAIRequest::unknown_fields(&body).is_empty());
+    }
+
+    #[test]
+    fn test_completion_request_to_user_message() {
+        let req: OpenAICompletionRequest = serde_json::from_value(json!({
+            "model": "gpt-3.5-turbo-instruct",
+            "prompt": "def fib(n):",
+            "max_tokens": 64,
+            "stream": true
+        }))
+        .unwrap();
+        assert!(req.stream);
+        let msg = req.to_user_message();
+        assert_eq!(msg.role, "user");
+        assert!(matches!(msg.content, Some(OpenAIContent::String(ref s)) if s == "def fib(n):"));
+
+        let req: OpenAICompletionRequest = serde_json::from_value(json!({
+            "model": "m",
+            "prompt": ["a", "b"]
+        }))
+        .unwrap();
+        assert!(matches!(req.to_user_message().content, Some(OpenAIContent::String(ref s)) if s == "a\nb"));
+
+        assert!(serde_json::from_value::<OpenAICompletionRequest>(json!({"model": "m", "prompt": [1, 2]})).is_err());
+    }
+}
@@ -1,18 +1,29 @@
 // OpenAI → Gemini 请求转换
 use super::models::*;
 use super::streaming::get_thought_signature;
+use crate::proxy::mappers::claude::models::{
+    FileData, FunctionCall, FunctionResponse, GeminiContent, GeminiPart, InlineData,
+};
+use crate::proxy::mappers::gemini::models::{
+    GeminiFunctionCallingConfig, GeminiGenerationConfig, GeminiRequest, GeminiSafetySetting,
+    GeminiSystemInstruction, GeminiThinkingConfig, GeminiToolConfig, V1InternalRequest,
+};
 use serde_json::{json, Value};
 
+/// 单次请求允许的最大候选数量 (OpenAI `n`)
+const MAX_CANDIDATE_COUNT: u32 = 4;
+
+/// Gemini topK 允许范围
+const MIN_TOP_K: i32 = 1;
+const MAX_TOP_K: i32 = 40;
+
 pub fn transform_openai_request(
     request: &OpenAIRequest,
     project_id: &str,
     mapped_model: &str,
 ) -> Value {
     // 将 OpenAI 工具转为 Value 数组以便探测
-    let tools_val = request
-        .tools
-        .as_ref()
-        .map(|list| list.iter().map(|v| v.clone()).collect::<Vec<_>>());
+    let tools_val = request.tools_as_values();
 
     let mapped_model_lower = mapped_model.to_lowercase();
 
@@ -63,34 +74,8 @@ pub fn transform_openai_request(
     );
 
     // 1. 提取所有 System Message 并注入补丁
-    let mut system_instructions: Vec<String> = request
-        .messages
-        .iter()
-        .filter(|msg| msg.role == "system" || msg.role == "developer")
-        .filter_map(|msg| {
-            msg.content.as_ref().map(|c| match c {
-                OpenAIContent::String(s) => s.clone(),
-                OpenAIContent::Array(blocks) => blocks
-                    .iter()
-                    .filter_map(|b| {
-                        if let OpenAIContentBlock::Text { text } = b {
-                            Some(text.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-            })
-        })
-        .collect();
-
-    // [NEW] 如果请求中包含 instructions 字段，优先使用它
-    if let Some(inst) = &request.instructions {
-        if !inst.is_empty() {
-            system_instructions.insert(0, inst.clone());
-        }
-    }
+    // system / developer 消息不进入 contents，合并后作为 systemInstruction 发送
+    let system_instruction = collect_system_instruction(request);
 
     // Pre-scan to map tool_call_id to function name (for Codex)
     let mut tool_id_to_name = std::collections::HashMap::new();
@@ -119,7 +104,7 @@ pub fn transform_openai_request(
 
     // [New] 预先构建工具名称到原始 Schema 的映射，用于后续参数类型修正
     let mut tool_name_to_schema = std::collections::HashMap::new();
-    if let Some(tools) = &request.tools {
+    if let Some(tools) = &tools_val {
         for tool in tools {
             if let (Some(name), Some(params)) = (
                 tool.get("function")
@@ -139,7 +124,7 @@ pub fn transform_openai_request(
     }
 
     // 2. 构建 Gemini contents (过滤掉 system/developer 指令)
-    let contents: Vec<Value> = request
+    let contents: Vec<GeminiContent> = request
         .messages
         .iter()
         .filter(|msg| msg.role != "system" && msg.role != "developer")
@@ -150,39 +135,37 @@ pub fn transform_openai_request(
                 _ => &msg.role,
             };
 
-            let mut parts = Vec::new();
+            let mut parts: Vec<GeminiPart> = Vec::new();
 
             // Handle reasoning_content (thinking)
             if let Some(reasoning) = &msg.reasoning_content {
                 if !reasoning.is_empty() {
-                    let mut thought_part = json!({
-                        "text": reasoning,
-                        "thought": true,
+                    parts.push(GeminiPart {
+                        text: Some(reasoning.clone()),
+                        thought: Some(true),
+                        thought_signature: global_thought_sig.clone(),
+                        ..Default::default()
                     });
-                    if let Some(ref sig) = global_thought_sig {
-                        thought_part["thoughtSignature"] = json!(sig);
-                    }
-                    parts.push(thought_part);
                 }
             } else if actual_include_thinking && role == "model" {
                 // [FIX] 解决 Claude 3.7 Thinking 模型的强制性校验:
                 // "Expected thinking... but found tool_use/text"
                 // 如果是思维模型且缺失 reasoning_content, 则注入占位符
                 tracing::debug!("[OpenAI-Thinking] Injecting placeholder thinking block for assistant message");
-                let mut thought_part = json!({
-                    "text": "Applying tool decisions and generating response...",
-                    "thought": true,
-                });
-                
+
                 // [NEW] 优先使用全局存储的思维签名 (如果可用)
-                if let Some(ref sig) = global_thought_sig {
-                    thought_part["thoughtSignature"] = json!(sig);
-                } else if !mapped_model.starts_with("projects/") && mapped_model.contains("gemini") {
-                    // [FIX] 仅针对 Gemini 思维模型注入跳过标签, Claude 不识别此标签
-                    thought_part["thoughtSignature"] = json!("skip_thought_signature_validator");
-                }
-                
-                parts.push(thought_part);
+                // [FIX] 仅针对 Gemini 思维模型注入跳过标签, Claude 不识别此标签
+                let thought_signature = global_thought_sig.clone().or_else(|| {
+                    (!mapped_model.starts_with("projects/") && mapped_model.contains("gemini"))
+                        .then(|| "skip_thought_signature_validator".to_string())
+                });
+
+                parts.push(GeminiPart {
+                    text: Some("Applying tool decisions and generating response...".to_string()),
+                    thought: Some(true),
+                    thought_signature,
+                    ..Default::default()
+                });
             }
 
             // Handle content (multimodal or text)
@@ -193,30 +176,34 @@ pub fn transform_openai_request(
                 match content {
                     OpenAIContent::String(s) => {
                         if !s.is_empty() {
-                            parts.push(json!({"text": s}));
+                            parts.push(text_part(s.clone()));
                         }
                     }
                     OpenAIContent::Array(blocks) => {
                         for block in blocks {
                             match block {
                                 OpenAIContentBlock::Text { text } => {
-                                    parts.push(json!({"text": text}));
+                                    parts.push(text_part(text.clone()));
                                 }
                                 OpenAIContentBlock::ImageUrl { image_url } => {
                                     if image_url.url.starts_with("data:") {
                                         if let Some(pos) = image_url.url.find(",") {
                                             let mime_part = &image_url.url[5..pos];
-                                            let mime_type = mime_part.split(';').next().unwrap_or("image/jpeg");
+                                            let mime_type = normalize_image_mime_type(
+                                                mime_part.split(';').next().unwrap_or("image/jpeg"),
+                                            );
                                             let data = &image_url.url[pos + 1..];
                                             
-                                            parts.push(json!({
-                                                "inlineData": { "mimeType": mime_type, "data": data }
-                                            }));
+                                            parts.push(inline_data_part(mime_type, data.to_string()));
                                         }
                                     } else if image_url.url.starts_with("http") {
-                                        parts.push(json!({
-                                            "fileData": { "fileUri": &image_url.url, "mimeType": "image/jpeg" }
-                                        }));
+                                        parts.push(GeminiPart {
+                                            file_data: Some(FileData {
+                                                file_uri: image_url.url.clone(),
+                                                mime_type: "image/jpeg".to_string(),
+                                            }),
+                                            ..Default::default()
+                                        });
                                     } else {
                                         // [NEW] 处理本地文件路径 (file:// 或 Windows/Unix 路径)
                                         let file_path = if image_url.url.starts_with("file://") {
@@ -237,30 +224,23 @@ pub fn transform_openai_request(
                                             let b64 = base64::engine::general_purpose::STANDARD.encode(&file_bytes);
                                             
                                             // 根据文件扩展名推断 MIME 类型
-                                            let mime_type = if file_path.to_lowercase().ends_with(".png") {
-                                                "image/png"
-                                            } else if file_path.to_lowercase().ends_with(".gif") {
-                                                "image/gif"
-                                            } else if file_path.to_lowercase().ends_with(".webp") {
-                                                "image/webp"
-                                            } else {
-                                                "image/jpeg"
-                                            };
+                                            let mime_type = image_mime_from_path(&file_path);
                                             
-                                            parts.push(json!({
-                                                "inlineData": { "mimeType": mime_type, "data": b64 }
-                                            }));
+                                            parts.push(inline_data_part(mime_type, b64));
                                             tracing::debug!("[OpenAI-Request] Successfully loaded image: {} ({} bytes)", file_path, file_bytes.len());
                                         } else {
                                             tracing::debug!("[OpenAI-Request] Failed to read local image: {}", file_path);
                                         }
                                     }
                                 }
-                                OpenAIContentBlock::AudioUrl { audio_url: _ } => {
-                                    // 暂时跳过 audio_url 处理
-                                    // 完整实现需要下载音频文件并转换为 Gemini inlineData 格式
-                                    // 这会与 v3.3.16 的 thinkingConfig 逻辑冲突，留待后续版本实现
-                                    tracing::debug!("[OpenAI-Request] Skipping audio_url (not yet implemented in v3.3.16)");
+                                OpenAIContentBlock::AudioUrl { audio_url } => {
+                                    // [NEW] data:audio/*;base64 URI 直接透传为 inlineData (MIME 已由 validate_audio_inputs 校验)
+                                    match parse_audio_data_url(&audio_url.url) {
+                                        Some((mime_type, data)) => parts.push(inline_data_part(mime_type, data.to_string())),
+                                        None => tracing::debug!(
+                                            "[OpenAI-Request] Skipping audio_url (only base64 data URIs are supported)"
+                                        ),
+                                    }
                                 }
                             }
                         }
@@ -287,107 +267,103 @@ pub fn transform_openai_request(
                         crate::proxy::common::json_schema::fix_tool_call_args(&mut args, original_schema);
                     }
 
-                    let mut func_call_part = json!({
-                        "functionCall": {
-                            "name": if tc.function.name == "local_shell_call" { "shell" } else { &tc.function.name },
-                            "args": args,
-                            "id": &tc.id,
-                        }
-                    });
-
                     // [New] 递归清理参数中可能存在的非法校验字段
-                    crate::proxy::common::json_schema::clean_json_schema(&mut func_call_part);
+                    crate::proxy::common::json_schema::clean_json_schema(&mut args);
 
                     // [修复] 为该消息内的所有工具调用注入 thoughtSignature
-                    if let Some(ref sig) = global_thought_sig {
-                        func_call_part["thoughtSignature"] = json!(sig);
+                    let thought_signature = if let Some(ref sig) = global_thought_sig {
+                        Some(sig.clone())
                     } else if is_thinking_model && !mapped_model.starts_with("projects/") {
                         // [NEW] Handle missing signature for Gemini thinking models
                         tracing::debug!("[OpenAI-Signature] Adding GEMINI_SKIP_SIGNATURE for tool_use: {}", tc.id);
-                        func_call_part["thoughtSignature"] = json!("skip_thought_signature_validator");
-                    }
+                        Some("skip_thought_signature_validator".to_string())
+                    } else {
+                        None
+                    };
 
-                    parts.push(func_call_part);
+                    let name = if tc.function.name == "local_shell_call" { "shell" } else { &tc.function.name };
+                    parts.push(GeminiPart {
+                        function_call: Some(FunctionCall {
+                            name: name.to_string(),
+                            id: Some(tc.id.clone()),
+                            args: Some(args),
+                        }),
+                        thought_signature,
+                        ..Default::default()
+                    });
                 }
             }
 
-            // Handle tool response
-            if msg.role == "tool" || msg.role == "function" {
-                let name = msg.name.as_deref().unwrap_or("unknown");
-                let final_name = if name == "local_shell_call" { "shell" } 
-                                else if let Some(id) = &msg.tool_call_id { tool_id_to_name.get(id).map(|s| s.as_str()).unwrap_or(name) }
-                                else { name };
-
-                let content_val = match &msg.content {
-                    Some(OpenAIContent::String(s)) => s.clone(),
-                    Some(OpenAIContent::Array(blocks)) => blocks.iter().filter_map(|b| if let OpenAIContentBlock::Text { text } = b { Some(text.clone()) } else { None }).collect::<Vec<_>>().join("\n"),
-                    None => "".to_string()
-                };
-
-                parts.push(json!({
-                    "functionResponse": {
-                       "name": final_name,
-                       "response": { "result": content_val },
-                       "id": msg.tool_call_id.clone().unwrap_or_default()
-                    }
-                }));
+            // Handle tool response (Gemini 要求以 user 角色 + functionResponse part 回传)
+            if is_tool_role {
+                parts.push(build_function_response_part(msg, &tool_id_to_name));
             }
 
-            json!({ "role": role, "parts": parts })
+            GeminiContent { role: role.to_string(), parts }
         })
-        .filter(|msg| !msg["parts"].as_array().map(|a| a.is_empty()).unwrap_or(true))
+        .filter(|content| !content.parts.is_empty())
         .collect();
 
-    // 合并连续相同角色的消息 (Gemini 强制要求 user/model 交替)
-    let mut merged_contents: Vec<Value> = Vec::new();
-    for msg in contents {
-        if let Some(last) = merged_contents.last_mut() {
-            if last["role"] == msg["role"] {
-                // 合并 parts
-                if let (Some(last_parts), Some(msg_parts)) =
-                    (last["parts"].as_array_mut(), msg["parts"].as_array())
-                {
-                    last_parts.extend(msg_parts.iter().cloned());
-                    continue;
-                }
-            }
-        }
-        merged_contents.push(msg);
-    }
-    let contents = merged_contents;
+    let contents = enforce_alternating_turns(contents);
 
     // 3. 构建请求体
 
-    let mut gen_config = json!({
-        "temperature": request.temperature.unwrap_or(1.0),
-        "topP": request.top_p.unwrap_or(0.95), // Gemini default is usually 0.95
-    });
+    let mut gen_config = GeminiGenerationConfig {
+        temperature: Some(request.temperature.unwrap_or(1.0)),
+        top_p: Some(request.top_p.unwrap_or(0.95)), // Gemini default is usually 0.95
+        presence_penalty: request.presence_penalty,
+        frequency_penalty: request.frequency_penalty,
+        // [FIX] 移除默认的 81920 maxOutputTokens，防止非思维模型 (如 claude-sonnet-4-5) 报 400 Invalid Argument
+        // 仅在用户显式提供时设置
+        max_output_tokens: request.max_tokens,
+        // seed: Gemini 尽力而为的确定性采样，相同 seed 不保证逐字一致
+        seed: request.seed,
+        stop_sequences: request.stop.as_ref().filter(|stop| !stop.0.is_empty()).map(|stop| stop.0.clone()),
+        ..Default::default()
+    };
 
-    // [FIX] 移除默认的 81920 maxOutputTokens，防止非思维模型 (如 claude-sonnet-4-5) 报 400 Invalid Argument
-    // 仅在用户显式提供时设置
-    if let Some(max_tokens) = request.max_tokens {
-         gen_config["maxOutputTokens"] = json!(max_tokens);
+    // topK 仅在显式提供时发送 (避免 null)，超出 1-40 范围时钳制
+    if let Some(top_k) = request.top_k {
+        let clamped = top_k.clamp(MIN_TOP_K, MAX_TOP_K);
+        if clamped != top_k {
+            tracing::warn!(
+                "[OpenAI-Request] top_k={} out of range [{}, {}], clamped to {}",
+                top_k, MIN_TOP_K, MAX_TOP_K, clamped
+            );
+        }
+        gen_config.top_k = Some(clamped as u32);
     }
 
-    // [NEW] 支持多候选结果数量 (n -> candidateCount)
+    // [NEW] 支持多候选结果数量 (n -> candidateCount)，上限 MAX_CANDIDATE_COUNT
     if let Some(n) = request.n {
-        gen_config["candidateCount"] = json!(n);
+        let n = if n > MAX_CANDIDATE_COUNT {
+            tracing::warn!(
+                "[OpenAI-Request] n={} exceeds limit, capping to {}",
+                n, MAX_CANDIDATE_COUNT
+            );
+            MAX_CANDIDATE_COUNT
+        } else {
+            n.max(1)
+        };
+        gen_config.candidate_count = Some(n);
     }
 
     // 为 thinking 模型注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
-    if actual_include_thinking {
-        let budget = 32000;
-        gen_config["thinkingConfig"] = json!({
-            "includeThoughts": true,
-            "thinkingBudget": budget
+    // [NEW] 客户端通过 x-antigravity-thinking-budget 指定预算时，Gemini 模型一律注入
+    let thinking_budget = request.x_antigravity_thinking_budget;
+    let is_anthropic_style = is_anthropic_style_model(&mapped_model_lower);
+    if actual_include_thinking || (thinking_budget.is_some() && !is_anthropic_style) {
+        let budget = thinking_budget.unwrap_or(32000);
+        gen_config.thinking_config = Some(GeminiThinkingConfig {
+            include_thoughts: Some(true),
+            thinking_budget: Some(budget),
         });
 
         // [CRITICAL] 思维模型的 maxOutputTokens 必须大于 thinkingBudget
         // 如果当前 maxOutputTokens 未设置或小于预算，强制提升
-        let current_max = gen_config["maxOutputTokens"].as_i64().unwrap_or(0);
-        if current_max <= budget {
-            let new_max = budget + 8192; // 预留 8k 给实际回答
-            gen_config["maxOutputTokens"] = json!(new_max);
+        if gen_config.max_output_tokens.unwrap_or(0) <= budget {
+            let new_max = budget.saturating_add(8192); // 预留 8k 给实际回答
+            gen_config.max_output_tokens = Some(new_max);
             tracing::debug!(
                 "[OpenAI-Request] Adjusted maxOutputTokens to {} for thinking model (budget={})",
                 new_max, budget
@@ -400,37 +376,56 @@ pub fn transform_openai_request(
         );
     }
 
-    if let Some(stop) = &request.stop {
-        if stop.is_string() {
-            gen_config["stopSequences"] = json!([stop]);
-        } else if stop.is_array() {
-            gen_config["stopSequences"] = stop.clone();
+    // response_format → responseMimeType / responseSchema
+    match &request.response_format {
+        Some(ResponseFormat::JsonObject) => {
+            gen_config.response_mime_type = Some("application/json".to_string());
         }
-    }
-
-    if let Some(fmt) = &request.response_format {
-        if fmt.r#type == "json_object" {
-            gen_config["responseMimeType"] = json!("application/json");
+        Some(ResponseFormat::JsonSchema { json_schema }) => {
+            gen_config.response_mime_type = Some("application/json".to_string());
+            if let Some(schema) = &json_schema.schema {
+                // 与工具参数相同的清洗：展开 $ref、移除不支持的字段、type 转大写
+                let mut schema = schema.clone();
+                crate::proxy::common::json_schema::clean_json_schema(&mut schema);
+                enforce_uppercase_types(&mut schema);
+                gen_config.response_schema = Some(schema);
+            }
         }
+        Some(ResponseFormat::Text) | None => {}
     }
 
-    let mut inner_request = json!({
-        "contents": contents,
-        "generationConfig": gen_config,
-        "safetySettings": [
-            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
+    // x-antigravity-safety-settings 优先，否则默认全部 OFF
+    let safety_settings = match &request.x_antigravity_safety_settings {
+        Some(settings) => settings
+            .iter()
+            .map(|s| GeminiSafetySetting::new(&s.category, &s.threshold))
+            .collect(),
+        None => [
+            "HARM_CATEGORY_HARASSMENT",
+            "HARM_CATEGORY_HATE_SPEECH",
+            "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+            "HARM_CATEGORY_DANGEROUS_CONTENT",
+            "HARM_CATEGORY_CIVIC_INTEGRITY",
         ]
-    });
+        .into_iter()
+        .map(|category| GeminiSafetySetting::new(category, "OFF"))
+        .collect(),
+    };
 
-    // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
-    crate::proxy::mappers::common_utils::deep_clean_undefined(&mut inner_request);
+    let mut inner_request = GeminiRequest {
+        contents,
+        generation_config: Some(gen_config),
+        safety_settings: Some(safety_settings),
+        ..Default::default()
+    };
+
+    // [NEW] Anthropic 风格模型使用 thinking.budget_tokens 表达思考预算
+    if let (Some(budget), true) = (thinking_budget, is_anthropic_style) {
+        inner_request.thinking = Some(json!({ "type": "enabled", "budget_tokens": budget }));
+    }
 
     // 4. Handle Tools (Merged Cleaning)
-    if let Some(tools) = &request.tools {
+    if let Some(tools) = &tools_val {
         let mut function_declarations: Vec<Value> = Vec::new();
         for tool in tools.iter() {
             let mut gemini_func = if let Some(func) = tool.get("function") {
@@ -518,7 +513,28 @@ pub fn transform_openai_request(
         }
 
         if !function_declarations.is_empty() {
-            inner_request["tools"] = json!([{ "functionDeclarations": function_declarations }]);
+            inner_request.tools = Some(vec![json!({ "functionDeclarations": function_declarations })]);
+            inner_request.tool_config = Some(GeminiToolConfig {
+                function_calling_config: function_calling_config(request.tool_choice.as_ref()),
+            });
+        }
+    }
+
+    // [NEW] x-antigravity-tools: Gemini 原生工具 (googleSearch 等) 原样追加到 tools 数组
+    // x-antigravity-code-execution: 追加 codeExecution 工具
+    {
+        let mut extra: Vec<Value> = match &request.x_antigravity_tools {
+            Some(Value::Array(arr)) => arr.clone(),
+            Some(Value::Null) | None => Vec::new(),
+            Some(other) => vec![other.clone()],
+        };
+        if request.x_antigravity_code_execution
+            && !extra.iter().any(|t| t.get("codeExecution").is_some())
+        {
+            extra.push(json!({ "codeExecution": {} }));
+        }
+        if !extra.is_empty() {
+            inner_request.tools.get_or_insert_with(Vec::new).extend(extra);
         }
     }
 
@@ -529,55 +545,589 @@ pub fn transform_openai_request(
     **Proactiveness**";
 
     // [HYBRID] 检查用户是否已提供 Antigravity 身份
-    let user_has_antigravity = system_instructions
-        .iter()
-        .any(|s| s.contains("You are Antigravity"));
+    let user_has_antigravity = system_instruction
+        .as_ref()
+        .is_some_and(|s| s.contains("You are Antigravity"));
 
     let mut parts = Vec::new();
 
     // 1. Antigravity 身份 (如果需要, 作为独立 Part 插入)
     if !user_has_antigravity {
-        parts.push(json!({"text": antigravity_identity}));
+        parts.push(text_part(antigravity_identity.to_string()));
     }
 
-    // 2. 追加用户指令 (作为独立 Parts)
-    for inst in system_instructions {
-        parts.push(json!({"text": inst}));
+    // 2. 追加用户指令 (合并为一个 Part)
+    if let Some(inst) = system_instruction {
+        parts.push(text_part(inst));
     }
 
-    inner_request["systemInstruction"] = json!({
-        "role": "user",
-        "parts": parts
+    inner_request.system_instruction = Some(GeminiSystemInstruction {
+        role: "user".to_string(),
+        parts,
     });
 
     if config.inject_google_search {
-        crate::proxy::mappers::common_utils::inject_google_search_tool(&mut inner_request);
+        crate::proxy::mappers::common_utils::inject_google_search_tools(
+            inner_request.tools.get_or_insert_with(Vec::new),
+        );
     }
 
     if let Some(image_config) = config.image_config {
-        if let Some(obj) = inner_request.as_object_mut() {
-            obj.remove("tools");
-            obj.remove("systemInstruction");
-            let gen_config = obj.entry("generationConfig").or_insert_with(|| json!({}));
-            if let Some(gen_obj) = gen_config.as_object_mut() {
-                gen_obj.remove("thinkingConfig");
-                gen_obj.remove("responseMimeType");
-                gen_obj.remove("responseModalities");
-                gen_obj.insert("imageConfig".to_string(), image_config);
-            }
-        }
+        inner_request.tools = None;
+        inner_request.tool_config = None;
+        inner_request.system_instruction = None;
+        let gen_config = inner_request.generation_config.get_or_insert_with(Default::default);
+        gen_config.thinking_config = None;
+        gen_config.response_mime_type = None;
+        gen_config.response_schema = None;
+        gen_config.response_modalities = None;
+        gen_config.image_config = Some(image_config);
     }
 
+    let envelope = V1InternalRequest {
+        project: project_id.to_string(),
+        request_id: format!("openai-{}", uuid::Uuid::new_v4()),
+        request: inner_request,
+        model: config.final_model,
+        user_agent: "antigravity".to_string(),
+        request_type: config.request_type,
+    };
+    let mut body = serde_json::to_value(envelope).unwrap_or_else(|e| {
+        tracing::error!("[OpenAI-Request] Failed to serialize request: {}", e);
+        json!({})
+    });
+
+    // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
+    crate::proxy::mappers::common_utils::deep_clean_undefined(&mut body["request"]);
+    body
+}
+
+/// 是否为 Anthropic 风格模型 (claude-*)，思考预算使用 thinking.budget_tokens 表达
+fn is_anthropic_style_model(model: &str) -> bool {
+    model.starts_with("claude-")
+}
+
+/// 构建 countTokens 请求体 (contents 与正式请求使用同一套转换)
+pub fn transform_openai_count_tokens_request(request: &OpenAIRequest, project_id: &str) -> Value {
+    let full = transform_openai_request(request, project_id, &request.model);
+    let model = full["model"].as_str().unwrap_or(&request.model).to_string();
     json!({
-        "project": project_id,
-        "requestId": format!("openai-{}", uuid::Uuid::new_v4()),
-        "request": inner_request,
-        "model": config.final_model,
-        "userAgent": "antigravity",
-        "requestType": config.request_type
+        "request": {
+            "model": format!("models/{}", model),
+            "contents": full["request"]["contents"].clone()
+        }
     })
 }
 
+/// 未登记模型的默认上下文窗口
+const DEFAULT_CONTEXT_WINDOW: u32 = 1_048_576;
+
+/// 图片等非文本块的估算 Token 数
+const ESTIMATED_MEDIA_TOKENS: usize = 258;
+
+/// 查询模型上下文窗口 (见 model_registry)：精确匹配查 MODEL_CONTEXT_WINDOWS，其次按最长前缀匹配
+pub fn context_window_for_model(model: &str) -> u32 {
+    use crate::proxy::model_registry::{get_model_info, MODEL_CONTEXT_WINDOWS};
+    MODEL_CONTEXT_WINDOWS
+        .get(model)
+        .copied()
+        .or_else(|| get_model_info(model).map(|info| info.context_window))
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// 粗略估算单条消息的 Token 数 (字符数 / 4)
+fn estimate_message_tokens(msg: &OpenAIMessage) -> usize {
+    let mut chars = msg.role.len();
+    let mut media_tokens = 0;
+    match &msg.content {
+        Some(OpenAIContent::String(s)) => chars += s.chars().count(),
+        Some(OpenAIContent::Array(blocks)) => {
+            for block in blocks {
+                match block {
+                    OpenAIContentBlock::Text { text } => chars += text.chars().count(),
+                    _ => media_tokens += ESTIMATED_MEDIA_TOKENS,
+                }
+            }
+        }
+        None => {}
+    }
+    if let Some(reasoning) = &msg.reasoning_content {
+        chars += reasoning.chars().count();
+    }
+    if let Some(calls) = &msg.tool_calls {
+        for call in calls {
+            chars += call.function.name.len() + call.function.arguments.chars().count();
+        }
+    }
+    chars / 4 + media_tokens
+}
+
+/// 收集 instructions 字段与所有 system / developer 消息，按顺序以换行拼接
+/// 没有任何系统指令时返回 None
+fn collect_system_instruction(request: &OpenAIRequest) -> Option<String> {
+    let mut system_instructions: Vec<String> = Vec::new();
+
+    // [NEW] 如果请求中包含 instructions 字段，优先使用它
+    if let Some(inst) = request.instructions.as_ref().filter(|s| !s.is_empty()) {
+        system_instructions.push(inst.clone());
+    }
+
+    for msg in request
+        .messages
+        .iter()
+        .filter(|msg| msg.role == "system" || msg.role == "developer")
+    {
+        let text = match &msg.content {
+            Some(OpenAIContent::String(s)) => s.clone(),
+            Some(OpenAIContent::Array(blocks)) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => continue,
+        };
+        system_instructions.push(text);
+    }
+
+    (!system_instructions.is_empty()).then(|| system_instructions.join("\n"))
+}
+
+/// 合并连续相同角色的消息 (Gemini 强制要求 user/model 交替)
+/// - 连续 model 回合的文本之间插入 "\n" 分隔 part，避免两段回答首尾粘连
+/// - 对话以 model 开头时在前面补一个空的 user 回合
+fn enforce_alternating_turns(contents: Vec<GeminiContent>) -> Vec<GeminiContent> {
+    let mut merged_contents: Vec<GeminiContent> = Vec::new();
+    for msg in contents {
+        if let Some(last) = merged_contents.last_mut() {
+            if last.role == msg.role {
+                // 仅在两侧都是文本时插入分隔，functionCall 等 part 保持相邻
+                let text_boundary = last.parts.last().is_some_and(|p| p.text.is_some())
+                    && msg.parts.first().is_some_and(|p| p.text.is_some());
+                if msg.role == "model" && text_boundary {
+                    last.parts.push(text_part("\n".to_string()));
+                }
+                last.parts.extend(msg.parts);
+                continue;
+            }
+        }
+        merged_contents.push(msg);
+    }
+
+    if merged_contents.first().is_some_and(|c| c.role == "model") {
+        merged_contents.insert(
+            0,
+            GeminiContent {
+                role: "user".to_string(),
+                parts: vec![text_part(String::new())],
+            },
+        );
+    }
+    merged_contents
+}
+
+fn text_part(text: String) -> GeminiPart {
+    GeminiPart {
+        text: Some(text),
+        ..Default::default()
+    }
+}
+
+fn inline_data_part(mime_type: &str, data: String) -> GeminiPart {
+    GeminiPart {
+        inline_data: Some(InlineData {
+            mime_type: mime_type.to_string(),
+            data,
+        }),
+        ..Default::default()
+    }
+}
+
+/// 估算整段对话的输入 token 数 (字符数 / 4，图片等按固定值计)
+pub fn estimate_prompt_tokens(messages: &[OpenAIMessage]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+/// 对话超出模型上下文窗口时，从最早的非 system 消息开始丢弃，直到估算值不超过
+/// (上下文窗口 - max_output_tokens)。最后一条消息始终保留，返回丢弃的消息数
+pub fn truncate_messages_to_context(
+    messages: &mut Vec<OpenAIMessage>,
+    model: &str,
+    max_output_tokens: i32,
+) -> usize {
+    let window = context_window_for_model(model) as usize;
+    let budget = window.saturating_sub(max_output_tokens.max(0) as usize);
+
+    let mut total: usize = messages.iter().map(estimate_message_tokens).sum();
+    if total <= budget {
+        return 0;
+    }
+    let original_total = total;
+
+    let mut dropped = 0;
+    while let Some(idx) = messages.iter().position(|m| m.role != "system") {
+        if idx + 1 >= messages.len() {
+            break;
+        }
+        // 工具结果必须紧跟其 tool_call，开头残留的 tool 消息一并丢弃
+        let next_is_orphan = |m: &OpenAIMessage| m.role == "tool" || m.role == "function";
+        if total <= budget && !next_is_orphan(&messages[idx]) {
+            break;
+        }
+        let removed = messages.remove(idx);
+        total -= estimate_message_tokens(&removed);
+        dropped += 1;
+    }
+
+    if dropped > 0 {
+        tracing::warn!(
+            "[OpenAI-Request] Conversation exceeds context window of {} ({} tokens, ~{} estimated), dropped {} oldest messages (~{} tokens remain)",
+            model,
+            window,
+            original_total,
+            dropped,
+            total
+        );
+    }
+    dropped
+}
+
+/// 对话摘要使用的 system 提示词
+pub const SUMMARISE_PROMPT: &str = "Summarise this conversation in 2 paragraphs. Keep names, decisions, open questions and any facts needed to continue the conversation.";
+
+/// 对话超出模型上下文窗口时，将最早 50% 的非 system 消息交给 `summarise` 生成摘要，
+/// 并替换为一条 system 摘要消息。最后一条消息始终保留，工具结果不与其 tool_call 拆开。
+/// 返回被替换的消息数 (未超出或摘要失败时为 0，调用方应再用截断兜底)
+pub async fn summarise_messages_to_context<F, Fut>(
+    messages: &mut Vec<OpenAIMessage>,
+    model: &str,
+    max_output_tokens: i32,
+    summarise: F,
+) -> usize
+where
+    F: FnOnce(Vec<OpenAIMessage>) -> Fut,
+    Fut: std::future::Future<Output = Result<String, String>>,
+{
+    let window = context_window_for_model(model) as usize;
+    let budget = window.saturating_sub(max_output_tokens.max(0) as usize);
+    if estimate_prompt_tokens(messages) <= budget {
+        return 0;
+    }
+
+    let positions: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role != "system")
+        .map(|(i, _)| i)
+        .collect();
+    let mut cut = positions.len() / 2;
+    // 紧随其后的工具结果一并归入摘要部分
+    let is_tool = |m: &OpenAIMessage| m.role == "tool" || m.role == "function";
+    while cut > 0 && cut < positions.len() - 1 && is_tool(&messages[positions[cut]]) {
+        cut += 1;
+    }
+    if cut == 0 || cut >= positions.len() {
+        return 0;
+    }
+
+    let oldest: Vec<OpenAIMessage> = positions[..cut].iter().map(|&i| messages[i].clone()).collect();
+    let summary = match summarise(oldest).await {
+        Ok(summary) if !summary.trim().is_empty() => summary,
+        Ok(_) => {
+            tracing::warn!("[OpenAI-Request] Conversation summary is empty, falling back to truncation");
+            return 0;
+        }
+        Err(e) => {
+            tracing::warn!("[OpenAI-Request] Conversation summarisation failed: {}, falling back to truncation", e);
+            return 0;
+        }
+    };
+
+    let first = positions[0];
+    let removed: std::collections::HashSet<usize> = positions[..cut].iter().copied().collect();
+    let mut kept = Vec::with_capacity(messages.len() - cut + 1);
+    for (i, msg) in std::mem::take(messages).into_iter().enumerate() {
+        if i == first {
+            kept.push(OpenAIMessage {
+                role: "system".to_string(),
+                content: Some(OpenAIContent::String(format!(
+                    "Summary of the earlier conversation:\n{}",
+                    summary.trim()
+                ))),
+                reasoning_content: None,
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+            });
+        }
+        if !removed.contains(&i) {
+            kept.push(msg);
+        }
+    }
+    *messages = kept;
+
+    tracing::info!(
+        "[OpenAI-Request] Conversation exceeds context window of {}, summarised {} oldest messages",
+        model,
+        cut
+    );
+    cut
+}
+
+/// 将 role:"tool"/"function" 消息转换为 Gemini functionResponse part
+/// - name: 优先通过 tool_call_id 反查对应 assistant tool_call 的函数名，其次使用消息自带 name
+/// - response: Gemini 要求是对象，内容为合法 JSON 时保留结构，否则按字符串放入 result
+fn build_function_response_part(
+    msg: &OpenAIMessage,
+    tool_id_to_name: &std::collections::HashMap<String, String>,
+) -> GeminiPart {
+    let name = msg
+        .tool_call_id
+        .as_ref()
+        .and_then(|id| tool_id_to_name.get(id).cloned())
+        .or_else(|| msg.name.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    let name = if name == "local_shell_call" { "shell".to_string() } else { name };
+
+    let content_val = match &msg.content {
+        Some(OpenAIContent::String(s)) => s.clone(),
+        Some(OpenAIContent::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|b| if let OpenAIContentBlock::Text { text } = b { Some(text.clone()) } else { None })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => String::new(),
+    };
+    let result = match serde_json::from_str::<Value>(&content_val) {
+        Ok(v @ Value::Object(_)) | Ok(v @ Value::Array(_)) => v,
+        _ => Value::String(content_val),
+    };
+
+    GeminiPart {
+        function_response: Some(FunctionResponse {
+            name,
+            response: json!({ "result": result }),
+            id: Some(msg.tool_call_id.clone().unwrap_or_default()),
+        }),
+        ..Default::default()
+    }
+}
+
+/// OpenAI tool_choice → Gemini functionCallingConfig
+/// - "none" → NONE, "auto" → AUTO, "required" → ANY
+/// - 指定函数 → ANY + allowedFunctionNames
+/// - 未指定 (或无法识别) 时沿用 VALIDATED
+fn function_calling_config(tool_choice: Option<&ToolChoice>) -> GeminiFunctionCallingConfig {
+    let mode = |mode: &str| GeminiFunctionCallingConfig {
+        mode: mode.to_string(),
+        allowed_function_names: None,
+    };
+    match tool_choice {
+        Some(ToolChoice::Function { function, .. }) => GeminiFunctionCallingConfig {
+            mode: "ANY".to_string(),
+            allowed_function_names: Some(vec![function.name.clone()]),
+        },
+        Some(ToolChoice::Mode(choice)) => match choice.as_str() {
+            "none" => mode("NONE"),
+            "auto" => mode("AUTO"),
+            "required" => mode("ANY"),
+            other => {
+                tracing::debug!("[OpenAI-Request] Unknown tool_choice '{}', using VALIDATED", other);
+                mode("VALIDATED")
+            }
+        },
+        None => mode("VALIDATED"),
+    }
+}
+
+/// 校验采样参数范围 (presence_penalty / frequency_penalty 必须在 [-2.0, 2.0])
+pub fn validate_sampling_params(request: &OpenAIRequest) -> Result<(), String> {
+    for (name, value) in [
+        ("presence_penalty", request.presence_penalty),
+        ("frequency_penalty", request.frequency_penalty),
+    ] {
+        if let Some(v) = value {
+            if !(-2.0..=2.0).contains(&v) {
+                return Err(format!("{} must be between -2.0 and 2.0, got {}", name, v));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 校验请求中内联 (data URL) 图片的大小，按 base64 长度估算解码后字节数
+pub fn validate_inline_image_sizes(request: &OpenAIRequest, max_bytes: usize) -> Result<(), String> {
+    for msg in &request.messages {
+        let Some(OpenAIContent::Array(blocks)) = &msg.content else {
+            continue;
+        };
+        for block in blocks {
+            let OpenAIContentBlock::ImageUrl { image_url } = block else {
+                continue;
+            };
+            if !image_url.url.starts_with("data:") {
+                continue;
+            }
+            let Some(pos) = image_url.url.find(',') else {
+                continue;
+            };
+            let estimated = super::response::estimated_decoded_len(&image_url.url[pos + 1..]);
+            if estimated > max_bytes {
+                return Err(format!(
+                    "Inline image too large: estimated {} bytes exceeds limit of {} bytes",
+                    estimated, max_bytes
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 支持透传的图片 MIME 类型
+pub const SUPPORTED_IMAGE_MIME_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/tiff",
+];
+
+/// 校验图片 MIME (忽略大小写，image/jpg 视为 image/jpeg)，不在白名单时回退为 image/jpeg 并记录警告
+pub fn normalize_image_mime_type(mime_type: &str) -> &'static str {
+    let lower = mime_type.trim().to_ascii_lowercase();
+    let lower = if lower == "image/jpg" { "image/jpeg".to_string() } else { lower };
+    match SUPPORTED_IMAGE_MIME_TYPES.iter().find(|m| **m == lower) {
+        Some(m) => m,
+        None => {
+            tracing::warn!(
+                "[OpenAI-Request] Unsupported image MIME type '{}', sending as image/jpeg",
+                mime_type
+            );
+            "image/jpeg"
+        }
+    }
+}
+
+/// 根据本地文件扩展名推断图片 MIME (未知扩展名按 image/jpeg)
+fn image_mime_from_path(path: &str) -> &'static str {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        _ => "image/jpeg",
+    }
+}
+
+/// 支持透传的音频 MIME 类型
+pub const SUPPORTED_AUDIO_MIME_TYPES: &[&str] = &["audio/wav", "audio/mp3", "audio/ogg", "audio/aac"];
+
+/// 解析 data:audio/<type>;base64,<data>，返回 (MIME, base64 数据)
+fn parse_audio_data_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime_type = meta.strip_suffix(";base64")?;
+    SUPPORTED_AUDIO_MIME_TYPES
+        .contains(&mime_type)
+        .then_some((mime_type, data))
+}
+
+/// 校验 audio_url 内容：必须为 base64 data URI，且 MIME 在白名单内
+pub fn validate_audio_inputs(request: &OpenAIRequest) -> Result<(), String> {
+    for msg in &request.messages {
+        let Some(OpenAIContent::Array(blocks)) = &msg.content else {
+            continue;
+        };
+        for block in blocks {
+            let OpenAIContentBlock::AudioUrl { audio_url } = block else {
+                continue;
+            };
+            if parse_audio_data_url(&audio_url.url).is_none() {
+                let mime_type = audio_url
+                    .url
+                    .strip_prefix("data:")
+                    .and_then(|rest| rest.split([';', ',']).next())
+                    .unwrap_or("<not a data URI>");
+                return Err(format!(
+                    "Unsupported audio input '{}', expected a base64 data URI with one of: {}",
+                    mime_type,
+                    SUPPORTED_AUDIO_MIME_TYPES.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gemini 支持的安全类别
+const SAFETY_CATEGORIES: &[&str] = &[
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+    "HARM_CATEGORY_CIVIC_INTEGRITY",
+];
+
+/// Gemini 支持的拦截阈值
+const SAFETY_THRESHOLDS: &[&str] = &[
+    "HARM_BLOCK_THRESHOLD_UNSPECIFIED",
+    "BLOCK_LOW_AND_ABOVE",
+    "BLOCK_MEDIUM_AND_ABOVE",
+    "BLOCK_ONLY_HIGH",
+    "BLOCK_NONE",
+    "OFF",
+];
+
+/// 校验 x-antigravity-safety-settings 的类别与阈值
+pub fn validate_safety_settings(request: &OpenAIRequest) -> Result<(), String> {
+    let Some(settings) = &request.x_antigravity_safety_settings else {
+        return Ok(());
+    };
+    for setting in settings {
+        if !SAFETY_CATEGORIES.contains(&setting.category.as_str()) {
+            return Err(format!(
+                "Invalid safety category '{}', expected one of: {}",
+                setting.category,
+                SAFETY_CATEGORIES.join(", ")
+            ));
+        }
+        if !SAFETY_THRESHOLDS.contains(&setting.threshold.as_str()) {
+            return Err(format!(
+                "Invalid safety threshold '{}', expected one of: {}",
+                setting.threshold,
+                SAFETY_THRESHOLDS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 部分旧模型不支持惩罚参数，上游报错提及这些字段时将其移除以便重试
+/// 返回 true 表示有字段被移除
+pub fn strip_unsupported_penalties(request: &mut OpenAIRequest, error_text: &str) -> bool {
+    let mentions = |keys: &[&str]| keys.iter().any(|k| error_text.contains(k));
+    let mut stripped = false;
+
+    if request.presence_penalty.is_some() && mentions(&["presencePenalty", "presence_penalty"]) {
+        request.presence_penalty = None;
+        stripped = true;
+    }
+    if request.frequency_penalty.is_some() && mentions(&["frequencyPenalty", "frequency_penalty"]) {
+        request.frequency_penalty = None;
+        stripped = true;
+    }
+    stripped
+}
+
 fn enforce_uppercase_types(value: &mut Value) {
     if let Value::Object(map) = value {
         if let Some(type_val) = map.get_mut("type") {
@@ -625,10 +1175,15 @@ mod tests {
                 name: None,
             }],
             stream: false,
+            stream_options: None,
             n: None,
+            seed: None,
             max_tokens: None,
             temperature: None,
             top_p: None,
+            top_k: None,
+            presence_penalty: None,
+            frequency_penalty: None,
             stop: None,
             response_format: None,
             tools: None,
@@ -640,6 +1195,10 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            x_antigravity_tools: None,
+            x_antigravity_code_execution: false,
+            x_antigravity_safety_settings: None,
+            x_antigravity_thinking_budget: None,
         };
 
         let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash");
@@ -651,6 +1210,39 @@ mod tests {
             "image/png"
         );
     }
+    #[test]
+    fn test_remote_image_and_tool_result_parts() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+                ]},
+                {"role": "assistant", "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": {"name": "lookup", "arguments": "{\"q\":\"cat\"}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "{\"found\": true}"}
+            ]
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let contents = &result["request"]["contents"];
+        assert_eq!(
+            contents[0]["parts"][0],
+            json!({"fileData": {"fileUri": "https://example.com/cat.png", "mimeType": "image/jpeg"}})
+        );
+        assert_eq!(
+            contents[1]["parts"][0]["functionCall"],
+            json!({"name": "lookup", "id": "call_1", "args": {"q": "cat"}})
+        );
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"],
+            json!({"name": "lookup", "response": {"result": {"found": true}}, "id": "call_1"})
+        );
+    }
+
     #[test]
     fn test_default_max_tokens_openai() {
         let req = OpenAIRequest {
@@ -664,10 +1256,15 @@ mod tests {
                 name: None,
             }],
             stream: false,
+            stream_options: None,
             n: None,
+            seed: None,
             max_tokens: None,
             temperature: None,
             top_p: None,
+            top_k: None,
+            presence_penalty: None,
+            frequency_penalty: None,
             stop: None,
             response_format: None,
             tools: None,
@@ -679,6 +1276,10 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            x_antigravity_tools: None,
+            x_antigravity_code_execution: false,
+            x_antigravity_safety_settings: None,
+            x_antigravity_thinking_budget: None,
         };
 
         let result = transform_openai_request(&req, "test-p", "gemini-3-pro-high-thinking");
@@ -691,4 +1292,720 @@ mod tests {
         let budget = gen_config["thinkingConfig"]["thinkingBudget"].as_i64().unwrap();
         assert_eq!(budget, 32000);
     }
+
+    #[test]
+    fn test_tools_map_to_function_declarations() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "What's the weather?"}],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the weather",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }],
+            "tool_choice": "auto"
+        }))
+        .unwrap();
+
+        let tools = req.tools.as_ref().unwrap();
+        assert_eq!(tools[0].function.as_ref().unwrap().name, "get_weather");
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let decls = &result["request"]["tools"][0]["functionDeclarations"];
+        assert_eq!(decls[0]["name"], "get_weather");
+        assert_eq!(decls[0]["parameters"]["type"], "OBJECT");
+        assert_eq!(decls[0]["parameters"]["properties"]["city"]["type"], "STRING");
+    }
+
+    #[test]
+    fn test_x_antigravity_tools_forwarded_verbatim() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Latest Rust release?"}],
+            "tools": [{
+                "type": "function",
+                "function": {"name": "get_weather", "parameters": {"type": "object", "properties": {}}}
+            }],
+            "x-antigravity-tools": [{"googleSearch": {}}]
+        }))
+        .unwrap();
+        assert!(req.x_antigravity_tools.is_some());
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let tools = result["request"]["tools"].as_array().unwrap();
+        assert_eq!(tools[0]["functionDeclarations"][0]["name"], "get_weather");
+        assert_eq!(tools[1], json!({"googleSearch": {}}));
+
+        let body = serde_json::to_string(&result).unwrap();
+        assert!(body.contains("\"googleSearch\""));
+    }
+
+    fn request_with_thinking_budget(budget: u32) -> OpenAIRequest {
+        serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "think hard"}],
+            "x-antigravity-thinking-budget": budget
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_thinking_budget_gemini() {
+        let req = request_with_thinking_budget(4096);
+        assert_eq!(req.x_antigravity_thinking_budget, Some(4096));
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["thinkingConfig"]["thinkingBudget"], 4096);
+        assert_eq!(gen_config["thinkingConfig"]["includeThoughts"], true);
+        assert!(gen_config["maxOutputTokens"].as_i64().unwrap() > 4096);
+        assert!(result["request"].get("thinking").is_none());
+
+        // 显式预算覆盖思维模型的默认值
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash-thinking");
+        assert_eq!(result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 4096);
+    }
+
+    #[test]
+    fn test_thinking_budget_anthropic_style() {
+        let req = request_with_thinking_budget(2048);
+        let result = transform_openai_request(&req, "test-p", "claude-sonnet-4-5");
+        assert_eq!(result["request"]["thinking"], json!({"type": "enabled", "budget_tokens": 2048}));
+        assert!(result["request"]["generationConfig"].get("thinkingConfig").is_none());
+
+        // 未指定预算时不注入
+        let mut req = req;
+        req.x_antigravity_thinking_budget = None;
+        let result = transform_openai_request(&req, "test-p", "claude-sonnet-4-5");
+        assert!(result["request"].get("thinking").is_none());
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert!(result["request"]["generationConfig"].get("thinkingConfig").is_none());
+    }
+
+    #[test]
+    fn test_x_antigravity_tools_single_object() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "x-antigravity-tools": {"googleSearch": {}}
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let tools = result["request"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|t| t.get("googleSearch").is_some()));
+    }
+
+    fn text_message(role: &str, chars: usize) -> OpenAIMessage {
+        OpenAIMessage {
+            role: role.to_string(),
+            content: Some(OpenAIContent::String("x".repeat(chars))),
+            reasoning_content: None,
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        assert_eq!(context_window_for_model("claude-sonnet-4-5-thinking"), 200_000);
+        assert_eq!(context_window_for_model("gemini-2.5-flash-lite"), 1_048_576);
+        assert_eq!(context_window_for_model("unknown-model"), DEFAULT_CONTEXT_WINDOW);
+    }
+
+    #[test]
+    fn test_truncate_long_conversation() {
+        // 1 条 system + 25 条 50k 字符消息，共 125 万字符 (~312k tokens)
+        let mut messages = vec![text_message("system", 1_000)];
+        for i in 0..25 {
+            messages.push(text_message(if i % 2 == 0 { "user" } else { "assistant" }, 50_000));
+        }
+        let total_chars: usize = messages.iter().map(|m| match &m.content {
+            Some(OpenAIContent::String(s)) => s.len(),
+            _ => 0,
+        }).sum();
+        assert!(total_chars > 1_000_000);
+        let last = messages.last().cloned().unwrap();
+
+        let dropped = truncate_messages_to_context(&mut messages, "claude-sonnet-4-5", 8192);
+
+        // 预算 200000 - 8192 = 191808 tokens，每条约 12500 tokens，只能保留 15 条
+        assert_eq!(dropped, 10);
+        assert_eq!(messages.len(), 16);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages.last().unwrap().content, last.content);
+        let estimated: usize = messages.iter().map(estimate_message_tokens).sum();
+        assert!(estimated <= 200_000 - 8192);
+    }
+
+    #[tokio::test]
+    async fn test_summarise_replaces_oldest_half() {
+        let mut messages = vec![text_message("system", 1_000)];
+        for i in 0..20 {
+            messages.push(text_message(if i % 2 == 0 { "user" } else { "assistant" }, 50_000));
+        }
+        let last = messages.last().cloned().unwrap();
+
+        let summarised = summarise_messages_to_context(&mut messages, "claude-sonnet-4-5", 8192, |oldest| async move {
+            assert_eq!(oldest.len(), 10);
+            assert!(oldest.iter().all(|m| m.role != "system"));
+            Ok::<_, String>("they discussed x".to_string())
+        })
+        .await;
+
+        assert_eq!(summarised, 10);
+        // system + 摘要 + 后 10 条
+        assert_eq!(messages.len(), 12);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].role, "system");
+        assert!(matches!(&messages[1].content, Some(OpenAIContent::String(s)) if s.ends_with("they discussed x")));
+        assert_eq!(messages.last().unwrap().content, last.content);
+    }
+
+    #[tokio::test]
+    async fn test_summarise_skips_short_and_falls_back_on_error() {
+        let mut short = vec![text_message("user", 100), text_message("assistant", 100)];
+        let summarised = summarise_messages_to_context(&mut short, "gemini-2.5-flash", 0, |_| async {
+            Ok::<_, String>("unexpected".to_string())
+        })
+        .await;
+        assert_eq!(summarised, 0);
+        assert_eq!(short.len(), 2);
+
+        let mut messages = vec![text_message("user", 500_000), text_message("assistant", 500_000), text_message("user", 10)];
+        let summarised = summarise_messages_to_context(&mut messages, "claude-sonnet-4-5", 0, |_| async {
+            Err::<String, _>("upstream 503".to_string())
+        })
+        .await;
+        assert_eq!(summarised, 0);
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_summarise_keeps_tool_results_with_their_call() {
+        let mut messages = vec![
+            text_message("user", 400_000),
+            text_message("assistant", 10),
+            text_message("tool", 10),
+            text_message("user", 400_000),
+            text_message("assistant", 10),
+        ];
+        let summarised = summarise_messages_to_context(&mut messages, "claude-sonnet-4-5", 0, |oldest| async move {
+            assert_eq!(oldest.last().unwrap().role, "tool");
+            Ok::<_, String>("summary".to_string())
+        })
+        .await;
+        assert_eq!(summarised, 3);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "user");
+    }
+
+    #[test]
+    fn test_truncate_keeps_short_conversation_and_last_message() {
+        let mut short = vec![text_message("user", 100)];
+        assert_eq!(truncate_messages_to_context(&mut short, "gemini-2.5-flash", 8192), 0);
+
+        // 单条超长消息无法再裁剪，保留原样交给上游处理
+        let mut single = vec![text_message("system", 10), text_message("user", 1_000_000)];
+        assert_eq!(truncate_messages_to_context(&mut single, "claude-opus-4-5", 0), 0);
+        assert_eq!(single.len(), 2);
+    }
+
+    #[test]
+    fn test_truncate_drops_orphan_tool_results() {
+        let mut messages = vec![
+            text_message("user", 400_000),
+            text_message("assistant", 10),
+            text_message("tool", 10),
+            text_message("user", 400_000),
+        ];
+        let dropped = truncate_messages_to_context(&mut messages, "claude-sonnet-4-5", 0);
+        // 丢弃第一条后预算已满足，但 assistant/tool 对须保持完整：这里 assistant 仍在，tool 不会成为开头
+        assert_eq!(dropped, 1);
+        assert_eq!(messages[0].role, "assistant");
+
+        let mut messages = vec![
+            text_message("assistant", 400_000),
+            text_message("tool", 10),
+            text_message("user", 400_000),
+        ];
+        let dropped = truncate_messages_to_context(&mut messages, "claude-sonnet-4-5", 0);
+        assert_eq!(dropped, 2);
+        assert_eq!(messages[0].role, "user");
+    }
+
+    #[test]
+    fn test_safety_settings_forwarded() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "x-antigravity-safety-settings": [
+                {"category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "BLOCK_LOW_AND_ABOVE"}
+            ]
+        }))
+        .unwrap();
+        assert!(validate_safety_settings(&req).is_ok());
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(
+            result["request"]["safetySettings"],
+            json!([{"category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "BLOCK_LOW_AND_ABOVE"}])
+        );
+
+        // 未指定时保持默认全部 OFF
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let defaults = result["request"]["safetySettings"].as_array().unwrap();
+        assert_eq!(defaults.len(), 5);
+        assert!(defaults.iter().all(|s| s["threshold"] == "OFF"));
+    }
+
+    #[test]
+    fn test_invalid_safety_settings_rejected() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "x-antigravity-safety-settings": [{"category": "HARM_CATEGORY_VIOLENCE", "threshold": "OFF"}]
+        }))
+        .unwrap();
+        assert!(validate_safety_settings(&req).unwrap_err().contains("HARM_CATEGORY_VIOLENCE"));
+
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "x-antigravity-safety-settings": [{"category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "BLOCK_ALL"}]
+        }))
+        .unwrap();
+        assert!(validate_safety_settings(&req).unwrap_err().contains("BLOCK_ALL"));
+    }
+
+    #[test]
+    fn test_seed_forwarded_to_generation_config() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "seed": 42
+        }))
+        .unwrap();
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(result["request"]["generationConfig"]["seed"], 42);
+
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert!(result["request"]["generationConfig"].get("seed").is_none());
+    }
+
+    #[test]
+    fn test_response_format_json_object() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Give me JSON"}],
+            "response_format": {"type": "json_object"}
+        }))
+        .unwrap();
+        assert_eq!(req.response_format, Some(ResponseFormat::JsonObject));
+        assert!(req.is_json_mode());
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert!(gen_config.get("responseSchema").is_none());
+    }
+
+    #[test]
+    fn test_response_format_json_schema() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Extract the city"}],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "city",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"],
+                        "additionalProperties": false
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        assert_eq!(gen_config["responseSchema"]["type"], "OBJECT");
+        assert_eq!(gen_config["responseSchema"]["properties"]["city"]["type"], "STRING");
+        assert_eq!(gen_config["responseSchema"]["required"], json!(["city"]));
+    }
+
+    #[test]
+    fn test_response_format_text_and_invalid_type() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "response_format": {"type": "text"}
+        }))
+        .unwrap();
+        assert!(!req.is_json_mode());
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert!(result["request"]["generationConfig"].get("responseMimeType").is_none());
+
+        let invalid = serde_json::from_value::<OpenAIRequest>(json!({
+            "model": "gpt-4",
+            "messages": [],
+            "response_format": {"type": "yaml"}
+        }));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_code_execution_flag_adds_tool() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Compute the 50th prime"}],
+            "x-antigravity-code-execution": true
+        }))
+        .unwrap();
+        assert!(req.x_antigravity_code_execution);
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let tools = result["request"]["tools"].as_array().unwrap();
+        assert_eq!(
+            tools.iter().filter(|t| t.get("codeExecution").is_some()).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_tool_message_becomes_user_function_response() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "{\"temp\": 21}"}
+            ]
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let contents = result["request"]["contents"].as_array().unwrap();
+        let last = contents.last().unwrap();
+        assert_eq!(last["role"], "user");
+
+        let fr = &last["parts"][0]["functionResponse"];
+        assert_eq!(fr["name"], "get_weather");
+        assert_eq!(fr["id"], "call_1");
+        assert_eq!(fr["response"]["result"]["temp"], 21);
+    }
+
+    #[test]
+    fn test_n_maps_to_capped_candidate_count() {
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "n": 2
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(result["request"]["generationConfig"]["candidateCount"], 2);
+
+        req.n = Some(10);
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(result["request"]["generationConfig"]["candidateCount"], 4);
+    }
+
+    #[test]
+    fn test_top_k_forwarding() {
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "top_k": 20
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(result["request"]["generationConfig"]["topK"], 20);
+
+        req.top_k = Some(100);
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert_eq!(result["request"]["generationConfig"]["topK"], 40);
+
+        req.top_k = None;
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert!(result["request"]["generationConfig"].get("topK").is_none());
+    }
+
+    #[test]
+    fn test_stop_sequences_forwarding() {
+        let build = |stop: Option<Value>| {
+            let mut body = json!({
+                "model": "gpt-4",
+                "messages": [{"role": "user", "content": "Hi"}]
+            });
+            if let Some(stop) = stop {
+                body["stop"] = stop;
+            }
+            let req: OpenAIRequest = serde_json::from_value(body).unwrap();
+            transform_openai_request(&req, "test-p", "gemini-2.5-flash")
+        };
+
+        let result = build(Some(json!("END")));
+        assert_eq!(result["request"]["generationConfig"]["stopSequences"], json!(["END"]));
+
+        let result = build(Some(json!(["<s1>", "<s2>"])));
+        assert_eq!(
+            result["request"]["generationConfig"]["stopSequences"],
+            json!(["<s1>", "<s2>"])
+        );
+
+        let result = build(None);
+        assert!(result["request"]["generationConfig"].get("stopSequences").is_none());
+    }
+
+    #[test]
+    fn test_penalties_forwarding_and_stripping() {
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "presence_penalty": 0.5,
+            "frequency_penalty": -1.0
+        }))
+        .unwrap();
+        assert!(validate_sampling_params(&req).is_ok());
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["presencePenalty"], 0.5);
+        assert_eq!(gen_config["frequencyPenalty"], -1.0);
+
+        let error_text = r#"{"error":{"code":400,"message":"Invalid JSON payload received. Unknown name \"presencePenalty\""}}"#;
+        assert!(strip_unsupported_penalties(&mut req, error_text));
+        assert!(req.presence_penalty.is_none());
+        assert!(req.frequency_penalty.is_some());
+        assert!(!strip_unsupported_penalties(&mut req, "unrelated error"));
+
+        req.frequency_penalty = Some(3.0);
+        assert!(validate_sampling_params(&req).is_err());
+    }
+
+    #[test]
+    fn test_oddly_ordered_messages_alternate_roles() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [
+                {"role": "system", "content": "Be brief"},
+                {"role": "assistant", "content": "Hello there"},
+                {"role": "assistant", "content": "How can I help?"},
+                {"role": "user", "content": "Hi"},
+                {"role": "user", "content": "Anyone?"},
+                {"role": "assistant", "content": "Yes"}
+            ]
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let contents = result["request"]["contents"].as_array().unwrap();
+        let roles: Vec<&str> = contents.iter().map(|c| c["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "model", "user", "model"]);
+        assert!(roles.windows(2).all(|w| w[0] != w[1]));
+
+        // 以 model 开头时补空 user 回合
+        assert_eq!(contents[0]["parts"], json!([{"text": ""}]));
+        // 连续 model 回合以 "\n" 分隔
+        let model_texts: Vec<&str> = contents[1]["parts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(model_texts, vec!["Hello there", "\n", "How can I help?"]);
+        // 连续 user 回合直接合并
+        assert_eq!(contents[2]["parts"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_system_messages_become_system_instruction() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [
+                {"role": "system", "content": "You are Antigravity, a test assistant."},
+                {"role": "user", "content": "Hi"},
+                {"role": "developer", "content": "Answer in French."}
+            ]
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let contents = result["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0]["parts"][0]["text"], "Hi");
+        assert!(!contents.iter().any(|c| c.to_string().contains("Answer in French")));
+
+        let sys_parts = result["request"]["systemInstruction"]["parts"].as_array().unwrap();
+        assert_eq!(sys_parts.len(), 1);
+        assert_eq!(
+            sys_parts[0]["text"],
+            "You are Antigravity, a test assistant.\nAnswer in French."
+        );
+    }
+
+    #[test]
+    fn test_oversized_inline_image_rejected() {
+        let image_url = format!("data:image/png;base64,{}", "A".repeat(400));
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "describe"},
+                    {"type": "image_url", "image_url": {"url": image_url}}
+                ]
+            }]
+        }))
+        .unwrap();
+
+        assert!(validate_inline_image_sizes(&req, 300).is_ok());
+        let err = validate_inline_image_sizes(&req, 299).unwrap_err();
+        assert!(err.contains("estimated 300 bytes"));
+    }
+
+    #[test]
+    fn test_image_mime_allowlist() {
+        for mime in SUPPORTED_IMAGE_MIME_TYPES {
+            assert_eq!(normalize_image_mime_type(mime), *mime);
+        }
+        assert_eq!(normalize_image_mime_type("IMAGE/WEBP"), "image/webp");
+        assert_eq!(normalize_image_mime_type("image/jpg"), "image/jpeg");
+        assert_eq!(normalize_image_mime_type("image/heic"), "image/jpeg");
+        assert_eq!(normalize_image_mime_type("text/plain"), "image/jpeg");
+
+        assert_eq!(image_mime_from_path("/tmp/a.GIF"), "image/gif");
+        assert_eq!(image_mime_from_path("/tmp/a.bmp"), "image/bmp");
+        assert_eq!(image_mime_from_path("/tmp/a.tif"), "image/tiff");
+        assert_eq!(image_mime_from_path("/tmp/a.jpeg"), "image/jpeg");
+        assert_eq!(image_mime_from_path("/tmp/noext"), "image/jpeg");
+    }
+
+    #[test]
+    fn test_data_uri_image_mime_validated() {
+        for (mime, expected) in [("image/gif", "image/gif"), ("image/webp", "image/webp"), ("image/x-icon", "image/jpeg")] {
+            let req: OpenAIRequest = serde_json::from_value(json!({
+                "model": "gemini-2.5-flash",
+                "messages": [{
+                    "role": "user",
+                    "content": [{"type": "image_url", "image_url": {"url": format!("data:{};base64,R0lGOD", mime)}}]
+                }]
+            }))
+            .unwrap();
+            let result = transform_openai_request(&req, "p", "gemini-2.5-flash");
+            let parts = &result["request"]["contents"][0]["parts"];
+            assert_eq!(parts[0]["inlineData"]["mimeType"], expected);
+            assert_eq!(parts[0]["inlineData"]["data"], "R0lGOD");
+        }
+    }
+
+    #[test]
+    fn test_audio_url_round_trip() {
+        let body = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "transcribe"},
+                    {"type": "audio_url", "audio_url": {"url": "data:audio/wav;base64,UklGRiQAAABXQVZF"}}
+                ]
+            }]
+        });
+        let req: OpenAIRequest = serde_json::from_value(body.clone()).unwrap();
+        assert!(validate_audio_inputs(&req).is_ok());
+
+        // 模型序列化后与原始请求一致
+        let reserialized = serde_json::to_value(&req.messages[0].content).unwrap();
+        assert_eq!(reserialized, body["messages"][0]["content"]);
+
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "audio/wav");
+        assert_eq!(parts[1]["inlineData"]["data"], "UklGRiQAAABXQVZF");
+    }
+
+    #[test]
+    fn test_unsupported_audio_mime_rejected() {
+        for url in ["data:audio/flac;base64,AAAA", "https://example.com/a.wav"] {
+            let req: OpenAIRequest = serde_json::from_value(json!({
+                "model": "gemini-2.5-flash",
+                "messages": [{
+                    "role": "user",
+                    "content": [{"type": "audio_url", "audio_url": {"url": url}}]
+                }]
+            }))
+            .unwrap();
+            assert!(validate_audio_inputs(&req).is_err(), "{} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn test_tool_choice_maps_to_function_calling_mode() {
+        let cases = [
+            (json!(null), json!({"mode": "VALIDATED"})),
+            (json!("none"), json!({"mode": "NONE"})),
+            (json!("auto"), json!({"mode": "AUTO"})),
+            (json!("required"), json!({"mode": "ANY"})),
+            (
+                json!({"type": "function", "function": {"name": "get_weather"}}),
+                json!({"mode": "ANY", "allowedFunctionNames": ["get_weather"]}),
+            ),
+        ];
+        for (tool_choice, expected) in cases {
+            let req: OpenAIRequest = serde_json::from_value(json!({
+                "model": "gpt-4",
+                "messages": [{"role": "user", "content": "Weather?"}],
+                "tools": [{
+                    "type": "function",
+                    "function": {"name": "get_weather", "parameters": {"type": "object", "properties": {}}}
+                }],
+                "tool_choice": tool_choice
+            }))
+            .unwrap();
+            let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+            assert_eq!(result["request"]["toolConfig"]["functionCallingConfig"], expected);
+        }
+    }
+
+    #[test]
+    fn test_no_tool_config_without_function_tools() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "tool_choice": "auto"
+        }))
+        .unwrap();
+        let result = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        assert!(result["request"].get("toolConfig").is_none());
+    }
 }
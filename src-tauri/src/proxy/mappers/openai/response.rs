@@ -1,8 +1,10 @@
 // OpenAI 协议响应转换模块
 use super::models::*;
+use crate::proxy::mappers::claude::models::InlineData;
+use crate::proxy::mappers::gemini::models::GeminiUsageMetadata;
 use serde_json::Value;
 
-pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
+pub fn transform_openai_response(gemini_response: &Value, max_inline_image_bytes: usize) -> OpenAIResponse {
     // 解包 response 字段
     let raw = gemini_response.get("response").unwrap_or(gemini_response);
 
@@ -71,17 +73,14 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                         });
                     }
 
+                    // 代码执行 (executableCode / codeExecutionResult) 转为围栏代码块
+                    if let Some(block) = code_execution_part_to_markdown(part) {
+                        content_out.push_str(&block);
+                    }
+
                     // 图片处理 (响应中直接返回图片的情况)
                     if let Some(img) = part.get("inlineData") {
-                        let mime_type = img
-                            .get("mimeType")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("image/png");
-                        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                        if !data.is_empty() {
-                            content_out
-                                .push_str(&format!("![image](data:{};base64,{})", mime_type, data));
-                        }
+                        push_image_markdown(&mut content_out, &inline_data_to_markdown(img, max_inline_image_bytes));
                     }
                 }
             }
@@ -160,40 +159,14 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                     tool_call_id: None,
                     name: None,
                 },
+                logprobs: None,
                 finish_reason: Some(finish_reason.to_string()),
             });
         }
     }
 
     // Extract and map usage metadata from Gemini to OpenAI format
-    let usage = raw.get("usageMetadata").and_then(|u| {
-        let prompt_tokens = u
-            .get("promptTokenCount")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
-        let completion_tokens = u
-            .get("candidatesTokenCount")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
-        let total_tokens = u
-            .get("totalTokenCount")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
-        let cached_tokens = u
-            .get("cachedContentTokenCount")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
-
-        Some(super::models::OpenAIUsage {
-            prompt_tokens,
-            completion_tokens,
-            total_tokens,
-            prompt_tokens_details: cached_tokens.map(|ct| super::models::PromptTokensDetails {
-                cached_tokens: Some(ct),
-            }),
-            completion_tokens_details: None,
-        })
-    });
+    let usage = raw.get("usageMetadata").map(usage_from_gemini_metadata);
 
     OpenAIResponse {
         id: raw
@@ -208,16 +181,232 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
             .and_then(|v| v.as_str())
             .unwrap_or("unknown")
             .to_string(),
+        system_fingerprint: Some(SYSTEM_FINGERPRINT.to_string()),
         choices,
         usage,
     }
 }
 
+/// Gemini 非流式响应 → 客户端可直接解析的 OpenAI ChatCompletion
+/// 与 transform_openai_response 相比：model 使用客户端请求的模型名，id 使用本次请求 ID，
+/// 且 usage 始终存在 (上游缺少 usageMetadata 时为 0)
+pub fn gemini_response_to_openai_chat_completion(
+    raw: &Value,
+    model: &str,
+    request_id: &str,
+    max_inline_image_bytes: usize,
+) -> OpenAIResponse {
+    let mut response = transform_openai_response(raw, max_inline_image_bytes);
+    response.id = format!("chatcmpl-{}", request_id);
+    response.model = model.to_string();
+    response.usage.get_or_insert_with(OpenAIUsage::default);
+    response
+}
+
+/// 按 base64 长度估算解码后的字节数
+pub fn estimated_decoded_len(b64: &str) -> usize {
+    b64.len() * 3 / 4
+}
+
+/// 将 Gemini inlineData 转为 Markdown 图片；超过 `max_bytes` 时以提示文本代替，避免响应体膨胀
+pub fn inline_data_to_markdown(img: &Value, max_bytes: usize) -> String {
+    let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
+    let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
+    image_markdown(mime_type, data, max_bytes)
+}
+
+/// inline_data_to_markdown 的类型化版本 (流式解析使用 GeminiStreamChunk)
+pub fn typed_inline_data_to_markdown(img: &InlineData, max_bytes: usize) -> String {
+    image_markdown(&img.mime_type, &img.data, max_bytes)
+}
+
+fn image_markdown(mime_type: &str, data: &str, max_bytes: usize) -> String {
+    if data.is_empty() {
+        return String::new();
+    }
+    let estimated = estimated_decoded_len(data);
+    if estimated > max_bytes {
+        tracing::warn!(
+            "[OpenAI-Response] Inline image too large ({} bytes > {}), replaced with placeholder",
+            estimated,
+            max_bytes
+        );
+        return format!("[Image too large to display: estimated {} bytes]", estimated);
+    }
+    format!("![image](data:{};base64,{})", mime_type, data)
+}
+
+/// 追加图片 Markdown，与前面的内容以换行分隔 (同一候选中的多张图片各占一行)
+pub fn push_image_markdown(content_out: &mut String, image: &str) {
+    if image.is_empty() {
+        return;
+    }
+    if !content_out.is_empty() && !content_out.ends_with('\n') {
+        content_out.push('\n');
+    }
+    content_out.push_str(image);
+}
+
+/// 将 Gemini 代码执行 part 转为 Markdown 围栏代码块
+/// - executableCode: ```<language>\n<code>\n```
+/// - codeExecutionResult: ```output\n<output>\n``` (执行失败时附带 outcome)
+pub fn code_execution_part_to_markdown(part: &Value) -> Option<String> {
+    if let Some(exec) = part.get("executableCode") {
+        let language = exec
+            .get("language")
+            .and_then(|v| v.as_str())
+            .unwrap_or("python")
+            .to_lowercase();
+        let code = exec.get("code").and_then(|v| v.as_str()).unwrap_or("");
+        return Some(format!("\n```{}\n{}\n```\n", language, code.trim_end()));
+    }
+
+    if let Some(result) = part.get("codeExecutionResult") {
+        let output = result.get("output").and_then(|v| v.as_str()).unwrap_or("");
+        let outcome = result.get("outcome").and_then(|v| v.as_str()).unwrap_or("OUTCOME_OK");
+        let header = if outcome == "OUTCOME_OK" {
+            String::new()
+        } else {
+            format!("{}\n", outcome)
+        };
+        return Some(format!("\n```output\n{}{}\n```\n", header, output.trim_end()));
+    }
+
+    None
+}
+
+/// Gemini usageMetadata → OpenAI usage
+/// totalTokenCount 缺失时以 prompt + completion 兜底
+pub fn usage_from_gemini_metadata(u: &Value) -> OpenAIUsage {
+    let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+    build_usage(
+        count("promptTokenCount"),
+        count("candidatesTokenCount"),
+        count("totalTokenCount"),
+        count("cachedContentTokenCount"),
+    )
+}
+
+/// usage_from_gemini_metadata 的类型化版本
+pub fn usage_from_usage_metadata(u: &GeminiUsageMetadata) -> OpenAIUsage {
+    build_usage(
+        u.prompt_token_count,
+        u.candidates_token_count,
+        u.total_token_count,
+        u.cached_content_token_count,
+    )
+}
+
+fn build_usage(
+    prompt: Option<u32>,
+    completion: Option<u32>,
+    total: Option<u32>,
+    cached: Option<u32>,
+) -> OpenAIUsage {
+    let prompt_tokens = prompt.unwrap_or(0);
+    let completion_tokens = completion.unwrap_or(0);
+    let total_tokens = total.unwrap_or(prompt_tokens + completion_tokens);
+
+    OpenAIUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens,
+        prompt_tokens_details: cached.map(|ct| PromptTokensDetails {
+            cached_tokens: Some(ct),
+        }),
+        completion_tokens_details: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proxy::config::DEFAULT_MAX_INLINE_IMAGE_BYTES;
     use serde_json::json;
 
+    #[test]
+    fn test_gemini_response_to_openai_chat_completion_fixture() {
+        let raw: Value = serde_json::from_str(include_str!(
+            "../../tests/fixtures/gemini_generate_content_response.json"
+        ))
+        .unwrap();
+
+        let completion =
+            gemini_response_to_openai_chat_completion(&raw, "gpt-4o", "req_42", DEFAULT_MAX_INLINE_IMAGE_BYTES);
+        let body = serde_json::to_value(&completion).unwrap();
+
+        assert_eq!(body["id"], "chatcmpl-req_42");
+        assert_eq!(body["object"], "chat.completion");
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["choices"][0]["index"], 0);
+        assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+        assert_eq!(body["choices"][0]["message"]["content"], "The capital of France is Paris.");
+        assert_eq!(body["choices"][0]["finish_reason"], "stop");
+        assert_eq!(body["usage"]["prompt_tokens"], 8);
+        assert_eq!(body["usage"]["completion_tokens"], 7);
+        assert_eq!(body["usage"]["total_tokens"], 15);
+    }
+
+    fn keys(v: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = v.as_object().unwrap().keys().map(|k| k.as_str()).collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn test_chat_completion_matches_openai_schema() {
+        let raw = json!({
+            "candidates": [{
+                "content": {"parts": [{"functionCall": {"name": "get_weather", "args": {"city": "Paris"}}}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 5, "candidatesTokenCount": 2, "totalTokenCount": 7}
+        });
+        let body = serde_json::to_value(gemini_response_to_openai_chat_completion(
+            &raw,
+            "gpt-4o",
+            "req_1",
+            DEFAULT_MAX_INLINE_IMAGE_BYTES,
+        ))
+        .unwrap();
+
+        assert_eq!(keys(&body), ["choices", "created", "id", "model", "object", "system_fingerprint", "usage"]);
+        assert!(body["created"].is_u64());
+
+        let choice = &body["choices"][0];
+        assert_eq!(keys(choice), ["finish_reason", "index", "logprobs", "message"]);
+        assert!(choice["logprobs"].is_null());
+
+        let message = &choice["message"];
+        assert_eq!(keys(message), ["content", "role", "tool_calls"]);
+        assert_eq!(message["role"], "assistant");
+        assert!(message["content"].is_null());
+        let call = &message["tool_calls"][0];
+        assert_eq!(keys(call), ["function", "id", "type"]);
+        assert_eq!(call["type"], "function");
+        assert_eq!(keys(&call["function"]), ["arguments", "name"]);
+        assert!(call["function"]["arguments"].is_string());
+
+        assert_eq!(keys(&body["usage"]), ["completion_tokens", "prompt_tokens", "total_tokens"]);
+
+        // 官方客户端按同一结构反序列化
+        let parsed: OpenAIResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(parsed.choices[0].message.tool_calls.as_ref().unwrap()[0].function.name, "get_weather");
+    }
+
+    #[test]
+    fn test_chat_completion_always_has_usage() {
+        let raw = json!({"candidates": [{"content": {"parts": [{"text": "hi"}]}, "finishReason": "STOP"}]});
+        let body = serde_json::to_value(gemini_response_to_openai_chat_completion(
+            &raw,
+            "m",
+            "1",
+            DEFAULT_MAX_INLINE_IMAGE_BYTES,
+        ))
+        .unwrap();
+        assert_eq!(body["usage"]["total_tokens"], 0);
+    }
+
     #[test]
     fn test_transform_openai_response() {
         let gemini_resp = json!({
@@ -231,7 +420,7 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, DEFAULT_MAX_INLINE_IMAGE_BYTES);
         assert_eq!(result.object, "chat.completion");
         let content = match result.choices[0].message.content.as_ref().unwrap() {
             OpenAIContent::String(s) => s,
@@ -239,6 +428,63 @@ mod tests {
         };
         assert_eq!(content, "Hello!");
         assert_eq!(result.choices[0].finish_reason, Some("stop".to_string()));
+        assert_eq!(result.system_fingerprint.as_deref(), Some(SYSTEM_FINGERPRINT));
+    }
+
+    #[test]
+    fn test_grounding_metadata_appended_to_content() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Rust 1.80 was released."}]},
+                "groundingMetadata": {
+                    "webSearchQueries": ["latest rust release"],
+                    "groundingChunks": [{"web": {"title": "Rust Blog", "uri": "https://blog.rust-lang.org"}}]
+                },
+                "finishReason": "STOP"
+            }]
+        });
+
+        let result = transform_openai_response(&gemini_resp, DEFAULT_MAX_INLINE_IMAGE_BYTES);
+        let content = match result.choices[0].message.content.as_ref().unwrap() {
+            OpenAIContent::String(s) => s,
+            _ => panic!("Expected string content"),
+        };
+        assert!(content.starts_with("Rust 1.80 was released."));
+        assert!(content.contains("latest rust release"));
+        assert!(content.contains("[Rust Blog](https://blog.rust-lang.org)"));
+    }
+
+    #[test]
+    fn test_code_execution_parts_become_fenced_blocks() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [
+                    {"text": "Let me compute it."},
+                    {"executableCode": {"language": "PYTHON", "code": "print(6 * 7)"}},
+                    {"codeExecutionResult": {"outcome": "OUTCOME_OK", "output": "42\n"}},
+                    {"text": "The answer is 42."}
+                ]},
+                "finishReason": "STOP"
+            }]
+        });
+
+        let result = transform_openai_response(&gemini_resp, DEFAULT_MAX_INLINE_IMAGE_BYTES);
+        let content = match result.choices[0].message.content.as_ref().unwrap() {
+            OpenAIContent::String(s) => s,
+            _ => panic!("Expected string content"),
+        };
+        assert_eq!(
+            content,
+            "Let me compute it.\n```python\nprint(6 * 7)\n```\n\n```output\n42\n```\nThe answer is 42."
+        );
+    }
+
+    #[test]
+    fn test_code_execution_failure_includes_outcome() {
+        let part = json!({"codeExecutionResult": {"outcome": "OUTCOME_FAILED", "output": "ZeroDivisionError"}});
+        let block = code_execution_part_to_markdown(&part).unwrap();
+        assert!(block.contains("OUTCOME_FAILED\nZeroDivisionError"));
+        assert!(code_execution_part_to_markdown(&json!({"text": "hi"})).is_none());
     }
 
     #[test]
@@ -258,7 +504,7 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, DEFAULT_MAX_INLINE_IMAGE_BYTES);
 
         assert!(result.usage.is_some());
         let usage = result.usage.unwrap();
@@ -280,7 +526,50 @@ mod tests {
             "responseId": "resp_123"
         });
 
-        let result = transform_openai_response(&gemini_resp);
+        let result = transform_openai_response(&gemini_resp, DEFAULT_MAX_INLINE_IMAGE_BYTES);
         assert!(result.usage.is_none());
     }
+
+    #[test]
+    fn test_usage_total_falls_back_to_sum() {
+        let usage = usage_from_gemini_metadata(&json!({
+            "promptTokenCount": 12,
+            "candidatesTokenCount": 30
+        }));
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 30);
+        assert_eq!(usage.total_tokens, 42);
+        assert!(usage.prompt_tokens_details.is_none());
+    }
+
+    #[test]
+    fn test_oversized_inline_image_replaced_with_placeholder() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{"inlineData": {"mimeType": "image/png", "data": "A".repeat(400)}}]},
+                "finishReason": "STOP"
+            }]
+        });
+
+        let small = transform_openai_response(&gemini_resp, 1024);
+        let content = small.choices[0].message.content.as_ref().unwrap();
+        assert!(matches!(content, OpenAIContent::String(s) if s.starts_with("![image](data:image/png;base64,AAAA")));
+
+        let large = transform_openai_response(&gemini_resp, 100);
+        let content = large.choices[0].message.content.as_ref().unwrap();
+        assert!(matches!(content, OpenAIContent::String(s) if s == "[Image too large to display: estimated 300 bytes]"));
+    }
+
+    #[test]
+    fn test_multiple_image_parts_each_on_own_line() {
+        let raw: Value = serde_json::from_str(include_str!(
+            "../../tests/fixtures/gemini_multi_image_response.json"
+        ))
+        .unwrap();
+        let result = transform_openai_response(&raw, DEFAULT_MAX_INLINE_IMAGE_BYTES);
+        let Some(OpenAIContent::String(content)) = &result.choices[0].message.content else {
+            panic!("expected text content");
+        };
+        assert_eq!(content.lines().filter(|l| l.starts_with("![image](data:")).count(), 2);
+    }
 }
@@ -1,4 +1,5 @@
 // OpenAI 流式转换
+use crate::proxy::mappers::gemini::models::GeminiStreamChunk;
 use bytes::{Bytes, BytesMut};
 use chrono::Utc;
 use futures::{Stream, StreamExt};
@@ -11,6 +12,9 @@ use uuid::Uuid;
 
 // === 全局 ThoughtSignature 存储 ===
 // 用于在流式响应和后续请求之间传递签名，避免嵌入到用户可见的文本中
+/// SSE 心跳注释：客户端按规范忽略注释行，不会产生 data chunk
+pub const KEEPALIVE_COMMENT: &str = ": keepalive\n\n";
+
 static GLOBAL_THOUGHT_SIG: OnceLock<Mutex<Option<String>>> = OnceLock::new();
 
 fn get_thought_sig_storage() -> &'static Mutex<Option<String>> {
@@ -53,41 +57,14 @@ pub fn get_thought_signature() -> Option<String> {
     }
 }
 
-/// Extract and convert Gemini usageMetadata to OpenAI usage format
-fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
-    use super::models::{OpenAIUsage, PromptTokensDetails};
-
-    let prompt_tokens = u
-        .get("promptTokenCount")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as u32;
-    let completion_tokens = u
-        .get("candidatesTokenCount")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as u32;
-    let total_tokens = u
-        .get("totalTokenCount")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as u32;
-    let cached_tokens = u
-        .get("cachedContentTokenCount")
-        .and_then(|v| v.as_u64())
-        .map(|v| v as u32);
-
-    Some(OpenAIUsage {
-        prompt_tokens,
-        completion_tokens,
-        total_tokens,
-        prompt_tokens_details: cached_tokens.map(|ct| PromptTokensDetails {
-            cached_tokens: Some(ct),
-        }),
-        completion_tokens_details: None,
-    })
-}
-
+/// `include_usage`: 对应 OpenAI `stream_options.include_usage`，在 [DONE] 前追加一个
+/// `choices` 为空、仅携带 usage 的 chunk
 pub fn create_openai_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    include_usage: bool,
+    max_inline_image_bytes: usize,
+    keepalive_interval: std::time::Duration,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
 
@@ -97,11 +74,14 @@ pub fn create_openai_sse_stream(
 
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        // 每个候选 (candidate) 各自的 tool_calls 序号，OpenAI 客户端按 index 聚合增量
+        let mut tool_call_indices: std::collections::HashMap<usize, u32> = std::collections::HashMap::new();
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        let mut reported_usage: Option<super::models::OpenAIUsage> = None; // include_usage 时用于末尾 usage chunk
         let mut error_occurred = false;  // [FIX] 标志位,避免双重 [DONE]
 
         // [P2 FIX] 添加心跳定时器
-        let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
+        let mut heartbeat_interval = tokio::time::interval(keepalive_interval);
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
@@ -127,212 +107,209 @@ pub fn create_openai_sse_stream(
                                     continue;
                                 }
 
-                                if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
+                                if let Ok(chunk) = GeminiStreamChunk::parse(json_part) {
                                     // Log raw chunk for debugging gemini-3 thoughts
                                     tracing::debug!("Gemini SSE Chunk: {}", json_part);
 
-                                    // Handle v1internal wrapper if present
-                                    let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) {
-                                        inner
-                                    } else {
-                                        json
-                                    };
-
                                     // Capture usageMetadata if present
-                                    if let Some(u) = actual_data.get("usageMetadata") {
-                                        final_usage = extract_usage_metadata(u);
+                                    if let Some(u) = &chunk.usage_metadata {
+                                        final_usage = Some(super::response::usage_from_usage_metadata(u));
+                                        reported_usage = final_usage.clone();
                                     }
 
                                     // Extract candidates
-                                    if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                        for (idx, candidate) in candidates.iter().enumerate() {
-                                            let parts = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
-
-                                            let mut content_out = String::new();
-                                            let mut thought_out = String::new();
-
-                                            if let Some(parts_list) = parts {
-                                                for part in parts_list {
-                                                    let is_thought_part = part.get("thought")
-                                                        .and_then(|v| v.as_bool())
-                                                        .unwrap_or(false);
-
-                                                    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                        if is_thought_part {
-                                                            thought_out.push_str(text);
-                                                        } else {
-                                                            content_out.push_str(text);
-                                                        }
-                                                    }
-                                                    // 捕获 thoughtSignature (Gemini 3 工具调用必需)
-                                                    if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
-                                                        store_thought_signature(sig);
-                                                    }
+                                    for (idx, candidate) in chunk.candidates.iter().enumerate() {
+                                        let mut content_out = String::new();
+                                        let mut thought_out = String::new();
 
-                                                    if let Some(img) = part.get("inlineData") {
-                                                        let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
-                                                        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                                        if !data.is_empty() {
-                                                            content_out.push_str(&format!("![image](data:{};base64,{})", mime_type, data));
-                                                        }
-                                                    }
+                                        for part in &candidate.content.parts {
+                                            let is_thought_part = part.thought.unwrap_or(false);
 
-                                                    // Handle function call
-                                                    if let Some(func_call) = part.get("functionCall") {
-                                                        let call_key = serde_json::to_string(func_call).unwrap_or_default();
-                                                        if !emitted_tool_calls.contains(&call_key) {
-                                                            emitted_tool_calls.insert(call_key);
-
-                                                            let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
-                                                            let args = func_call.get("args").unwrap_or(&json!({})).to_string();
-
-                                                            // Generate stable ID
-                                                            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                                                            use std::hash::{Hash, Hasher};
-                                                            serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
-                                                            let call_id = format!("call_{:x}", hasher.finish());
-
-                                                            // Emit tool_calls delta
-                                                            let tool_call_chunk = json!({
-                                                                "id": &stream_id,
-                                                                "object": "chat.completion.chunk",
-                                                                "created": created_ts,
-                                                                "model": &model,
-                                                                "choices": [{
-                                                                    "index": idx as u32,
-                                                                    "delta": {
-                                                                        "role": "assistant",
-                                                                        "tool_calls": [{
-                                                                            "index": 0,
-                                                                            "id": call_id,
-                                                                            "type": "function",
-                                                                            "function": {
-                                                                                "name": name,
-                                                                                "arguments": args
-                                                                            }
-                                                                        }]
-                                                                    },
-                                                                    "finish_reason": serde_json::Value::Null
-                                                                }]
-                                                            });
-
-                                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
-                                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
-                                                        }
-                                                    }
+                                            if let Some(text) = &part.text {
+                                                if is_thought_part {
+                                                    thought_out.push_str(text);
+                                                } else {
+                                                    content_out.push_str(text);
                                                 }
                                             }
+                                            // 捕获 thoughtSignature (Gemini 3 工具调用必需)
+                                            if let Some(sig) = &part.thought_signature {
+                                                store_thought_signature(sig);
+                                            }
 
-
-                                            // 处理联网搜索引文 (Grounding Metadata) - 流式
-                                            if let Some(grounding) = candidate.get("groundingMetadata") {
-                                                let mut grounding_text = String::new();
-
-                                                // 1. 处理搜索词
-                                                if let Some(queries) = grounding.get("webSearchQueries").and_then(|q| q.as_array()) {
-                                                    let query_list: Vec<&str> = queries.iter().filter_map(|v| v.as_str()).collect();
-                                                    if !query_list.is_empty() {
-                                                        grounding_text.push_str("\n\n---\n**🔍 已为您搜索：** ");
-                                                        grounding_text.push_str(&query_list.join(", "));
-                                                    }
-                                                }
-
-                                                // 2. 处理来源链接 (Chunks)
-                                                if let Some(chunks) = grounding.get("groundingChunks").and_then(|c| c.as_array()) {
-                                                    let mut links = Vec::new();
-                                                    for (i, chunk) in chunks.iter().enumerate() {
-                                                        if let Some(web) = chunk.get("web") {
-                                                            let title = web.get("title").and_then(|v| v.as_str()).unwrap_or("网页来源");
-                                                            let uri = web.get("uri").and_then(|v| v.as_str()).unwrap_or("#");
-                                                            links.push(format!("[{}] [{}]({})", i + 1, title, uri));
-                                                        }
-                                                    }
-                                                    if !links.is_empty() {
-                                                        grounding_text.push_str("\n\n**🌐 来源引文：**\n");
-                                                        grounding_text.push_str(&links.join("\n"));
-                                                    }
-                                                }
-
-                                                if !grounding_text.is_empty() {
-                                                    content_out.push_str(&grounding_text);
+                                            // 代码执行结果转为围栏代码块
+                                            if part.executable_code.is_some() || part.code_execution_result.is_some() {
+                                                let raw_part = serde_json::to_value(part).unwrap_or_default();
+                                                if let Some(block) = super::response::code_execution_part_to_markdown(&raw_part) {
+                                                    content_out.push_str(&block);
                                                 }
                                             }
 
-                                            // 只有当 content 和 thought 都为空时才跳过
-                                            if content_out.is_empty() && thought_out.is_empty() {
-                                                // Skip empty chunks if no text/grounding/thought was found
-                                                if candidate.get("finishReason").is_none() {
-                                                    continue;
-                                                }
+                                            if let Some(img) = &part.inline_data {
+                                                super::response::push_image_markdown(
+                                                    &mut content_out,
+                                                    &super::response::typed_inline_data_to_markdown(img, max_inline_image_bytes),
+                                                );
                                             }
 
-                                            // Extract finish reason
-                                            let finish_reason = candidate.get("finishReason")
-                                                .and_then(|f| f.as_str())
-                                                .map(|f| match f {
-                                                    "STOP" => "stop",
-                                                    "MAX_TOKENS" => "length",
-                                                    "SAFETY" => "content_filter",
-                                                    "RECITATION" => "content_filter",
-                                                    _ => f,
-                                                });
-
-                                            // Construct OpenAI SSE chunk
-                                            // 如果有思考内容，先发送 reasoning_content chunk
-                                            if !thought_out.is_empty() {
-                                                let reasoning_chunk = json!({
-                                                    "id": &stream_id,
-                                                    "object": "chat.completion.chunk",
-                                                    "created": created_ts,
-                                                    "model": model,
-                                                    "choices": [
-                                                        {
+                                            // Handle function call
+                                            if let Some(func_call) = &part.function_call {
+                                                let call_key = serde_json::to_string(func_call).unwrap_or_default();
+                                                if !emitted_tool_calls.contains(&call_key) {
+                                                    emitted_tool_calls.insert(call_key.clone());
+
+                                                    let name = func_call.name.as_str();
+                                                    let args = func_call.args.as_ref().unwrap_or(&json!({})).to_string();
+
+                                                    // Generate stable ID
+                                                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                                                    use std::hash::{Hash, Hasher};
+                                                    call_key.hash(&mut hasher);
+                                                    let call_id = format!("call_{:x}", hasher.finish());
+
+                                                    let call_index = tool_call_indices.entry(idx).or_insert(0);
+                                                    let tool_call_delta = super::models::ToolCallDelta::from_complete_call(
+                                                        *call_index,
+                                                        call_id,
+                                                        name.to_string(),
+                                                        args,
+                                                    );
+                                                    *call_index += 1;
+
+                                                    // Emit tool_calls delta
+                                                    let tool_call_chunk = json!({
+                                                        "id": &stream_id,
+                                                        "object": "chat.completion.chunk",
+                                                        "created": created_ts,
+                                                        "model": &model,
+                                                        "system_fingerprint": super::models::SYSTEM_FINGERPRINT,
+                                                        "choices": [{
                                                             "index": idx as u32,
                                                             "delta": {
                                                                 "role": "assistant",
-                                                                "content": serde_json::Value::Null,
-                                                                "reasoning_content": thought_out
+                                                                "tool_calls": [tool_call_delta]
                                                             },
                                                             "finish_reason": serde_json::Value::Null
-                                                        }
-                                                    ]
-                                                });
-                                                let sse_out = format!("data: {}\n\n", serde_json::to_string(&reasoning_chunk).unwrap_or_default());
-                                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                                        }]
+                                                    });
+
+                                                    let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
+                                                    yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                                }
                                             }
+                                        }
 
-                                            // 发送正常 content chunk
-                                            if !content_out.is_empty() || finish_reason.is_some() {
-                                                let mut openai_chunk = json!({
-                                                    "id": &stream_id,
-                                                    "object": "chat.completion.chunk",
-                                                    "created": created_ts,
-                                                    "model": model,
-                                                    "choices": [
-                                                        {
-                                                            "index": idx as u32,
-                                                            "delta": {
-                                                                "content": content_out
-                                                            },
-                                                            "finish_reason": finish_reason
-                                                        }
-                                                    ]
-                                                });
 
-                                                // [FIX] 将 usage 嵌入到 chunk 中
-                                                if let Some(ref usage) = final_usage {
-                                                    openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
+                                        // 处理联网搜索引文 (Grounding Metadata) - 流式
+                                        if let Some(grounding) = &candidate.grounding_metadata {
+                                            let mut grounding_text = String::new();
+
+                                            // 1. 处理搜索词
+                                            if let Some(queries) = &grounding.web_search_queries {
+                                                if !queries.is_empty() {
+                                                    grounding_text.push_str("\n\n---\n**🔍 已为您搜索：** ");
+                                                    grounding_text.push_str(&queries.join(", "));
                                                 }
+                                            }
 
-                                                // [FIX] 如果是最后一个 chunk,标记 usage 已发送
-                                                if finish_reason.is_some() {
-                                                    final_usage = None;
+                                            // 2. 处理来源链接 (Chunks)
+                                            if let Some(chunks) = &grounding.grounding_chunks {
+                                                let mut links = Vec::new();
+                                                for (i, chunk) in chunks.iter().enumerate() {
+                                                    if let Some(web) = &chunk.web {
+                                                        let title = web.title.as_deref().unwrap_or("网页来源");
+                                                        let uri = web.uri.as_deref().unwrap_or("#");
+                                                        links.push(format!("[{}] [{}]({})", i + 1, title, uri));
+                                                    }
                                                 }
+                                                if !links.is_empty() {
+                                                    grounding_text.push_str("\n\n**🌐 来源引文：**\n");
+                                                    grounding_text.push_str(&links.join("\n"));
+                                                }
+                                            }
 
-                                                let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
-                                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                            if !grounding_text.is_empty() {
+                                                content_out.push_str(&grounding_text);
                                             }
                                         }
+
+                                        // 只有当 content 和 thought 都为空时才跳过
+                                        if content_out.is_empty() && thought_out.is_empty() {
+                                            // Skip empty chunks if no text/grounding/thought was found
+                                            if candidate.finish_reason.is_none() {
+                                                continue;
+                                            }
+                                        }
+
+                                        // Extract finish reason
+                                        let finish_reason = candidate.finish_reason
+                                            .as_deref()
+                                            .map(|f| match f {
+                                                "STOP" if tool_call_indices.contains_key(&idx) => "tool_calls",
+                                                "STOP" => "stop",
+                                                "MAX_TOKENS" => "length",
+                                                "SAFETY" => "content_filter",
+                                                "RECITATION" => "content_filter",
+                                                _ => f,
+                                            });
+
+                                        // Construct OpenAI SSE chunk
+                                        // 如果有思考内容，先发送 reasoning_content chunk
+                                        if !thought_out.is_empty() {
+                                            let reasoning_chunk = json!({
+                                                "id": &stream_id,
+                                                "object": "chat.completion.chunk",
+                                                "created": created_ts,
+                                                "model": model,
+                                                "system_fingerprint": super::models::SYSTEM_FINGERPRINT,
+                                                "choices": [
+                                                    {
+                                                        "index": idx as u32,
+                                                        "delta": {
+                                                            "role": "assistant",
+                                                            "content": serde_json::Value::Null,
+                                                            "reasoning_content": thought_out
+                                                        },
+                                                        "finish_reason": serde_json::Value::Null
+                                                    }
+                                                ]
+                                            });
+                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&reasoning_chunk).unwrap_or_default());
+                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                        }
+
+                                        // 发送正常 content chunk
+                                        if !content_out.is_empty() || finish_reason.is_some() {
+                                            let mut openai_chunk = json!({
+                                                "id": &stream_id,
+                                                "object": "chat.completion.chunk",
+                                                "created": created_ts,
+                                                "model": model,
+                                                "system_fingerprint": super::models::SYSTEM_FINGERPRINT,
+                                                "choices": [
+                                                    {
+                                                        "index": idx as u32,
+                                                        "delta": {
+                                                            "content": content_out
+                                                        },
+                                                        "finish_reason": finish_reason
+                                                    }
+                                                ]
+                                            });
+
+                                            // [FIX] 将 usage 嵌入到 chunk 中
+                                            if let Some(ref usage) = final_usage {
+                                                openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
+                                            }
+
+                                            // [FIX] 如果是最后一个 chunk,标记 usage 已发送
+                                            if finish_reason.is_some() {
+                                                final_usage = None;
+                                            }
+
+                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
+                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                        }
                                     }
                                 }
                             }
@@ -357,6 +334,7 @@ pub fn create_openai_sse_stream(
                                 "object": "chat.completion.chunk",
                                 "created": created_ts,
                                 "model": &model,
+                                "system_fingerprint": super::models::SYSTEM_FINGERPRINT,
                                 "choices": [],
                                 "error": {
                                     "type": error_type,
@@ -382,14 +360,29 @@ pub fn create_openai_sse_stream(
                 // [P2 FIX] 发送心跳
                 _ = heartbeat_interval.tick() => {
                     // 发送 SSE 注释作为心跳
-                    yield Ok::<Bytes, String>(Bytes::from(": ping\n\n"));
+                    yield Ok::<Bytes, String>(Bytes::from(KEEPALIVE_COMMENT));
                 }
             }
         }
 
         // [FIX] 只有在没有错误时才发送 [DONE]
-        // usage 已经嵌入到 finish_reason chunk,不需要单独发送
+        // usage 默认嵌入到 finish_reason chunk; include_usage 时额外发送独立的 usage chunk
+        // (OpenAI 约定该 chunk 必定出现，上游未返回 usageMetadata 时以 0 填充)
         if !error_occurred {
+            if include_usage {
+                let usage = reported_usage.take().unwrap_or_default();
+                let usage_chunk = json!({
+                    "id": &stream_id,
+                    "object": "chat.completion.chunk",
+                    "created": created_ts,
+                    "model": &model,
+                    "system_fingerprint": super::models::SYSTEM_FINGERPRINT,
+                    "choices": [],
+                    "usage": usage
+                });
+                let sse_out = format!("data: {}\n\n", serde_json::to_string(&usage_chunk).unwrap_or_default());
+                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+            }
             yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
         }
     };
@@ -400,6 +393,7 @@ pub fn create_openai_sse_stream(
 pub fn create_legacy_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    keepalive_interval: std::time::Duration,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
 
@@ -420,7 +414,7 @@ pub fn create_legacy_sse_stream(
         let mut error_occurred = false;  // [FIX] 标志位,避免双重 [DONE]
 
         // [P2 FIX] 添加心跳定时器
-        let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
+        let mut heartbeat_interval = tokio::time::interval(keepalive_interval);
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
@@ -445,7 +439,7 @@ pub fn create_legacy_sse_stream(
 
                                     // Capture usageMetadata if present
                                     if let Some(u) = actual_data.get("usageMetadata") {
-                                        final_usage = extract_usage_metadata(u);
+                                        final_usage = Some(super::response::usage_from_gemini_metadata(u));
                                     }
 
                                     let mut content_out = String::new();
@@ -559,7 +553,7 @@ pub fn create_legacy_sse_stream(
                 // [P2 FIX] 发送心跳
                 _ = heartbeat_interval.tick() => {
                     // 发送 SSE 注释作为心跳
-                    yield Ok::<Bytes, String>(Bytes::from(": ping\n\n"));
+                    yield Ok::<Bytes, String>(Bytes::from(KEEPALIVE_COMMENT));
                 }
             }
         }
@@ -580,6 +574,7 @@ pub fn create_legacy_sse_stream(
 pub fn create_codex_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     _model: String,
+    keepalive_interval: std::time::Duration,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
 
@@ -611,14 +606,14 @@ pub fn create_codex_sse_stream(
         let mut accumulated_usage: Option<super::models::OpenAIUsage> = None;
 
         // [P2 FIX] Add heartbeat interval for Codex stream
-        let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
+        let mut heartbeat_interval = tokio::time::interval(keepalive_interval);
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
             tokio::select! {
                 // Heartbeat
                 _ = heartbeat_interval.tick() => {
-                    yield Ok::<Bytes, String>(Bytes::from(": ping\n\n"));
+                    yield Ok::<Bytes, String>(Bytes::from(KEEPALIVE_COMMENT));
                 }
 
                 // Upstream data
@@ -640,7 +635,7 @@ pub fn create_codex_sse_stream(
 
                                         // Capture usageMetadata if present
                                         if let Some(u) = actual_data.get("usageMetadata") {
-                                            accumulated_usage = extract_usage_metadata(u);
+                                            accumulated_usage = Some(super::response::usage_from_gemini_metadata(u));
                                         }
 
                                         // Capture finish reason
@@ -847,3 +842,132 @@ pub fn create_codex_sse_stream(
 
     Box::pin(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect_chunks(include_usage: bool) -> Vec<Value> {
+        let sse = concat!(
+            "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hi\"}]}}]}}\n\n",
+            "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"!\"}]},\"finishReason\":\"STOP\"}],",
+            "\"usageMetadata\":{\"promptTokenCount\":7,\"candidatesTokenCount\":3,\"totalTokenCount\":10}}}\n\n"
+        );
+        collect_sse(sse, include_usage).await
+    }
+
+    async fn collect_sse(sse: &str, include_usage: bool) -> Vec<Value> {
+        let upstream = futures::stream::iter(vec![Ok::<Bytes, reqwest::Error>(Bytes::from(sse.to_string()))]);
+        let stream = create_openai_sse_stream(
+            Box::pin(upstream),
+            "gemini-2.5-flash".to_string(),
+            include_usage,
+            crate::proxy::config::DEFAULT_MAX_INLINE_IMAGE_BYTES,
+            std::time::Duration::from_secs(30),
+        );
+        let frames: Vec<_> = stream.collect().await;
+
+        frames
+            .into_iter()
+            .map(|f| String::from_utf8(f.unwrap().to_vec()).unwrap())
+            .filter_map(|f| f.strip_prefix("data: ").map(|d| d.trim().to_string()))
+            .filter(|d| d != "[DONE]")
+            .map(|d| serde_json::from_str(&d).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_usage_embedded_in_finish_chunk() {
+        let chunks = collect_chunks(false).await;
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "stop");
+        assert_eq!(last["usage"]["prompt_tokens"], 7);
+        assert_eq!(last["usage"]["completion_tokens"], 3);
+        assert_eq!(last["usage"]["total_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_include_usage_appends_usage_chunk() {
+        let chunks = collect_chunks(true).await;
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"].as_array().unwrap().len(), 0);
+        assert_eq!(last["usage"]["prompt_tokens"], 7);
+        assert_eq!(last["usage"]["completion_tokens"], 3);
+        assert_eq!(last["usage"]["total_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_include_usage_chunk_sent_without_usage_metadata() {
+        let sse = "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hi\"}]},\"finishReason\":\"STOP\"}]}}\n\n";
+        let chunks = collect_sse(sse, true).await;
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"].as_array().unwrap().len(), 0);
+        assert_eq!(last["usage"]["total_tokens"], 0);
+        assert_eq!(chunks[chunks.len() - 2]["choices"][0]["finish_reason"], "stop");
+    }
+
+    #[tokio::test]
+    async fn test_all_image_parts_sent_in_one_chunk() {
+        let raw: Value = serde_json::from_str(include_str!(
+            "../../tests/fixtures/gemini_multi_image_response.json"
+        ))
+        .unwrap();
+        let chunks = collect_sse(&format!("data: {}\n\n", raw), false).await;
+
+        let content_chunks: Vec<&Value> = chunks
+            .iter()
+            .filter(|c| c["choices"][0]["delta"]["content"].is_string())
+            .collect();
+        assert_eq!(content_chunks.len(), 1);
+        assert_eq!(
+            content_chunks[0]["choices"][0]["delta"]["content"],
+            "Here are two variations:\n\
+             ![image](data:image/png;base64,iVBORw0KGgo=)\n\
+             ![image](data:image/jpeg;base64,/9j/4AAQSkZJRg==)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunk_fixture_mapped() {
+        let chunks = collect_sse(include_str!("../../tests/fixtures/gemini_stream_chunks.sse"), false).await;
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0]["choices"][0]["delta"]["reasoning_content"], "Thinking about the weather...");
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "The answer");
+
+        let call = &chunks[2]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], "get_weather");
+        assert_eq!(call["function"]["arguments"], "{\"city\":\"Paris\"}");
+
+        assert_eq!(chunks[3]["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunks[3]["usage"]["total_tokens"], 42);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_sent_during_upstream_stall() {
+        let upstream = async_stream::stream! {
+            yield Ok::<Bytes, reqwest::Error>(Bytes::from(
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]}}]}\n\n",
+            ));
+            // 上游停顿 35 秒后继续输出
+            tokio::time::sleep(std::time::Duration::from_secs(35)).await;
+            yield Ok(Bytes::from(
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"lo\"}]},\"finishReason\":\"STOP\"}]}\n\n",
+            ));
+        };
+        let frames: Vec<String> = create_openai_sse_stream(
+            Box::pin(upstream),
+            "gemini-2.5-flash".to_string(),
+            false,
+            crate::proxy::config::DEFAULT_MAX_INLINE_IMAGE_BYTES,
+            std::time::Duration::from_secs(30),
+        )
+        .map(|f| String::from_utf8(f.unwrap().to_vec()).unwrap())
+        .collect()
+        .await;
+
+        let first = frames.iter().position(|f| f.contains("Hel")).unwrap();
+        let second = frames.iter().position(|f| f.contains("lo\"")).unwrap();
+        assert!(frames[first..second].iter().any(|f| f == KEEPALIVE_COMMENT));
+        assert_eq!(frames.last().unwrap(), "data: [DONE]\n\n");
+    }
+}
@@ -0,0 +1,190 @@
+// 思考内容输出方式 - Gemini thought: true 的 part 被映射为 reasoning_content，
+// 按 thinking_mode 原样保留、丢弃，或从响应体移出 (改由 x-thinking-tokens 响应头返回)
+use super::models::OpenAIResponse;
+use crate::proxy::config::ThinkingMode;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+use std::pin::Pin;
+
+type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 携带思考内容的响应头 (JSON 编码的字符串数组，每个候选一项)
+pub const THINKING_HEADER: &str = "x-thinking-tokens";
+
+/// 处理非流式响应中的思考内容，SeparateField 模式下返回移出的思考文本
+pub fn apply_thinking_mode(response: &mut OpenAIResponse, mode: &ThinkingMode) -> Vec<String> {
+    if *mode == ThinkingMode::Passthrough {
+        return Vec::new();
+    }
+    let thoughts: Vec<String> = response
+        .choices
+        .iter_mut()
+        .filter_map(|choice| choice.message.reasoning_content.take())
+        .filter(|thought| !thought.is_empty())
+        .collect();
+    match mode {
+        ThinkingMode::SeparateField => thoughts,
+        _ => Vec::new(),
+    }
+}
+
+/// x-thinking-tokens 响应头取值：JSON 数组，非 ASCII 字符转义为 \uXXXX (响应头仅允许可见 ASCII)
+pub fn thinking_header_value(thoughts: &[String]) -> String {
+    let json = serde_json::to_string(thoughts).unwrap_or_default();
+    let mut out = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                out.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    out
+}
+
+/// 流式响应无法在发送后追加响应头，Strip / SeparateField 均从 SSE 中移除思考内容
+pub fn strip_thinking_from_sse(inner: SseStream, mode: &ThinkingMode) -> SseStream {
+    if *mode == ThinkingMode::Passthrough {
+        return inner;
+    }
+    Box::pin(inner.filter_map(|item| async move {
+        match item {
+            Ok(bytes) => strip_frame(&bytes).map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    }))
+}
+
+/// 移除一帧 SSE 中的 reasoning_content；只含思考内容的事件整体丢弃
+fn strip_frame(bytes: &Bytes) -> Option<Bytes> {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return Some(bytes.clone());
+    };
+    if !text.contains("\"reasoning_content\"") {
+        return Some(bytes.clone());
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for event in text.split_inclusive("\n\n") {
+        let Some(data) = event.trim_end().strip_prefix("data: ") else {
+            out.push_str(event);
+            continue;
+        };
+        let Ok(mut chunk) = serde_json::from_str::<Value>(data) else {
+            out.push_str(event);
+            continue;
+        };
+        if strip_chunk(&mut chunk) {
+            out.push_str(&format!(
+                "data: {}\n\n",
+                serde_json::to_string(&chunk).unwrap_or_default()
+            ));
+        }
+    }
+    (!out.is_empty()).then(|| Bytes::from(out))
+}
+
+/// 返回移除思考内容后该 chunk 是否仍需发送
+fn strip_chunk(chunk: &mut Value) -> bool {
+    let mut has_payload = chunk.get("usage").is_some_and(|u| !u.is_null());
+    let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) else {
+        return true;
+    };
+    for choice in choices.iter_mut() {
+        if let Some(delta) = choice.get_mut("delta").and_then(|d| d.as_object_mut()) {
+            delta.remove("reasoning_content");
+            has_payload |= delta.iter().any(|(k, v)| k != "role" && !v.is_null());
+        }
+        has_payload |= choice.get("finish_reason").is_some_and(|f| !f.is_null());
+    }
+    has_payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response_with_thought() -> OpenAIResponse {
+        serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gemini-2.5-flash-thinking",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "42", "reasoning_content": "6 * 7"},
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn frame(chunk: Value) -> Bytes {
+        Bytes::from(format!("data: {}\n\n", chunk))
+    }
+
+    async fn collect(mode: ThinkingMode) -> Vec<String> {
+        let frames: Vec<Result<Bytes, String>> = vec![
+            Ok(frame(json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": null, "reasoning_content": "6 * 7"}, "finish_reason": null}]}))),
+            Ok(frame(json!({"choices": [{"index": 0, "delta": {"content": "42"}, "finish_reason": "stop"}]}))),
+            Ok(Bytes::from("data: [DONE]\n\n")),
+        ];
+        strip_thinking_from_sse(Box::pin(futures::stream::iter(frames)), &mode)
+            .map(|b| String::from_utf8(b.unwrap().to_vec()).unwrap())
+            .collect()
+            .await
+    }
+
+    #[test]
+    fn test_passthrough_keeps_reasoning() {
+        let mut response = response_with_thought();
+        assert!(apply_thinking_mode(&mut response, &ThinkingMode::Passthrough).is_empty());
+        assert_eq!(response.choices[0].message.reasoning_content.as_deref(), Some("6 * 7"));
+    }
+
+    #[test]
+    fn test_strip_drops_reasoning() {
+        let mut response = response_with_thought();
+        assert!(apply_thinking_mode(&mut response, &ThinkingMode::Strip).is_empty());
+        assert_eq!(response.choices[0].message.reasoning_content, None);
+        assert!(!serde_json::to_string(&response).unwrap().contains("6 * 7"));
+    }
+
+    #[test]
+    fn test_separate_field_moves_reasoning_out() {
+        let mut response = response_with_thought();
+        let thoughts = apply_thinking_mode(&mut response, &ThinkingMode::SeparateField);
+        assert_eq!(thoughts, vec!["6 * 7".to_string()]);
+        assert_eq!(response.choices[0].message.reasoning_content, None);
+        assert_eq!(thinking_header_value(&thoughts), r#"["6 * 7"]"#);
+    }
+
+    #[test]
+    fn test_header_value_is_ascii() {
+        let thoughts = vec!["先算 6×7\n😀".to_string()];
+        let value = thinking_header_value(&thoughts);
+        assert!(value.is_ascii());
+        assert!(axum::http::HeaderValue::from_str(&value).is_ok());
+        assert_eq!(serde_json::from_str::<Vec<String>>(&value).unwrap(), thoughts);
+    }
+
+    #[tokio::test]
+    async fn test_stream_modes() {
+        let passthrough = collect(ThinkingMode::Passthrough).await;
+        assert_eq!(passthrough.len(), 3);
+        assert!(passthrough[0].contains("reasoning_content"));
+
+        for mode in [ThinkingMode::Strip, ThinkingMode::SeparateField] {
+            let stripped = collect(mode).await;
+            assert_eq!(stripped.len(), 2);
+            assert!(stripped.iter().all(|f| !f.contains("reasoning_content")));
+            assert!(stripped[0].contains("\"42\""));
+            assert_eq!(stripped[1], "data: [DONE]\n\n");
+        }
+    }
+}
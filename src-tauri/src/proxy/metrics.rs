@@ -0,0 +1,373 @@
+// Prometheus 指标 - 进程内计数器，由 GET /metrics 以文本格式导出
+use dashmap::DashMap;
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 请求耗时直方图的桶上界 (秒)
+const DURATION_BUCKETS: [f64; 11] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// 流式分块间隔直方图的桶上界 (秒)
+const CHUNK_INTERVAL_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+struct Histogram {
+    bounds: &'static [f64],
+    /// 各桶的非累计计数 (最后一个元素为 +Inf)
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, secs: f64) {
+        let idx = self
+            .bounds
+            .iter()
+            .position(|b| secs <= *b)
+            .unwrap_or(self.bounds.len());
+        self.buckets[idx] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+/// 按模型分组的直方图族
+struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    bounds: &'static [f64],
+    series: DashMap<String, Mutex<Histogram>>,
+}
+
+impl HistogramVec {
+    fn new(name: &'static str, help: &'static str, bounds: &'static [f64]) -> Self {
+        Self {
+            name,
+            help,
+            bounds,
+            series: DashMap::new(),
+        }
+    }
+
+    fn observe(&self, model: &str, duration: Duration) {
+        let entry = self
+            .series
+            .entry(model.to_string())
+            .or_insert_with(|| Mutex::new(Histogram::new(self.bounds)));
+        let locked = entry.lock();
+        if let Ok(mut hist) = locked {
+            hist.observe(duration.as_secs_f64());
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        let name = self.name;
+        let _ = writeln!(out, "# HELP {} {}", name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut models: Vec<String> = self.series.iter().map(|e| e.key().clone()).collect();
+        models.sort();
+        for model in models {
+            let Some(entry) = self.series.get(&model) else {
+                continue;
+            };
+            let Ok(hist) = entry.lock() else {
+                continue;
+            };
+            let label = escape_label(&model);
+            let mut cumulative = 0;
+            for (i, bound) in hist.bounds.iter().enumerate() {
+                cumulative += hist.buckets[i];
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{model=\"{}\",le=\"{}\"}} {}",
+                    name, label, bound, cumulative
+                );
+            }
+            let _ = writeln!(out, "{}_bucket{{model=\"{}\",le=\"+Inf\"}} {}", name, label, hist.count);
+            let _ = writeln!(out, "{}_sum{{model=\"{}\"}} {}", name, label, hist.sum);
+            let _ = writeln!(out, "{}_count{{model=\"{}\"}} {}", name, label, hist.count);
+        }
+    }
+}
+
+pub struct Metrics {
+    requests_total: DashMap<(String, u16), AtomicU64>,
+    token_errors_total: DashMap<(String, String), AtomicU64>,
+    request_duration: HistogramVec,
+    time_to_first_byte: HistogramVec,
+    stream_duration: HistogramVec,
+    stream_chunk_interval: HistogramVec,
+    active_tokens: AtomicI64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            requests_total: DashMap::new(),
+            token_errors_total: DashMap::new(),
+            request_duration: HistogramVec::new(
+                "antigravity_request_duration_seconds",
+                "Request latency in seconds.",
+                &DURATION_BUCKETS,
+            ),
+            time_to_first_byte: HistogramVec::new(
+                "antigravity_time_to_first_byte_seconds",
+                "Time from upstream request to the first response chunk.",
+                &DURATION_BUCKETS,
+            ),
+            stream_duration: HistogramVec::new(
+                "antigravity_stream_duration_seconds",
+                "Total duration of streaming responses, until the last chunk.",
+                &DURATION_BUCKETS,
+            ),
+            stream_chunk_interval: HistogramVec::new(
+                "antigravity_stream_chunk_interval_seconds",
+                "Interval between consecutive chunks of a streaming response.",
+                &CHUNK_INTERVAL_BUCKETS,
+            ),
+            active_tokens: AtomicI64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_request(&self, model: &str, status: u16, duration: Duration) {
+        self.requests_total
+            .entry((model.to_string(), status))
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+
+        self.request_duration.observe(model, duration);
+    }
+
+    /// 首字节耗时 (从发起上游请求到收到第一个数据块)
+    pub fn record_ttfb(&self, model: &str, elapsed: Duration) {
+        self.time_to_first_byte.observe(model, elapsed);
+    }
+
+    pub fn record_token_error(&self, email: &str, reason: &str) {
+        self.token_errors_total
+            .entry((email.to_string(), reason.to_string()))
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_active_tokens(&self, count: usize) {
+        self.active_tokens.store(count as i64, Ordering::Relaxed);
+    }
+
+    /// 以 Prometheus 文本格式 (exposition format 0.0.4) 导出
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP antigravity_requests_total Total number of proxied requests.\n");
+        out.push_str("# TYPE antigravity_requests_total counter\n");
+        let mut requests: Vec<_> = self
+            .requests_total
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        requests.sort();
+        for ((model, status), value) in requests {
+            let _ = writeln!(
+                out,
+                "antigravity_requests_total{{model=\"{}\",status=\"{}\"}} {}",
+                escape_label(&model),
+                status,
+                value
+            );
+        }
+
+        out.push_str("# HELP antigravity_token_errors_total Upstream errors attributed to an account.\n");
+        out.push_str("# TYPE antigravity_token_errors_total counter\n");
+        let mut errors: Vec<_> = self
+            .token_errors_total
+            .iter()
+            .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+            .collect();
+        errors.sort();
+        for ((email, reason), value) in errors {
+            let _ = writeln!(
+                out,
+                "antigravity_token_errors_total{{email=\"{}\",reason=\"{}\"}} {}",
+                escape_label(&email),
+                escape_label(&reason),
+                value
+            );
+        }
+
+        self.request_duration.render(&mut out);
+        self.time_to_first_byte.render(&mut out);
+        self.stream_duration.render(&mut out);
+        self.stream_chunk_interval.render(&mut out);
+
+        out.push_str("# HELP antigravity_active_tokens Number of accounts currently available.\n");
+        out.push_str("# TYPE antigravity_active_tokens gauge\n");
+        let _ = writeln!(
+            out,
+            "antigravity_active_tokens {}",
+            self.active_tokens.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+/// 全局指标实例
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// 为流式响应计时：记录相邻数据块的间隔，流结束 (或客户端断开) 时记录总时长
+pub fn instrument_stream<S>(model: &str, stream: S) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+{
+    instrument_stream_with(metrics(), model, stream)
+}
+
+fn instrument_stream_with<S>(metrics: &'static Metrics, model: &str, stream: S) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+{
+    let mut timer = StreamTimer {
+        metrics,
+        model: model.to_string(),
+        start: Instant::now(),
+        last_chunk: None,
+    };
+    stream.map(move |item| {
+        timer.tick();
+        item
+    })
+}
+
+struct StreamTimer {
+    metrics: &'static Metrics,
+    model: String,
+    start: Instant,
+    last_chunk: Option<Instant>,
+}
+
+impl StreamTimer {
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(prev) = self.last_chunk {
+            self.metrics.stream_chunk_interval.observe(&self.model, now - prev);
+        }
+        self.last_chunk = Some(now);
+    }
+}
+
+impl Drop for StreamTimer {
+    fn drop(&mut self) {
+        if let Some(last) = self.last_chunk {
+            self.metrics.stream_duration.observe(&self.model, last - self.start);
+        }
+    }
+}
+
+/// 根据上游状态码归类账号错误原因
+pub fn token_error_reason(status: u16) -> &'static str {
+    match status {
+        401 => "unauthorized",
+        403 => "forbidden",
+        429 => "rate_limited",
+        529 => "overloaded",
+        500..=599 => "server_error",
+        _ => "client_error",
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_format() {
+        let m = Metrics::default();
+        m.record_request("gemini-2.5-flash", 200, Duration::from_millis(300));
+        m.record_request("gemini-2.5-flash", 200, Duration::from_secs(3));
+        m.record_request("gemini-2.5-flash", 429, Duration::from_millis(50));
+        m.record_token_error("a@example.com", token_error_reason(429));
+        m.set_active_tokens(3);
+
+        let text = m.render();
+        assert!(text.contains("antigravity_requests_total{model=\"gemini-2.5-flash\",status=\"200\"} 2"));
+        assert!(text.contains("antigravity_requests_total{model=\"gemini-2.5-flash\",status=\"429\"} 1"));
+        assert!(text.contains(
+            "antigravity_token_errors_total{email=\"a@example.com\",reason=\"rate_limited\"} 1"
+        ));
+        assert!(text.contains("antigravity_request_duration_seconds_bucket{model=\"gemini-2.5-flash\",le=\"0.5\"} 2"));
+        assert!(text.contains("antigravity_request_duration_seconds_bucket{model=\"gemini-2.5-flash\",le=\"+Inf\"} 3"));
+        assert!(text.contains("antigravity_request_duration_seconds_count{model=\"gemini-2.5-flash\"} 3"));
+        assert!(text.contains("antigravity_active_tokens 3"));
+    }
+
+    #[test]
+    fn test_render_latency_histograms() {
+        let m = Metrics::default();
+        m.record_ttfb("gemini-2.5-pro", Duration::from_millis(500));
+        m.record_ttfb("gemini-2.5-pro", Duration::from_secs(4));
+
+        let text = m.render();
+        assert!(text.contains("# TYPE antigravity_time_to_first_byte_seconds histogram"));
+        assert!(text.contains("antigravity_time_to_first_byte_seconds_bucket{model=\"gemini-2.5-pro\",le=\"1\"} 1"));
+        assert!(text.contains("antigravity_time_to_first_byte_seconds_bucket{model=\"gemini-2.5-pro\",le=\"5\"} 2"));
+        assert!(text.contains("antigravity_time_to_first_byte_seconds_sum{model=\"gemini-2.5-pro\"} 4.5"));
+        assert!(text.contains("antigravity_time_to_first_byte_seconds_count{model=\"gemini-2.5-pro\"} 2"));
+        assert!(text.contains("# TYPE antigravity_stream_chunk_interval_seconds histogram"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_instrument_stream_records_intervals_and_duration() {
+        let m: &'static Metrics = Box::leak(Box::default());
+        let chunks = futures::stream::iter([20u64, 200, 2000]).then(|ms| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            ms
+        });
+        let collected: Vec<u64> = instrument_stream_with(m, "gemini-2.5-flash", chunks).collect().await;
+        assert_eq!(collected, vec![20, 200, 2000]);
+
+        let text = m.render();
+        // 3 个数据块 → 2 个间隔 (0.2s 与 2s)
+        assert!(text.contains(
+            "antigravity_stream_chunk_interval_seconds_bucket{model=\"gemini-2.5-flash\",le=\"0.25\"} 1"
+        ));
+        assert!(text.contains(
+            "antigravity_stream_chunk_interval_seconds_count{model=\"gemini-2.5-flash\"} 2"
+        ));
+        // 总时长截至最后一个数据块 (2.22s)
+        assert!(text.contains(
+            "antigravity_stream_duration_seconds_bucket{model=\"gemini-2.5-flash\",le=\"2.5\"} 1"
+        ));
+        assert!(text.contains("antigravity_stream_duration_seconds_count{model=\"gemini-2.5-flash\"} 1"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
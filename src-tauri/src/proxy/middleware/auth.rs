@@ -1,16 +1,102 @@
 // API Key 认证中间件
 use axum::{
-    extract::State,
-    extract::Request,
+    extract::{ConnectInfo, Request, State},
     http::{header, StatusCode},
     middleware::Next,
     response::Response,
 };
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+use crate::proxy::api_key_hash::{is_verified, verify_api_key_async};
+use crate::proxy::middleware::ClientIp;
 use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
 
+/// 同一 IP 在窗口内允许的认证失败次数，超出后直接返回 429，不再执行 Argon2 验证
+const MAX_AUTH_FAILURES: u32 = 10;
+const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+/// 失败记录的最大 IP 数 (超出时清理过期记录，仍超出则淘汰最早的未限流记录)
+const MAX_TRACKED_IPS: usize = 4096;
+
+/// IP -> (窗口内失败次数, 窗口开始时间)
+static AUTH_FAILURES: Lazy<DashMap<IpAddr, (u32, Instant)>> = Lazy::new(DashMap::new);
+
+/// 限流使用的客户端 IP：优先取 IP 白名单层按受信任代理解析出的地址，否则为连接对端
+fn client_ip(request: &Request) -> Option<IpAddr> {
+    let extensions = request.extensions();
+    extensions
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| *ip)
+        .or_else(|| extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip()))
+}
+
+fn is_throttled(ip: Option<IpAddr>) -> bool {
+    let Some(ip) = ip else {
+        return false;
+    };
+    AUTH_FAILURES
+        .get(&ip)
+        .is_some_and(|entry| entry.0 >= MAX_AUTH_FAILURES && entry.1.elapsed() < AUTH_FAILURE_WINDOW)
+}
+
+fn record_auth_result(ip: Option<IpAddr>, authorized: bool) {
+    let Some(ip) = ip else {
+        return;
+    };
+    if authorized {
+        AUTH_FAILURES.remove(&ip);
+    } else {
+        record_failure(&AUTH_FAILURES, MAX_TRACKED_IPS, ip);
+    }
+}
+
+fn record_failure(failures: &DashMap<IpAddr, (u32, Instant)>, capacity: usize, ip: IpAddr) {
+    if !failures.contains_key(&ip) && failures.len() >= capacity {
+        evict_failure_records(failures, capacity);
+    }
+    let mut entry = failures.entry(ip).or_insert((0, Instant::now()));
+    if entry.1.elapsed() >= AUTH_FAILURE_WINDOW {
+        *entry = (0, Instant::now());
+    }
+    entry.0 += 1;
+}
+
+/// 为新 IP 腾出位置：先清理过期记录，仍已满时优先淘汰未触发限流且最早的记录
+fn evict_failure_records(failures: &DashMap<IpAddr, (u32, Instant)>, capacity: usize) {
+    failures.retain(|_, (_, start)| start.elapsed() < AUTH_FAILURE_WINDOW);
+    while failures.len() >= capacity {
+        let victim = failures
+            .iter()
+            .min_by_key(|entry| (entry.value().0 >= MAX_AUTH_FAILURES, entry.value().1))
+            .map(|entry| *entry.key());
+        match victim {
+            Some(ip) => {
+                failures.remove(&ip);
+            }
+            None => break,
+        }
+    }
+}
+
+/// 校验 key：验证缓存命中直接通过；否则先计入一次失败再执行 Argon2，
+/// 并发的垃圾 key 在排队验证前即触发限流，验证通过后清除记录
+async fn verify_with_throttle(ip: Option<IpAddr>, key: &str, encoded: &str) -> bool {
+    if is_verified(key, encoded) {
+        record_auth_result(ip, true);
+        return true;
+    }
+    record_auth_result(ip, false);
+    let authorized = verify_api_key_async(key, encoded).await;
+    if authorized {
+        record_auth_result(ip, true);
+    }
+    authorized
+}
+
 /// API Key 认证中间件 (代理接口使用，遵循 auth_mode)
 pub async fn auth_middleware(
     state: State<Arc<RwLock<ProxySecurityConfig>>>,
@@ -95,9 +181,10 @@ async fn auth_middleware_internal(
                 .headers()
                 .get("x-goog-api-key")
                 .and_then(|h| h.to_str().ok())
-        });
+        })
+        .map(str::to_string);
 
-    if security.api_key.is_empty() && (security.admin_password.is_none() || security.admin_password.as_ref().unwrap().is_empty()) {
+    if security.api_key_hash.is_empty() && (security.admin_password.is_none() || security.admin_password.as_ref().unwrap().is_empty()) {
         if force_strict {
              tracing::error!("Admin auth is required but both api_key and admin_password are empty; denying request");
              return Err(StatusCode::UNAUTHORIZED);
@@ -106,26 +193,78 @@ async fn auth_middleware_internal(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    let ip = client_ip(&request);
+    if is_throttled(ip) {
+        tracing::warn!("Too many failed auth attempts from {:?}; rejecting without verification", ip);
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
     // 认证逻辑
-    let authorized = if force_strict {
+    let admin_password = security.admin_password.as_deref().filter(|pwd| !pwd.is_empty());
+    let authorized = match api_key {
         // 管理接口：优先使用独立的 admin_password，如果没有则回退使用 api_key
-        match &security.admin_password {
-            Some(pwd) if !pwd.is_empty() => {
-                api_key.map(|k| k == pwd).unwrap_or(false)
-            }
-            _ => {
-                // 回退使用 api_key
-                api_key.map(|k| k == security.api_key).unwrap_or(false)
-            }
+        Some(key) if force_strict && admin_password.is_some() => {
+            let ok = admin_password == Some(key.as_str());
+            record_auth_result(ip, ok);
+            ok
         }
-    } else {
         // AI 代理接口：仅允许使用 api_key
-        api_key.map(|k| k == security.api_key).unwrap_or(false)
+        Some(key) => verify_with_throttle(ip, &key, &security.api_key_hash).await,
+        None => {
+            record_auth_result(ip, false);
+            false
+        }
+    };
+
+    if authorized {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// /admin 运行时管理接口认证 (仅接受独立的 admin_api_key，不受 auth_mode 影响)
+/// 未配置 admin_api_key 时整个 /admin 接口视为不存在
+pub async fn admin_api_key_middleware(
+    State(security): State<Arc<RwLock<ProxySecurityConfig>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(expected_hash) = security.read().await.admin_api_key_hash.clone() else {
+        return Err(StatusCode::NOT_FOUND);
     };
 
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
+        .or_else(|| {
+            request
+                .headers()
+                .get("x-api-key")
+                .and_then(|h| h.to_str().ok())
+        })
+        .map(str::to_string);
+
+    let ip = client_ip(&request);
+    if is_throttled(ip) {
+        tracing::warn!("Too many failed /admin auth attempts from {:?}", ip);
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    // 与 api_key 相同按哈希校验 (验证缓存 + Argon2)
+    let authorized = match provided {
+        Some(key) => verify_with_throttle(ip, &key, &expected_hash).await,
+        None => {
+            record_auth_result(ip, false);
+            false
+        }
+    };
     if authorized {
         Ok(next.run(request).await)
     } else {
+        tracing::warn!("Rejected /admin request: invalid admin_api_key");
         Err(StatusCode::UNAUTHORIZED)
     }
 }
@@ -139,8 +278,9 @@ mod tests {
     async fn test_admin_auth_with_password() {
         let security = Arc::new(RwLock::new(ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Strict,
-            api_key: "sk-api".to_string(),
+            api_key_hash: crate::proxy::api_key_hash::hash_api_key("sk-api"),
             admin_password: Some("admin123".to_string()),
+            admin_api_key_hash: None,
             allow_lan_access: true,
             port: 8045,
         }));
@@ -156,6 +296,161 @@ mod tests {
         // 我们在 auth_middleware_internal 基础上做了逻辑校验即可
     }
 
+    #[tokio::test]
+    async fn test_admin_api_key_middleware() {
+        use tower::ServiceExt;
+
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Off,
+            api_key_hash: String::new(),
+            admin_password: None,
+            admin_api_key_hash: Some(crate::proxy::api_key_hash::hash_api_key("adm-key")),
+            allow_lan_access: false,
+            port: 8045,
+        }));
+        let app = axum::Router::new()
+            .route("/admin/tokens", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(security.clone(), admin_api_key_middleware));
+        let call = |key: Option<&'static str>| {
+            let mut req = Request::builder().uri("/admin/tokens");
+            if let Some(key) = key {
+                req = req.header("Authorization", format!("Bearer {}", key));
+            }
+            app.clone().oneshot(req.body(axum::body::Body::empty()).unwrap())
+        };
+
+        assert_eq!(call(Some("adm-key")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(call(Some("adm-kez")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(call(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        // 未配置 admin 密钥时 /admin 不存在
+        security.write().await.admin_api_key_hash = None;
+        assert_eq!(call(Some("adm-key")).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failures_are_throttled_per_ip() {
+        use tower::ServiceExt;
+
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key_hash: crate::proxy::api_key_hash::hash_api_key("sk-right"),
+            admin_password: None,
+            admin_api_key_hash: None,
+            allow_lan_access: false,
+            port: 8045,
+        }));
+        let app = axum::Router::new()
+            .route("/v1/models", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(security, auth_middleware));
+        let call = |ip: &'static str, key: &'static str| {
+            let mut req = Request::builder()
+                .uri("/v1/models")
+                .header("Authorization", format!("Bearer {}", key))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            req.extensions_mut()
+                .insert(ConnectInfo(format!("{}:40000", ip).parse::<SocketAddr>().unwrap()));
+            app.clone().oneshot(req)
+        };
+
+        for _ in 0..MAX_AUTH_FAILURES {
+            assert_eq!(call("192.0.2.7", "sk-wrong").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        }
+        // 达到上限后即使 key 正确也被限流；其他 IP 不受影响
+        assert_eq!(call("192.0.2.7", "sk-right").await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(call("192.0.2.8", "sk-right").await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_throttle_keyed_by_resolved_client_ip() {
+        use tower::ServiceExt;
+
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key_hash: crate::proxy::api_key_hash::hash_api_key("sk-right"),
+            admin_password: None,
+            admin_api_key_hash: None,
+            allow_lan_access: false,
+            port: 8045,
+        }));
+        let app = axum::Router::new()
+            .route("/v1/models", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(security, auth_middleware));
+        // 所有请求经同一反向代理到达，真实客户端 IP 由白名单层解析
+        let call = |client: &'static str, key: &'static str| {
+            let mut req = Request::builder()
+                .uri("/v1/models")
+                .header("Authorization", format!("Bearer {}", key))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            req.extensions_mut()
+                .insert(ConnectInfo("127.0.0.1:40000".parse::<SocketAddr>().unwrap()));
+            req.extensions_mut().insert(ClientIp(client.parse().unwrap()));
+            app.clone().oneshot(req)
+        };
+
+        for _ in 0..MAX_AUTH_FAILURES {
+            assert_eq!(call("198.51.100.1", "sk-wrong").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(call("198.51.100.1", "sk-right").await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        // 同一代理后的其他客户端不受影响
+        assert_eq!(call("198.51.100.2", "sk-right").await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_junk_keys_throttled_before_verification() {
+        use tower::ServiceExt;
+
+        let security = Arc::new(RwLock::new(ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key_hash: crate::proxy::api_key_hash::hash_api_key("sk-right"),
+            admin_password: None,
+            admin_api_key_hash: None,
+            allow_lan_access: false,
+            port: 8045,
+        }));
+        let app = axum::Router::new()
+            .route("/v1/models", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(security, auth_middleware));
+        let call = |key: String| {
+            let mut req = Request::builder()
+                .uri("/v1/models")
+                .header("Authorization", format!("Bearer {}", key))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            req.extensions_mut().insert(ClientIp("192.0.2.99".parse().unwrap()));
+            app.clone().oneshot(req)
+        };
+
+        // 同时发起的垃圾请求在进入 Argon2 前计数，超出上限的直接 429
+        let junk = (0..MAX_AUTH_FAILURES * 3).map(|i| call(format!("sk-junk-{}", i)));
+        let statuses: Vec<StatusCode> = futures::future::join_all(junk)
+            .await
+            .into_iter()
+            .map(|r| r.unwrap().status())
+            .collect();
+        let verified = statuses.iter().filter(|s| **s == StatusCode::UNAUTHORIZED).count();
+        assert!(verified <= MAX_AUTH_FAILURES as usize);
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[test]
+    fn test_full_failure_table_keeps_throttled_ips() {
+        let failures = DashMap::new();
+        let throttled: IpAddr = "203.0.113.250".parse().unwrap();
+        for _ in 0..MAX_AUTH_FAILURES {
+            record_failure(&failures, 8, throttled);
+        }
+        // 轮换源地址填满记录表：只淘汰未触发限流的记录，不会整体清空
+        for i in 0..32u8 {
+            record_failure(&failures, 8, IpAddr::from([198, 51, 100, i]));
+        }
+        assert_eq!(failures.len(), 8);
+        assert_eq!(failures.get(&throttled).unwrap().0, MAX_AUTH_FAILURES);
+        assert!(failures.contains_key(&IpAddr::from([198, 51, 100, 31])));
+    }
+
     #[test]
     fn test_auth_placeholder() {
         assert!(true);
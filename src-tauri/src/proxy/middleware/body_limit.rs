@@ -0,0 +1,101 @@
+// 请求体大小限制 - 超限返回 413 + OpenAI 兼容错误体
+use axum::{
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use crate::proxy::error::OpenAIError;
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// 为路由附加请求体大小限制
+/// Content-Length 超限时直接拒绝；分块上传在读取超过上限时由提取器返回 413
+pub fn with_body_limit<S>(router: Router<S>, max_bytes: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_bytes))
+        .layer(axum::middleware::from_fn_with_state(max_bytes, payload_too_large_middleware))
+}
+
+/// 将 413 响应统一改写为 OpenAI 错误格式
+async fn payload_too_large_middleware(
+    State(max_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/json"));
+    if is_json {
+        return response;
+    }
+
+    OpenAIError::new(
+        format!("Request body exceeds the maximum allowed size of {} bytes", max_bytes),
+        "invalid_request_error",
+    )
+    .with_code("payload_too_large")
+    .response(StatusCode::PAYLOAD_TOO_LARGE)
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, body::Bytes, routing::post};
+    use tower::ServiceExt;
+
+    const LIMIT: usize = 1024;
+
+    fn app() -> Router {
+        let router = Router::new().route(
+            "/v1/chat/completions",
+            post(|body: Bytes| async move { body.len().to_string() }),
+        );
+        with_body_limit(router, LIMIT)
+    }
+
+    async fn post_bytes(len: usize, with_length: bool) -> Response {
+        let body = vec![b'a'; len];
+        let builder = Request::builder().method("POST").uri("/v1/chat/completions");
+        let request = if with_length {
+            builder
+                .header(header::CONTENT_LENGTH, len)
+                .body(Body::from(body))
+                .unwrap()
+        } else {
+            let stream = futures::stream::iter(vec![Ok::<_, std::io::Error>(Bytes::from(body))]);
+            builder.body(Body::from_stream(stream)).unwrap()
+        };
+        app().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_body_under_limit_is_accepted() {
+        let resp = post_bytes(LIMIT - 1, true).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], (LIMIT - 1).to_string().as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_body_over_limit_is_rejected() {
+        for with_length in [true, false] {
+            let resp = post_bytes(LIMIT + 1, with_length).await;
+            assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["error"]["code"], "payload_too_large");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// 响应体压缩 - 依据客户端 Accept-Encoding 协商 gzip / zstd
+use axum::Router;
+use tower_http::compression::{
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+    CompressionLayer,
+};
+
+/// 为路由附加响应压缩 (enabled 为 false 时原样返回)
+/// SSE 流式响应不压缩，否则压缩器缓冲会破坏逐块推送
+pub fn with_compression<S>(router: Router<S>, enabled: bool) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !enabled {
+        return router;
+    }
+    let predicate = DefaultPredicate::new().and(NotForContentType::const_new("text/event-stream"));
+    router.layer(
+        CompressionLayer::new()
+            .gzip(true)
+            .zstd(true)
+            .compress_when(predicate),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::{IntoResponse, Response},
+        routing::get,
+        Json,
+    };
+    use std::io::Read;
+    use tower::ServiceExt;
+
+    fn large_json() -> serde_json::Value {
+        serde_json::json!({ "data": "iVBORw0KGgo".repeat(10_000) })
+    }
+
+    fn app(enabled: bool) -> Router {
+        let router = Router::new()
+            .route("/v1/models", get(|| async { Json(large_json()) }))
+            .route(
+                "/v1/stream",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "text/event-stream")],
+                        "data: x\n\n".repeat(1_000),
+                    )
+                        .into_response()
+                }),
+            );
+        with_compression(router, enabled)
+    }
+
+    async fn get_with(app: Router, uri: &str, accept_encoding: Option<&str>) -> Response {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(enc) = accept_encoding {
+            builder = builder.header(header::ACCEPT_ENCODING, enc);
+        }
+        app.oneshot(builder.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_large_json_is_gzip_compressed() {
+        let resp = get_with(app(true), "/v1/models", Some("gzip")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut decoded).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(json, large_json());
+    }
+
+    #[tokio::test]
+    async fn test_respects_accept_encoding() {
+        let resp = get_with(app(true), "/v1/models", None).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let resp = get_with(app(true), "/v1/models", Some("zstd")).await;
+        assert_eq!(resp.headers().get(header::CONTENT_ENCODING).unwrap(), "zstd");
+    }
+
+    #[tokio::test]
+    async fn test_sse_and_disabled_are_not_compressed() {
+        let resp = get_with(app(true), "/v1/stream", Some("gzip")).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let resp = get_with(app(false), "/v1/models", Some("gzip")).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+}
@@ -1,33 +1,204 @@
 // CORS 中间件
-use tower_http::cors::{CorsLayer, Any};
-use axum::http::Method;
+use tower_http::cors::{AllowOrigin, CorsLayer, Any};
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
 
 /// 创建 CORS layer
-pub fn cors_layer() -> CorsLayer {
-    CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::HEAD,
-            Method::OPTIONS,
-            Method::PATCH,
-        ])
-        .allow_headers(Any)
-        .allow_credentials(false)
-        .max_age(std::time::Duration::from_secs(3600))
+/// - 空列表: 返回 None，不附加任何 CORS 头
+/// - 包含 "*": 允许任意来源
+/// - 其他: 仅允许列表中的来源 (无法解析的条目会被忽略)
+pub fn cors_layer(allowed_origins: &[String]) -> Option<CorsLayer> {
+    if allowed_origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if allowed_origins.iter().any(|o| o.trim() == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = allowed_origins
+            .iter()
+            .filter_map(|o| match HeaderValue::from_str(o.trim()) {
+                Ok(v) => Some(v),
+                Err(_) => {
+                    tracing::warn!("[CORS] Ignoring invalid origin: {}", o);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::HEAD,
+                Method::OPTIONS,
+                Method::PATCH,
+            ])
+            .allow_headers(Any)
+            .allow_credentials(false)
+            .max_age(std::time::Duration::from_secs(3600)),
+    )
+}
+
+/// 预检请求返回 204 No Content (CorsLayer 默认以 200 应答)
+/// 需挂在 CorsLayer 外层；CorsLayer 直接应答所有 OPTIONS 请求，不进入路由，仅注册 GET/POST 的路由同样适用
+pub async fn preflight_no_content_middleware(request: Request, next: Next) -> Response {
+    let is_preflight = request.method() == Method::OPTIONS;
+    let mut response = next.run(request).await;
+    if is_preflight && response.status() == StatusCode::OK {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+    }
+    response
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::{
+        body::Body,
+        http::Request,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn test_app(origins: &[&str]) -> Router {
+        let origins: Vec<String> = origins.iter().map(|s| s.to_string()).collect();
+        let router = Router::new()
+            .route("/v1/models", get(|| async { "ok" }))
+            .route("/v1/chat/completions", post(|| async { "ok" }));
+        match cors_layer(&origins) {
+            Some(layer) => router
+                .layer(layer)
+                .layer(axum::middleware::from_fn(preflight_no_content_middleware)),
+            None => router,
+        }
+    }
 
     #[test]
-    fn test_cors_layer_creation() {
-        let _layer = cors_layer();
-        // Layer 创建成功
-        assert!(true);
+    fn test_cors_layer_disabled_when_empty() {
+        assert!(cors_layer(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors_allows_listed_origin() {
+        let app = test_app(&["http://localhost:3000"]);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/models")
+                    .header("Origin", "http://localhost:3000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            resp.headers().get("access-control-allow-origin").unwrap(),
+            "http://localhost:3000"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight() {
+        let app = test_app(&["http://localhost:3000"]);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri("/v1/models")
+                    .header("Origin", "http://localhost:3000")
+                    .header("Access-Control-Request-Method", "POST")
+                    .header("Access-Control-Request-Headers", "authorization,content-type")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get("access-control-allow-origin").unwrap(),
+            "http://localhost:3000"
+        );
+        assert!(headers.get("access-control-allow-methods").is_some());
+        assert!(headers.get("access-control-allow-headers").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_on_post_only_route() {
+        let app = test_app(&["http://localhost:3000"]);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri("/v1/chat/completions")
+                    .header("Origin", "http://localhost:3000")
+                    .header("Access-Control-Request-Method", "POST")
+                    .header("Access-Control-Request-Headers", "authorization,content-type")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get("access-control-allow-origin").unwrap(),
+            "http://localhost:3000"
+        );
+        let methods = headers.get("access-control-allow-methods").unwrap().to_str().unwrap();
+        assert!(methods.contains("POST") && methods.contains("OPTIONS"));
+        assert!(headers.get("access-control-allow-headers").is_some());
+        assert_eq!(headers.get("access-control-max-age").unwrap(), "3600");
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cors_rejects_unlisted_origin() {
+        let app = test_app(&["http://localhost:3000"]);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/models")
+                    .header("Origin", "http://evil.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(resp.headers().get("access-control-allow-origin").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard() {
+        let app = test_app(&["*"]);
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/models")
+                    .header("Origin", "http://anything.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.headers().get("access-control-allow-origin").unwrap(), "*");
     }
 }
@@ -0,0 +1,159 @@
+// IP 白名单中间件 - 仅允许白名单 (CIDR) 内的客户端访问，其余返回 403
+// 对端地址来自连接 (ConnectInfo)；只有对端是受信任代理时才采信 X-Forwarded-For，防止伪造
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allowlist: Vec<IpNetwork>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl IpFilter {
+    pub fn new(allowlist: Vec<IpNetwork>, trusted_proxies: Vec<IpAddr>) -> Self {
+        Self { allowlist, trusted_proxies }
+    }
+
+    /// 解析真实客户端 IP：对端为受信任代理时，从 X-Forwarded-For 右侧向左取第一个非受信任地址
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+        let Some(forwarded_for) = forwarded_for else {
+            return peer;
+        };
+        let mut client = peer;
+        for hop in forwarded_for.rsplit(',') {
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = ip;
+            if !self.trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+        client
+    }
+
+    /// 白名单为空时允许所有 IP
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.allowlist.is_empty() || self.allowlist.iter().any(|net| net.contains(ip))
+    }
+}
+
+/// 解析后的真实客户端 IP，写入请求扩展供内层中间件 (如鉴权失败限流) 使用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+pub async fn ip_allowlist_middleware(
+    State(filter): State<Arc<IpFilter>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        if filter.allowlist.is_empty() {
+            return next.run(request).await;
+        }
+        tracing::warn!("[IpAllowlist] Missing peer address, rejecting request");
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let forwarded_for = request
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok());
+    let client_ip = filter.client_ip(peer.ip(), forwarded_for);
+
+    if !filter.is_allowed(client_ip) {
+        tracing::warn!("[IpAllowlist] Rejected request from {}", client_ip);
+        return (StatusCode::FORBIDDEN, "Forbidden: IP not allowed").into_response();
+    }
+    request.extensions_mut().insert(ClientIp(client_ip));
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn filter() -> IpFilter {
+        IpFilter::new(
+            vec!["10.0.0.0/8".parse().unwrap(), "192.168.1.5/32".parse().unwrap()],
+            vec!["127.0.0.1".parse().unwrap()],
+        )
+    }
+
+    async fn status_for(filter: IpFilter, peer: &str, forwarded_for: Option<&str>) -> StatusCode {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(Arc::new(filter), ip_allowlist_middleware));
+        let mut builder = Request::builder().uri("/");
+        if let Some(xff) = forwarded_for {
+            builder = builder.header("x-forwarded-for", xff);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(format!("{}:50000", peer).parse::<SocketAddr>().unwrap()));
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_by_peer_address() {
+        assert_eq!(status_for(filter(), "10.1.2.3", None).await, StatusCode::OK);
+        assert_eq!(status_for(filter(), "192.168.1.5", None).await, StatusCode::OK);
+        assert_eq!(status_for(filter(), "192.168.1.6", None).await, StatusCode::FORBIDDEN);
+        // 白名单为空时不限制
+        assert_eq!(status_for(IpFilter::default(), "8.8.8.8", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_only_trusted_from_proxies() {
+        // 经受信任代理转发：采信 X-Forwarded-For
+        assert_eq!(status_for(filter(), "127.0.0.1", Some("10.0.0.7")).await, StatusCode::OK);
+        assert_eq!(status_for(filter(), "127.0.0.1", Some("8.8.8.8")).await, StatusCode::FORBIDDEN);
+        // 非受信任对端伪造 X-Forwarded-For 无效
+        assert_eq!(status_for(filter(), "8.8.8.8", Some("10.0.0.7")).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_resolved_client_ip_passed_to_inner_layers() {
+        use axum::Extension;
+
+        let filter = IpFilter::new(Vec::new(), vec!["127.0.0.1".parse().unwrap()]);
+        let app = Router::new()
+            .route("/", get(|Extension(ClientIp(ip)): Extension<ClientIp>| async move { ip.to_string() }))
+            .layer(axum::middleware::from_fn_with_state(Arc::new(filter), ip_allowlist_middleware));
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", "203.0.113.9")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+        let body = app.oneshot(request).await.unwrap().into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(&bytes[..], b"203.0.113.9");
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_hops() {
+        let filter = filter();
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        // 客户端自行填写的最左侧地址被忽略，取最右侧的非受信任地址
+        assert_eq!(
+            filter.client_ip(proxy, Some("10.0.0.1, 8.8.4.4, 127.0.0.1")),
+            "8.8.4.4".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(filter.client_ip(proxy, Some("garbage")), proxy);
+    }
+}
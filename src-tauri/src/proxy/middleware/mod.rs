@@ -1,13 +1,21 @@
 // Middleware 模块 - Axum 中间件
 
 pub mod auth;
+pub mod body_limit;
+pub mod compression;
 pub mod cors;
+pub mod ip_allowlist;
 pub mod logging;
 pub mod monitor;
+pub mod request_id;
 
 pub mod service_status;
 
-pub use cors::cors_layer;
+pub use body_limit::with_body_limit;
+pub use compression::with_compression;
+pub use cors::{cors_layer, preflight_no_content_middleware};
+pub use ip_allowlist::{ip_allowlist_middleware, ClientIp, IpFilter};
 pub use monitor::monitor_middleware;
+pub use request_id::request_id_middleware;
 pub use service_status::service_status_middleware;
-pub use auth::{auth_middleware, admin_auth_middleware};
+pub use auth::{auth_middleware, admin_auth_middleware, admin_api_key_middleware};
@@ -1,18 +1,57 @@
 use axum::{
     extract::{Request, State},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     body::Body,
 };
 use std::time::Instant;
 use crate::proxy::server::AppState;
 use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::audit_db::AuditDb;
+use crate::proxy::audit_log::{self, AuditLogger, AuditRecord};
+use crate::proxy::token_manager::TokenManager;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
 use futures::StreamExt;
 
 const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
 const MAX_RESPONSE_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB for image responses
 
+/// 判断读取请求体的错误是否由大小限制 (`Limited` / `RequestBodyLimit`) 引起
+fn is_length_limit_error(err: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(e) = source {
+        if e.is::<http_body_util::LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// 指标标签中的模型名：仅保留已知模型 (/v1/models 列出的模型、别名与映射目标)，其余归入 "other"
+fn metrics_model_label(
+    model: Option<&str>,
+    custom_mapping: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+) -> String {
+    let Some(model) = model else {
+        return "unknown".to_string();
+    };
+    let known = aliases.contains_key(model)
+        || custom_mapping.values().any(|target| target == model)
+        || crate::proxy::mappers::openai::is_embedding_model(model)
+        || crate::proxy::common::model_mapping::collect_model_ids(custom_mapping)
+            .iter()
+            .any(|id| id == model);
+    if known {
+        model.to_string()
+    } else {
+        "other".to_string()
+    }
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -23,7 +62,8 @@ pub async fn monitor_middleware(
     let method = request.method().to_string();
     let uri = request.uri().to_string();
     
-    if uri.contains("event_logging") || uri.contains("/api/") {
+    // 统计接口 (含 SSE 长连接) 不计入统计，也不记录日志
+    if uri.contains("event_logging") || uri.contains("/api/") || uri.starts_with("/v1/stats") {
         return next.run(request).await;
     }
     
@@ -55,9 +95,16 @@ pub async fn monitor_middleware(
                 };
                 Request::from_parts(parts, Body::from(bytes))
             }
-            Err(_) => {
-                request_body_str = None;
-                Request::from_parts(parts, Body::empty())
+            Err(e) => {
+                tracing::warn!("Failed to read request body: {}", e);
+                // 仅超过请求体大小限制时返回 413 (外层 body_limit 会改写为 OpenAI 错误体)，
+                // 其余读取失败 (客户端断开、分块编码错误等) 按 400 处理
+                let status = if is_length_limit_error(&e) {
+                    axum::http::StatusCode::PAYLOAD_TOO_LARGE
+                } else {
+                    axum::http::StatusCode::BAD_REQUEST
+                };
+                return status.into_response();
             }
         }
     } else {
@@ -67,8 +114,21 @@ pub async fn monitor_middleware(
     
     let response = next.run(request).await;
     
-    let duration = start.elapsed().as_millis() as u64;
+    let elapsed = start.elapsed();
+    let duration = elapsed.as_millis() as u64;
     let status = response.status().as_u16();
+    let model_label = metrics_model_label(
+        model.as_deref(),
+        &*state.custom_mapping.read().await,
+        &*state.model_aliases.read().await,
+    );
+    crate::proxy::metrics::metrics().record_request(&model_label, status, elapsed);
+    state.stats.record_request(&model_label, status);
+    // [NEW] 有 SSE 订阅者时推送最新统计
+    if state.stats.has_subscribers() {
+        let availability = state.token_manager.availability().await;
+        state.stats.publish(availability.available, availability.blacklisted);
+    }
     
     let content_type = response.headers().get("content-type")
         .and_then(|v| v.to_str().ok())
@@ -101,6 +161,9 @@ pub async fn monitor_middleware(
     };
 
     let monitor = state.monitor.clone();
+    let audit_logger = state.audit_logger.clone();
+    let audit_db = state.audit_db.clone();
+    let token_manager = state.token_manager.clone();
     let mut log = ProxyRequestLog {
         id: uuid::Uuid::new_v4().to_string(),
         timestamp: chrono::Utc::now().timestamp_millis(),
@@ -126,6 +189,7 @@ pub async fn monitor_middleware(
         
         tokio::spawn(async move {
             let mut all_stream_data = Vec::new();
+            let mut finish_reason: Option<String> = None;
             let mut last_few_bytes = Vec::new();
             
             while let Some(chunk_res) = stream.next().await {
@@ -162,6 +226,10 @@ pub async fn monitor_middleware(
                     }
                     
                     if let Ok(json) = serde_json::from_str::<Value>(json_str) {
+                        if let Some(reason) = audit_log::extract_finish_reason(&json) {
+                            finish_reason = Some(reason);
+                        }
+
                         // OpenAI format: choices[0].delta.content / reasoning_content
                         if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
                             for choice in choices {
@@ -281,6 +349,8 @@ pub async fn monitor_middleware(
             if log.status >= 400 {
                 log.error = Some("Stream Error or Failed".to_string());
             }
+            emit_audit_record(&audit_logger, &audit_db, &log, finish_reason);
+            record_account_usage(&token_manager, &log);
             monitor.log_request(log).await;
         });
 
@@ -289,8 +359,10 @@ pub async fn monitor_middleware(
         let (parts, body) = response.into_parts();
         match axum::body::to_bytes(body, MAX_RESPONSE_LOG_SIZE).await {
             Ok(bytes) => {
+                let mut finish_reason = None;
                 if let Ok(s) = std::str::from_utf8(&bytes) {
                     if let Ok(json) = serde_json::from_str::<Value>(&s) {
+                        finish_reason = audit_log::extract_finish_reason(&json);
                         // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
                         if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
                             log.input_tokens = usage.get("prompt_tokens")
@@ -320,18 +392,121 @@ pub async fn monitor_middleware(
                 if log.status >= 400 {
                     log.error = log.response_body.clone();
                 }
+                emit_audit_record(&audit_logger, &audit_db, &log, finish_reason);
+                record_account_usage(&token_manager, &log);
                 monitor.log_request(log).await;
                 Response::from_parts(parts, Body::from(bytes))
             }
             Err(_) => {
                 log.response_body = Some("[Response too large (>100MB)]".to_string());
+                emit_audit_record(&audit_logger, &audit_db, &log, None);
                 monitor.log_request(log).await;
                 Response::from_parts(parts, Body::empty())
             }
         }
     } else {
         log.response_body = Some(format!("[{}]", content_type));
+        emit_audit_record(&audit_logger, &audit_db, &log, None);
         monitor.log_request(log).await;
         response
     }
 }
+
+/// 成功请求的 token 用量累加到对应账号
+fn record_account_usage(token_manager: &TokenManager, log: &ProxyRequestLog) {
+    if log.status >= 400 {
+        return;
+    }
+    if let Some(email) = log.account_email.as_deref() {
+        token_manager.record_usage(
+            email,
+            log.input_tokens.unwrap_or(0) as u64,
+            log.output_tokens.unwrap_or(0) as u64,
+        );
+    }
+}
+
+/// 将已完成的请求写入审计日志 / 审计数据库 (若已启用)
+fn emit_audit_record(
+    audit_logger: &Option<Arc<AuditLogger>>,
+    audit_db: &Option<Arc<AuditDb>>,
+    log: &ProxyRequestLog,
+    finish_reason: Option<String>,
+) {
+    if audit_logger.is_none() && audit_db.is_none() {
+        return;
+    }
+    let prompt_tokens = log.input_tokens.or_else(|| {
+        log.request_body
+            .as_deref()
+            .map(audit_log::estimate_prompt_tokens)
+    });
+    let model = log.mapped_model.clone().or_else(|| log.model.clone());
+    let estimated_cost_usd = model.as_deref().zip(prompt_tokens).and_then(|(model, prompt_tokens)| {
+        crate::proxy::pricing::estimate_cost_usd(
+            model,
+            prompt_tokens as u64,
+            log.output_tokens.unwrap_or(0) as u64,
+        )
+    });
+    let record = AuditRecord {
+        timestamp: log.timestamp,
+        request_id: log.id.clone(),
+        account_email: log.account_email.clone(),
+        model,
+        prompt_tokens,
+        completion_tokens: log.output_tokens,
+        finish_reason,
+        latency_ms: log.duration,
+        status: log.status,
+        error_type: (log.status >= 400)
+            .then(|| crate::proxy::metrics::token_error_reason(log.status).to_string()),
+        estimated_cost_usd,
+    };
+    if let Some(db) = audit_db {
+        db.log(record.clone());
+    }
+    if let Some(logger) = audit_logger {
+        logger.log(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_length_limit_error_detected() {
+        let err = axum::body::to_bytes(Body::from(vec![0u8; 16]), 8)
+            .await
+            .unwrap_err();
+        assert!(is_length_limit_error(&err));
+    }
+
+    #[test]
+    fn test_metrics_label_buckets_unknown_models() {
+        let custom_mapping = HashMap::from([("gpt-4o".to_string(), "gemini-3-flash".to_string())]);
+        let aliases = HashMap::from([("fast".to_string(), "gemini-2.5-flash".to_string())]);
+        let label = |model| metrics_model_label(model, &custom_mapping, &aliases);
+
+        assert_eq!(label(Some("gemini-2.5-flash")), "gemini-2.5-flash");
+        assert_eq!(label(Some("gpt-4o")), "gpt-4o");
+        assert_eq!(label(Some("gemini-3-flash")), "gemini-3-flash");
+        assert_eq!(label(Some("fast")), "fast");
+        assert_eq!(label(Some("text-embedding-004")), "text-embedding-004");
+        assert_eq!(label(Some("random-7f3a9c")), "other");
+        assert_eq!(label(None), "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_other_body_errors_not_treated_as_length_limit() {
+        let stream = futures::stream::iter(vec![
+            Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"partial")),
+            Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "client gone")),
+        ]);
+        let err = axum::body::to_bytes(Body::from_stream(stream), MAX_REQUEST_LOG_SIZE)
+            .await
+            .unwrap_err();
+        assert!(!is_length_limit_error(&err));
+    }
+}
@@ -0,0 +1,104 @@
+// Request ID 中间件 - 为每个请求分配 / 透传 x-request-id，贯穿日志与上游调用
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    /// 当前请求的 ID (供 UpstreamClient 转发为 x-goog-request-id)
+    static REQUEST_ID: String;
+}
+
+/// 读取当前任务上下文中的请求 ID (不在请求范围内时返回 None)
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // 保证下游 handler 也能从 header 中读到 (生成的 ID 同样写回请求)
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn test_app() -> Router {
+        Router::new()
+            .route(
+                "/echo",
+                get(|| async { current_request_id().unwrap_or_default() }),
+            )
+            .layer(axum::middleware::from_fn(request_id_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_request_id_round_trip() {
+        let resp = test_app()
+            .oneshot(
+                Request::builder()
+                    .uri("/echo")
+                    .header(REQUEST_ID_HEADER, "client-req-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "client-req-123");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"client-req-123");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated_when_absent() {
+        let resp = test_app()
+            .oneshot(Request::builder().uri("/echo").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = resp
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, header.as_bytes());
+    }
+
+    #[test]
+    fn test_no_request_id_outside_scope() {
+        assert!(current_request_id().is_none());
+    }
+}
@@ -2,10 +2,13 @@
 
 // 现有模块 (保留)
 pub mod config;
+pub mod error;             // 上游错误类型
 pub mod token_manager;
 pub mod project_resolver;
 pub mod server;
+pub mod server_ws;       // WebSocket 流式接口
 pub mod security;
+pub mod api_key_hash;      // API Key 哈希存储
 
 // 新架构模块
 pub mod mappers;           // 协议转换器
@@ -23,6 +26,23 @@ pub mod session_manager;   // 会话指纹管理
 pub mod audio;             // 音频处理模块
 pub mod signature_cache;   // Signature Cache (v3.3.16)
 pub mod cli_sync;          // CLI 配置同步 (v3.3.35)
+pub mod audit_log;         // 审计日志 (JSON Lines + 轮转)
+pub mod audit_db;          // 审计数据库 (SQLite)
+pub mod metrics;           // Prometheus 指标
+pub mod tls;               // HTTPS (rustls)
+pub mod inflight;          // 在途请求计数 (优雅停机)
+pub mod dedup;             // 在途请求去重
+pub mod prompt_cache;      // Prompt 缓存 (CachedContent)
+pub mod sse_replay;        // SSE 断线续传 (Last-Event-ID)
+pub mod session_affinity;  // 会话账号亲和 (x-session-id)
+pub mod traffic_split;     // 流量拆分 (A/B 测试)
+pub mod shadow;            // 影子模式 (请求镜像)
+pub mod otel;              // OpenTelemetry 链路追踪
+pub mod alert;             // 连续失败告警 Webhook
+pub mod stats;             // 实时统计 (前端仪表盘)
+pub mod pricing;           // 费用估算 (按模型单价)
+pub mod model_registry;    // 模型注册表 (上下文窗口 / 最大输出)
+pub mod live_config;       // 运行中的配置 (按请求读取的选项热更新)
 
 
 pub use config::ProxyConfig;
@@ -0,0 +1,120 @@
+// 模型注册表 - 各模型上下文窗口与最大输出 Token
+// 按精确名或最长前缀匹配 (如 gemini-2.5-flash-thinking 命中 gemini-2.5-flash)
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// 最大输入 Token (上下文窗口)
+    pub context_window: u32,
+    /// 单次最大输出 Token (0 表示不产生文本输出，如向量模型)
+    pub output_token_limit: u32,
+}
+
+const fn info(context_window: u32, output_token_limit: u32) -> ModelInfo {
+    ModelInfo { context_window, output_token_limit }
+}
+
+/// 模型注册表 (覆盖 /v1/models 列出的全部模型及其上游映射目标)
+pub static MODEL_REGISTRY: Lazy<HashMap<&'static str, ModelInfo>> = Lazy::new(|| {
+    HashMap::from([
+        // Gemini
+        ("gemini-2.0-flash", info(1_048_576, 8_192)),
+        ("gemini-2.5-pro", info(1_048_576, 65_536)),
+        ("gemini-2.5-flash", info(1_048_576, 65_536)),
+        ("gemini-2.5-flash-lite", info(1_048_576, 65_536)),
+        ("gemini-3-pro", info(1_048_576, 65_536)),
+        ("gemini-3-pro-image", info(65_536, 32_768)),
+        ("gemini-3-flash", info(1_048_576, 65_536)),
+        // Claude
+        ("claude-sonnet-4-5", info(200_000, 64_000)),
+        ("claude-opus-4", info(200_000, 32_000)),
+        ("claude-opus-4-5", info(200_000, 64_000)),
+        ("claude-haiku-4", info(200_000, 64_000)),
+        ("claude-3-5-sonnet", info(200_000, 8_192)),
+        ("claude-3-haiku", info(200_000, 4_096)),
+        // OpenAI 兼容名
+        ("gpt-4", info(8_192, 8_192)),
+        ("gpt-4-turbo", info(128_000, 4_096)),
+        ("gpt-4-0125-preview", info(128_000, 4_096)),
+        ("gpt-4-1106-preview", info(128_000, 4_096)),
+        ("gpt-4o", info(128_000, 16_384)),
+        ("gpt-3.5-turbo", info(16_385, 4_096)),
+        ("gpt-oss-120b", info(131_072, 32_768)),
+        ("internal-background-task", info(1_048_576, 65_536)),
+        // 向量模型
+        ("text-embedding-004", info(2_048, 0)),
+        ("gemini-embedding-001", info(2_048, 0)),
+    ])
+});
+
+/// 各模型上下文窗口 (由注册表派生)
+pub static MODEL_CONTEXT_WINDOWS: Lazy<HashMap<&'static str, u32>> = Lazy::new(|| {
+    MODEL_REGISTRY
+        .iter()
+        .map(|(name, info)| (*name, info.context_window))
+        .collect()
+});
+
+/// 查询模型注册信息 (精确匹配优先，其次最长前缀)
+pub fn get_model_info(model: &str) -> Option<ModelInfo> {
+    if let Some(info) = MODEL_REGISTRY.get(model) {
+        return Some(*info);
+    }
+    MODEL_REGISTRY
+        .iter()
+        .filter(|(name, _)| model.starts_with(*name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, info)| *info)
+}
+
+/// 将 max_tokens 限制在模型最大输出以内 (超出时记录警告；未登记模型原样返回)
+pub fn cap_max_tokens(model: &str, max_tokens: Option<u32>) -> Option<u32> {
+    let requested = max_tokens?;
+    let limit = match get_model_info(model) {
+        Some(info) if info.output_token_limit > 0 => info.output_token_limit,
+        _ => return Some(requested),
+    };
+    if requested > limit {
+        tracing::warn!(
+            "max_tokens={} exceeds output limit of {} ({}), capping",
+            requested,
+            model,
+            limit
+        );
+        return Some(limit);
+    }
+    Some(requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_exact_and_prefix() {
+        assert_eq!(get_model_info("gemini-2.5-flash").unwrap().output_token_limit, 65_536);
+        assert_eq!(get_model_info("claude-opus-4-5-thinking").unwrap(), info(200_000, 64_000));
+        assert_eq!(get_model_info("claude-opus-4").unwrap().output_token_limit, 32_000);
+        assert_eq!(get_model_info("gpt-4o-mini").unwrap().context_window, 128_000);
+        assert_eq!(get_model_info("gemini-3-pro-image-4k-16x9").unwrap().context_window, 65_536);
+        assert_eq!(get_model_info("unknown-model"), None);
+        assert_eq!(MODEL_CONTEXT_WINDOWS["gpt-3.5-turbo"], 16_385);
+    }
+
+    #[test]
+    fn test_listed_models_are_registered() {
+        let ids = crate::proxy::common::model_mapping::collect_model_ids(&HashMap::new());
+        for id in ids {
+            assert!(get_model_info(&id).is_some(), "{} missing from model registry", id);
+        }
+    }
+
+    #[test]
+    fn test_cap_max_tokens() {
+        assert_eq!(cap_max_tokens("gpt-4o", Some(100_000)), Some(16_384));
+        assert_eq!(cap_max_tokens("gpt-4o", Some(1_000)), Some(1_000));
+        assert_eq!(cap_max_tokens("gpt-4o", None), None);
+        assert_eq!(cap_max_tokens("unknown-model", Some(1_000_000)), Some(1_000_000));
+    }
+}
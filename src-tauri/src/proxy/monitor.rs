@@ -68,6 +68,18 @@ impl ProxyMonitor {
         }
     }
 
+    /// 测试用：不初始化日志数据库
+    #[cfg(test)]
+    pub(crate) fn in_memory(max_logs: usize) -> Self {
+        Self {
+            logs: RwLock::new(VecDeque::with_capacity(max_logs)),
+            stats: RwLock::new(ProxyStats::default()),
+            max_logs,
+            enabled: AtomicBool::new(false),
+            app_handle: None,
+        }
+    }
+
     pub fn set_enabled(&self, enabled: bool) {
         self.enabled.store(enabled, Ordering::Relaxed);
     }
@@ -0,0 +1,159 @@
+// OpenTelemetry 链路追踪 - 将 tracing span 经 OTLP/HTTP 导出
+//
+// 日志系统启动时注册一个空的可重载层 (见 logger::init_logger)，
+// 反代服务器按配置在运行时装载/卸载导出器，无需重建全局 subscriber。
+use crate::proxy::config::OtelConfig;
+use once_cell::sync::OnceCell;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::Mutex;
+use tracing_subscriber::{reload, Registry};
+
+type OtelLayer = tracing_opentelemetry::OpenTelemetryLayer<Registry, Tracer>;
+pub type ReloadLayer = reload::Layer<Option<OtelLayer>, Registry>;
+
+static RELOAD_HANDLE: OnceCell<reload::Handle<Option<OtelLayer>, Registry>> = OnceCell::new();
+static PROVIDER: Mutex<Option<TracerProvider>> = Mutex::new(None);
+
+/// 供日志系统注册的追踪层 (初始为空，不产生任何开销)
+pub fn reload_layer() -> ReloadLayer {
+    let (layer, handle) = reload::Layer::new(None);
+    let _ = RELOAD_HANDLE.set(handle);
+    layer
+}
+
+/// 按配置启用或关闭 span 导出；重复调用会替换之前的导出器
+pub fn configure(config: Option<&OtelConfig>) -> Result<(), String> {
+    let Some(handle) = RELOAD_HANDLE.get() else {
+        if config.is_some() {
+            tracing::warn!("日志系统未注册追踪层，OpenTelemetry 导出不可用");
+        }
+        return Ok(());
+    };
+
+    let layer = match config {
+        Some(cfg) => {
+            let provider = build_provider(cfg)?;
+            let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("antigravity-proxy"));
+            replace_provider(Some(provider));
+            tracing::info!("OpenTelemetry 导出已启用: {} ({})", cfg.endpoint, cfg.service_name);
+            Some(layer)
+        }
+        None => {
+            replace_provider(None);
+            None
+        }
+    };
+    handle.reload(layer).map_err(|e| format!("追踪层重载失败: {}", e))
+}
+
+/// 刷新并关闭导出器 (停机时调用，避免丢失尾部 span)
+pub fn shutdown() {
+    replace_provider(None);
+}
+
+fn build_provider(cfg: &OtelConfig) -> Result<TracerProvider, String> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(cfg.endpoint.clone())
+        .build()
+        .map_err(|e| format!("OTLP 导出器创建失败: {}", e))?;
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            cfg.service_name.clone(),
+        )]))
+        .build())
+}
+
+fn replace_provider(provider: Option<TracerProvider>) {
+    let previous = match PROVIDER.lock() {
+        Ok(mut guard) => std::mem::replace(&mut *guard, provider),
+        Err(_) => return,
+    };
+    if let Some(previous) = previous {
+        if let Err(e) = previous.shutdown() {
+            tracing::warn!("OpenTelemetry 导出器关闭失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy::upstream::client::UpstreamClient;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use serde_json::json;
+    use tracing::Instrument;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    async fn spawn_upstream() -> String {
+        let app = axum::Router::new().fallback(|| async { "data: {}\n\n" });
+        crate::proxy::tests::support::spawn_mock_upstream(app).await
+    }
+
+    #[tokio::test]
+    async fn test_upstream_span_is_child_of_request_span() {
+        // 内存导出器充当 OTLP 收集端
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = UpstreamClient::new(None).with_base_urls(vec![spawn_upstream().await]);
+        let body = json!({ "model": "gemini-2.5-flash", "request": {} });
+        let resp = client
+            .call_v1_internal_with_headers(
+                "streamGenerateContent",
+                "token",
+                body,
+                Some("alt=sse"),
+                Default::default(),
+                None,
+            )
+            .instrument(tracing::info_span!("chat_completions", model = "gemini-2.5-flash"))
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+
+        // 连接池中的连接任务在请求 span 内创建，连接关闭前请求 span 不会结束
+        drop(resp);
+        drop(client);
+        let mut spans = Vec::new();
+        for _ in 0..50 {
+            let _ = provider.force_flush();
+            spans = exporter.get_finished_spans().unwrap();
+            if spans.iter().any(|s| s.name == "chat_completions") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let root = spans.iter().find(|s| s.name == "chat_completions").unwrap();
+        let upstream = spans.iter().find(|s| s.name == "stream_generate").unwrap();
+        assert_eq!(upstream.parent_span_id, root.span_context.span_id());
+        assert_eq!(upstream.span_context.trace_id(), root.span_context.trace_id());
+        assert!(upstream
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "model" && kv.value.as_str() == "gemini-2.5-flash"));
+    }
+
+    #[test]
+    fn test_configure_without_layer_is_noop() {
+        let cfg = crate::proxy::config::OtelConfig {
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "test".to_string(),
+        };
+        // 测试进程未初始化日志系统，RELOAD_HANDLE 为空
+        assert!(super::configure(Some(&cfg)).is_ok());
+    }
+}
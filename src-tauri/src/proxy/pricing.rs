@@ -0,0 +1,71 @@
+// 费用估算 - 按模型单价与 token 用量估算单次请求费用 (USD)
+// 单价为公开定价的近似值，仅供参考，不代表实际扣费
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// 每 1K token 单价 (input_cost, output_cost)，按模型名前缀匹配
+pub static COST_PER_1K_TOKENS: Lazy<HashMap<&'static str, (f64, f64)>> = Lazy::new(|| {
+    HashMap::from([
+        ("gemini-3-pro", (0.002, 0.012)),
+        ("gemini-3-flash", (0.0005, 0.003)),
+        ("gemini-2.5-pro", (0.00125, 0.01)),
+        ("gemini-2.5-flash", (0.0003, 0.0025)),
+        ("gemini-2.5-flash-lite", (0.0001, 0.0004)),
+        ("gemini-2.0-flash", (0.0001, 0.0004)),
+        ("claude-opus-4", (0.005, 0.025)),
+        ("claude-sonnet-4", (0.003, 0.015)),
+    ])
+});
+
+/// 模型单价：取最长匹配前缀 (如 gemini-2.5-flash-lite 优先于 gemini-2.5-flash)
+fn price_for(model: &str) -> Option<(f64, f64)> {
+    COST_PER_1K_TOKENS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(*prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+/// 估算请求费用 (USD)；未知模型返回 None
+pub fn estimate_cost_usd(model: &str, prompt_tokens: u64, completion_tokens: u64) -> Option<f64> {
+    let (input_cost, output_cost) = price_for(model)?;
+    Some((prompt_tokens as f64 * input_cost + completion_tokens as f64 * output_cost) / 1000.0)
+}
+
+/// x-estimated-cost 响应头取值
+pub fn format_cost(cost: f64) -> String {
+    format!("{:.6}", cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn test_estimate_known_token_counts() {
+        // 2000 * 0.00125/1K + 1000 * 0.01/1K = 0.0025 + 0.01
+        let cost = estimate_cost_usd("gemini-2.5-pro", 2000, 1000).unwrap();
+        assert!(approx_eq(cost, 0.0125));
+        assert_eq!(format_cost(cost), "0.012500");
+
+        // 前缀匹配带后缀的模型名
+        let cost = estimate_cost_usd("gemini-2.5-flash-thinking", 10_000, 500).unwrap();
+        assert!(approx_eq(cost, 0.003 + 0.00125));
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let cost = estimate_cost_usd("gemini-2.5-flash-lite", 1000, 1000).unwrap();
+        assert!(approx_eq(cost, 0.0005));
+    }
+
+    #[test]
+    fn test_unknown_model() {
+        assert_eq!(estimate_cost_usd("gpt-4o", 1000, 1000), None);
+        assert_eq!(estimate_cost_usd("gemini-2.5-pro", 0, 0), Some(0.0));
+    }
+}
@@ -1,11 +1,15 @@
 use serde_json::Value;
 
+/// loadCodeAssist 端点 (使用 Sandbox 环境，避免 Prod 环境的 429 错误)
+const LOAD_CODE_ASSIST_URL: &str = "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal:loadCodeAssist";
+
 /// 使用 Antigravity 的 loadCodeAssist API 获取 project_id
-/// 这是获取 cloudaicompanionProject 的正确方式
-pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
-    // 使用 Sandbox 环境，避免 Prod 环境的 429 错误
-    let url = "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal:loadCodeAssist";
-    
+/// 这是获取 cloudaicompanionProject 的正确方式；`user_agent` 为配置的上游 User-Agent
+pub async fn fetch_project_id(access_token: &str, user_agent: &str) -> Result<String, String> {
+    fetch_project_id_from(LOAD_CODE_ASSIST_URL, access_token, user_agent).await
+}
+
+async fn fetch_project_id_from(url: &str, access_token: &str, user_agent: &str) -> Result<String, String> {
     let request_body = serde_json::json!({
         "metadata": {
             "ideType": "ANTIGRAVITY"
@@ -16,9 +20,7 @@ pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
     let response = client
         .post(url)
         .bearer_auth(access_token)
-        // .header("Host", "cloudcode-pa.googleapis.com") // 移除 Host header，因为已切换域名
-
-        .header("User-Agent", "antigravity/1.11.9 windows/amd64")
+        .header("User-Agent", user_agent)
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
@@ -70,3 +72,40 @@ pub fn generate_mock_project_id() -> String {
     
     format!("{}-{}-{}", adj, noun, random_num)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_configured_user_agent_sent() {
+        use axum::{http::HeaderMap, routing::post};
+
+        let captured = std::sync::Arc::new(std::sync::Mutex::new(None::<String>));
+        let captured_clone = captured.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:loadCodeAssist",
+            post(move |headers: HeaderMap| {
+                let captured = captured_clone.clone();
+                async move {
+                    *captured.lock().unwrap() = headers
+                        .get("user-agent")
+                        .and_then(|v| v.to_str().ok())
+                        .map(|s| s.to_string());
+                    axum::Json(serde_json::json!({"cloudaicompanionProject": "proj-123"}))
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(router).await;
+
+        let project_id = fetch_project_id_from(
+            &format!("{}/v1internal:loadCodeAssist", base),
+            "token",
+            "antigravity/1.12.0 linux/amd64",
+        )
+        .await
+        .unwrap();
+        assert_eq!(project_id, "proj-123");
+        assert_eq!(captured.lock().unwrap().as_deref(), Some("antigravity/1.12.0 linux/amd64"));
+    }
+}
@@ -0,0 +1,150 @@
+// Prompt 缓存 - 较长的 system prompt 通过 Gemini CachedContent 固定在服务端，
+// 后续相同 prompt 的请求只携带 cachedContent 引用，节省输入 token 与延迟
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// 低于该长度的 system prompt 不缓存 (上游要求缓存内容至少约 1024 tokens)
+pub const PROMPT_CACHE_MIN_CHARS: usize = 4096;
+
+/// hash(system_prompt + model + project) -> 缓存名
+pub type PromptCaches = RwLock<HashMap<u64, String>>;
+
+/// 缓存键：CachedContent 归属于项目，不同账号不能共用
+pub fn cache_key(system_prompt: &str, model: &str, project_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    system_prompt.hash(&mut hasher);
+    model.hash(&mut hasher);
+    project_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 提取 v1internal 请求体中的 system prompt；带工具的请求不可使用缓存，返回 None
+pub fn cacheable_system_prompt(body: &Value) -> Option<String> {
+    let request = body.get("request")?;
+    if request.get("tools").is_some() || request.get("toolConfig").is_some() {
+        return None;
+    }
+    let parts = request.get("systemInstruction")?.get("parts")?.as_array()?;
+    let text = parts
+        .iter()
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    (text.chars().count() >= PROMPT_CACHE_MIN_CHARS).then_some(text)
+}
+
+/// 用 cachedContent 引用替换 systemInstruction
+pub fn use_cached_content(body: &mut Value, cache_name: &str) {
+    if let Some(request) = body.get_mut("request").and_then(|r| r.as_object_mut()) {
+        request.remove("systemInstruction");
+        request.insert("cachedContent".to_string(), json!(cache_name));
+    }
+}
+
+/// 为请求体应用 prompt 缓存：命中时直接引用，未命中时创建缓存并在 TTL 到期前移除映射
+/// 创建失败不影响请求，照常发送完整的 systemInstruction
+/// 返回 Some(命中与否)；system prompt 不可缓存时返回 None
+pub async fn apply_prompt_cache(
+    caches: &Arc<PromptCaches>,
+    upstream: &crate::proxy::upstream::client::UpstreamClient,
+    body: &mut Value,
+    model: &str,
+    ttl_secs: u64,
+    access_token: &str,
+    project_id: &str,
+) -> Option<bool> {
+    let system_prompt = cacheable_system_prompt(body)?;
+    let key = cache_key(&system_prompt, model, project_id);
+
+    if let Some(name) = caches.read().await.get(&key).cloned() {
+        tracing::debug!("Prompt cache hit: {}", name);
+        use_cached_content(body, &name);
+        return Some(true);
+    }
+
+    match upstream
+        .create_cache(&system_prompt, model, ttl_secs, access_token, project_id)
+        .await
+    {
+        Ok(name) => {
+            tracing::info!("Created prompt cache {} (model: {}, ttl: {}s)", name, model, ttl_secs);
+            caches.write().await.insert(key, name.clone());
+            use_cached_content(body, &name);
+
+            // 提前一分钟失效，避免引用已过期的缓存
+            let caches = caches.clone();
+            let expire_after = Duration::from_secs(ttl_secs.saturating_sub(60).max(1));
+            tokio::spawn(async move {
+                tokio::time::sleep(expire_after).await;
+                caches.write().await.remove(&key);
+            });
+        }
+        Err(e) => tracing::warn!("Failed to create prompt cache: {}", e),
+    }
+    Some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body_with_system(text: &str) -> Value {
+        json!({
+            "project": "p",
+            "request": {
+                "contents": [{"role": "user", "parts": [{"text": "hi"}]}],
+                "systemInstruction": {"role": "user", "parts": [{"text": text}]}
+            }
+        })
+    }
+
+    #[test]
+    fn test_short_or_tool_prompts_are_not_cached() {
+        assert!(cacheable_system_prompt(&body_with_system("short")).is_none());
+
+        let long = "x".repeat(PROMPT_CACHE_MIN_CHARS);
+        let mut body = body_with_system(&long);
+        assert_eq!(cacheable_system_prompt(&body).as_deref(), Some(long.as_str()));
+
+        body["request"]["tools"] = json!([{"functionDeclarations": []}]);
+        assert!(cacheable_system_prompt(&body).is_none());
+    }
+
+    #[test]
+    fn test_use_cached_content_replaces_system_instruction() {
+        let mut body = body_with_system("prompt");
+        use_cached_content(&mut body, "cachedContents/abc");
+        assert!(body["request"].get("systemInstruction").is_none());
+        assert_eq!(body["request"]["cachedContent"], "cachedContents/abc");
+        assert_eq!(body["request"]["contents"][0]["parts"][0]["text"], "hi");
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_project() {
+        assert_eq!(cache_key("p", "m", "a"), cache_key("p", "m", "a"));
+        assert_ne!(cache_key("p", "m", "a"), cache_key("p", "m", "b"));
+        assert_ne!(cache_key("p", "m1", "a"), cache_key("p", "m2", "a"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_skips_upstream() {
+        let long = "y".repeat(PROMPT_CACHE_MIN_CHARS);
+        let caches = Arc::new(PromptCaches::default());
+        caches
+            .write()
+            .await
+            .insert(cache_key(&long, "gemini-2.5-pro", "proj"), "cachedContents/hit".to_string());
+
+        // 命中缓存时直接引用，不会调用上游
+        let upstream = crate::proxy::upstream::client::UpstreamClient::new(None);
+        let mut body = body_with_system(&long);
+        let hit = apply_prompt_cache(&caches, &upstream, &mut body, "gemini-2.5-pro", 3600, "t", "proj").await;
+        assert_eq!(hit, Some(true));
+        assert_eq!(body["request"]["cachedContent"], "cachedContents/hit");
+    }
+}
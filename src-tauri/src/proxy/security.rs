@@ -3,8 +3,11 @@ use crate::proxy::config::{ProxyAuthMode, ProxyConfig};
 #[derive(Debug, Clone)]
 pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
-    pub api_key: String,
+    /// API Key 哈希 (见 api_key_hash 模块)，内存中也不保留明文
+    pub api_key_hash: String,
     pub admin_password: Option<String>,
+    /// /admin 密钥哈希 (None = 不开放 /admin 接口)
+    pub admin_api_key_hash: Option<String>,
     pub allow_lan_access: bool,
     pub port: u16,
 }
@@ -13,24 +16,16 @@ impl ProxySecurityConfig {
     pub fn from_proxy_config(config: &ProxyConfig) -> Self {
         Self {
             auth_mode: config.auth_mode.clone(),
-            api_key: config.api_key.clone(),
+            api_key_hash: config.effective_api_key_hash(),
             admin_password: config.admin_password.clone(),
+            admin_api_key_hash: config.effective_admin_api_key_hash(),
             allow_lan_access: config.allow_lan_access,
             port: config.port,
         }
     }
 
     pub fn effective_auth_mode(&self) -> ProxyAuthMode {
-        match self.auth_mode {
-            ProxyAuthMode::Auto => {
-                if self.allow_lan_access {
-                    ProxyAuthMode::AllExceptHealth
-                } else {
-                    ProxyAuthMode::Off
-                }
-            }
-            ref other => other.clone(),
-        }
+        self.auth_mode.resolve(self.allow_lan_access)
     }
 }
 
@@ -42,8 +37,9 @@ mod tests {
     fn auto_mode_resolves_off_for_local_only() {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
-            api_key: "sk-test".to_string(),
+            api_key_hash: String::new(),
             admin_password: None,
+            admin_api_key_hash: None,
             allow_lan_access: false,
             port: 8080,
         };
@@ -54,8 +50,9 @@ mod tests {
     fn auto_mode_resolves_all_except_health_for_lan() {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
-            api_key: "sk-test".to_string(),
+            api_key_hash: String::new(),
             admin_password: None,
+            admin_api_key_hash: None,
             allow_lan_access: true,
             port: 8080,
         };
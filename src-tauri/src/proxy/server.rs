@@ -1,6 +1,6 @@
 use crate::proxy::TokenManager;
 use axum::{
-    extract::{DefaultBodyLimit, Path, State, Query},
+    extract::{Path, State, Query},
     http::{StatusCode, HeaderMap},
     response::{IntoResponse, Json, Response, Html},
     routing::{any, get, post, delete},
@@ -21,6 +21,8 @@ use crate::models::{Account, AppConfig, QuotaData, DeviceProfile};
 pub struct AppState {
     pub token_manager: Arc<TokenManager>,
     pub custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    pub model_aliases: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    pub model_fallbacks: Arc<tokio::sync::RwLock<std::collections::HashMap<String, Vec<String>>>>, // [NEW] 模型降级链
     #[allow(dead_code)]
     pub request_timeout: u64, // API 请求超时(秒)
     #[allow(dead_code)]
@@ -39,6 +41,98 @@ pub struct AppState {
     pub security: Arc<RwLock<crate::proxy::ProxySecurityConfig>>, // [NEW] 安全配置状态
     pub cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>, // [NEW] Cloudflared 插件状态
     pub is_running: Arc<RwLock<bool>>, // [NEW] 运行状态标识
+    pub audit_logger: Option<Arc<crate::proxy::audit_log::AuditLogger>>, // [NEW] 审计日志 (可选)
+    pub audit_db: Option<Arc<crate::proxy::audit_db::AuditDb>>, // [NEW] 审计数据库 (可选)
+    pub started_at: std::time::Instant, // [NEW] 服务启动时间 (用于健康检查 uptime)
+    pub batch_max_requests: usize, // [NEW] 批量补全单次最大子请求数
+    pub live_config: Arc<crate::proxy::live_config::LiveConfig>, // [NEW] 运行中的配置 (重试/超时/并发上限等按请求读取，可热更新)
+    pub in_flight: Arc<AtomicUsize>, // [NEW] 在途请求数 (优雅停机)
+    pub racing_tokens: usize, // [NEW] 请求竞速账号数 (0 = 关闭)
+    pub in_flight_requests: Arc<crate::proxy::dedup::InFlightRequests>, // [NEW] 相同非流式请求去重
+    pub prompt_caches: Arc<crate::proxy::prompt_cache::PromptCaches>, // [NEW] system prompt -> CachedContent 名称
+    pub forwarded_header_prefix: String, // [NEW] 透传到上游的请求头前缀 (空 = 不透传)
+    pub image_upload_url: Option<String>, // [NEW] 响应图片上传地址 (None = 保持内联 base64)
+    pub max_inline_image_bytes: usize, // [NEW] 内联 base64 图片大小上限 (字节)
+    pub sse_keepalive_interval_secs: u64, // [NEW] OpenAI SSE 心跳间隔 (秒)
+    pub sse_replays: Arc<crate::proxy::sse_replay::SseReplays>, // [NEW] SSE 断线续传缓冲
+    pub sse_replay_window_secs: u64, // [NEW] 续传缓冲保留时间 (秒, 0 = 关闭)
+    pub sse_reconnect_grace_secs: u64, // [NEW] 声明续传的客户端断开后等待重连的时间 (秒)
+    pub active_profile: Arc<RwLock<Option<String>>>, // [NEW] 当前使用的配置方案名称 (None = 平铺配置)
+    pub session_affinity: Arc<crate::proxy::session_affinity::SessionAffinity>, // [NEW] 会话 -> 账号邮箱
+    pub capability_routing: bool, // [NEW] 主模型不可用时按能力改道
+    pub shadow: Option<Arc<crate::proxy::shadow::ShadowMirror>>, // [NEW] 影子模式 (None = 关闭)
+    pub consecutive_failures: Arc<std::sync::atomic::AtomicU32>, // [NEW] 跨账号连续失败次数 (与 TokenManager 共享)
+    pub stats: Arc<crate::proxy::stats::StatsCounters>, // [NEW] 实时统计 (GET /v1/stats)
+}
+
+impl AppState {
+    /// 获取模型的上游请求超时
+    pub fn timeout_for_model(&self, model: &str) -> std::time::Duration {
+        self.live_config.timeout_for_model(model)
+    }
+
+    /// SSE 心跳间隔 (至少 1 秒)
+    pub fn sse_keepalive_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.sse_keepalive_interval_secs.max(1))
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// 测试用状态：给定账号池与上游客户端，其余配置取 ProxyConfig 默认值
+    pub(crate) fn for_test(
+        token_manager: Arc<TokenManager>,
+        upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
+    ) -> Self {
+        let config = crate::proxy::config::ProxyConfig::default();
+        Self {
+            token_manager: token_manager.clone(),
+            custom_mapping: Arc::new(tokio::sync::RwLock::new(config.custom_mapping.clone())),
+            model_aliases: Arc::new(tokio::sync::RwLock::new(config.model_aliases.clone())),
+            model_fallbacks: Arc::new(tokio::sync::RwLock::new(config.model_fallbacks.clone())),
+            request_timeout: config.request_timeout,
+            thought_signature_map: Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())),
+            upstream_proxy: Arc::new(tokio::sync::RwLock::new(config.upstream_proxy.clone())),
+            upstream,
+            zai: Arc::new(RwLock::new(config.zai.clone())),
+            provider_rr: Arc::new(AtomicUsize::new(0)),
+            zai_vision_mcp: Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new()),
+            monitor: Arc::new(crate::proxy::monitor::ProxyMonitor::in_memory(10)),
+            experimental: Arc::new(RwLock::new(config.experimental.clone())),
+            switching: Arc::new(RwLock::new(false)),
+            integration: crate::modules::integration::SystemManager::Headless,
+            account_service: Arc::new(crate::modules::account_service::AccountService::new(
+                crate::modules::integration::SystemManager::Headless,
+            )),
+            security: Arc::new(RwLock::new(crate::proxy::ProxySecurityConfig::from_proxy_config(&config))),
+            cloudflared_state: Arc::new(crate::commands::cloudflared::CloudflaredState::new()),
+            is_running: Arc::new(RwLock::new(true)),
+            audit_logger: None,
+            audit_db: None,
+            started_at: std::time::Instant::now(),
+            batch_max_requests: config.batch_max_requests,
+            live_config: Arc::new(crate::proxy::live_config::LiveConfig::new(&config)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            racing_tokens: 0,
+            in_flight_requests: Arc::new(crate::proxy::dedup::InFlightRequests::new()),
+            prompt_caches: Arc::new(crate::proxy::prompt_cache::PromptCaches::default()),
+            forwarded_header_prefix: String::new(),
+            image_upload_url: None,
+            max_inline_image_bytes: config.max_inline_image_bytes,
+            sse_keepalive_interval_secs: config.sse_keepalive_interval_secs,
+            sse_replays: Arc::new(crate::proxy::sse_replay::SseReplays::new()),
+            sse_replay_window_secs: 0,
+            sse_reconnect_grace_secs: config.sse_reconnect_grace_secs,
+            active_profile: Arc::new(RwLock::new(None)),
+            session_affinity: Arc::new(crate::proxy::session_affinity::SessionAffinity::new(
+                std::time::Duration::from_secs(config.session_ttl_secs),
+            )),
+            capability_routing: false,
+            shadow: None,
+            consecutive_failures: token_manager.consecutive_failures(),
+            stats: Arc::new(crate::proxy::stats::StatsCounters::default()),
+        }
+    }
 }
 
 // 为 AppState 实现 FromRef，以便中间件提取 security 状态
@@ -48,9 +142,11 @@ impl axum::extract::FromRef<AppState> for Arc<RwLock<crate::proxy::ProxySecurity
     }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+/// 错误响应统一使用 OpenAI 错误格式
+type ErrorResponse = crate::proxy::error::OpenAIErrorBody;
+
+fn api_error(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    crate::proxy::error::OpenAIError::for_status(status, message).response(status)
 }
 
 #[derive(Serialize)]
@@ -120,17 +216,41 @@ fn to_account_response(account: &crate::models::account::Account, current_id: &O
     }
 }
 
+/// AxumServer::start 失败原因
+#[derive(Debug, thiserror::Error)]
+pub enum StartError {
+    /// 配置校验未通过 (绑定端口前检查，包含全部问题)
+    #[error("{}", crate::proxy::config::describe_config_errors(.0))]
+    InvalidConfig(Vec<crate::proxy::config::ConfigError>),
+
+    /// 加载 TLS 证书、绑定地址等启动步骤失败
+    #[error("{0}")]
+    Startup(String),
+}
+
+impl From<String> for StartError {
+    fn from(message: String) -> Self {
+        StartError::Startup(message)
+    }
+}
+
 /// Axum 服务器实例
 #[derive(Clone)]
 pub struct AxumServer {
     shutdown_tx: Arc<tokio::sync::Mutex<Option<oneshot::Sender<()>>>>,
     custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    model_aliases: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
+    model_fallbacks: Arc<tokio::sync::RwLock<std::collections::HashMap<String, Vec<String>>>>,
     proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
     security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
     experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
-    pub cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>,
     pub is_running: Arc<RwLock<bool>>,
+    pub upstream: Arc<crate::proxy::upstream::client::UpstreamClient>, // [NEW] 与 AppState 共享的上游客户端
+    active_profile: Arc<RwLock<Option<String>>>, // [NEW] 与 AppState 共享的配置方案名称
+    stats: Arc<crate::proxy::stats::StatsCounters>, // [NEW] 与 AppState 共享的实时统计
+    token_manager: Arc<TokenManager>,
+    live_config: Arc<crate::proxy::live_config::LiveConfig>, // [NEW] 与 AppState 共享
 }
 
 impl AxumServer {
@@ -139,6 +259,14 @@ impl AxumServer {
             let mut m = self.custom_mapping.write().await;
             *m = config.custom_mapping.clone();
         }
+        {
+            let mut a = self.model_aliases.write().await;
+            *a = config.model_aliases.clone();
+        }
+        {
+            let mut f = self.model_fallbacks.write().await;
+            *f = config.model_fallbacks.clone();
+        }
         tracing::debug!("模型映射 (Custom) 已全量热更新");
     }
 
@@ -152,6 +280,8 @@ impl AxumServer {
     pub async fn update_security(&self, config: &crate::proxy::config::ProxyConfig) {
         let mut sec = self.security_state.write().await;
         *sec = crate::proxy::ProxySecurityConfig::from_proxy_config(config);
+        // 旧 key 的验证缓存随之失效 (轮换 API Key 同样经由此处)
+        crate::proxy::api_key_hash::clear_verified_keys();
         tracing::info!("反代服务安全配置已热更新");
     }
 
@@ -167,28 +297,93 @@ impl AxumServer {
         tracing::info!("实验性配置已热更新");
     }
 
+    /// 更新按请求读取的处理选项 (重试/超时/并发上限/流量拆分等，见 LiveConfig)
+    pub fn update_request_options(&self, config: &crate::proxy::config::ProxyConfig) {
+        self.live_config.apply(config);
+        tracing::info!("请求处理选项已热更新");
+    }
+
+    /// 热更新全部可在运行中生效的配置 (见 HOT_RELOADABLE_FIELDS)，下一个请求即按新配置处理
+    pub async fn apply_config(&self, config: &crate::proxy::config::ProxyConfig) {
+        self.update_mapping(config).await;
+        self.update_proxy(config.upstream_proxy.clone()).await;
+        self.update_security(config).await;
+        self.update_zai(config).await;
+        self.update_experimental(config).await;
+        self.update_request_options(config);
+    }
+
+    /// 记录当前使用的配置方案 (由 /healthz 返回)
+    pub async fn set_active_profile(&self, profile: Option<String>) {
+        let mut p = self.active_profile.write().await;
+        *p = profile.filter(|name| !name.is_empty());
+    }
+
+    /// 仪表盘实时统计 (与 GET /v1/stats 相同，供 Tauri 命令在进程内直接读取)
+    pub async fn dashboard_stats(&self) -> crate::proxy::stats::DashboardStats {
+        dashboard_snapshot(&self.stats, &self.token_manager).await
+    }
+
     pub async fn set_running(&self, running: bool) {
         let mut r = self.is_running.write().await;
         *r = running;
         tracing::info!("反代服务运行状态更新为: {}", running);
     }
 
-    /// 启动 Axum 服务器
+    /// 启动 Axum 服务器：监听地址与各项选项取自 `config`，其余为运行期依赖
     pub async fn start(
-        host: String,
-        port: u16,
+        config: &crate::proxy::config::ProxyConfig,
         token_manager: Arc<TokenManager>,
-        custom_mapping: std::collections::HashMap<String, String>,
-        _request_timeout: u64,
-        upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
-        security_config: crate::proxy::ProxySecurityConfig,
-        zai_config: crate::proxy::ZaiConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
-        experimental_config: crate::proxy::config::ExperimentalConfig,
         integration: crate::modules::integration::SystemManager,
         cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>,
-    ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
+        audit_logger: Option<Arc<crate::proxy::audit_log::AuditLogger>>,
+        audit_db: Option<Arc<crate::proxy::audit_db::AuditDb>>,
+    ) -> Result<(Self, tokio::task::JoinHandle<()>), StartError> {
+        // 绑定端口前校验配置，一次性返回全部问题
+        config.validate().map_err(StartError::InvalidConfig)?;
+
+        let host = config.get_bind_address().to_string();
+        let port = config.port;
+        let custom_mapping = config.custom_mapping.clone();
+        let model_aliases = config.model_aliases.clone();
+        let model_fallbacks = config.model_fallbacks.clone();
+        let upstream_proxy = config.upstream_proxy.clone();
+        let security_config = crate::proxy::ProxySecurityConfig::from_proxy_config(config);
+        let zai_config = config.zai.clone();
+        let experimental_config = config.experimental.clone();
+        let cors_allowed_origins = config.cors_allowed_origins.clone();
+        let metrics_enabled = config.metrics_enabled;
+        let batch_max_requests = config.batch_max_requests;
+        let tls_cert_path = config.tls_cert_path.clone();
+        let tls_key_path = config.tls_key_path.clone();
+        let max_request_body_bytes = config.max_request_body_bytes;
+        let shutdown_timeout_secs = config.shutdown_timeout_secs;
+        let racing_tokens = config.effective_racing_tokens();
+        let http_pool_max_idle_per_host = config.http_pool_max_idle_per_host;
+        let http_pool_idle_timeout_secs = config.http_pool_idle_timeout_secs;
+        let user_agent = config.user_agent.clone();
+        let forwarded_header_prefix = config.effective_forwarded_header_prefix();
+        let image_upload_url = config.image_upload_url.clone();
+        let max_inline_image_bytes = config.max_inline_image_bytes;
+        let sse_keepalive_interval_secs = config.sse_keepalive_interval_secs;
+        let sse_replay_window_secs = config.sse_replay_window_secs;
+        let sse_reconnect_grace_secs = config.sse_reconnect_grace_secs;
+        let session_ttl_secs = config.session_ttl_secs;
+        let capability_routing = config.capability_routing;
+        let shadow_endpoint = config.shadow_endpoint.clone();
+        let shadow_percentage = config.shadow_percentage;
+        let ip_allowlist = config.ip_allowlist.clone();
+        let trusted_proxies = config.trusted_proxies.clone();
+        let compress_requests = config.compress_requests;
+        let compress_responses = config.compress_responses;
+        let otel = config.otel.clone();
+        let alert_webhook_url = config.alert_webhook_url.clone();
+        let alert_on_consecutive_failures = config.alert_on_consecutive_failures;
+
         let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
+        let model_aliases_state = Arc::new(tokio::sync::RwLock::new(model_aliases));
+        let model_fallbacks_state = Arc::new(tokio::sync::RwLock::new(model_fallbacks));
 	        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
 	        let security_state = Arc::new(RwLock::new(security_config));
 	        let zai_state = Arc::new(RwLock::new(zai_config));
@@ -196,19 +391,58 @@ impl AxumServer {
 	        let zai_vision_mcp_state =
 	            Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
 	        let experimental_state = Arc::new(RwLock::new(experimental_config));
+        let active_profile_state = Arc::new(RwLock::new(None));
+        let stats = Arc::new(crate::proxy::stats::StatsCounters::default());
+        let live_config = Arc::new(crate::proxy::live_config::LiveConfig::new(config));
             let is_running_state = Arc::new(RwLock::new(true));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        // 所有 handler 共享同一个上游客户端 (连接池)
+        let upstream_client = Arc::new(crate::proxy::upstream::client::UpstreamClient::with_options(
+            Some(upstream_proxy.clone()),
+            http_pool_max_idle_per_host,
+            std::time::Duration::from_secs(http_pool_idle_timeout_secs),
+            &user_agent,
+        )
+        .with_compression(compress_requests));
+        token_manager.set_upstream(upstream_client.clone());
+        // [NEW] 连续失败告警 (未配置地址或阈值为 0 时关闭)
+        let alert_webhook = alert_webhook_url
+            .filter(|url| !url.trim().is_empty() && alert_on_consecutive_failures > 0)
+            .map(|url| {
+                tracing::info!("告警 Webhook 已启用: {} (连续 {} 次失败)", url, alert_on_consecutive_failures);
+                crate::proxy::alert::AlertWebhook::new(url, alert_on_consecutive_failures)
+            });
+        token_manager.set_alert_webhook(alert_webhook);
+        // [NEW] OpenTelemetry 导出 (未配置时卸载之前的导出器)
+        if let Err(e) = crate::proxy::otel::configure(otel.as_ref()) {
+            tracing::warn!("OpenTelemetry 初始化失败，链路追踪已禁用: {}", e);
+        }
+        // [NEW] 影子模式使用独立客户端 (同样的代理与连接池配置，端点替换为 shadow_endpoint)
+        let shadow = shadow_endpoint
+            .filter(|url| !url.trim().is_empty())
+            .map(|url| {
+                tracing::info!("影子模式已启用: {} ({}%)", url, shadow_percentage);
+                let client = crate::proxy::upstream::client::UpstreamClient::with_options(
+                    Some(upstream_proxy.clone()),
+                    http_pool_max_idle_per_host,
+                    std::time::Duration::from_secs(http_pool_idle_timeout_secs),
+                    &user_agent,
+                )
+                .with_base_urls(vec![url.trim_end_matches('/').to_string()]);
+                Arc::new(crate::proxy::shadow::ShadowMirror::new(Arc::new(client), shadow_percentage))
+            });
 
 	        let state = AppState {
 	            token_manager: token_manager.clone(),
 	            custom_mapping: custom_mapping_state.clone(),
+	            model_aliases: model_aliases_state.clone(),
+	            model_fallbacks: model_fallbacks_state.clone(),
 	            request_timeout: 300, // 5分钟超时
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(
                 std::collections::HashMap::new(),
             )),
             upstream_proxy: proxy_state.clone(),
-            upstream: Arc::new(crate::proxy::upstream::client::UpstreamClient::new(Some(
-                upstream_proxy.clone(),
-            ))),
+            upstream: upstream_client.clone(),
             zai: zai_state.clone(),
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
@@ -218,31 +452,109 @@ impl AxumServer {
             integration: integration.clone(),
             account_service: Arc::new(crate::modules::account_service::AccountService::new(integration.clone())),
             security: security_state.clone(),
-            cloudflared_state: cloudflared_state.clone(),
+            cloudflared_state,
             is_running: is_running_state.clone(),
+            audit_logger,
+            audit_db,
+            started_at: std::time::Instant::now(),
+            batch_max_requests,
+            live_config: live_config.clone(),
+            in_flight: in_flight.clone(),
+            racing_tokens,
+            in_flight_requests: Arc::new(crate::proxy::dedup::InFlightRequests::new()),
+            prompt_caches: Arc::new(crate::proxy::prompt_cache::PromptCaches::default()),
+            forwarded_header_prefix,
+            image_upload_url: image_upload_url.filter(|url| !url.trim().is_empty()),
+            max_inline_image_bytes,
+            sse_keepalive_interval_secs,
+            sse_replays: Arc::new(crate::proxy::sse_replay::SseReplays::new()),
+            sse_replay_window_secs,
+            sse_reconnect_grace_secs,
+            active_profile: active_profile_state.clone(),
+            session_affinity: Arc::new(crate::proxy::session_affinity::SessionAffinity::new(
+                std::time::Duration::from_secs(session_ttl_secs),
+            )),
+            capability_routing,
+            shadow,
+            consecutive_failures: token_manager.consecutive_failures(),
+            stats: stats.clone(),
+        };
+
+        // [NEW] 定期清理过期的会话亲和绑定 (停止监听时终止)
+        let affinity_cleanup_task = {
+            let affinity = state.session_affinity.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    let cleaned = affinity.cleanup_expired();
+                    if cleaned > 0 {
+                        tracing::debug!("Session affinity: removed {} expired binding(s)", cleaned);
+                    }
+                }
+            })
         };
 
 
+        // [NEW] 账号累计用量: 启动时从审计数据库恢复，之后定期回写 (停止监听时终止定时任务并再写一次)
+        let usage_db = state.audit_db.clone();
+        let mut usage_persist_task: Option<tokio::task::JoinHandle<()>> = None;
+        if let Some(db) = &usage_db {
+            let load_db = db.clone();
+            match tokio::task::spawn_blocking(move || load_db.load_account_usage()).await {
+                Ok(Ok(usage)) => {
+                    tracing::info!("已恢复 {} 个账号的累计用量", usage.len());
+                    token_manager.restore_usage(&usage);
+                }
+                Ok(Err(e)) => tracing::warn!("恢复账号累计用量失败: {}", e),
+                Err(e) => tracing::warn!("恢复账号累计用量失败: {}", e),
+            }
+
+            let tm = token_manager.clone();
+            let db = db.clone();
+            usage_persist_task = Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    persist_account_usage(&tm, &db).await;
+                }
+            }));
+        }
+
         // 构建路由 - 使用新架构的 handlers！
         use crate::proxy::handlers;
         use crate::proxy::middleware::{
             auth_middleware, admin_auth_middleware, monitor_middleware, 
-            service_status_middleware, cors_layer
+            service_status_middleware, cors_layer, request_id_middleware,
+            admin_api_key_middleware, preflight_no_content_middleware
         };
 
         // 1. 构建主 AI 代理路由 (遵循 auth_mode 配置)
         let proxy_routes = Router::new()
             // OpenAI Protocol
             .route("/v1/models", get(handlers::openai::handle_list_models))
+            .route("/v1/models/:model_id", get(handlers::openai::handle_get_model))
+            .route("/v1/stats", get(stats_handler)) // 仪表盘实时统计
+            .route("/v1/stats/stream", get(stats_stream_handler)) // 统计变化推送 (SSE)
             .route(
                 "/v1/chat/completions",
-                post(handlers::openai::handle_chat_completions),
+                post(handlers::openai::handle_chat_completions_dispatch),
             )
+            .route(
+                "/v1/chat/completions/batch",
+                post(handlers::openai::handle_chat_completions_batch),
+            )
+            .route(
+                "/v1/chat/completions/ws",
+                get(crate::proxy::server_ws::handle_chat_completions_ws),
+            ) // WebSocket 流式
             .route(
                 "/v1/completions",
                 post(handlers::openai::handle_completions),
             )
             .route("/v1/responses", post(handlers::openai::handle_completions)) // 兼容 Codex CLI
+            .route("/v1/embeddings", post(handlers::openai::handle_embeddings)) // 向量 API
             .route(
                 "/v1/images/generations",
                 post(handlers::openai::handle_images_generations),
@@ -255,6 +567,7 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API
+            .route("/v1/audio/speech", post(handlers::audio::handle_audio_speech)) // 语音合成 (未实现，返回 501)
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
@@ -290,12 +603,13 @@ impl AxumServer {
                 post(handlers::gemini::handle_count_tokens),
             ) // Specific route priority
             .route("/v1/models/detect", post(handlers::common::handle_detect_model))
+            .route("/v1/stats/history", get(stats_history_handler)) // 审计数据库查询
             .route("/internal/warmup", post(handlers::warmup::handle_warmup)) // 内部预热端点
             .route("/v1/api/event_logging/batch", post(silent_ok_handler))
             .route("/v1/api/event_logging", post(silent_ok_handler))
-            // 应用 AI 服务特定的层
-            .layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware))
-            .layer(axum::middleware::from_fn_with_state(state.clone(), monitor_middleware));
+            // 应用 AI 服务特定的层 (鉴权在外层，未通过鉴权的请求不计入监控与指标)
+            .layer(axum::middleware::from_fn_with_state(state.clone(), monitor_middleware))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware));
 
         // 2. 构建管理 API (强制鉴权)
         let admin_routes = Router::new()
@@ -342,6 +656,7 @@ impl AxumServer {
             .route("/proxy/stop", post(admin_stop_proxy_service))
             .route("/proxy/mapping", post(admin_update_model_mapping))
             .route("/proxy/api-key/generate", post(admin_generate_api_key))
+            .route("/proxy/api-key/rotate", post(admin_rotate_api_key))
             .route("/proxy/session-bindings/clear", post(admin_clear_proxy_session_bindings))
             .route("/proxy/rate-limits", delete(admin_clear_all_rate_limits))
             .route("/proxy/rate-limits/:accountId", delete(admin_clear_rate_limit))
@@ -415,23 +730,62 @@ impl AxumServer {
             // 应用管理特定鉴权层 (强制校验)
             .layer(axum::middleware::from_fn_with_state(state.clone(), admin_auth_middleware));
 
+        // 2.1 运行时 token 管理 API (独立 admin_api_key 鉴权)
+        let token_admin_routes = Router::new()
+            .route("/tokens", get(admin_list_tokens).post(admin_add_token))
+            .route("/tokens/:email", delete(admin_remove_token))
+            .route("/tokens/:email/reset", post(admin_reset_token))
+            .route("/tokens/:email/history", get(admin_token_history))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), admin_api_key_middleware));
+
         // 3. 整合并应用全局层
-        // 从环境变量读取 body 大小限制，默认 50MB
+        // body 大小限制: 环境变量 ABV_MAX_BODY_SIZE 优先，否则使用 max_request_body_bytes
         let max_body_size: usize = std::env::var("ABV_MAX_BODY_SIZE")
             .ok()
             .and_then(|s| s.parse().ok())
-            .unwrap_or(100 * 1024 * 1024); // 默认 100MB
+            .unwrap_or(max_request_body_bytes);
         tracing::info!("请求体大小限制: {} MB", max_body_size / 1024 / 1024);
 
         let app = Router::new()
             .nest("/api", admin_routes)
+            .nest("/admin", token_admin_routes)
             .merge(proxy_routes)
             // 公开路由 (无需鉴权)
             .route("/auth/callback", get(handle_oauth_callback))
-            // 应用全局监控与状态层 (外层)
+            // 健康检查 (供负载均衡器探测，无需鉴权)
+            .route("/healthz", get(healthz_handler))
+            .route("/healthz/ready", get(readiness_handler));
+
+        // Prometheus 指标 (metrics_enabled 为 false 时不注册路由)
+        let app = if metrics_enabled {
+            app.route("/metrics", get(metrics_handler))
+        } else {
+            app
+        };
+
+        // 应用全局监控与状态层 (外层)
+        let app = app
             .layer(axum::middleware::from_fn_with_state(state.clone(), service_status_middleware))
-            .layer(cors_layer())
-            .layer(DefaultBodyLimit::max(max_body_size)) // 放宽 body 大小限制
+            // 请求 ID (确保所有日志与响应都带上 x-request-id)
+            .layer(axum::middleware::from_fn(request_id_middleware));
+
+        // CORS (cors_allowed_origins 为空时不附加任何 CORS 头)
+        // 预检请求由 CorsLayer 统一应答 (覆盖全部路由)，返回 204
+        let app = match cors_layer(&cors_allowed_origins) {
+            Some(layer) => app
+                .layer(layer)
+                .layer(axum::middleware::from_fn(preflight_no_content_middleware)),
+            None => {
+                tracing::info!("CORS 未启用 (cors_allowed_origins 为空)");
+                app
+            }
+        };
+
+        // 请求体大小限制 (超限返回 413 + OpenAI 错误体)
+        let app = crate::proxy::middleware::with_body_limit(app, max_body_size);
+
+        // [NEW] 响应压缩 (按 Accept-Encoding 协商 gzip/zstd，SSE 除外)
+        let app = crate::proxy::middleware::with_compression(app, compress_responses)
             .with_state(state.clone());
 
         // 静态文件托管 (用于 Headless/Docker 模式)
@@ -446,13 +800,34 @@ impl AxumServer {
             app
         };
 
+        // [NEW] IP 白名单 (最外层，覆盖 CORS 预检与静态资源；白名单为空时直接放行)
+        if !ip_allowlist.is_empty() {
+            tracing::info!("IP 白名单已启用: {} 条规则", ip_allowlist.len());
+        }
+        let ip_filter = Arc::new(crate::proxy::middleware::IpFilter::new(ip_allowlist, trusted_proxies));
+        let app = app.layer(axum::middleware::from_fn_with_state(
+            ip_filter,
+            crate::proxy::middleware::ip_allowlist_middleware,
+        ));
+
+        // TLS (证书与私钥均配置时启用 HTTPS，否则保持明文 HTTP)
+        let tls_acceptor = match (&tls_cert_path, &tls_key_path) {
+            (Some(cert), Some(key)) => Some(crate::proxy::tls::load_tls_acceptor(cert, key)?),
+            (None, None) => None,
+            _ => {
+                tracing::warn!("tls_cert_path 与 tls_key_path 需同时配置，已回退为 HTTP");
+                None
+            }
+        };
+
         // 绑定地址
         let addr = format!("{}:{}", host, port);
         let listener = tokio::net::TcpListener::bind(&addr)
             .await
             .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
 
-        tracing::info!("反代服务器启动在 http://{}", addr);
+        let scheme = if tls_acceptor.is_some() { "https" } else { "http" };
+        tracing::info!("反代服务器启动在 {}://{}", scheme, addr);
 
         // 创建关闭通道
         let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
@@ -460,12 +835,18 @@ impl AxumServer {
         let server_instance = Self {
             shutdown_tx: Arc::new(tokio::sync::Mutex::new(Some(shutdown_tx))),
             custom_mapping: custom_mapping_state.clone(),
+            model_aliases: model_aliases_state,
+            model_fallbacks: model_fallbacks_state,
             proxy_state,
             security_state,
             zai_state,
             experimental: experimental_state.clone(),
-            cloudflared_state,
             is_running: is_running_state,
+            upstream: upstream_client,
+            active_profile: active_profile_state,
+            stats,
+            token_manager: token_manager.clone(),
+            live_config,
         };
 
         // 在新任务中启动服务器
@@ -478,16 +859,39 @@ impl AxumServer {
                 tokio::select! {
                     res = listener.accept() => {
                         match res {
-                            Ok((stream, _)) => {
-                                let io = TokioIo::new(stream);
-                                let service = TowerToHyperService::new(app.clone());
+                            Ok((stream, peer)) => {
+                                // 对端地址写入请求扩展 (ConnectInfo)，供 IP 白名单等使用
+                                let service = TowerToHyperService::new(tower::ServiceExt::map_request(
+                                    app.clone(),
+                                    move |mut req: axum::http::Request<hyper::body::Incoming>| {
+                                        req.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+                                        req
+                                    },
+                                ));
+                                let tls_acceptor = tls_acceptor.clone();
 
                                 tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, service)
-                                        .with_upgrades() // 支持 WebSocket (如果以后需要)
-                                        .await
-                                    {
+                                    let result = match tls_acceptor {
+                                        Some(acceptor) => match acceptor.accept(stream).await {
+                                            Ok(tls_stream) => {
+                                                http1::Builder::new()
+                                                    .serve_connection(TokioIo::new(tls_stream), service)
+                                                    .with_upgrades()
+                                                    .await
+                                            }
+                                            Err(e) => {
+                                                debug!("TLS 握手失败: {:?}", e);
+                                                return;
+                                            }
+                                        },
+                                        None => {
+                                            http1::Builder::new()
+                                                .serve_connection(TokioIo::new(stream), service)
+                                                .with_upgrades() // 支持 WebSocket
+                                                .await
+                                        }
+                                    };
+                                    if let Err(err) = result {
                                         debug!("连接处理结束或出错: {:?}", err);
                                     }
                                 });
@@ -499,6 +903,24 @@ impl AxumServer {
                     }
                     _ = &mut shutdown_rx => {
                         tracing::info!("反代服务器停止监听");
+                        // 不再接受新连接，等待进行中的请求 (含 SSE 流) 完成
+                        let pending = in_flight.load(std::sync::atomic::Ordering::SeqCst);
+                        if pending > 0 {
+                            tracing::info!("等待 {} 个在途请求完成 (最长 {}s)", pending, shutdown_timeout_secs);
+                            crate::proxy::inflight::wait_for_drain(
+                                &in_flight,
+                                std::time::Duration::from_secs(shutdown_timeout_secs),
+                            )
+                            .await;
+                        }
+                        affinity_cleanup_task.abort();
+                        if let Some(task) = usage_persist_task.take() {
+                            task.abort();
+                        }
+                        if let Some(db) = &usage_db {
+                            persist_account_usage(&token_manager, db).await;
+                        }
+                        crate::proxy::otel::shutdown();
                         break;
                     }
                 }
@@ -524,11 +946,251 @@ impl AxumServer {
 // ===== API 处理器 (旧代码已移除，由 src/proxy/handlers/* 接管) =====
 
 /// 健康检查处理器
-async fn health_check_handler() -> Response {
-    Json(serde_json::json!({
-        "status": "ok"
-    }))
-    .into_response()
+async fn health_check_handler(State(state): State<AppState>) -> Response {
+    healthz_handler(State(state)).await
+}
+
+/// 健康检查响应
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: String,
+    tokens_total: usize,
+    tokens_available: usize,
+    tokens_blacklisted: usize,
+    uptime_secs: u64,
+    circuit_breakers: crate::proxy::token_manager::CircuitBreakerCounts,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+}
+
+impl HealthResponse {
+    fn new(
+        availability: crate::proxy::token_manager::TokenAvailability,
+        uptime_secs: u64,
+        circuit_breakers: crate::proxy::token_manager::CircuitBreakerCounts,
+        profile: Option<String>,
+    ) -> Self {
+        let status = if availability.available > 0 { "ok" } else { "unavailable" };
+        Self {
+            status: status.to_string(),
+            tokens_total: availability.total,
+            tokens_available: availability.available,
+            tokens_blacklisted: availability.blacklisted,
+            uptime_secs,
+            circuit_breakers,
+            profile,
+        }
+    }
+
+    /// 至少一个账号可用时返回 200，否则 503 (负载均衡器据此摘除实例)
+    fn status_code(&self) -> StatusCode {
+        if self.tokens_available > 0 {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// 详细健康状态 (账号可用性 + 运行时长)
+async fn healthz_handler(State(state): State<AppState>) -> Response {
+    let availability = state.token_manager.availability().await;
+    let health = HealthResponse::new(
+        availability,
+        state.started_at.elapsed().as_secs(),
+        state.token_manager.circuit_breaker_counts(),
+        state.active_profile.read().await.clone(),
+    );
+    (health.status_code(), Json(health)).into_response()
+}
+
+#[derive(Deserialize)]
+struct StatsHistoryQuery {
+    #[serde(default = "default_history_limit")]
+    limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    100
+}
+
+/// 将账号累计用量写回审计数据库
+async fn persist_account_usage(token_manager: &TokenManager, db: &Arc<crate::proxy::audit_db::AuditDb>) {
+    let usage = token_manager.usage_report();
+    if usage.is_empty() {
+        return;
+    }
+    let db = db.clone();
+    match tokio::task::spawn_blocking(move || db.save_account_usage(&usage)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!("保存账号累计用量失败: {}", e),
+        Err(e) => tracing::warn!("保存账号累计用量失败: {}", e),
+    }
+}
+
+/// 查询审计数据库中最近 N 条请求记录
+async fn stats_history_handler(
+    State(state): State<AppState>,
+    Query(query): Query<StatsHistoryQuery>,
+) -> Response {
+    let Some(db) = state.audit_db.clone() else {
+        return api_error(StatusCode::NOT_FOUND, "Audit database is not enabled (set db_path)")
+            .into_response();
+    };
+    let limit = query.limit.clamp(1, 1000);
+
+    match tokio::task::spawn_blocking(move || db.recent(limit)).await {
+        Ok(Ok(records)) => Json(records).into_response(),
+        Ok(Err(e)) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            .into_response(),
+    }
+}
+
+/// Prometheus 指标导出
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let availability = state.token_manager.availability().await;
+    let metrics = crate::proxy::metrics::metrics();
+    metrics.set_active_tokens(availability.available);
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+        .into_response()
+}
+
+/// GET /v1/stats：仪表盘实时统计
+async fn stats_handler(State(state): State<AppState>) -> Json<crate::proxy::stats::DashboardStats> {
+    Json(dashboard_snapshot(&state.stats, &state.token_manager).await)
+}
+
+async fn dashboard_snapshot(
+    stats: &crate::proxy::stats::StatsCounters,
+    token_manager: &TokenManager,
+) -> crate::proxy::stats::DashboardStats {
+    let availability = token_manager.availability().await;
+    let mut snapshot = stats.snapshot(availability.available, availability.blacklisted);
+    snapshot.accounts = token_manager.usage_report();
+    snapshot
+}
+
+/// GET /v1/stats/stream：统计变化时以 SSE 推送 (前端 EventSource 订阅)
+async fn stats_stream_handler(State(state): State<AppState>) -> Response {
+    let availability = state.token_manager.availability().await;
+    state
+        .stats
+        .sse(availability.available, availability.blacklisted)
+        .into_response()
+}
+
+/// 就绪探测：仅返回能否处理请求
+async fn readiness_handler(State(state): State<AppState>) -> Response {
+    let availability = state.token_manager.availability().await;
+    if availability.available > 0 {
+        StatusCode::OK.into_response()
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE.into_response()
+    }
+}
+
+/// GET /admin/tokens
+async fn admin_list_tokens(State(state): State<AppState>) -> Response {
+    Json(state.token_manager.list_tokens().await).into_response()
+}
+
+#[derive(Deserialize)]
+struct AdminAddTokenRequest {
+    email: String,
+    access_token: String,
+    #[serde(default)]
+    refresh_token: String,
+    #[serde(default = "default_admin_token_expires_in")]
+    expires_in: i64,
+    #[serde(default)]
+    project_id: Option<String>,
+    #[serde(default)]
+    subscription_tier: Option<String>,
+    /// 每日 token 预算 (可选)
+    #[serde(default)]
+    daily_token_budget: Option<u64>,
+    /// 添加前先发送探测请求验证凭据
+    #[serde(default)]
+    validate: bool,
+}
+
+fn default_admin_token_expires_in() -> i64 {
+    3600
+}
+
+/// POST /admin/tokens - 运行时添加账号 (仅内存，立即参与调度)
+async fn admin_add_token(
+    State(state): State<AppState>,
+    Json(payload): Json<AdminAddTokenRequest>,
+) -> Response {
+    if payload.email.trim().is_empty() || payload.access_token.trim().is_empty() {
+        return api_error(StatusCode::BAD_REQUEST, "email and access_token are required")
+            .into_response();
+    }
+
+    let token = crate::proxy::token_manager::ProxyToken {
+        account_id: format!("runtime-{}", uuid::Uuid::new_v4()),
+        access_token: payload.access_token,
+        refresh_token: payload.refresh_token,
+        expires_in: payload.expires_in,
+        timestamp: chrono::Utc::now().timestamp() + payload.expires_in,
+        email: payload.email.trim().to_string(),
+        account_path: std::path::PathBuf::new(),
+        project_id: payload.project_id,
+        subscription_tier: payload.subscription_tier,
+        remaining_quota: None,
+        protected_models: std::collections::HashSet::new(),
+        health_score: 1.0,
+        history: Default::default(),
+        daily_token_budget: payload.daily_token_budget,
+        budget_reset_at: None,
+    };
+    let email = token.email.clone();
+    let account_id = match state.token_manager.add_token(token, payload.validate).await {
+        Ok(account_id) => account_id,
+        Err(e) => {
+            return api_error(StatusCode::UNPROCESSABLE_ENTITY, e).into_response();
+        }
+    };
+
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "account_id": account_id, "email": email })),
+    )
+        .into_response()
+}
+
+/// DELETE /admin/tokens/:email
+async fn admin_remove_token(State(state): State<AppState>, Path(email): Path<String>) -> Response {
+    if state.token_manager.remove_token(&email) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        api_error(StatusCode::NOT_FOUND, format!("Token not found: {}", email))
+            .into_response()
+    }
+}
+
+/// POST /admin/tokens/:email/reset - 重置熔断与限流状态
+async fn admin_reset_token(State(state): State<AppState>, Path(email): Path<String>) -> Response {
+    if state.token_manager.reset_token(&email) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        api_error(StatusCode::NOT_FOUND, format!("Token not found: {}", email))
+            .into_response()
+    }
+}
+
+/// GET /admin/tokens/:email/history - 最近请求记录 (旧 -> 新，最多 100 条)
+async fn admin_token_history(State(state): State<AppState>, Path(email): Path<String>) -> Response {
+    match state.token_manager.request_history(&email) {
+        Some(history) => Json(history).into_response(),
+        None => api_error(StatusCode::NOT_FOUND, format!("Token not found: {}", email))
+            .into_response(),
+    }
 }
 
 /// 静默成功处理器 (用于拦截遥测日志等)
@@ -548,10 +1210,7 @@ async fn admin_list_accounts(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let accounts = state.account_service.list_accounts().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     let current_id = state.account_service.get_current_id().ok().flatten();
@@ -600,7 +1259,7 @@ async fn admin_get_current_account(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     let response = if let Some(id) = current_id {
@@ -652,10 +1311,7 @@ async fn admin_add_account(
     Json(payload): Json<AddAccountRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let account = state.account_service.add_account(&payload.refresh_token).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // [FIX #1166] 账号变动后立即重新加载 TokenManager
@@ -664,7 +1320,7 @@ async fn admin_add_account(
     }
 
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(to_account_response(&account, &current_id)))
 }
@@ -674,10 +1330,7 @@ async fn admin_delete_account(
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.account_service.delete_account(&account_id).map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // [FIX #1166] 账号变动后立即重新加载 TokenManager
@@ -701,12 +1354,7 @@ async fn admin_switch_account(
     {
         let switching = state.switching.read().await;
         if *switching {
-            return Err((
-                StatusCode::CONFLICT,
-                Json(ErrorResponse {
-                    error: "Another switch operation is already in progress".to_string(),
-                }),
-            ));
+            return Err(api_error(StatusCode::CONFLICT, "Another switch operation is already in progress"));
         }
     }
 
@@ -739,10 +1387,7 @@ async fn admin_switch_account(
         }
         Err(e) => {
             logger::log_error(&format!("[API] Account switch failed: {}", e));
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse { error: e }),
-            ))
+            Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
         }
     }
 }
@@ -750,7 +1395,7 @@ async fn admin_switch_account(
 async fn admin_refresh_all_quotas() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     logger::log_info("[API] Starting refresh of all account quotas");
     let stats = account::refresh_all_quotas_logic().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     Ok(Json(stats))
@@ -762,7 +1407,7 @@ async fn admin_prepare_oauth_url(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let url = state.account_service.prepare_oauth_url().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(serde_json::json!({ "url": url })))
 }
@@ -771,10 +1416,10 @@ async fn admin_start_oauth_login(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let account = state.account_service.start_oauth_login().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(to_account_response(&account, &current_id)))
 }
@@ -783,10 +1428,10 @@ async fn admin_complete_oauth_login(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let account = state.account_service.complete_oauth_login().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(to_account_response(&account, &current_id)))
 }
@@ -809,10 +1454,7 @@ async fn admin_submit_oauth_code(
     Json(payload): Json<SubmitCodeRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.account_service.submit_oauth_code(payload.code, payload.state).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(StatusCode::OK)
 }
@@ -830,10 +1472,7 @@ async fn admin_bind_device(
     Json(payload): Json<BindDeviceRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = account::bind_device_profile(&account_id, &payload.mode).map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e }),
-        )
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     Ok(Json(serde_json::json!({
@@ -861,9 +1500,9 @@ async fn admin_get_logs(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let limit = if params.limit == 0 { 50 } else { params.limit };
     let total = proxy_db::get_logs_count_filtered(&params.filter, params.errors_only)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     let logs = proxy_db::get_logs_filtered(&params.filter, params.errors_only, limit, params.offset)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     Ok(Json(serde_json::json!({
         "total": total,
@@ -879,7 +1518,7 @@ async fn admin_get_logs(
 
 async fn admin_get_config() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let cfg = config::load_app_config().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(cfg))
 }
@@ -897,7 +1536,7 @@ async fn admin_save_config(
     let new_config = payload.config;
     // 1. 持久化
     config::save_app_config(&new_config).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // 2. 热更新内存状态
@@ -913,6 +1552,14 @@ async fn admin_save_config(
         let mut mapping = state.custom_mapping.write().await;
         *mapping = new_config.clone().proxy.custom_mapping;
     }
+    {
+        let mut aliases = state.model_aliases.write().await;
+        *aliases = new_config.proxy.model_aliases.clone();
+    }
+    {
+        let mut fallbacks = state.model_fallbacks.write().await;
+        *fallbacks = new_config.proxy.model_fallbacks.clone();
+    }
     
     // 更新上游代理
     {
@@ -938,6 +1585,9 @@ async fn admin_save_config(
         *exp = new_config.clone().proxy.experimental;
     }
 
+    // 更新按请求读取的处理选项 (重试/超时/并发上限等)
+    state.live_config.apply(&new_config.proxy);
+
     Ok(StatusCode::OK)
 }
 
@@ -1013,13 +1663,13 @@ async fn admin_update_model_mapping(
     // 2. 持久化到硬盘 (修复 #1149)
     // 加载当前配置，更新 mapping，然后保存
     let mut app_config = crate::modules::config::load_app_config().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     
     app_config.proxy.custom_mapping = config.custom_mapping;
     
     crate::modules::config::save_app_config(&app_config).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     logger::log_info("[API] 模型映射已通过 API 热更新并保存");
@@ -1031,6 +1681,29 @@ async fn admin_generate_api_key() -> impl IntoResponse {
     Json(new_key)
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RotateApiKeyRequest {
+    #[serde(default)]
+    new_key: Option<String>,
+}
+
+/// 轮换 API Key (Web 模式)，返回仅此一次可见的新 key 明文
+async fn admin_rotate_api_key(
+    State(state): State<AppState>,
+    Json(payload): Json<RotateApiKeyRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let (new_key, config) = crate::commands::proxy::persist_rotated_api_key(payload.new_key)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?;
+    {
+        let mut security = state.security.write().await;
+        *security = crate::proxy::ProxySecurityConfig::from_proxy_config(&config.proxy);
+    }
+    crate::proxy::api_key_hash::clear_verified_keys();
+    logger::log_info("[API] API Key 已轮换");
+    Ok(Json(new_key))
+}
+
 async fn admin_clear_proxy_session_bindings(
     State(state): State<AppState>,
 ) -> impl IntoResponse {
@@ -1089,7 +1762,7 @@ async fn admin_fetch_zai_models(
     // 目前前端 fetch_zai_models 本质上也是一个工具函数，
     // 我们可以在后端通过 reqwest 代理抓取。
     let zai_config = payload.get("zai").ok_or_else(|| {
-        (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Missing zai config".to_string() }))
+        api_error(StatusCode::BAD_REQUEST, "Missing zai config")
     })?;
     
     let api_key = zai_config.get("api_key").and_then(|v| v.as_str()).unwrap_or("");
@@ -1101,10 +1774,10 @@ async fn admin_fetch_zai_models(
         .header("Authorization", format!("Bearer {}", api_key))
         .send()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let data: serde_json::Value = resp.json().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
     })?;
 
     // 提取模型 ID 列表
@@ -1141,8 +1814,8 @@ async fn admin_get_proxy_logs_count_filtered(
 
     match res {
         Ok(Ok(count)) => Ok(Json(count)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1165,8 +1838,8 @@ async fn admin_get_proxy_log_detail(
 
     match res {
         Ok(Ok(log)) => Ok(Json(log)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1197,8 +1870,8 @@ async fn admin_get_proxy_logs_filtered(
 
     match res {
         Ok(Ok(logs)) => Ok(Json(logs)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1218,20 +1891,20 @@ async fn admin_get_data_dir_path() -> impl IntoResponse {
 
 async fn admin_should_check_updates() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let settings = crate::modules::update_checker::load_update_settings()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     let should = crate::modules::update_checker::should_check_for_updates(&settings);
     Ok(Json(should))
 }
 
 async fn admin_get_antigravity_path() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let path = crate::commands::get_antigravity_path(Some(true)).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(Json(path))
 }
 
 async fn admin_get_antigravity_args() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let args = crate::commands::get_antigravity_args().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(Json(args))
 }
 
@@ -1252,8 +1925,8 @@ async fn admin_get_token_stats_hourly(Query(p): Query<StatsPeriodQuery>) -> Resu
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1265,8 +1938,8 @@ async fn admin_get_token_stats_daily(Query(p): Query<StatsPeriodQuery>) -> Resul
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1278,8 +1951,8 @@ async fn admin_get_token_stats_weekly(Query(p): Query<StatsPeriodQuery>) -> Resu
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1291,8 +1964,8 @@ async fn admin_get_token_stats_by_account(Query(p): Query<StatsPeriodQuery>) ->
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1304,8 +1977,8 @@ async fn admin_get_token_stats_summary(Query(p): Query<StatsPeriodQuery>) -> Res
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1317,8 +1990,8 @@ async fn admin_get_token_stats_by_model(Query(p): Query<StatsPeriodQuery>) -> Re
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1329,8 +2002,8 @@ async fn admin_get_token_stats_model_trend_hourly() -> Result<impl IntoResponse,
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1341,8 +2014,8 @@ async fn admin_get_token_stats_model_trend_daily() -> Result<impl IntoResponse,
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1353,8 +2026,8 @@ async fn admin_get_token_stats_account_trend_hourly() -> Result<impl IntoRespons
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1365,8 +2038,8 @@ async fn admin_get_token_stats_account_trend_daily() -> Result<impl IntoResponse
 
     match res {
         Ok(Ok(stats)) => Ok(Json(stats)),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1405,13 +2078,13 @@ async fn admin_get_update_settings() -> impl IntoResponse {
 
 async fn admin_check_for_updates() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let info = crate::modules::update_checker::check_for_updates().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(Json(info))
 }
 
 async fn admin_update_last_check_time() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::modules::update_checker::update_last_check_time()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(StatusCode::OK)
 }
 
@@ -1451,7 +2124,7 @@ async fn admin_delete_accounts(
     Json(payload): Json<BulkDeleteRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::modules::account::delete_accounts(&payload.account_ids)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(StatusCode::OK)
 }
 
@@ -1466,7 +2139,7 @@ async fn admin_reorder_accounts(
     Json(payload): Json<ReorderRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::modules::account::reorder_accounts(&payload.account_ids)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     
     // [FIX #1166] 排序变动后立即重新加载 TokenManager
     if let Err(e) = state.token_manager.load_accounts().await {
@@ -1480,13 +2153,13 @@ async fn admin_fetch_account_quota(
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let mut account = crate::modules::load_account(&account_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     
     let quota = crate::modules::account::fetch_quota_with_retry(&mut account).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     
     crate::modules::update_account_quota(&account_id, quota.clone())
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     
     Ok(Json(quota))
 }
@@ -1504,7 +2177,7 @@ async fn admin_toggle_proxy_status(
     Json(payload): Json<ToggleProxyRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::modules::account::toggle_proxy_status(&account_id, payload.enable, payload.reason.as_deref())
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     // 同步到运行中的反代服务
     let _ = state.token_manager.reload_account(&account_id).await;
@@ -1514,7 +2187,7 @@ async fn admin_toggle_proxy_status(
 
 async fn admin_warm_up_all_accounts() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = crate::commands::warm_up_all_accounts().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(Json(result))
 }
 
@@ -1522,7 +2195,7 @@ async fn admin_warm_up_account(
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = crate::commands::warm_up_account(account_id).await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(Json(result))
 }
 
@@ -1542,8 +2215,8 @@ async fn admin_save_text_file(
 
     match res {
         Ok(Ok(_)) => Ok(StatusCode::OK),
-        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() }))),
+        Ok(Err(e)) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
 
@@ -1551,7 +2224,7 @@ async fn admin_save_http_api_settings(
     Json(payload): Json<crate::modules::http_api::HttpApiSettings>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::modules::http_api::save_settings(&payload)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
     Ok(StatusCode::OK)
 }
 
@@ -1560,7 +2233,7 @@ async fn admin_cloudflared_get_status(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.cloudflared_state.ensure_manager().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     let lock = state.cloudflared_state.manager.read().await;
     if let Some(manager) = lock.as_ref() {
@@ -1582,15 +2255,15 @@ async fn admin_cloudflared_install(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.cloudflared_state.ensure_manager().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     let lock = state.cloudflared_state.manager.read().await;
     if let Some(manager) = lock.as_ref() {
         let status = manager.install().await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
         Ok(Json(status))
     } else {
-        Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: "Manager not initialized".to_string() })))
+        Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "Manager not initialized"))
     }
 }
 
@@ -1605,15 +2278,15 @@ async fn admin_cloudflared_start(
     Json(payload): Json<CloudflaredStartRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.cloudflared_state.ensure_manager().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     let lock = state.cloudflared_state.manager.read().await;
     if let Some(manager) = lock.as_ref() {
         let status = manager.start(payload.config).await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
         Ok(Json(status))
     } else {
-        Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: "Manager not initialized".to_string() })))
+        Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "Manager not initialized"))
     }
 }
 
@@ -1621,15 +2294,15 @@ async fn admin_cloudflared_stop(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     state.cloudflared_state.ensure_manager().await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     let lock = state.cloudflared_state.manager.read().await;
     if let Some(manager) = lock.as_ref() {
         let status = manager.stop().await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
         Ok(Json(status))
     } else {
-        Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: "Manager not initialized".to_string() })))
+        Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "Manager not initialized"))
     }
 }
 
@@ -1640,7 +2313,7 @@ async fn admin_get_device_profiles(
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let profiles = account::get_device_profiles(&account_id).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(profiles))
 }
@@ -1650,7 +2323,7 @@ async fn admin_list_device_versions(
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let profiles = account::get_device_profiles(&account_id).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(profiles))
 }
@@ -1666,14 +2339,14 @@ async fn admin_bind_device_profile_with_profile(
     Json(profile): Json<crate::models::account::DeviceProfile>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = account::bind_device_profile_with_profile(&account_id, profile, None).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(result))
 }
 
 async fn admin_restore_original_device() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let msg = account::restore_original_device().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(msg))
 }
@@ -1683,7 +2356,7 @@ async fn admin_restore_device_version(
     Path((account_id, version_id)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let profile = account::restore_device_version(&account_id, &version_id).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(profile))
 }
@@ -1693,7 +2366,7 @@ async fn admin_delete_device_version(
     Path((account_id, version_id)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     account::delete_device_version(&account_id, &version_id).map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -1702,7 +2375,7 @@ async fn admin_open_folder() -> Result<impl IntoResponse, (StatusCode, Json<Erro
     // Note: In Web mode, this may not actually open a local folder unless the backend handles it.
     // For ABV_Refactor, the backend should use opener to open it on the server (the desktop).
     crate::commands::open_data_folder().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(StatusCode::OK)
 }
@@ -1713,14 +2386,14 @@ async fn admin_import_v1_accounts(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let accounts = migration::import_from_v1().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     
     // [FIX #1166] 导入后立即加载
     let _ = state.token_manager.load_accounts().await;
 
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     let responses: Vec<AccountResponse> = accounts.iter().map(|a| to_account_response(a, &current_id)).collect();
     Ok(Json(responses))
@@ -1730,14 +2403,14 @@ async fn admin_import_from_db(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let account = migration::import_from_db().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // [FIX #1166] 导入后立即加载
     let _ = state.token_manager.load_accounts().await;
 
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(to_account_response(&account, &current_id)))
 }
@@ -1752,14 +2425,14 @@ async fn admin_import_custom_db(
     Json(payload): Json<CustomDbRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let account = migration::import_from_custom_db_path(payload.path).await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // [FIX #1166] 导入后立即加载
     let _ = state.token_manager.load_accounts().await;
 
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(to_account_response(&account, &current_id)))
 }
@@ -1775,7 +2448,7 @@ async fn admin_sync_account_from_db(
         }
     };
     let curr_account = account::get_current_account().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     if let Some(acc) = curr_account {
@@ -1785,14 +2458,14 @@ async fn admin_sync_account_from_db(
     }
 
     let account = migration::import_from_db().await.map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
 
     // [FIX #1166] 同步后立即重新加载 TokenManager
     let _ = state.token_manager.load_accounts().await;
 
     let current_id = state.account_service.get_current_id().map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e }))
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, e)
     })?;
     Ok(Json(Some(to_account_response(&account, &current_id))))
 }
@@ -1812,7 +2485,7 @@ async fn admin_get_cli_sync_status(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::proxy::cli_sync::get_cli_sync_status(payload.app_type, payload.proxy_url).await
         .map(Json)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
 }
 
 #[derive(Deserialize)]
@@ -1828,7 +2501,7 @@ async fn admin_execute_cli_sync(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::proxy::cli_sync::execute_cli_sync(payload.app_type, payload.proxy_url, payload.api_key).await
         .map(|_| StatusCode::OK)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
 }
 
 #[derive(Deserialize)]
@@ -1842,7 +2515,7 @@ async fn admin_execute_cli_restore(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::proxy::cli_sync::execute_cli_restore(payload.app_type).await
         .map(|_| StatusCode::OK)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
 }
 
 #[derive(Deserialize)]
@@ -1857,7 +2530,7 @@ async fn admin_get_cli_config_content(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     crate::proxy::cli_sync::get_cli_config_content(payload.app_type, payload.file_name).await
         .map(Json)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))
 }
 
 #[derive(Deserialize)]
@@ -1984,7 +2657,7 @@ async fn admin_prepare_oauth_url_web(
     
     // 初始化授权流状态，以及后台处理器
     let (auth_url, mut code_rx) = crate::modules::oauth_server::prepare_oauth_flow_manually(redirect_uri.clone(), state_str.clone())
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
 
     // 启动后台任务处理回调/手动提交的代码
     let token_manager = state.token_manager.clone();
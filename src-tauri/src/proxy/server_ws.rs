@@ -0,0 +1,172 @@
+// WebSocket 流式接口: GET /v1/chat/completions/ws
+// 客户端每发送一条 Chat Completions 请求 (JSON 文本帧)，服务端复用 HTTP 处理流程，
+// 将每个 SSE chunk 作为一个文本帧推送，最后发送 "[DONE]"
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Json, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use crate::proxy::handlers::openai::handle_chat_completions;
+use crate::proxy::server::AppState;
+
+pub async fn handle_chat_completions_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    // 握手请求头 (透传前缀匹配的请求头) 对该连接上的所有请求生效
+    ws.on_upgrade(move |socket| serve_socket(socket, state, headers))
+}
+
+async fn serve_socket(mut socket: WebSocket, state: AppState, headers: HeaderMap) {
+    while let Some(msg) = socket.recv().await {
+        let text = match msg {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => break,
+            // Ping/Pong 由 axum 自动处理，二进制帧忽略
+            Ok(_) => continue,
+        };
+
+        let mut body: Value = match serde_json::from_str(&text) {
+            Ok(v) => v,
+            Err(e) => {
+                if send_error(&mut socket, 400, &format!("Invalid request: {}", e)).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+        if let Some(obj) = body.as_object_mut() {
+            obj.insert("stream".to_string(), Value::Bool(true));
+        }
+
+        match stream_completion(&mut socket, &state, &headers, body).await {
+            Ok(true) => {}
+            // 客户端已断开：丢弃上游流后退出
+            Ok(false) | Err(_) => break,
+        }
+    }
+    tracing::debug!("[WS] Connection closed");
+}
+
+/// 处理单个请求，返回 Ok(false) 表示客户端在流式过程中断开
+async fn stream_completion(
+    socket: &mut WebSocket,
+    state: &AppState,
+    headers: &HeaderMap,
+    body: Value,
+) -> Result<bool, axum::Error> {
+    let response = match handle_chat_completions(State(state.clone()), headers.clone(), Json(body)).await {
+        Ok(resp) => resp.into_response(),
+        Err(err) => err.into_response(),
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap_or_default();
+        send_error(socket, status.as_u16(), &String::from_utf8_lossy(&bytes)).await?;
+        return Ok(true);
+    }
+
+    let mut upstream = response.into_body().into_data_stream();
+    let mut decoder = SseDataDecoder::default();
+
+    loop {
+        tokio::select! {
+            chunk = upstream.next() => {
+                let Some(chunk) = chunk else { break };
+                let chunk = match chunk {
+                    Ok(c) => c,
+                    Err(e) => {
+                        send_error(socket, 502, &e.to_string()).await?;
+                        return Ok(true);
+                    }
+                };
+                for data in decoder.push(&chunk) {
+                    socket.send(Message::Text(data)).await?;
+                }
+            }
+            // 流式过程中监听客户端：断开时直接返回，upstream 随之 drop，取消上游请求
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        tracing::info!("[WS] Client disconnected mid-stream, cancelling upstream");
+                        return Ok(false);
+                    }
+                    Some(Ok(_)) => {
+                        tracing::debug!("[WS] Ignoring client message while streaming");
+                    }
+                }
+            }
+        }
+    }
+
+    // 上游流结束时 SSE 中通常已包含 [DONE]；错误中断的流可能没有
+    if !decoder.done_seen {
+        socket.send(Message::Text("[DONE]".to_string())).await?;
+    }
+    Ok(true)
+}
+
+async fn send_error(socket: &mut WebSocket, status: u16, message: &str) -> Result<(), axum::Error> {
+    let frame = json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "code": status
+        }
+    });
+    socket.send(Message::Text(frame.to_string())).await
+}
+
+/// 将 SSE 字节流拆分为 `data:` 负载 (跨 chunk 的半行会缓存到下次)
+#[derive(Default)]
+struct SseDataDecoder {
+    buffer: String,
+    done_seen: bool,
+}
+
+impl SseDataDecoder {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+
+        let mut out = Vec::new();
+        while let Some(pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=pos).collect();
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                // 空行 / 心跳注释 (": ping") / event: 行
+                continue;
+            };
+            let data = data.trim();
+            if data.is_empty() {
+                continue;
+            }
+            if data == "[DONE]" {
+                self.done_seen = true;
+            }
+            out.push(data.to_string());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoder_splits_frames_across_chunks() {
+        let mut decoder = SseDataDecoder::default();
+        assert!(decoder.push(b"data: {\"a\":").is_empty());
+        assert_eq!(decoder.push(b"1}\n\n: ping\n\ndata: {\"b\":2}\n\n"), vec!["{\"a\":1}", "{\"b\":2}"]);
+        assert!(!decoder.done_seen);
+        assert_eq!(decoder.push(b"data: [DONE]\n\n"), vec!["[DONE]"]);
+        assert!(decoder.done_seen);
+    }
+}
@@ -0,0 +1,100 @@
+// 会话账号亲和 - 客户端显式携带会话 ID (x-session-id 请求头或 body.session_id) 时，
+// 同一会话固定使用同一账号，保持 Gemini sessionId 对应的上下文；空闲超过 TTL 后失效
+use dashmap::DashMap;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+/// 会话 ID 请求头
+pub const SESSION_ID_HEADER: &str = "x-session-id";
+
+/// session_id -> (账号邮箱, 最近使用时间)
+pub struct SessionAffinity {
+    entries: DashMap<String, (String, Instant)>,
+    ttl: Duration,
+}
+
+impl SessionAffinity {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// 会话绑定的账号邮箱；已过期的绑定会被移除
+    pub fn get(&self, session_id: &str) -> Option<String> {
+        let (email, last_used) = self.entries.get(session_id).map(|e| e.value().clone())?;
+        if last_used.elapsed() >= self.ttl {
+            self.entries.remove(session_id);
+            return None;
+        }
+        Some(email)
+    }
+
+    /// 绑定 (或续期) 会话与账号
+    pub fn bind(&self, session_id: &str, email: &str) {
+        self.entries
+            .insert(session_id.to_string(), (email.to_string(), Instant::now()));
+    }
+
+    /// 移除全部过期绑定，返回移除数量
+    pub fn cleanup_expired(&self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, (_, last_used)| last_used.elapsed() < self.ttl);
+        before - self.entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// 提取客户端显式指定的会话 ID (请求头优先)
+pub fn explicit_session_id(headers: &axum::http::HeaderMap, body: &Value) -> Option<String> {
+    headers
+        .get(SESSION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| body.get("session_id").and_then(|v| v.as_str()))
+        .map(str::trim)
+        .filter(|sid| !sid.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_session_id_sources() {
+        let mut headers = axum::http::HeaderMap::new();
+        let body = serde_json::json!({"session_id": "from-body"});
+        assert_eq!(explicit_session_id(&headers, &body).as_deref(), Some("from-body"));
+
+        headers.insert(SESSION_ID_HEADER, "from-header".parse().unwrap());
+        assert_eq!(explicit_session_id(&headers, &body).as_deref(), Some("from-header"));
+
+        let empty = axum::http::HeaderMap::new();
+        assert_eq!(explicit_session_id(&empty, &serde_json::json!({"session_id": "  "})), None);
+    }
+
+    #[test]
+    fn test_affinity_expires_after_ttl() {
+        let affinity = SessionAffinity::new(Duration::from_millis(50));
+        affinity.bind("s1", "a@example.com");
+        assert_eq!(affinity.get("s1").as_deref(), Some("a@example.com"));
+
+        // 重新绑定即切换账号
+        affinity.bind("s1", "b@example.com");
+        assert_eq!(affinity.get("s1").as_deref(), Some("b@example.com"));
+
+        affinity.bind("s2", "c@example.com");
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(affinity.get("s1"), None);
+        assert_eq!(affinity.cleanup_expired(), 1);
+        assert!(affinity.is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+// 影子模式 - 按比例把请求镜像到备用上游，响应直接丢弃，只记录耗时与错误
+// 镜像请求在独立任务中执行，不阻塞也不影响主请求
+use rand::Rng;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::proxy::upstream::client::UpstreamClient;
+
+pub struct ShadowMirror {
+    client: Arc<UpstreamClient>,
+    percentage: u8,
+}
+
+impl ShadowMirror {
+    pub fn new(client: Arc<UpstreamClient>, percentage: u8) -> Self {
+        Self { client, percentage }
+    }
+
+    /// 本次请求是否需要镜像 (按 percentage 随机抽样)
+    pub fn should_mirror(&self) -> bool {
+        self.percentage >= 100 || rand::thread_rng().gen_range(0..100) < self.percentage
+    }
+
+    /// 抽样命中时在后台发送镜像请求 (总是非流式 generateContent)
+    pub fn maybe_mirror(&self, access_token: &str, body: &Value, timeout: Duration, trace_id: &str) {
+        if !self.should_mirror() {
+            return;
+        }
+        let client = self.client.clone();
+        let access_token = access_token.to_string();
+        let body = body.clone();
+        let trace_id = trace_id.to_string();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = client
+                .call_v1_internal("generateContent", &access_token, body, None, Some(timeout))
+                .await;
+            match result {
+                Ok(resp) => {
+                    let status = resp.status();
+                    // 读完响应体以计入完整耗时，内容丢弃
+                    let _ = resp.bytes().await;
+                    tracing::info!(
+                        "[{}] [Shadow] status={} latency={}ms",
+                        trace_id,
+                        status.as_u16(),
+                        started.elapsed().as_millis()
+                    );
+                }
+                Err(e) => tracing::warn!(
+                    "[{}] [Shadow] failed after {}ms: {}",
+                    trace_id,
+                    started.elapsed().as_millis(),
+                    e
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn spawn_shadow_upstream(delay: Duration) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let hits_clone = hits.clone();
+        let app = Router::new().route(
+            "/v1internal:generateContent",
+            post(move || {
+                let hits = hits_clone.clone();
+                async move {
+                    tokio::time::sleep(delay).await;
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    "{}"
+                }
+            }),
+        );
+        (crate::proxy::tests::support::spawn_mock_upstream(app).await, hits)
+    }
+
+    #[tokio::test]
+    async fn test_mirror_runs_in_background() {
+        let (base, hits) = spawn_shadow_upstream(Duration::from_millis(300)).await;
+        let client = Arc::new(UpstreamClient::new(None).with_base_urls(vec![base]));
+        let mirror = ShadowMirror::new(client, 100);
+
+        let started = Instant::now();
+        mirror.maybe_mirror("token", &serde_json::json!({}), Duration::from_secs(5), "t1");
+        // 调用立即返回，不等待慢速影子上游
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_sampling_bounds() {
+        let client = Arc::new(UpstreamClient::new(None));
+        assert!(!ShadowMirror::new(client.clone(), 0).should_mirror());
+        assert!(ShadowMirror::new(client, 100).should_mirror());
+    }
+}
@@ -0,0 +1,406 @@
+// SSE 断线续传 - 流式响应的每个事件带上 id 并在内存中保留一段时间，
+// 客户端携带 Last-Event-ID 重连时补发缺失的事件并继续跟随实时输出
+// [NEW] 客户端断开且宽限期内无人重连时，丢弃上游流
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 客户端声明支持断线续传的请求头 (值为 1 / true)
+pub const RESUME_HEADER: &str = "x-sse-resume";
+
+/// 断线重连宽限期：客户端声明续传时取配置值，否则为 0
+pub fn reconnect_grace(headers: &axum::http::HeaderMap, configured: Duration) -> Duration {
+    let opted_in = headers
+        .get(RESUME_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().eq_ignore_ascii_case("true") || v.trim() == "1")
+        .unwrap_or(false);
+    if opted_in {
+        configured
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// 流 key -> 已记录的事件
+pub type SseReplays = DashMap<String, Arc<ReplayStream>>;
+
+/// 单个流式响应的事件缓冲
+pub struct ReplayStream {
+    events: Mutex<Vec<Bytes>>,
+    error: Mutex<Option<String>>,
+    /// (已记录事件数, 是否结束)
+    progress: watch::Sender<(usize, bool)>,
+    /// 当前读取方数量，变化时通知 follower_changed
+    followers: AtomicUsize,
+    follower_changed: Notify,
+}
+
+/// 读取方计数守卫，随客户端流一起释放 (客户端断开时 Body 流被 drop)
+struct FollowerGuard(Arc<ReplayStream>);
+
+impl FollowerGuard {
+    fn new(stream: Arc<ReplayStream>) -> Self {
+        stream.followers.fetch_add(1, Ordering::SeqCst);
+        stream.follower_changed.notify_waiters();
+        Self(stream)
+    }
+}
+
+impl Drop for FollowerGuard {
+    fn drop(&mut self) {
+        self.0.followers.fetch_sub(1, Ordering::SeqCst);
+        self.0.follower_changed.notify_waiters();
+    }
+}
+
+impl ReplayStream {
+    fn new() -> Self {
+        Self {
+            events: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+            progress: watch::channel((0, false)).0,
+            followers: AtomicUsize::new(0),
+            follower_changed: Notify::new(),
+        }
+    }
+
+    /// 所有读取方断开且宽限期内无人重连时返回
+    async fn abandoned(&self, grace: Duration) {
+        loop {
+            let changed = self.follower_changed.notified();
+            if self.followers.load(Ordering::SeqCst) > 0 {
+                changed.await;
+                continue;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(grace) => return,
+                _ = changed => {}
+            }
+        }
+    }
+
+    fn push(&self, frame: Bytes) {
+        let len = {
+            let mut events = self.events.lock().unwrap();
+            events.push(frame);
+            events.len()
+        };
+        self.progress.send_modify(|p| p.0 = len);
+    }
+
+    fn finish(&self, error: Option<String>) {
+        *self.error.lock().unwrap() = error;
+        self.progress.send_modify(|p| p.1 = true);
+    }
+
+    /// 从第 `from` 个事件开始读取：先补发已记录的事件，再跟随实时输出直到流结束
+    fn follow(self: Arc<Self>, from: usize) -> SseStream {
+        let mut rx = self.progress.subscribe();
+        let guard = FollowerGuard::new(self.clone());
+        Box::pin(async_stream::stream! {
+            let _guard = guard;
+            let mut next = from;
+            loop {
+                let (len, finished) = *rx.borrow_and_update();
+                if next < len {
+                    let batch: Vec<Bytes> = self.events.lock().unwrap()[next..len].to_vec();
+                    next = len;
+                    for frame in batch {
+                        yield Ok(frame);
+                    }
+                    continue;
+                }
+                if finished {
+                    let error = self.error.lock().unwrap().clone();
+                    if let Some(e) = error {
+                        yield Err(e);
+                    }
+                    break;
+                }
+                if rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+/// 为 data 事件加上 `id: <key>-<seq>` 行；注释 (心跳) 原样保留
+fn tag_event(key: &str, seq: usize, frame: &Bytes) -> Bytes {
+    if frame.starts_with(b":") {
+        return frame.clone();
+    }
+    let mut tagged = format!("id: {}-{}\n", key, seq).into_bytes();
+    tagged.extend_from_slice(frame);
+    Bytes::from(tagged)
+}
+
+/// 解析 Last-Event-ID，返回 (流 key, 下一个待发送事件的序号)
+fn parse_event_id(last_event_id: &str) -> Option<(&str, usize)> {
+    let (key, seq) = last_event_id.trim().rsplit_once('-')?;
+    Some((key, seq.parse::<usize>().ok()? + 1))
+}
+
+/// 开始记录 SSE 流：后台任务消费 `inner` 并为每个事件编号，返回供客户端读取的流
+/// 流结束 `window` 后缓冲被移除；客户端全部断开超过 `reconnect_grace` 时中止上游并立即移除
+pub fn record(
+    replays: &Arc<SseReplays>,
+    mut inner: SseStream,
+    window: Duration,
+    reconnect_grace: Duration,
+) -> SseStream {
+    let key = uuid::Uuid::new_v4().simple().to_string();
+    let stream = Arc::new(ReplayStream::new());
+    replays.insert(key.clone(), stream.clone());
+    let client = stream.clone().follow(0);
+
+    let replays = replays.clone();
+    let producer = stream;
+    tokio::spawn(async move {
+        let mut error = None;
+        let abandoned = producer.abandoned(reconnect_grace);
+        tokio::pin!(abandoned);
+        loop {
+            tokio::select! {
+                item = inner.next() => match item {
+                    Some(Ok(frame)) => {
+                        let seq = producer.events.lock().unwrap().len();
+                        producer.push(tag_event(&key, seq, &frame));
+                    }
+                    Some(Err(e)) => {
+                        error = Some(e);
+                        break;
+                    }
+                    None => break,
+                },
+                _ = &mut abandoned => {
+                    tracing::info!("[SSE-Replay] Client gone for {:?}, aborting upstream stream {}", reconnect_grace, key);
+                    // 释放上游流 -> reqwest 关闭连接
+                    drop(inner);
+                    producer.finish(Some("client_disconnected".to_string()));
+                    replays.remove(&key);
+                    return;
+                }
+            }
+        }
+        producer.finish(error);
+
+        tokio::time::sleep(window).await;
+        replays.remove(&key);
+    });
+
+    client
+}
+
+/// 按 Last-Event-ID 恢复：补发该事件之后的内容并继续跟随；缓冲已过期或 ID 无效时返回 None
+pub fn resume(replays: &SseReplays, last_event_id: &str) -> Option<SseStream> {
+    let (key, from) = parse_event_id(last_event_id)?;
+    let stream = replays.get(key)?.clone();
+    Some(stream.follow(from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(i: usize) -> Bytes {
+        Bytes::from(format!("data: {{\"n\":{}}}\n\n", i))
+    }
+
+    /// 每 100ms 输出一个事件，中间夹一个心跳
+    fn mock_upstream(count: usize) -> SseStream {
+        Box::pin(async_stream::stream! {
+            for i in 0..count {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                yield Ok(frame(i));
+                if i == 1 {
+                    yield Ok(Bytes::from(": keepalive\n\n"));
+                }
+            }
+        })
+    }
+
+    fn last_event_id(frame: &Bytes) -> Option<String> {
+        let text = std::str::from_utf8(frame).unwrap();
+        text.strip_prefix("id: ")
+            .and_then(|rest| rest.split('\n').next())
+            .map(|id| id.to_string())
+    }
+
+    fn payload(frame: &Bytes) -> Option<String> {
+        let text = std::str::from_utf8(frame).unwrap();
+        text.lines().find(|l| l.starts_with("data: ")).map(|l| l.to_string())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_mid_stream_loses_no_chunks() {
+        let replays = Arc::new(SseReplays::new());
+        let mut client = record(&replays, mock_upstream(6), Duration::from_secs(60), Duration::from_secs(10));
+
+        // 读到第 3 个 data 事件后断开
+        let mut received = Vec::new();
+        let mut last_id = None;
+        while received.len() < 3 {
+            let frame = client.next().await.unwrap().unwrap();
+            if let Some(id) = last_event_id(&frame) {
+                last_id = Some(id);
+                received.push(payload(&frame).unwrap());
+            }
+        }
+        drop(client);
+
+        // 断开期间上游继续输出
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        let resumed: Vec<Bytes> = resume(&replays, &last_id.unwrap())
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        received.extend(resumed.iter().filter_map(payload));
+
+        let expected: Vec<String> = (0..6).map(|i| payload(&frame(i)).unwrap()).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_event_ids_are_monotonic() {
+        let replays = Arc::new(SseReplays::new());
+        let frames: Vec<Bytes> = record(&replays, mock_upstream(3), Duration::from_secs(60), Duration::from_secs(10))
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+
+        let seqs: Vec<usize> = frames
+            .iter()
+            .filter_map(last_event_id)
+            .map(|id| parse_event_id(&id).unwrap().1 - 1)
+            .collect();
+        assert_eq!(seqs, vec![0, 1, 3]);
+        assert!(frames.iter().any(|f| f.as_ref() == b": keepalive\n\n"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_buffer_evicted_after_window() {
+        let replays = Arc::new(SseReplays::new());
+        let _: Vec<_> = record(&replays, mock_upstream(1), Duration::from_secs(60), Duration::from_secs(10)).collect().await;
+        assert_eq!(replays.len(), 1);
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert!(replays.is_empty());
+        assert!(resume(&replays, "deadbeef-0").is_none());
+    }
+
+    /// 本地 mock 上游：每 50ms 输出一个事件，连接被关闭 (响应体被 drop) 时发出通知
+    async fn spawn_streaming_upstream() -> (String, tokio::sync::oneshot::Receiver<()>) {
+        use axum::{body::Body, routing::get, Router};
+
+        struct DropSignal(Option<tokio::sync::oneshot::Sender<()>>);
+        impl Drop for DropSignal {
+            fn drop(&mut self) {
+                if let Some(tx) = self.0.take() {
+                    let _ = tx.send(());
+                }
+            }
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let signal = Arc::new(Mutex::new(Some(tx)));
+        let app = Router::new().route(
+            "/stream",
+            get(move || {
+                let signal = DropSignal(signal.lock().unwrap().take());
+                async move {
+                    Body::from_stream(async_stream::stream! {
+                        let _signal = signal;
+                        for i in 0.. {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            yield Ok::<Bytes, std::io::Error>(frame(i));
+                        }
+                    })
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(app).await;
+        (format!("{}/stream", base), rx)
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_aborts_upstream_after_grace() {
+        let (url, upstream_closed) = spawn_streaming_upstream().await;
+        let upstream: SseStream = Box::pin(
+            reqwest::get(&url)
+                .await
+                .unwrap()
+                .bytes_stream()
+                .map(|r| r.map_err(|e| e.to_string())),
+        );
+
+        let replays = Arc::new(SseReplays::new());
+        let mut client = record(&replays, upstream, Duration::from_secs(60), Duration::from_millis(100));
+        assert!(client.next().await.unwrap().is_ok());
+        drop(client);
+
+        // 宽限期过后上游连接被关闭，缓冲立即移除
+        tokio::time::timeout(Duration::from_secs(5), upstream_closed)
+            .await
+            .expect("upstream connection was not dropped")
+            .unwrap();
+        assert!(replays.is_empty());
+    }
+
+    #[test]
+    fn test_reconnect_grace_requires_opt_in() {
+        use axum::http::{HeaderMap, HeaderValue};
+        let configured = Duration::from_secs(10);
+        assert_eq!(reconnect_grace(&HeaderMap::new(), configured), Duration::ZERO);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RESUME_HEADER, HeaderValue::from_static("true"));
+        assert_eq!(reconnect_grace(&headers, configured), configured);
+        headers.insert(RESUME_HEADER, HeaderValue::from_static("0"));
+        assert_eq!(reconnect_grace(&headers, configured), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_without_grace_aborts_upstream_immediately() {
+        let (url, upstream_closed) = spawn_streaming_upstream().await;
+        let upstream: SseStream = Box::pin(
+            reqwest::get(&url)
+                .await
+                .unwrap()
+                .bytes_stream()
+                .map(|r| r.map_err(|e| e.to_string())),
+        );
+
+        let replays = Arc::new(SseReplays::new());
+        let mut client = record(&replays, upstream, Duration::from_secs(60), Duration::ZERO);
+        assert!(client.next().await.unwrap().is_ok());
+        drop(client);
+
+        tokio::time::timeout(Duration::from_millis(500), upstream_closed)
+            .await
+            .expect("upstream connection was not dropped")
+            .unwrap();
+        assert!(replays.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_kept_alive_while_client_attached() {
+        let replays = Arc::new(SseReplays::new());
+        let frames: Vec<Bytes> = record(&replays, mock_upstream(6), Duration::from_secs(60), Duration::from_millis(50))
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        // 读取方一直在线时，即使总耗时远超宽限期也不会中止
+        assert_eq!(frames.iter().filter_map(payload).count(), 6);
+    }
+}
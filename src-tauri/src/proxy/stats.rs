@@ -0,0 +1,236 @@
+// 实时统计 - 供前端仪表盘轮询 (GET /v1/stats) 或订阅 (GET /v1/stats/stream, SSE)
+// 计数器由监控中间件与 Prompt 缓存更新，读取时组装快照
+use axum::response::sse::{Event, KeepAlive, Sse};
+use dashmap::DashMap;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// 订阅者处理不及时最多积压的快照数 (落后时只保留最新的)
+const UPDATE_CHANNEL_CAPACITY: usize = 16;
+
+/// GET /v1/stats 响应
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DashboardStats {
+    pub requests_total: u64,
+    pub requests_success: u64,
+    pub requests_failed: u64,
+    pub active_tokens: usize,
+    pub blacklisted_tokens: usize,
+    /// Prompt 缓存命中率 (0.0-1.0，无查询时为 0)
+    pub cache_hit_rate: f64,
+    pub uptime_secs: u64,
+    pub requests_per_model: HashMap<String, u64>,
+    /// 失败请求按错误类型计数 (rate_limited / unauthorized / server_error ...)
+    pub errors_per_type: HashMap<String, u64>,
+    /// 各账号累计 token 用量 (由 /v1/stats 处理器填充，SSE 推送中为空)
+    #[serde(default)]
+    pub accounts: Vec<crate::proxy::token_manager::AccountUsage>,
+}
+
+pub struct StatsCounters {
+    started_at: Instant,
+    requests_total: AtomicU64,
+    requests_success: AtomicU64,
+    requests_failed: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    requests_per_model: DashMap<String, AtomicU64>,
+    errors_per_type: DashMap<String, AtomicU64>,
+    updates: broadcast::Sender<DashboardStats>,
+}
+
+impl Default for StatsCounters {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            requests_total: AtomicU64::new(0),
+            requests_success: AtomicU64::new(0),
+            requests_failed: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            requests_per_model: DashMap::new(),
+            errors_per_type: DashMap::new(),
+            updates: broadcast::channel(UPDATE_CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl StatsCounters {
+    /// 记录一次已完成的请求 (按最终返回给客户端的状态码判定成败)
+    pub fn record_request(&self, model: &str, status: u16) {
+        self.requests_total.fetch_add(1, Ordering::Relaxed);
+        self.requests_per_model
+            .entry(model.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+        if status < 400 {
+            self.requests_success.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.requests_failed.fetch_add(1, Ordering::Relaxed);
+            self.errors_per_type
+                .entry(crate::proxy::metrics::token_error_reason(status).to_string())
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 记录一次 Prompt 缓存查询
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = if hit { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 是否有 SSE 订阅者 (没有时无需组装快照)
+    pub fn has_subscribers(&self) -> bool {
+        self.updates.receiver_count() > 0
+    }
+
+    /// 向所有订阅者推送最新快照
+    pub fn publish(&self, active_tokens: usize, blacklisted_tokens: usize) {
+        if self.has_subscribers() {
+            let _ = self.updates.send(self.snapshot(active_tokens, blacklisted_tokens));
+        }
+    }
+
+    /// SSE 响应：先推送当前快照，之后每次计数变化推送一次 (event: stats)
+    pub fn sse(
+        &self,
+        active_tokens: usize,
+        blacklisted_tokens: usize,
+    ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+        let initial = self.snapshot(active_tokens, blacklisted_tokens);
+        // 落后过多 (Lagged) 时跳过旧快照，下一条即为最新
+        let updates = tokio_stream::wrappers::BroadcastStream::new(self.updates.subscribe())
+            .filter_map(|update| async move { update.ok() });
+        let stream = futures::stream::once(async move { initial })
+            .chain(updates)
+            .map(|stats| Ok(Event::default().event("stats").json_data(&stats).unwrap_or_default()));
+        Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+    }
+
+    pub fn snapshot(&self, active_tokens: usize, blacklisted_tokens: usize) -> DashboardStats {
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let lookups = hits + self.cache_misses.load(Ordering::Relaxed);
+        DashboardStats {
+            requests_total: self.requests_total.load(Ordering::Relaxed),
+            requests_success: self.requests_success.load(Ordering::Relaxed),
+            requests_failed: self.requests_failed.load(Ordering::Relaxed),
+            active_tokens,
+            blacklisted_tokens,
+            cache_hit_rate: if lookups == 0 { 0.0 } else { hits as f64 / lookups as f64 },
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            requests_per_model: collect(&self.requests_per_model),
+            errors_per_type: collect(&self.errors_per_type),
+            accounts: Vec::new(),
+        }
+    }
+}
+
+fn collect(map: &DashMap<String, AtomicU64>) -> HashMap<String, u64> {
+    map.iter()
+        .map(|e| (e.key().clone(), e.value().load(Ordering::Relaxed)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_increment() {
+        let stats = StatsCounters::default();
+        stats.record_request("gemini-2.5-flash", 200);
+        stats.record_request("gemini-2.5-flash", 429);
+        stats.record_request("gemini-2.5-pro", 200);
+        stats.record_request("gemini-2.5-pro", 503);
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(false);
+
+        let snapshot = stats.snapshot(3, 1);
+        assert_eq!(snapshot.requests_total, 4);
+        assert_eq!(snapshot.requests_success, 2);
+        assert_eq!(snapshot.requests_failed, 2);
+        assert_eq!(snapshot.active_tokens, 3);
+        assert_eq!(snapshot.blacklisted_tokens, 1);
+        assert_eq!(snapshot.cache_hit_rate, 0.75);
+        assert_eq!(snapshot.requests_per_model["gemini-2.5-flash"], 2);
+        assert_eq!(snapshot.requests_per_model["gemini-2.5-pro"], 2);
+        assert_eq!(snapshot.errors_per_type["rate_limited"], 1);
+        assert_eq!(snapshot.errors_per_type["server_error"], 1);
+    }
+
+    /// 读取下一个 SSE 事件的 data 部分
+    async fn next_event(
+        body: &mut (impl Stream<Item = reqwest::Result<bytes::Bytes>> + Unpin),
+        buf: &mut String,
+    ) -> DashboardStats {
+        loop {
+            if let Some(end) = buf.find("\n\n") {
+                let event: String = buf.drain(..end + 2).collect();
+                if let Some(data) = event.lines().find_map(|l| l.strip_prefix("data: ")) {
+                    return serde_json::from_str(data).unwrap();
+                }
+                continue;
+            }
+            let chunk = body.next().await.unwrap().unwrap();
+            buf.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_pushes_update_after_request() {
+        let stats = std::sync::Arc::new(StatsCounters::default());
+        let app = axum::Router::new().route(
+            "/v1/stats/stream",
+            axum::routing::get({
+                let stats = stats.clone();
+                move || {
+                    let stats = stats.clone();
+                    async move { stats.sse(2, 0) }
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_server(app).await;
+
+        let resp = reqwest::get(format!("{}/v1/stats/stream", base)).await.unwrap();
+        assert!(resp.headers()["content-type"].to_str().unwrap().starts_with("text/event-stream"));
+        let mut body = Box::pin(resp.bytes_stream());
+        let mut buf = String::new();
+
+        let initial = next_event(&mut body, &mut buf).await;
+        assert_eq!(initial.requests_total, 0);
+        assert_eq!(initial.active_tokens, 2);
+        assert!(stats.has_subscribers());
+
+        // 请求完成 → 计数更新并推送
+        stats.record_request("gemini-2.5-flash", 200);
+        stats.publish(2, 0);
+        let update = tokio::time::timeout(Duration::from_millis(100), next_event(&mut body, &mut buf))
+            .await
+            .expect("no stats event within 100ms");
+        assert_eq!(update.requests_total, 1);
+        assert_eq!(update.requests_per_model["gemini-2.5-flash"], 1);
+    }
+
+    #[test]
+    fn test_publish_without_subscribers_is_noop() {
+        let stats = StatsCounters::default();
+        assert!(!stats.has_subscribers());
+        stats.publish(0, 0);
+    }
+
+    #[test]
+    fn test_empty_snapshot() {
+        let snapshot = StatsCounters::default().snapshot(0, 0);
+        assert_eq!(snapshot.requests_total, 0);
+        assert_eq!(snapshot.cache_hit_rate, 0.0);
+        assert!(snapshot.requests_per_model.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+//! 测试辅助：本地 mock 上游与对应的 AppState
+
+use std::sync::Arc;
+
+use crate::proxy::server::AppState;
+use crate::proxy::token_manager::{runtime_token, TokenManager};
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// 在随机端口启动本地 mock 服务，返回根地址 (http://127.0.0.1:port)
+pub(crate) async fn spawn_mock_server(router: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// 启动 mock 上游，返回 v1internal 基础地址
+pub(crate) async fn spawn_mock_upstream(router: axum::Router) -> String {
+    format!("{}/v1internal", spawn_mock_server(router).await)
+}
+
+/// 指向 mock 上游的 AppState，账号池中仅有 `email` 一个账号
+pub(crate) async fn mock_app_state(email: &str, router: axum::Router) -> AppState {
+    let base = spawn_mock_upstream(router).await;
+    let token_manager = Arc::new(TokenManager::new(
+        std::env::temp_dir().join(format!("mock-state-{}", email)),
+    ));
+    token_manager.add_token(runtime_token(email), false).await.unwrap();
+    let upstream = Arc::new(UpstreamClient::new(None).with_base_urls(vec![base]));
+    AppState::for_test(token_manager, upstream)
+}
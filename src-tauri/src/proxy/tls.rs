@@ -0,0 +1,144 @@
+// HTTPS 支持 - 从 PEM 文件加载证书与私钥 (rustls)
+//
+// 本地测试可用 openssl 生成自签名证书:
+//   openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes \
+//     -keyout key.pem -out cert.pem -days 365 -subj "/CN=localhost" \
+//     -addext "subjectAltName=DNS:localhost,IP:127.0.0.1"
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+/// 加载证书链与私钥，构建 TLS acceptor
+pub fn load_tls_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| format!("读取证书失败 ({}): {}", cert_path.display(), e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("解析证书失败 ({}): {}", cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("证书文件中没有证书: {}", cert_path.display()));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("读取私钥失败 ({}): {}", key_path.display(), e))?;
+
+    if is_self_signed(&certs[0]) == Some(true) {
+        tracing::warn!(
+            "TLS 证书为自签名证书 ({})，客户端需要手动信任；生产环境请使用受信任 CA 签发的证书",
+            cert_path.display()
+        );
+    }
+
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("TLS 配置无效: {}", e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// 证书的 issuer 与 subject 是否相同 (无法解析时返回 None)
+fn is_self_signed(cert: &[u8]) -> Option<bool> {
+    // Certificate ::= SEQUENCE { tbsCertificate SEQUENCE { [0] version?, serial, sigAlg, issuer, validity, subject, ... } }
+    let (certificate, _) = read_der(cert, 0x30)?;
+    let (tbs, _) = read_der(certificate, 0x30)?;
+
+    let mut rest = tbs;
+    if rest.first() == Some(&0xA0) {
+        rest = read_der(rest, 0xA0)?.1;
+    }
+    let (_serial, rest) = read_der(rest, 0x02)?;
+    let (_sig_alg, rest) = read_der(rest, 0x30)?;
+    let (issuer, rest) = read_der(rest, 0x30)?;
+    let (_validity, rest) = read_der(rest, 0x30)?;
+    let (subject, _) = read_der(rest, 0x30)?;
+    Some(issuer == subject)
+}
+
+/// 读取一个 DER TLV，返回 (内容, 剩余字节)
+fn read_der(input: &[u8], expected_tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    if tag != expected_tag {
+        return None;
+    }
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first & 0x80 == 0 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7F) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        (len, &rest[n..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((&rest[..len], &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/proxy/tests/fixtures")
+            .join(name)
+    }
+
+    fn first_cert(name: &str) -> CertificateDer<'static> {
+        CertificateDer::pem_file_iter(fixture(name))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_detects_self_signed() {
+        assert_eq!(is_self_signed(&first_cert("tls_cert.pem")), Some(true));
+        assert_eq!(is_self_signed(&first_cert("tls_ca_signed_cert.pem")), Some(false));
+        assert_eq!(is_self_signed(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn test_load_acceptor_errors() {
+        assert!(load_tls_acceptor(&fixture("missing.pem"), &fixture("tls_key.pem")).is_err());
+        assert!(load_tls_acceptor(&fixture("tls_cert.pem"), &fixture("missing.pem")).is_err());
+        assert!(load_tls_acceptor(&fixture("tls_cert.pem"), &fixture("tls_key.pem")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_https_request_succeeds_with_test_certificate() {
+        use hyper_util::rt::TokioIo;
+        use hyper_util::service::TowerToHyperService;
+
+        let acceptor = load_tls_acceptor(&fixture("tls_cert.pem"), &fixture("tls_key.pem")).unwrap();
+        let app = axum::Router::new().route("/healthz", axum::routing::get(|| async { "ok" }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tls_stream = acceptor.accept(stream).await.unwrap();
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(tls_stream), TowerToHyperService::new(app))
+                .await;
+        });
+
+        let ca = reqwest::Certificate::from_pem(&std::fs::read(fixture("tls_cert.pem")).unwrap()).unwrap();
+        let client = reqwest::Client::builder().add_root_certificate(ca).build().unwrap();
+        let resp = client
+            .get(format!("https://127.0.0.1:{}/healthz", port))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(resp.text().await.unwrap(), "ok");
+    }
+}
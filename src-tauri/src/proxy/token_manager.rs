@@ -5,6 +5,16 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
+mod budget;
+mod circuit_breaker;
+mod history;
+mod jwt;
+mod usage;
+pub use budget::DailyBudgets;
+pub use circuit_breaker::{CircuitState, TokenCircuitBreakers};
+pub use history::{TokenHistory, TokenRequestRecord};
+pub use usage::{AccountUsage, TokenUsageTracker};
+
 use crate::proxy::rate_limit::RateLimitTracker;
 use crate::proxy::sticky_config::StickySessionConfig;
 
@@ -22,8 +32,40 @@ pub struct ProxyToken {
     pub remaining_quota: Option<i32>, // [FIX #563] Remaining quota for priority sorting
     pub protected_models: HashSet<String>, // [NEW #621]
     pub health_score: f32, // [NEW] 健康分数 (0.0 - 1.0)
+    pub history: TokenHistory, // [NEW] 最近请求记录 (按邮箱共享，重新加载后保留)
+    pub daily_token_budget: Option<u64>, // [NEW] 每日 token 预算 (prompt + completion，None 为不限)
+    pub budget_reset_at: Option<chrono::DateTime<chrono::Utc>>, // [NEW] 超出预算后的解锁时间 (次日 UTC 零点)
+}
+
+/// 运行时管理接口使用的账号状态摘要
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TokenStatus {
+    pub account_id: String,
+    pub email: String,
+    pub subscription_tier: Option<String>,
+    pub expires_at: i64,
+    pub rate_limited: bool,
+    pub circuit_state: CircuitState,
+    pub daily_token_budget: Option<u64>,
+    /// 当日预算使用率 (百分比，未设置预算时为 null)
+    pub budget_utilization_pct: Option<f64>,
+    pub budget_reset_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// 账号可用性统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAvailability {
+    pub total: usize,
+    pub available: usize,
+    pub blacklisted: usize,
+}
+
+/// 熔断中的账号数 (健康检查只返回计数，不暴露账号)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CircuitBreakerCounts {
+    pub open: usize,
+    pub half_open: usize,
+}
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
@@ -36,6 +78,12 @@ pub struct TokenManager {
     preferred_account_id: Arc<tokio::sync::RwLock<Option<String>>>, // [FIX #820] 优先使用的账号ID（固定账号模式）
     health_scores: Arc<DashMap<String, f32>>, // account_id -> health_score
     circuit_breaker_config: Arc<tokio::sync::RwLock<crate::models::CircuitBreakerConfig>>, // [NEW] 熔断配置缓存
+    token_breakers: Arc<TokenCircuitBreakers>, // [NEW] 账号级熔断器 (连续失败计数)
+    token_usage: Arc<TokenUsageTracker>, // [NEW] 账号累计 token 用量 (email -> counters)
+    upstream: Arc<std::sync::RwLock<Option<Arc<crate::proxy::upstream::client::UpstreamClient>>>>, // [NEW] 新账号探测使用的上游客户端
+    histories: Arc<DashMap<String, TokenHistory>>, // [NEW] 账号请求历史 (email -> history)
+    failures: crate::proxy::alert::FailureTracker, // [NEW] 跨账号连续失败计数 (触发告警 Webhook)
+    budgets: Arc<DailyBudgets>, // [NEW] 每日 token 预算用量与锁定 (email -> usage/lock)
 }
 
 impl TokenManager {
@@ -52,9 +100,51 @@ impl TokenManager {
             preferred_account_id: Arc::new(tokio::sync::RwLock::new(None)), // [FIX #820]
             health_scores: Arc::new(DashMap::new()),
             circuit_breaker_config: Arc::new(tokio::sync::RwLock::new(crate::models::CircuitBreakerConfig::default())),
+            token_breakers: Arc::new(TokenCircuitBreakers::new(
+                crate::proxy::config::TokenCircuitBreakerConfig::default(),
+            )),
+            token_usage: Arc::new(TokenUsageTracker::new()),
+            upstream: Arc::new(std::sync::RwLock::new(None)),
+            histories: Arc::new(DashMap::new()),
+            failures: crate::proxy::alert::FailureTracker::default(),
+            budgets: Arc::new(DailyBudgets::new()),
         }
     }
 
+    /// 设置探测新账号使用的上游客户端 (与反代服务共享代理与连接池配置)
+    pub fn set_upstream(&self, client: Arc<crate::proxy::upstream::client::UpstreamClient>) {
+        *self.upstream.write().unwrap() = Some(client);
+    }
+
+    /// 探测账号凭据使用的上游客户端 (未设置时使用默认配置)
+    pub fn upstream_client(&self) -> Arc<crate::proxy::upstream::client::UpstreamClient> {
+        self.upstream
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Arc::new(crate::proxy::upstream::client::UpstreamClient::new(None)))
+    }
+
+    /// 获取 project_id 等直连请求使用的 User-Agent (与上游客户端一致，未设置时为默认值)
+    fn upstream_user_agent(&self) -> String {
+        self.upstream
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|client| client.user_agent().to_string())
+            .unwrap_or_else(|| crate::proxy::config::DEFAULT_USER_AGENT.to_string())
+    }
+
+    /// 设置连续失败告警 Webhook (None 表示关闭)
+    pub fn set_alert_webhook(&self, webhook: Option<crate::proxy::alert::AlertWebhook>) {
+        self.failures.set_webhook(webhook);
+    }
+
+    /// 跨账号连续失败次数 (任一请求成功即清零)
+    pub fn consecutive_failures(&self) -> Arc<std::sync::atomic::AtomicU32> {
+        self.failures.counter()
+    }
+
     /// 启动限流记录自动清理后台任务（每15秒检查并清除过期记录）
     pub fn start_auto_cleanup(&self) {
         let tracker = self.rate_limit_tracker.clone();
@@ -70,6 +160,29 @@ impl TokenManager {
         });
         tracing::info!("✅ Rate limit auto-cleanup task started (interval: 15s)");
     }
+
+    /// 启动预算解锁任务 (每分钟检查一次，到期的预算锁定自动解除)
+    pub fn start_budget_reset_task(&self) {
+        let budgets = self.budgets.clone();
+        let tokens = self.tokens.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let released = budgets.release_expired(chrono::Utc::now());
+                if released.is_empty() {
+                    continue;
+                }
+                for mut entry in tokens.iter_mut() {
+                    if released.contains(&entry.email) {
+                        entry.budget_reset_at = None;
+                    }
+                }
+                tracing::info!("💰 Budget reset: Re-enabled {} account(s): {:?}", released.len(), released);
+            }
+        });
+        tracing::info!("✅ Daily budget reset task started (interval: 60s)");
+    }
     
     /// 从主应用账号目录加载所有账号
     pub async fn load_accounts(&self) -> Result<usize, String> {
@@ -245,7 +358,12 @@ impl TokenManager {
             })
             .unwrap_or_default();
         
+        // [NEW] 每日 token 预算 (可选)
+        let daily_token_budget = account.get("daily_token_budget").and_then(|v| v.as_u64());
+
         let health_score = self.health_scores.get(&account_id).map(|v| *v).unwrap_or(1.0);
+        let history = self.history_for(&email);
+        let budget_reset_at = self.budgets.reset_at(&email, chrono::Utc::now());
         
         Ok(Some(ProxyToken {
             account_id,
@@ -259,7 +377,10 @@ impl TokenManager {
             subscription_tier,
             remaining_quota,
             protected_models,
+            history,
             health_score,
+            daily_token_budget,
+            budget_reset_at,
         }))
     }
 
@@ -485,6 +606,11 @@ impl TokenManager {
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
     /// 参数 `target_model` 用于检查配额保护 (Issue #621)
+    #[tracing::instrument(
+        name = "get_token",
+        skip_all,
+        fields(model = %target_model, quota_group = %quota_group, force_rotate = force_rotate, token_email = tracing::field::Empty)
+    )]
     pub async fn get_token(
         &self, 
         quota_group: &str, 
@@ -494,10 +620,14 @@ impl TokenManager {
     ) -> Result<(String, String, String, u64), String> {
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
-        match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, target_model)).await {
+        let result = match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, target_model)).await {
             Ok(result) => result,
             Err(_) => Err("Token acquisition timeout (5s) - system too busy or deadlock detected".to_string()),
+        };
+        if let Ok((_, _, email, _)) = &result {
+            tracing::Span::current().record("token_email", email.as_str());
         }
+        result
     }
 
     /// 内部实现：获取 Token 的核心逻辑
@@ -509,9 +639,15 @@ impl TokenManager {
         target_model: &str,
     ) -> Result<(String, String, String, u64), String> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
+        if tokens_snapshot.is_empty() {
+            return Err("Token pool is empty".to_string());
+        }
+
+        // [NEW] 排除已用尽每日预算的账号 (锁定至次日 UTC 零点)
+        tokens_snapshot.retain(|t| !self.budget_exhausted(t));
         let total = tokens_snapshot.len();
         if total == 0 {
-            return Err("Token pool is empty".to_string());
+            return Err("All accounts have exhausted their daily token budget".to_string());
         }
 
         // ===== 【优化】根据订阅等级和剩余配额排序 =====
@@ -589,7 +725,7 @@ impl TokenManager {
 
                     // 检查 token 是否过期（提前5分钟刷新）
                     let now = chrono::Utc::now().timestamp();
-                    if now >= token.timestamp - 300 {
+                    if token_needs_refresh(&token, now) {
                         tracing::debug!("账号 {} 的 token 即将过期，正在刷新...", token.email);
                         match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
                             Ok(token_response) => {
@@ -615,7 +751,7 @@ impl TokenManager {
                     let project_id = if let Some(pid) = &token.project_id {
                         pid.clone()
                     } else {
-                        match crate::proxy::project_resolver::fetch_project_id(&token.access_token).await {
+                        match crate::proxy::project_resolver::fetch_project_id(&token.access_token, &self.upstream_user_agent()).await {
                             Ok(pid) => {
                                 if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
                                     entry.project_id = Some(pid.clone());
@@ -744,6 +880,11 @@ impl TokenManager {
                             continue;
                         }
 
+                        // 【新增】跳过熔断中的账号 (只读检查，试探名额在最终返回账号时才占用)
+                        if !self.token_breakers.would_allow(&candidate.account_id) {
+                            continue;
+                        }
+
                         target_token = Some(candidate.clone());
                         // 【优化】标记需要更新，稍后统一写回
                         need_update_last_used = Some((candidate.account_id.clone(), std::time::Instant::now()));
@@ -783,6 +924,12 @@ impl TokenManager {
                         continue;
                     }
 
+                    // 【新增】跳过熔断中的账号 (只读检查)
+                    if !self.token_breakers.would_allow(&candidate.account_id) {
+                        tracing::debug!("  🔌 {} - SKIP: circuit open", candidate.email);
+                        continue;
+                    }
+
                     tracing::debug!("  [{}] {} - SELECTED", idx, candidate.email);
                     target_token = Some(candidate.clone());
                     
@@ -853,11 +1000,19 @@ impl TokenManager {
             };
 
         
-            // 3. 检查 token 是否过期（提前5分钟刷新）
+            // 3. 检查 token 是否过期（提前5分钟刷新，JWT 以 payload 中的 exp 为准）
             let now = chrono::Utc::now().timestamp();
-            if now >= token.timestamp - 300 {
+            if token_needs_refresh(&token, now) {
                 tracing::debug!("账号 {} 的 token 即将过期，正在刷新...", token.email);
 
+                // 没有 refresh_token 无法续期，直接跳过该账号
+                if token.refresh_token.is_empty() {
+                    tracing::warn!("账号 {} 的 token 即将过期且缺少 refresh_token，跳过", token.email);
+                    last_error = Some("Access token expiring and no refresh token available".to_string());
+                    attempted.insert(token.account_id.clone());
+                    continue;
+                }
+
                 // 调用 OAuth 刷新 token
                 match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
                     Ok(token_response) => {
@@ -912,7 +1067,7 @@ impl TokenManager {
                 pid.clone()
             } else {
                 tracing::debug!("账号 {} 缺少 project_id，尝试获取...", token.email);
-                match crate::proxy::project_resolver::fetch_project_id(&token.access_token).await {
+                match crate::proxy::project_resolver::fetch_project_id(&token.access_token, &self.upstream_user_agent()).await {
                     Ok(pid) => {
                         if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
                             entry.project_id = Some(pid.clone());
@@ -936,6 +1091,13 @@ impl TokenManager {
                 }
             };
 
+            // 半开状态下占用唯一的试探名额 (其他请求可能已抢先占用)
+            if !self.token_breakers.allow_request(&token.account_id) {
+                last_error = Some(format!("Account {} circuit is open", token.email));
+                attempted.insert(token.account_id.clone());
+                continue;
+            }
+
             // 【优化】在成功返回前，统一更新 last_used_account（如果需要）
             if let Some((new_account_id, new_time)) = need_update_last_used {
                 if quota_group != "image_gen" {
@@ -1032,6 +1194,204 @@ impl TokenManager {
         self.tokens.len()
     }
 
+    /// 更新账号级熔断配置
+    pub fn update_token_circuit_breaker_config(&self, config: crate::proxy::config::TokenCircuitBreakerConfig) {
+        self.token_breakers.update_config(config);
+    }
+
+    /// 当前账号池快照 (按邮箱排序，供导出备份)
+    pub fn snapshot_tokens(&self) -> Vec<ProxyToken> {
+        let mut tokens: Vec<ProxyToken> = self.tokens.iter().map(|t| t.value().clone()).collect();
+        tokens.sort_by(|a, b| a.email.cmp(&b.email));
+        tokens
+    }
+
+    /// 处于 Open / HalfOpen 的账号数 (供健康检查使用)
+    pub fn circuit_breaker_counts(&self) -> CircuitBreakerCounts {
+        self.token_breakers
+            .states()
+            .into_values()
+            .fold(CircuitBreakerCounts::default(), |mut counts, state| {
+                match state {
+                    CircuitState::Open => counts.open += 1,
+                    CircuitState::HalfOpen => counts.half_open += 1,
+                    CircuitState::Closed => {}
+                }
+                counts
+            })
+    }
+
+    /// 列出当前内存中的所有账号及其状态 (按邮箱排序)
+    pub async fn list_tokens(&self) -> Vec<TokenStatus> {
+        let tokens: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
+        let now = chrono::Utc::now();
+        let mut statuses = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            statuses.push(TokenStatus {
+                rate_limited: self.is_rate_limited(&token.account_id, None).await,
+                circuit_state: self.token_breakers.state_of(&token.account_id),
+                budget_utilization_pct: self.budgets.utilization_pct(&token.email, token.daily_token_budget, now),
+                budget_reset_at: self.budgets.reset_at(&token.email, now),
+                daily_token_budget: token.daily_token_budget,
+                account_id: token.account_id,
+                email: token.email,
+                subscription_tier: token.subscription_tier,
+                expires_at: token.timestamp,
+            });
+        }
+        statuses.sort_by(|a, b| a.email.cmp(&b.email));
+        statuses
+    }
+
+    /// 运行时添加账号 (仅内存，不写入账号文件)
+    /// 同邮箱账号已存在时替换其凭据并沿用原 account_id，返回 account_id
+    /// `validate` 为 true 时先发送探测请求：401/403 拒绝添加，429 仍添加但立即标记为限流
+    pub async fn add_token(&self, mut token: ProxyToken, validate: bool) -> Result<String, String> {
+        let mut rate_limited = false;
+        if validate {
+            use crate::proxy::error::ProxyError;
+            match self.upstream_client().validate_token(&token).await {
+                Ok(()) => {}
+                Err(ProxyError::Unauthorized) => return Err("token rejected by upstream (401/403)".to_string()),
+                Err(ProxyError::RateLimited { .. } | ProxyError::QuotaExhausted) => rate_limited = true,
+                Err(e) => tracing::warn!("[Admin] Token probe inconclusive for {}: {}", token.email, e),
+            }
+        }
+
+        if let Some(existing_id) = self.email_to_account_id(&token.email) {
+            token.account_id = existing_id;
+        }
+        let account_id = token.account_id.clone();
+        self.health_scores.insert(account_id.clone(), token.health_score);
+        self.token_breakers.reset(&account_id);
+        self.rate_limit_tracker.clear(&account_id);
+        if rate_limited {
+            self.rate_limit_tracker.set_lockout_until(
+                &account_id,
+                std::time::SystemTime::now() + std::time::Duration::from_secs(60),
+                crate::proxy::rate_limit::RateLimitReason::RateLimitExceeded,
+                None,
+            );
+            tracing::warn!("[Admin] Token {} is rate limited, added in locked-out state", token.email);
+        }
+        tracing::info!("[Admin] Token added at runtime: {}", token.email);
+        token.history = self.history_for(&token.email);
+        self.tokens.insert(account_id.clone(), token);
+        Ok(account_id)
+    }
+
+    /// 运行时移除账号 (同时清理其会话绑定与熔断/限流状态)
+    pub fn remove_token(&self, email: &str) -> bool {
+        let Some(account_id) = self.email_to_account_id(email) else {
+            return false;
+        };
+        self.tokens.remove(&account_id);
+        self.session_accounts.retain(|_, bound| bound != &account_id);
+        self.token_breakers.reset(&account_id);
+        self.rate_limit_tracker.clear(&account_id);
+        tracing::info!("[Admin] Token removed at runtime: {}", email);
+        true
+    }
+
+    /// 重置账号熔断与限流状态，使其立即重新参与调度
+    pub fn reset_token(&self, email: &str) -> bool {
+        let Some(account_id) = self.email_to_account_id(email) else {
+            return false;
+        };
+        self.token_breakers.reset(&account_id);
+        self.rate_limit_tracker.clear(&account_id);
+        true
+    }
+
+    /// 累加账号 token 用量 (请求成功后由监控中间件调用)
+    pub fn record_usage(&self, email: &str, prompt_tokens: u64, completion_tokens: u64) {
+        self.token_usage.record(email, prompt_tokens, completion_tokens);
+        self.budgets.record(email, prompt_tokens + completion_tokens, chrono::Utc::now());
+    }
+
+    /// 账号是否已用尽当日预算 (超出时锁定并同步 budget_reset_at)
+    fn budget_exhausted(&self, token: &ProxyToken) -> bool {
+        let reset_at = self.budgets.check(&token.email, token.daily_token_budget, chrono::Utc::now());
+        if reset_at != token.budget_reset_at {
+            if let Some(mut entry) = self.tokens.get_mut(&token.account_id) {
+                entry.budget_reset_at = reset_at;
+            }
+        }
+        reset_at.is_some()
+    }
+
+    /// 邮箱对应的请求历史 (不存在时创建)
+    fn history_for(&self, email: &str) -> TokenHistory {
+        self.histories.entry(email.to_string()).or_default().clone()
+    }
+
+    /// 记录一次请求尝试 (各处理器在每次上游调用结束后调用)
+    pub fn record_request(&self, email: &str, model: &str, latency_ms: u64, error: Option<&crate::proxy::error::ProxyError>) {
+        self.history_for(email).push(TokenRequestRecord {
+            timestamp: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+            latency_ms,
+            success: error.is_none(),
+            error_type: error.cloned(),
+        });
+        self.failures.record(error.map(|e| e.to_string()).as_deref());
+
+        // 结束熔断试探：成功即恢复；失败是否计入熔断由 record_token_failure / 限流标记决定，这里只释放名额
+        let id = self.email_to_account_id(email).unwrap_or_else(|| email.to_string());
+        match error {
+            None => self.token_breakers.record_success(&id),
+            Some(_) => self.token_breakers.release_trial(&id),
+        }
+    }
+
+    /// 账号的请求历史 (旧 -> 新)；账号不在池中时返回 None
+    pub fn request_history(&self, email: &str) -> Option<Vec<TokenRequestRecord>> {
+        self.email_to_account_id(email)?;
+        Some(self.history_for(email).snapshot())
+    }
+
+    /// 各账号累计 token 用量
+    pub fn usage_report(&self) -> Vec<AccountUsage> {
+        self.token_usage.report()
+    }
+
+    /// 从审计数据库恢复累计用量
+    pub fn restore_usage(&self, usage: &[AccountUsage]) {
+        self.token_usage.restore(usage);
+    }
+
+    /// 账号可用性统计 (供健康检查使用，限流/熔断中的账号计为 blacklisted)
+    pub async fn availability(&self) -> TokenAvailability {
+        let account_ids: Vec<String> = self.tokens.iter().map(|e| e.key().clone()).collect();
+        let mut blacklisted = 0;
+        for account_id in &account_ids {
+            if self.is_rate_limited(account_id, None).await || self.token_breakers.is_open(account_id) {
+                blacklisted += 1;
+            }
+        }
+        crate::proxy::metrics::metrics().set_active_tokens(account_ids.len() - blacklisted);
+        TokenAvailability {
+            total: account_ids.len(),
+            available: account_ids.len() - blacklisted,
+            blacklisted,
+        }
+    }
+
+    /// 账号是否存在且可参与调度 (未限流、未熔断、未用尽当日预算)
+    pub async fn is_email_available(&self, email: &str) -> bool {
+        let Some(token) = self
+            .tokens
+            .iter()
+            .find(|entry| entry.value().email == email)
+            .map(|entry| entry.value().clone())
+        else {
+            return false;
+        };
+        !self.budget_exhausted(&token)
+            && !self.is_rate_limited(&token.account_id, None).await
+            && !self.token_breakers.is_open(&token.account_id)
+    }
+
     /// 通过 email 获取指定账号的 Token（用于预热等需要指定账号的场景）
     /// 此方法会自动刷新过期的 token
     pub async fn get_token_by_email(&self, email: &str) -> Result<(String, String, String, u64), String> {
@@ -1181,12 +1541,21 @@ impl TokenManager {
         self.rate_limit_tracker.clear_all();
     }
     
+    /// 记录一次账号级失败 (累加熔断计数，email 或 account_id 均可)
+    pub fn record_token_failure(&self, email: &str) {
+        let id = self.email_to_account_id(email).unwrap_or_else(|| email.to_string());
+        self.token_breakers.record_failure(&id);
+    }
+
     /// 标记账号请求成功，重置连续失败计数
     /// 
     /// 在请求成功完成后调用，将该账号的失败计数归零，
     /// 下次失败时从最短的锁定时间开始（智能限流）。
     pub fn mark_account_success(&self, account_id: &str) {
         self.rate_limit_tracker.mark_success(account_id);
+        // 调用方通常传入 email，统一转换为 account_id 以更新熔断状态
+        let id = self.email_to_account_id(account_id).unwrap_or_else(|| account_id.to_string());
+        self.token_breakers.record_success(&id);
     }
     
     /// 检查是否有可用的 Google 账号
@@ -1255,6 +1624,17 @@ impl TokenManager {
         false
     }
     
+    /// 是否至少有一个账号可用于 `model` (未被该模型限流且熔断未打开)
+    pub async fn is_model_available(&self, model: &str) -> bool {
+        for entry in self.tokens.iter() {
+            let account_id = entry.key();
+            if !self.is_rate_limited(account_id, Some(model)).await && !self.token_breakers.is_open(account_id) {
+                return true;
+            }
+        }
+        false
+    }
+
     /// 从账号文件获取配额刷新时间
     /// 
     /// 返回该账号最近的配额刷新时间字符串（ISO 8601 格式）
@@ -1400,6 +1780,10 @@ impl TokenManager {
         error_body: &str,
         model: Option<&str>,  // 🆕 新增模型参数
     ) {
+        crate::proxy::metrics::metrics()
+            .record_token_error(email, crate::proxy::metrics::token_error_reason(status));
+        self.record_token_failure(email);
+
         // [NEW] 检查熔断是否启用
         let config = self.circuit_breaker_config.read().await.clone();
         if !config.enabled {
@@ -1554,7 +1938,7 @@ impl TokenManager {
             .map_err(|e| format!("Invalid refresh token: {}", e))?;
 
         // 2. 获取项目 ID (Project ID)
-        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_info.access_token)
+        let project_id = crate::proxy::project_resolver::fetch_project_id(&token_info.access_token, &self.upstream_user_agent())
             .await
             .unwrap_or_else(|_| "bamboo-precept-lgxtn".to_string()); // Fallback
 
@@ -1598,6 +1982,17 @@ impl TokenManager {
     }
 }
 
+/// 判断 token 是否需要刷新
+/// - 本地记录的过期时间前 5 分钟
+/// - 或 JWT payload 中的 exp 距今不足 2 分钟 (不透明 token 无法解析时忽略)
+fn token_needs_refresh(token: &ProxyToken, now: i64) -> bool {
+    if now >= token.timestamp - 300 {
+        return true;
+    }
+    let now_utc = chrono::DateTime::from_timestamp(now, 0).unwrap_or_else(chrono::Utc::now);
+    jwt::jwt_expires_within(&token.access_token, jwt::JWT_EXPIRY_SKEW_SECS, now_utc).unwrap_or(false)
+}
+
 /// 截断过长的原因字符串
 fn truncate_reason(reason: &str, max_len: usize) -> String {
     if reason.len() <= max_len {
@@ -1606,3 +2001,206 @@ fn truncate_reason(reason: &str, max_len: usize) -> String {
         format!("{}...", &reason[..max_len - 3])
     }
 }
+
+/// 测试用账号 (token 未过期、已有 project_id，可直接调度)
+#[cfg(test)]
+pub(crate) fn runtime_token(email: &str) -> ProxyToken {
+    ProxyToken {
+        account_id: format!("runtime-{}", email),
+        access_token: format!("ya29.{}", email),
+        refresh_token: String::new(),
+        expires_in: 3600,
+        timestamp: chrono::Utc::now().timestamp() + 3600,
+        email: email.to_string(),
+        account_path: PathBuf::new(),
+        project_id: Some("test-project".to_string()),
+        subscription_tier: None,
+        remaining_quota: None,
+        protected_models: HashSet::new(),
+        health_score: 1.0,
+        history: TokenHistory::default(),
+        daily_token_budget: None,
+        budget_reset_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runtime_added_token_is_immediately_usable() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-runtime-test"));
+        assert!(manager.get_token("gemini", false, None, "gemini-2.5-flash").await.is_err());
+
+        manager.add_token(runtime_token("new@example.com"), false).await.unwrap();
+        let (access_token, project_id, email, _) = manager
+            .get_token("gemini", false, None, "gemini-2.5-flash")
+            .await
+            .unwrap();
+        assert_eq!(access_token, "ya29.new@example.com");
+        assert_eq!(project_id, "test-project");
+        assert_eq!(email, "new@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_budget_exhausted_token_is_skipped() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-budget-test"));
+        let mut limited = runtime_token("limited@example.com");
+        limited.daily_token_budget = Some(1000);
+        manager.add_token(limited, false).await.unwrap();
+        manager.add_token(runtime_token("free@example.com"), false).await.unwrap();
+
+        manager.record_usage("limited@example.com", 700, 300);
+        for _ in 0..4 {
+            let (_, _, email, _) = manager.get_token("gemini", true, None, "gemini-2.5-flash").await.unwrap();
+            assert_eq!(email, "free@example.com");
+        }
+
+        let statuses = manager.list_tokens().await;
+        let limited = statuses.iter().find(|s| s.email == "limited@example.com").unwrap();
+        assert_eq!(limited.budget_utilization_pct, Some(100.0));
+        assert_eq!(limited.budget_reset_at, Some(budget::next_utc_midnight(chrono::Utc::now())));
+        let free = statuses.iter().find(|s| s.email == "free@example.com").unwrap();
+        assert_eq!(free.budget_utilization_pct, None);
+
+        // 会话亲和路径同样受预算约束
+        assert!(!manager.is_email_available("limited@example.com").await);
+        assert!(manager.is_email_available("free@example.com").await);
+
+        manager.remove_token("free@example.com");
+        assert!(manager.get_token("gemini", false, None, "gemini-2.5-flash").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_reset_token() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-runtime-test"));
+        let account_id = manager.add_token(runtime_token("a@example.com"), false).await.unwrap();
+        // 同邮箱再次添加沿用原 account_id
+        assert_eq!(manager.add_token(runtime_token("a@example.com"), false).await.unwrap(), account_id);
+        assert_eq!(manager.len(), 1);
+
+        for _ in 0..10 {
+            manager.record_token_failure("a@example.com");
+        }
+        assert!(manager.reset_token("a@example.com"));
+        assert_eq!(manager.list_tokens().await[0].circuit_state, CircuitState::Closed);
+
+        assert!(manager.remove_token("a@example.com"));
+        assert!(!manager.remove_token("a@example.com"));
+        assert!(!manager.reset_token("a@example.com"));
+        assert_eq!(manager.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_trial_settled_by_request_outcome() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-breaker-trial-test"));
+        manager.update_token_circuit_breaker_config(crate::proxy::config::TokenCircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 1,
+            recovery_timeout_secs: 0,
+        });
+        manager.add_token(runtime_token("a@example.com"), false).await.unwrap();
+        manager.record_token_failure("a@example.com");
+
+        // 恢复期为 0：熔断后立即进入半开，返回账号时占用试探名额
+        let (_, _, email, _) = manager.get_token("gemini", false, None, "gemini-2.5-flash").await.unwrap();
+        assert_eq!(email, "a@example.com");
+        assert_eq!(manager.list_tokens().await[0].circuit_state, CircuitState::HalfOpen);
+        assert_eq!(manager.circuit_breaker_counts(), CircuitBreakerCounts { open: 0, half_open: 1 });
+
+        // 非账号原因的失败只释放名额
+        let err = crate::proxy::error::ProxyError::NetworkError("reset".to_string());
+        manager.record_request("a@example.com", "gemini-2.5-flash", 10, Some(&err));
+        assert_eq!(manager.list_tokens().await[0].circuit_state, CircuitState::HalfOpen);
+        manager.get_token("gemini", false, None, "gemini-2.5-flash").await.unwrap();
+
+        manager.record_request("a@example.com", "gemini-2.5-flash", 10, None);
+        assert_eq!(manager.list_tokens().await[0].circuit_state, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_model_availability_respects_model_lockout() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-model-avail-test"));
+        assert!(!manager.is_model_available("gemini-3-pro-preview").await);
+
+        let account_id = manager.add_token(runtime_token("m@example.com"), false).await.unwrap();
+        assert!(manager.is_model_available("gemini-3-pro-preview").await);
+
+        manager.rate_limit_tracker.set_lockout_until(
+            &account_id,
+            std::time::SystemTime::now() + std::time::Duration::from_secs(60),
+            crate::proxy::rate_limit::RateLimitReason::QuotaExhausted,
+            Some("gemini-3-pro-preview".to_string()),
+        );
+        assert!(!manager.is_model_available("gemini-3-pro-preview").await);
+        assert!(manager.is_model_available("gemini-3-flash").await);
+    }
+
+    #[tokio::test]
+    async fn test_request_history_capped_and_kept_across_re_add() {
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-history-test"));
+        assert!(manager.request_history("h@example.com").is_none());
+
+        manager.add_token(runtime_token("h@example.com"), false).await.unwrap();
+        manager.record_request("h@example.com", "gemini-2.5-flash", 10, Some(&crate::proxy::error::ProxyError::Unauthorized));
+        for i in 0..120 {
+            manager.record_request("h@example.com", "gemini-2.5-pro", i, None);
+        }
+
+        // 重新添加同邮箱账号后历史仍保留
+        manager.add_token(runtime_token("h@example.com"), false).await.unwrap();
+        let history = manager.request_history("h@example.com").unwrap();
+        assert_eq!(history.len(), 100);
+        assert!(history.iter().all(|r| r.success && r.error_type.is_none()));
+        assert_eq!(history[0].latency_ms, 20);
+        assert_eq!(history[99].latency_ms, 119);
+    }
+
+    /// 本地 mock countTokens 端点，按 access_token 返回不同状态码
+    async fn probe_manager() -> TokenManager {
+        use axum::{http::{HeaderMap, StatusCode}, routing::post, Json};
+
+        let router = axum::Router::new().route(
+            "/v1internal:countTokens",
+            post(|headers: HeaderMap| async move {
+                match headers["authorization"].to_str().unwrap() {
+                    "Bearer ya29.bad@example.com" => (StatusCode::UNAUTHORIZED, Json(serde_json::json!({}))),
+                    "Bearer ya29.busy@example.com" => (StatusCode::TOO_MANY_REQUESTS, Json(serde_json::json!({}))),
+                    _ => (StatusCode::OK, Json(serde_json::json!({"totalTokens": 1}))),
+                }
+            }),
+        );
+        let base = crate::proxy::tests::support::spawn_mock_upstream(router).await;
+
+        let manager = TokenManager::new(std::env::temp_dir().join("tm-probe-test"));
+        manager.set_upstream(Arc::new(
+            crate::proxy::upstream::client::UpstreamClient::new(None).with_base_urls(vec![base]),
+        ));
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_add_token_probe_success() {
+        let manager = probe_manager().await;
+        let account_id = manager.add_token(runtime_token("good@example.com"), true).await.unwrap();
+        assert_eq!(manager.len(), 1);
+        assert!(!manager.rate_limit_tracker.is_rate_limited(&account_id, None));
+    }
+
+    #[tokio::test]
+    async fn test_add_token_probe_rejects_unauthorized() {
+        let manager = probe_manager().await;
+        let err = manager.add_token(runtime_token("bad@example.com"), true).await.unwrap_err();
+        assert!(err.contains("rejected"));
+        assert_eq!(manager.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_add_token_probe_rate_limited_is_blacklisted() {
+        let manager = probe_manager().await;
+        let account_id = manager.add_token(runtime_token("busy@example.com"), true).await.unwrap();
+        assert_eq!(manager.len(), 1);
+        assert!(manager.rate_limit_tracker.is_rate_limited(&account_id, None));
+    }
+}
@@ -0,0 +1,135 @@
+// 账号每日 token 预算 (按 UTC 自然日统计，按邮箱记录，账号重新加载后仍保留)
+// 当日用量达到预算后锁定至次日 UTC 零点，由后台任务按分钟解锁
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use dashmap::DashMap;
+
+#[derive(Debug, Clone, Copy)]
+struct DailyUsage {
+    day: NaiveDate,
+    tokens: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct DailyBudgets {
+    usage: DashMap<String, DailyUsage>,
+    locks: DashMap<String, DateTime<Utc>>, // email -> 解锁时间
+}
+
+/// 下一个 UTC 零点
+pub fn next_utc_midnight(now: DateTime<Utc>) -> DateTime<Utc> {
+    let tomorrow = now.date_naive().checked_add_days(Days::new(1)).unwrap_or(now.date_naive());
+    tomorrow.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+impl DailyBudgets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 累加当日用量 (跨日后自动从零开始)
+    pub fn record(&self, email: &str, tokens: u64, now: DateTime<Utc>) {
+        if tokens == 0 {
+            return;
+        }
+        let today = now.date_naive();
+        let mut entry = self
+            .usage
+            .entry(email.to_string())
+            .or_insert(DailyUsage { day: today, tokens: 0 });
+        if entry.day != today {
+            *entry = DailyUsage { day: today, tokens: 0 };
+        }
+        entry.tokens = entry.tokens.saturating_add(tokens);
+    }
+
+    /// 当日已用 token 数
+    pub fn used_today(&self, email: &str, now: DateTime<Utc>) -> u64 {
+        self.usage
+            .get(email)
+            .filter(|u| u.day == now.date_naive())
+            .map(|u| u.tokens)
+            .unwrap_or(0)
+    }
+
+    /// 检查预算；超出时锁定至次日零点。返回锁定截止时间 (未锁定为 None)
+    pub fn check(&self, email: &str, budget: Option<u64>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if let Some(reset_at) = self.reset_at(email, now) {
+            return Some(reset_at);
+        }
+        let budget = budget?;
+        if self.used_today(email, now) < budget {
+            return None;
+        }
+        let reset_at = next_utc_midnight(now);
+        self.locks.insert(email.to_string(), reset_at);
+        tracing::warn!("账号 {} 已用尽每日 token 预算 ({})，禁用至 {}", email, budget, reset_at);
+        Some(reset_at)
+    }
+
+    /// 未过期的锁定截止时间
+    pub fn reset_at(&self, email: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.locks.get(email).map(|r| *r).filter(|r| *r > now)
+    }
+
+    /// 解除已到期的锁定，返回被解锁的邮箱
+    pub fn release_expired(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut released = Vec::new();
+        self.locks.retain(|email, reset_at| {
+            if *reset_at > now {
+                return true;
+            }
+            released.push(email.clone());
+            false
+        });
+        released
+    }
+
+    /// 预算使用率 (百分比，未设置预算时为 None)
+    pub fn utilization_pct(&self, email: &str, budget: Option<u64>, now: DateTime<Utc>) -> Option<f64> {
+        let budget = budget?;
+        if budget == 0 {
+            return Some(100.0);
+        }
+        Some(self.used_today(email, now) as f64 * 100.0 / budget as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 14, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_lock_when_budget_exceeded_until_midnight() {
+        let budgets = DailyBudgets::new();
+        budgets.record("a@example.com", 600, at(10, 0));
+        assert_eq!(budgets.check("a@example.com", Some(1000), at(10, 0)), None);
+        assert_eq!(budgets.utilization_pct("a@example.com", Some(1000), at(10, 0)), Some(60.0));
+
+        budgets.record("a@example.com", 400, at(11, 0));
+        let reset_at = budgets.check("a@example.com", Some(1000), at(11, 0)).unwrap();
+        assert_eq!(reset_at, Utc.with_ymd_and_hms(2026, 3, 15, 0, 0, 0).unwrap());
+        assert_eq!(budgets.check("b@example.com", Some(1000), at(11, 0)), None);
+        // 锁定期间不再重新判定预算
+        assert_eq!(budgets.check("a@example.com", None, at(11, 0)), Some(reset_at));
+
+        // 未到零点不解锁
+        assert!(budgets.release_expired(at(23, 59)).is_empty());
+        assert_eq!(budgets.release_expired(reset_at), vec!["a@example.com".to_string()]);
+        // 跨日后用量清零
+        assert_eq!(budgets.used_today("a@example.com", reset_at), 0);
+        assert_eq!(budgets.check("a@example.com", Some(1000), reset_at), None);
+    }
+
+    #[test]
+    fn test_no_budget_never_locks() {
+        let budgets = DailyBudgets::new();
+        budgets.record("a@example.com", u64::MAX, at(0, 0));
+        assert_eq!(budgets.check("a@example.com", None, at(0, 0)), None);
+        assert_eq!(budgets.utilization_pct("a@example.com", None, at(0, 0)), None);
+    }
+}
@@ -0,0 +1,349 @@
+// 账号级熔断器 (Closed → Open → HalfOpen → Closed)
+// 连续失败达到阈值后熔断，恢复期过后放行一次试探请求
+// 试探结果未回报 (如请求中途被取消) 时，超过恢复期后允许重新试探，避免名额泄漏
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::proxy::config::TokenCircuitBreakerConfig;
+
+/// 对外暴露的熔断状态 (供健康检查使用)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed { consecutive_failures: u32 },
+    Open { since: Instant },
+    /// trial_started: 进行中的试探请求开始时间 (None 表示名额空闲)
+    HalfOpen { trial_started: Option<Instant> },
+}
+
+pub struct TokenCircuitBreakers {
+    config: RwLock<TokenCircuitBreakerConfig>,
+    states: DashMap<String, BreakerState>,
+}
+
+impl TokenCircuitBreakers {
+    pub fn new(config: TokenCircuitBreakerConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            states: DashMap::new(),
+        }
+    }
+
+    pub fn update_config(&self, config: TokenCircuitBreakerConfig) {
+        if let Ok(mut guard) = self.config.write() {
+            *guard = config;
+        }
+    }
+
+    fn config(&self) -> TokenCircuitBreakerConfig {
+        self.config.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// 是否允许向该账号发送请求 (半开状态下会占用唯一的试探名额)
+    pub fn allow_request(&self, account_id: &str) -> bool {
+        self.allow_request_at(account_id, Instant::now())
+    }
+
+    fn allow_request_at(&self, account_id: &str, now: Instant) -> bool {
+        let config = self.config();
+        if !config.enabled {
+            return true;
+        }
+        let Some(mut state) = self.states.get_mut(account_id) else {
+            return true;
+        };
+
+        let recovery = Duration::from_secs(config.recovery_timeout_secs);
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { since } => {
+                if now.duration_since(since) >= recovery {
+                    tracing::info!("[CircuitBreaker] {} half-open, allowing one trial request", account_id);
+                    *state = BreakerState::HalfOpen { trial_started: Some(now) };
+                    true
+                } else {
+                    false
+                }
+            }
+            BreakerState::HalfOpen { trial_started } => match trial_started {
+                Some(started) if now.duration_since(started) < recovery => false,
+                Some(_) => {
+                    tracing::warn!("[CircuitBreaker] {} trial request never reported back, retrying trial", account_id);
+                    *state = BreakerState::HalfOpen { trial_started: Some(now) };
+                    true
+                }
+                None => {
+                    *state = BreakerState::HalfOpen { trial_started: Some(now) };
+                    true
+                }
+            },
+        }
+    }
+
+    /// 只读版 allow_request：判断当前是否会放行，但不占用试探名额 (供候选账号筛选使用)
+    pub fn would_allow(&self, account_id: &str) -> bool {
+        self.would_allow_at(account_id, Instant::now())
+    }
+
+    fn would_allow_at(&self, account_id: &str, now: Instant) -> bool {
+        let config = self.config();
+        if !config.enabled {
+            return true;
+        }
+        let recovery = Duration::from_secs(config.recovery_timeout_secs);
+        match self.states.get(account_id).map(|s| *s) {
+            None | Some(BreakerState::Closed { .. }) => true,
+            Some(BreakerState::Open { since }) => now.duration_since(since) >= recovery,
+            Some(BreakerState::HalfOpen { trial_started }) => {
+                trial_started.is_none_or(|started| now.duration_since(started) >= recovery)
+            }
+        }
+    }
+
+    /// 试探请求结束但结果不代表账号健康状况 (如客户端错误、网络中断)：释放名额，保持半开
+    pub fn release_trial(&self, account_id: &str) {
+        if let Some(mut state) = self.states.get_mut(account_id) {
+            if let BreakerState::HalfOpen { trial_started: Some(_) } = *state {
+                *state = BreakerState::HalfOpen { trial_started: None };
+            }
+        }
+    }
+
+    /// 只读检查：熔断中且未到恢复期 (不占用试探名额)
+    pub fn is_open(&self, account_id: &str) -> bool {
+        let config = self.config();
+        if !config.enabled {
+            return false;
+        }
+        match self.states.get(account_id).map(|s| *s) {
+            Some(BreakerState::Open { since }) => {
+                since.elapsed() < Duration::from_secs(config.recovery_timeout_secs)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn record_success(&self, account_id: &str) {
+        if let Some(mut state) = self.states.get_mut(account_id) {
+            if !matches!(*state, BreakerState::Closed { consecutive_failures: 0 }) {
+                tracing::debug!("[CircuitBreaker] {} closed", account_id);
+            }
+            *state = BreakerState::Closed { consecutive_failures: 0 };
+        }
+    }
+
+    pub fn record_failure(&self, account_id: &str) {
+        self.record_failure_at(account_id, Instant::now());
+    }
+
+    fn record_failure_at(&self, account_id: &str, now: Instant) {
+        let threshold = self.config().failure_threshold.max(1);
+        let mut state = self
+            .states
+            .entry(account_id.to_string())
+            .or_insert(BreakerState::Closed { consecutive_failures: 0 });
+
+        *state = match *state {
+            BreakerState::Closed { consecutive_failures } => {
+                let failures = consecutive_failures + 1;
+                if failures >= threshold {
+                    tracing::warn!(
+                        "[CircuitBreaker] {} opened after {} consecutive failures",
+                        account_id,
+                        failures
+                    );
+                    BreakerState::Open { since: now }
+                } else {
+                    BreakerState::Closed { consecutive_failures: failures }
+                }
+            }
+            // 试探失败：重新熔断
+            BreakerState::HalfOpen { .. } => {
+                tracing::warn!("[CircuitBreaker] {} trial request failed, reopening", account_id);
+                BreakerState::Open { since: now }
+            }
+            BreakerState::Open { since } => BreakerState::Open { since },
+        };
+    }
+
+    /// 手动重置 (恢复为 Closed 并清零失败计数)
+    pub fn reset(&self, account_id: &str) {
+        if self.states.remove(account_id).is_some() {
+            tracing::info!("[CircuitBreaker] {} manually reset", account_id);
+        }
+    }
+
+    /// 单个账号的熔断状态 (未记录过视为 Closed)
+    pub fn state_of(&self, account_id: &str) -> CircuitState {
+        match self.states.get(account_id).map(|s| *s) {
+            Some(BreakerState::Open { .. }) => CircuitState::Open,
+            Some(BreakerState::HalfOpen { .. }) => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+
+    /// 当前所有账号的熔断状态 (未记录过的账号视为 Closed，不出现在结果中)
+    pub fn states(&self) -> HashMap<String, CircuitState> {
+        self.states
+            .iter()
+            .map(|e| {
+                let state = match e.value() {
+                    BreakerState::Closed { .. } => CircuitState::Closed,
+                    BreakerState::Open { .. } => CircuitState::Open,
+                    BreakerState::HalfOpen { .. } => CircuitState::HalfOpen,
+                };
+                (e.key().clone(), state)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakers() -> TokenCircuitBreakers {
+        TokenCircuitBreakers::new(TokenCircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 3,
+            recovery_timeout_secs: 30,
+        })
+    }
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let b = breakers();
+        let now = Instant::now();
+        b.record_failure_at("acc", now);
+        b.record_failure_at("acc", now);
+        assert!(b.allow_request_at("acc", now));
+        b.record_failure_at("acc", now);
+        assert_eq!(b.states()["acc"], CircuitState::Open);
+        assert!(!b.allow_request_at("acc", now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_half_open_allows_single_trial() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        let later = now + Duration::from_secs(31);
+        assert!(b.allow_request_at("acc", later));
+        assert_eq!(b.states()["acc"], CircuitState::HalfOpen);
+        // 试探进行中，其他请求继续跳过
+        assert!(!b.allow_request_at("acc", later));
+
+        b.record_success("acc");
+        assert_eq!(b.states()["acc"], CircuitState::Closed);
+        assert!(b.allow_request_at("acc", later));
+    }
+
+    #[test]
+    fn test_half_open_failure_reopens() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        let later = now + Duration::from_secs(31);
+        assert!(b.allow_request_at("acc", later));
+        b.record_failure_at("acc", later);
+        assert_eq!(b.states()["acc"], CircuitState::Open);
+        assert!(!b.allow_request_at("acc", later + Duration::from_secs(5)));
+        assert!(b.allow_request_at("acc", later + Duration::from_secs(31)));
+    }
+
+    #[test]
+    fn test_would_allow_does_not_take_trial() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        let later = now + Duration::from_secs(31);
+        assert!(b.would_allow_at("acc", later));
+        assert!(b.would_allow_at("acc", later));
+        assert!(b.allow_request_at("acc", later));
+        assert!(!b.would_allow_at("acc", later));
+    }
+
+    #[test]
+    fn test_released_trial_can_be_retaken() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        let later = now + Duration::from_secs(31);
+        assert!(b.allow_request_at("acc", later));
+        b.release_trial("acc");
+        assert_eq!(b.state_of("acc"), CircuitState::HalfOpen);
+        assert!(b.allow_request_at("acc", later));
+        assert!(!b.allow_request_at("acc", later));
+    }
+
+    #[test]
+    fn test_unreported_trial_expires() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        let later = now + Duration::from_secs(31);
+        assert!(b.allow_request_at("acc", later));
+        // 试探结果从未回报
+        assert!(!b.allow_request_at("acc", later + Duration::from_secs(10)));
+        assert!(b.would_allow_at("acc", later + Duration::from_secs(30)));
+        assert!(b.allow_request_at("acc", later + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let b = breakers();
+        let now = Instant::now();
+        b.record_failure_at("acc", now);
+        b.record_failure_at("acc", now);
+        b.record_success("acc");
+        b.record_failure_at("acc", now);
+        b.record_failure_at("acc", now);
+        assert_eq!(b.states()["acc"], CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_manual_reset_closes_breaker() {
+        let b = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at("acc", now);
+        }
+        assert_eq!(b.state_of("acc"), CircuitState::Open);
+        b.reset("acc");
+        assert_eq!(b.state_of("acc"), CircuitState::Closed);
+        assert!(b.allow_request_at("acc", now));
+    }
+
+    #[test]
+    fn test_disabled_always_allows() {
+        let b = breakers();
+        b.update_config(TokenCircuitBreakerConfig {
+            enabled: false,
+            ..TokenCircuitBreakerConfig::default()
+        });
+        for _ in 0..10 {
+            b.record_failure("acc");
+        }
+        assert!(b.allow_request("acc"));
+    }
+}
@@ -0,0 +1,74 @@
+// 账号请求历史 - 每个账号保留最近 MAX_HISTORY 次请求尝试，便于排查反复失败的原因
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::proxy::error::ProxyError;
+
+/// 每个账号最多保留的记录数，超出后淘汰最旧的记录
+pub const MAX_HISTORY: usize = 100;
+
+/// 单次请求尝试记录
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenRequestRecord {
+    pub timestamp: i64,
+    pub model: String,
+    pub latency_ms: u64,
+    pub success: bool,
+    pub error_type: Option<ProxyError>,
+}
+
+/// 有界请求历史；clone 后共享同一缓冲 (ProxyToken 被复制时历史不分叉)
+#[derive(Debug, Clone, Default)]
+pub struct TokenHistory(Arc<Mutex<VecDeque<TokenRequestRecord>>>);
+
+impl TokenHistory {
+    pub fn push(&self, record: TokenRequestRecord) {
+        let mut records = self.0.lock().unwrap();
+        if records.len() >= MAX_HISTORY {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// 按时间顺序 (旧 -> 新) 返回全部记录
+    pub fn snapshot(&self) -> Vec<TokenRequestRecord> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(i: u64) -> TokenRequestRecord {
+        TokenRequestRecord {
+            timestamp: i as i64,
+            model: "gemini-2.5-flash".to_string(),
+            latency_ms: i,
+            success: i.is_multiple_of(2),
+            error_type: (!i.is_multiple_of(2)).then_some(ProxyError::Timeout),
+        }
+    }
+
+    #[test]
+    fn test_history_capped_and_evicts_oldest() {
+        let history = TokenHistory::default();
+        for i in 0..(MAX_HISTORY as u64 + 20) {
+            history.push(record(i));
+        }
+
+        let records = history.snapshot();
+        assert_eq!(records.len(), MAX_HISTORY);
+        assert_eq!(records.first().unwrap().latency_ms, 20);
+        assert_eq!(records.last().unwrap().latency_ms, MAX_HISTORY as u64 + 19);
+    }
+
+    #[test]
+    fn test_clones_share_history() {
+        let history = TokenHistory::default();
+        let clone = history.clone();
+        clone.push(record(1));
+        assert_eq!(history.snapshot(), vec![record(1)]);
+    }
+}
@@ -0,0 +1,96 @@
+// JWT 过期时间解析 (仅解码 payload，不校验签名)
+use base64::Engine as _;
+use chrono::{DateTime, TimeZone, Utc};
+
+/// 距离过期不足该秒数的 token 视为即将过期，需要提前刷新
+pub const JWT_EXPIRY_SKEW_SECS: i64 = 120;
+
+/// 从 JWT 的 payload 段解析 `exp` (Unix 秒)
+/// 非 JWT 格式 (如 Google 不透明的 `ya29.` token) 或缺少 exp 时返回 None
+pub fn parse_jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let mut segments = token.split('.');
+    let (_header, payload, _signature) = (segments.next()?, segments.next()?, segments.next()?);
+    if segments.next().is_some() {
+        return None;
+    }
+
+    // JWT 使用 base64url 编码，通常不带 padding
+    let payload = payload.trim_end_matches('=');
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+
+    let exp = claims.get("exp")?;
+    let secs = exp
+        .as_i64()
+        .or_else(|| exp.as_f64().map(|f| f as i64))?;
+    Utc.timestamp_opt(secs, 0).single()
+}
+
+/// token 是否将在 `skew_secs` 秒内过期 (无法解析时返回 None，由调用方回退到本地记录的过期时间)
+pub fn jwt_expires_within(token: &str, skew_secs: i64, now: DateTime<Utc>) -> Option<bool> {
+    parse_jwt_expiry(token).map(|exp| exp.timestamp() - now.timestamp() <= skew_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(json: &str) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    fn make_jwt(payload: &str) -> String {
+        format!("{}.{}.signature", encode(r#"{"alg":"RS256","typ":"JWT"}"#), encode(payload))
+    }
+
+    #[test]
+    fn test_parse_valid_exp() {
+        let token = make_jwt(r#"{"sub":"123","exp":1700000000}"#);
+        let exp = parse_jwt_expiry(&token).unwrap();
+        assert_eq!(exp.timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_parse_float_exp() {
+        let token = make_jwt(r#"{"exp":1700000000.5}"#);
+        assert_eq!(parse_jwt_expiry(&token).unwrap().timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_parse_padded_payload() {
+        let payload = base64::engine::general_purpose::URL_SAFE.encode(r#"{"exp":1700000000}"#);
+        let token = format!("h.{}.s", payload);
+        assert_eq!(parse_jwt_expiry(&token).unwrap().timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_parse_missing_exp() {
+        assert!(parse_jwt_expiry(&make_jwt(r#"{"sub":"123"}"#)).is_none());
+        assert!(parse_jwt_expiry(&make_jwt(r#"{"exp":"soon"}"#)).is_none());
+    }
+
+    #[test]
+    fn test_parse_non_jwt_tokens() {
+        // Google OAuth access token 通常是不透明字符串
+        assert!(parse_jwt_expiry("ya29.a0AfH6SMBxyz").is_none());
+        assert!(parse_jwt_expiry("").is_none());
+        assert!(parse_jwt_expiry("a.b.c.d").is_none());
+        assert!(parse_jwt_expiry("header.!!!notbase64!!!.sig").is_none());
+        assert!(parse_jwt_expiry(&format!("h.{}.s", encode("not json"))).is_none());
+    }
+
+    #[test]
+    fn test_expires_within() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let soon = make_jwt(r#"{"exp":1700000060}"#);
+        let later = make_jwt(r#"{"exp":1700003600}"#);
+        let expired = make_jwt(r#"{"exp":1699999000}"#);
+
+        assert_eq!(jwt_expires_within(&soon, JWT_EXPIRY_SKEW_SECS, now), Some(true));
+        assert_eq!(jwt_expires_within(&later, JWT_EXPIRY_SKEW_SECS, now), Some(false));
+        assert_eq!(jwt_expires_within(&expired, JWT_EXPIRY_SKEW_SECS, now), Some(true));
+        assert_eq!(jwt_expires_within("ya29.opaque", JWT_EXPIRY_SKEW_SECS, now), None);
+    }
+}
@@ -0,0 +1,105 @@
+// 账号累计 token 用量 (按邮箱统计，账号重新加载后仍保留)
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 单个账号的累计用量快照
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountUsage {
+    pub email: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Default)]
+struct UsageCounters {
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenUsageTracker {
+    counters: DashMap<String, UsageCounters>,
+}
+
+impl TokenUsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, email: &str, prompt_tokens: u64, completion_tokens: u64) {
+        if prompt_tokens == 0 && completion_tokens == 0 {
+            return;
+        }
+        let counters = self.counters.entry(email.to_string()).or_default();
+        counters.prompt_tokens.fetch_add(prompt_tokens, Ordering::Relaxed);
+        counters.completion_tokens.fetch_add(completion_tokens, Ordering::Relaxed);
+    }
+
+    /// 从持久化数据恢复 (覆盖当前值)
+    pub fn restore(&self, usage: &[AccountUsage]) {
+        for u in usage {
+            let counters = self.counters.entry(u.email.clone()).or_default();
+            counters.prompt_tokens.store(u.prompt_tokens, Ordering::Relaxed);
+            counters.completion_tokens.store(u.completion_tokens, Ordering::Relaxed);
+        }
+    }
+
+    /// 按邮箱排序的用量报告
+    pub fn report(&self) -> Vec<AccountUsage> {
+        let mut report: Vec<AccountUsage> = self
+            .counters
+            .iter()
+            .map(|e| {
+                let prompt_tokens = e.prompt_tokens.load(Ordering::Relaxed);
+                let completion_tokens = e.completion_tokens.load(Ordering::Relaxed);
+                AccountUsage {
+                    email: e.key().clone(),
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.email.cmp(&b.email));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_per_account() {
+        let tracker = TokenUsageTracker::new();
+        tracker.record("b@example.com", 10, 5);
+        tracker.record("a@example.com", 1, 2);
+        tracker.record("b@example.com", 20, 15);
+        tracker.record("c@example.com", 0, 0);
+
+        let report = tracker.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].email, "a@example.com");
+        assert_eq!(report[1].prompt_tokens, 30);
+        assert_eq!(report[1].completion_tokens, 20);
+        assert_eq!(report[1].total_tokens, 50);
+    }
+
+    #[test]
+    fn test_restore_then_record() {
+        let tracker = TokenUsageTracker::new();
+        tracker.restore(&[AccountUsage {
+            email: "a@example.com".to_string(),
+            prompt_tokens: 100,
+            completion_tokens: 50,
+            total_tokens: 150,
+        }]);
+        tracker.record("a@example.com", 1, 1);
+
+        let report = tracker.report();
+        assert_eq!(report[0].prompt_tokens, 101);
+        assert_eq!(report[0].completion_tokens, 51);
+    }
+}
@@ -0,0 +1,77 @@
+// 流量拆分 (A/B 测试) - 请求模型匹配规则时按百分比随机改用某个变体模型
+use rand::Rng;
+use regex::Regex;
+
+use crate::proxy::config::TrafficSplit;
+
+/// 预编译正则的拆分规则 (按配置顺序匹配第一条)
+#[derive(Debug, Default)]
+pub struct TrafficSplitter {
+    rules: Vec<(Regex, TrafficSplit)>,
+}
+
+impl TrafficSplitter {
+    /// 正则无效的规则记录警告后跳过 (启动前已由 ProxyConfig::validate 拦截)
+    pub fn new(splits: &[TrafficSplit]) -> Self {
+        let rules = splits
+            .iter()
+            .filter_map(|split| match Regex::new(&split.model_pattern) {
+                Ok(re) => Some((re, split.clone())),
+                Err(e) => {
+                    tracing::warn!("[TrafficSplit] Invalid model_pattern {}: {}", split.model_pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// 为请求模型选取变体；未匹配任何规则时返回 None
+    pub fn select(&self, model: &str) -> Option<String> {
+        self.select_with(model, &mut rand::thread_rng())
+    }
+
+    fn select_with<R: Rng>(&self, model: &str, rng: &mut R) -> Option<String> {
+        let (_, split) = self.rules.iter().find(|(re, _)| re.is_match(model))?;
+        split.pick(rng.gen_range(0..100)).map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splitter() -> TrafficSplitter {
+        TrafficSplitter::new(&[TrafficSplit {
+            model_pattern: "^gpt-4o".to_string(),
+            variants: vec![("gemini-2.5-flash".to_string(), 70), ("gemini-3-flash".to_string(), 30)],
+        }])
+    }
+
+    #[test]
+    fn test_distribution_within_tolerance() {
+        let splitter = splitter();
+        let iterations = 10_000;
+        let flash = (0..iterations)
+            .filter(|_| splitter.select("gpt-4o-mini").as_deref() == Some("gemini-2.5-flash"))
+            .count();
+        let ratio = flash as f64 / iterations as f64;
+        assert!((ratio - 0.70).abs() <= 0.05, "ratio {}", ratio);
+    }
+
+    #[test]
+    fn test_unmatched_model_not_split() {
+        assert_eq!(splitter().select("claude-sonnet-4-5"), None);
+        assert_eq!(TrafficSplitter::default().select("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_pick_boundaries() {
+        let splitter = splitter();
+        let split = &splitter.rules[0].1;
+        assert_eq!(split.pick(0), Some("gemini-2.5-flash"));
+        assert_eq!(split.pick(69), Some("gemini-2.5-flash"));
+        assert_eq!(split.pick(70), Some("gemini-3-flash"));
+        assert_eq!(split.pick(99), Some("gemini-3-flash"));
+    }
+}
@@ -1,6 +1,9 @@
 // 上游客户端实现
 // 基于高性能通讯接口封装
 
+use crate::proxy::error::ProxyError;
+use crate::proxy::mappers::claude::models::{GeminiContent, GeminiPart};
+use crate::proxy::mappers::gemini::models::{GeminiRequest, GeminiSystemInstruction, V1InternalBody};
 use reqwest::{header, Client, Response, StatusCode};
 use serde_json::Value;
 use tokio::time::Duration;
@@ -19,18 +22,55 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 3] = [
 
 pub struct UpstreamClient {
     http_client: Client,
+    base_urls: Vec<String>, // v1internal 端点 (按 Fallback 顺序)
+    user_agent: header::HeaderValue,
+    compress_requests: bool, // [NEW] 请求体 gzip 压缩
 }
 
 impl UpstreamClient {
     pub fn new(proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>) -> Self {
+        let defaults = crate::proxy::config::ProxyConfig::default();
+        Self::with_options(
+            proxy_config,
+            defaults.http_pool_max_idle_per_host,
+            Duration::from_secs(defaults.http_pool_idle_timeout_secs),
+            &defaults.user_agent,
+        )
+    }
+
+    /// 按 ProxyConfig 的上游代理、连接池与 User-Agent 配置创建
+    pub fn with_config(cfg: &crate::proxy::config::ProxyConfig) -> Self {
+        Self::with_options(
+            Some(cfg.upstream_proxy.clone()),
+            cfg.http_pool_max_idle_per_host,
+            Duration::from_secs(cfg.http_pool_idle_timeout_secs),
+            &cfg.user_agent,
+        )
+        .with_compression(cfg.compress_requests)
+    }
+
+    /// 指定连接池大小与 User-Agent
+    /// - 每主机最多保留 `pool_max_idle_per_host` 个空闲连接，空闲超过 `pool_idle_timeout` 后关闭
+    /// - `user_agent` 无效 (含非法字符) 时回落到默认值；Host 由 reqwest 按请求 URL 自动设置
+    pub fn with_options(
+        proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>,
+        pool_max_idle_per_host: usize,
+        pool_idle_timeout: Duration,
+        user_agent: &str,
+    ) -> Self {
+        let user_agent = header::HeaderValue::from_str(user_agent).unwrap_or_else(|_| {
+            tracing::warn!("Invalid upstream User-Agent {:?}, using default", user_agent);
+            header::HeaderValue::from_static(crate::proxy::config::DEFAULT_USER_AGENT)
+        });
+
         let mut builder = Client::builder()
             // Connection settings (优化连接复用，减少建立开销)
             .connect_timeout(Duration::from_secs(20))
-            .pool_max_idle_per_host(16)                  // 每主机最多 16 个空闲连接
-            .pool_idle_timeout(Duration::from_secs(90))  // 空闲连接保持 90 秒
+            .pool_max_idle_per_host(pool_max_idle_per_host)
+            .pool_idle_timeout(pool_idle_timeout)
             .tcp_keepalive(Duration::from_secs(60))      // TCP 保活探测 60 秒
             .timeout(Duration::from_secs(600))
-            .user_agent("antigravity/1.11.9 windows/amd64");
+            .user_agent(user_agent.clone());
 
         if let Some(config) = proxy_config {
             if config.enabled && !config.url.is_empty() {
@@ -43,7 +83,34 @@ impl UpstreamClient {
 
         let http_client = builder.build().expect("Failed to create HTTP client");
 
-        Self { http_client }
+        Self {
+            http_client,
+            base_urls: V1_INTERNAL_BASE_URL_FALLBACKS.iter().map(|s| s.to_string()).collect(),
+            user_agent,
+            compress_requests: false,
+        }
+    }
+
+    /// 启用后 v1internal 请求体以 gzip 压缩发送 (Content-Encoding: gzip)
+    pub fn with_compression(mut self, compress_requests: bool) -> Self {
+        self.compress_requests = compress_requests;
+        self
+    }
+
+    /// 发往上游的 User-Agent (配置值，非法时为默认值)
+    pub fn user_agent(&self) -> &str {
+        self.user_agent.to_str().unwrap_or(crate::proxy::config::DEFAULT_USER_AGENT)
+    }
+
+    /// 替换 v1internal 端点 (影子模式的备用上游；测试中指向本地 mock 服务)
+    pub fn with_base_urls(mut self, base_urls: Vec<String>) -> Self {
+        self.base_urls = base_urls;
+        self
+    }
+
+    /// 共享的 HTTP 客户端 (复用上游代理配置，用于下载远程图片等辅助请求)
+    pub fn http_client(&self) -> &Client {
+        &self.http_client
     }
 
     /// 构建 v1internal URL
@@ -57,6 +124,22 @@ impl UpstreamClient {
         }
     }
 
+    /// 序列化请求体；`compress` 为 true 时 gzip 压缩
+    fn encode_body(body: &Value, compress: bool) -> Result<bytes::Bytes, ProxyError> {
+        use std::io::Write;
+
+        let json = serde_json::to_vec(body).map_err(|e| ProxyError::ParseError(e.to_string()))?;
+        if !compress {
+            return Ok(json.into());
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&json)
+            .and_then(|_| encoder.finish())
+            .map(Into::into)
+            .map_err(|e| ProxyError::ParseError(format!("gzip failed: {}", e)))
+    }
+
     /// 判断是否应尝试下一个端点
     /// 
     /// 当遇到以下错误时，尝试切换到备用端点：
@@ -74,14 +157,16 @@ impl UpstreamClient {
     /// 调用 v1internal API（基础方法）
     /// 
     /// 发起基础网络请求，支持多端点自动 Fallback
+    /// `timeout` 为 None 时使用客户端默认超时
     pub async fn call_v1_internal(
         &self,
         method: &str,
         access_token: &str,
         body: Value,
         query_string: Option<&str>,
-    ) -> Result<Response, String> {
-        self.call_v1_internal_with_headers(method, access_token, body, query_string, std::collections::HashMap::new()).await
+        timeout: Option<Duration>,
+    ) -> Result<Response, ProxyError> {
+        self.call_v1_internal_with_headers(method, access_token, body, query_string, std::collections::HashMap::new(), timeout).await
     }
 
     /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
@@ -92,7 +177,31 @@ impl UpstreamClient {
         body: Value,
         query_string: Option<&str>,
         extra_headers: std::collections::HashMap<String, String>,
-    ) -> Result<Response, String> {
+        timeout: Option<Duration>,
+    ) -> Result<Response, ProxyError> {
+        use tracing::Instrument;
+
+        // [NEW] 链路追踪：流式/非流式生成各自一个 span
+        let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default();
+        let span = match method {
+            "streamGenerateContent" => tracing::info_span!("stream_generate", model = %model),
+            "generateContent" => tracing::info_span!("generate", model = %model),
+            _ => tracing::info_span!("v1internal", method = %method, model = %model),
+        };
+        self.send_v1_internal(method, access_token, body, query_string, extra_headers, timeout)
+            .instrument(span)
+            .await
+    }
+
+    async fn send_v1_internal(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: Value,
+        query_string: Option<&str>,
+        extra_headers: std::collections::HashMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> Result<Response, ProxyError> {
         // 构建 Headers (所有端点复用)
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -102,12 +211,21 @@ impl UpstreamClient {
         headers.insert(
             header::AUTHORIZATION,
             header::HeaderValue::from_str(&format!("Bearer {}", access_token))
-                .map_err(|e| e.to_string())?,
-        );
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
+                .map_err(|_| ProxyError::Unauthorized)?,
         );
+        headers.insert(header::USER_AGENT, self.user_agent.clone());
+        if self.compress_requests {
+            headers.insert(header::CONTENT_ENCODING, header::HeaderValue::from_static("gzip"));
+        }
+        // 请求体只序列化 (压缩) 一次，所有端点复用
+        let body = Self::encode_body(&body, self.compress_requests)?;
+
+        // 透传请求 ID，便于与上游日志关联
+        if let Some(request_id) = crate::proxy::middleware::request_id::current_request_id() {
+            if let Ok(hv) = header::HeaderValue::from_str(&request_id) {
+                headers.insert("x-goog-request-id", hv);
+            }
+        }
 
         // 注入额外的 Headers (如 anthropic-beta)
         for (k, v) in extra_headers {
@@ -118,20 +236,23 @@ impl UpstreamClient {
             }
         }
 
-        let mut last_err: Option<String> = None;
+        let mut last_err: Option<ProxyError> = None;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in self.base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+            let has_next = idx + 1 < self.base_urls.len();
 
-            let response = self
+            let mut request = self
                 .http_client
                 .post(&url)
                 .headers(headers.clone())
-                .json(&body)
-                .send()
-                .await;
+                .body(body.clone());
+            // 按模型覆盖客户端默认超时 (整体 600s)
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+            let response = request.send().await;
 
             match response {
                 Ok(resp) => {
@@ -143,7 +264,7 @@ impl UpstreamClient {
                                 base_url,
                                 status,
                                 idx + 1,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len()
+                                self.base_urls.len()
                             );
                         } else {
                             tracing::debug!("✓ Upstream request succeeded | Endpoint: {} | Status: {}", base_url, status);
@@ -159,7 +280,10 @@ impl UpstreamClient {
                             base_url,
                             method
                         );
-                        last_err = Some(format!("Upstream {} returned {}", base_url, status));
+                        last_err = Some(ProxyError::ApiError {
+                            status: status.as_u16(),
+                            body: format!("Upstream {} returned {}", base_url, status),
+                        });
                         continue;
                     }
 
@@ -167,9 +291,8 @@ impl UpstreamClient {
                     return Ok(resp);
                 }
                 Err(e) => {
-                    let msg = format!("HTTP request failed at {}: {}", base_url, e);
-                    tracing::debug!("{}", msg);
-                    last_err = Some(msg);
+                    tracing::debug!("HTTP request failed at {}: {}", base_url, e);
+                    last_err = Some(ProxyError::from(e));
 
                     // 如果是最后一个端点，退出循环
                     if !has_next {
@@ -180,7 +303,103 @@ impl UpstreamClient {
             }
         }
 
-        Err(last_err.unwrap_or_else(|| "All endpoints failed".to_string()))
+        Err(last_err.unwrap_or_else(|| ProxyError::NetworkError("All endpoints failed".to_string())))
+    }
+
+    /// 调用 batchEmbedContents，一次请求获取多条文本的向量
+    pub async fn batch_embed_contents(&self, access_token: &str, body: Value) -> Result<Response, ProxyError> {
+        self.call_v1_internal("batchEmbedContents", access_token, body, None, None).await
+    }
+
+    /// 调用 countTokens 获取请求的精确 Token 数
+    /// 请求体与正式请求共用 OpenAI → Gemini 的 contents 转换逻辑
+    pub async fn count_tokens(
+        &self,
+        request: &crate::proxy::mappers::openai::OpenAIRequest,
+        access_token: &str,
+        project_id: &str,
+    ) -> Result<u32, ProxyError> {
+        let body = crate::proxy::mappers::openai::transform_openai_count_tokens_request(request, project_id);
+        let resp = self.call_v1_internal("countTokens", access_token, body, None, None).await?;
+        if !resp.status().is_success() {
+            return Err(ProxyError::from_response(resp).await);
+        }
+
+        let json: Value = resp.json().await.map_err(|e| ProxyError::ParseError(e.to_string()))?;
+        json.get("totalTokens")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32)
+            .ok_or_else(|| ProxyError::ParseError(format!("countTokens response missing totalTokens: {}", json)))
+    }
+
+    /// 用最小的 countTokens 请求探测账号凭据是否可用 (不消耗生成配额)
+    pub async fn validate_token(
+        &self,
+        token: &crate::proxy::token_manager::ProxyToken,
+    ) -> Result<(), ProxyError> {
+        self.validate_access_token(&token.access_token).await
+    }
+
+    /// 同 validate_token，直接使用 access_token (导入尚未入池的账号时使用)
+    pub async fn validate_access_token(&self, access_token: &str) -> Result<(), ProxyError> {
+        let body = V1InternalBody {
+            project: None,
+            request: GeminiRequest {
+                model: Some("models/gemini-2.5-flash".to_string()),
+                contents: vec![GeminiContent {
+                    role: "user".to_string(),
+                    parts: vec![GeminiPart {
+                        text: Some("ping".to_string()),
+                        ..Default::default()
+                    }],
+                }],
+                ..Default::default()
+            },
+        };
+        let body = serde_json::to_value(body).map_err(|e| ProxyError::ParseError(e.to_string()))?;
+        let resp = self
+            .call_v1_internal("countTokens", access_token, body, None, Some(Duration::from_secs(30)))
+            .await?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(ProxyError::from_response(resp).await)
+        }
+    }
+
+    /// 创建 CachedContent，将 system prompt 固定在服务端，返回缓存名 (cachedContents/xxx)
+    /// 缓存归属于 project_id，只能被同一账号的请求引用
+    pub async fn create_cache(
+        &self,
+        system_prompt: &str,
+        model: &str,
+        ttl_secs: u64,
+        access_token: &str,
+        project_id: &str,
+    ) -> Result<String, ProxyError> {
+        let body = V1InternalBody {
+            project: Some(project_id.to_string()),
+            request: GeminiRequest {
+                model: Some(format!("models/{}", model)),
+                system_instruction: Some(GeminiSystemInstruction::text(system_prompt)),
+                ttl: Some(format!("{}s", ttl_secs)),
+                ..Default::default()
+            },
+        };
+        let body = serde_json::to_value(body).map_err(|e| ProxyError::ParseError(e.to_string()))?;
+        let resp = self
+            .call_v1_internal("createCachedContent", access_token, body, None, None)
+            .await?;
+        if !resp.status().is_success() {
+            return Err(ProxyError::from_response(resp).await);
+        }
+
+        let json: Value = resp.json().await.map_err(|e| ProxyError::ParseError(e.to_string()))?;
+        json.get("name")
+            .or_else(|| json.get("response").and_then(|r| r.get("name")))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(|| ProxyError::ParseError(format!("createCachedContent response missing name: {}", json)))
     }
 
     /// 调用 v1internal API（带 429 重试,支持闭包）
@@ -206,7 +425,7 @@ impl UpstreamClient {
     /// 
     /// 获取远端模型列表，支持多端点自动 Fallback
     #[allow(dead_code)] // API ready for future model discovery feature
-    pub async fn fetch_available_models(&self, access_token: &str) -> Result<Value, String> {
+    pub async fn fetch_available_models(&self, access_token: &str) -> Result<Value, ProxyError> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::CONTENT_TYPE,
@@ -215,17 +434,14 @@ impl UpstreamClient {
         headers.insert(
             header::AUTHORIZATION,
             header::HeaderValue::from_str(&format!("Bearer {}", access_token))
-                .map_err(|e| e.to_string())?,
-        );
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
+                .map_err(|_| ProxyError::Unauthorized)?,
         );
+        headers.insert(header::USER_AGENT, self.user_agent.clone());
 
-        let mut last_err: Option<String> = None;
+        let mut last_err: Option<ProxyError> = None;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in self.base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, "fetchAvailableModels", None);
 
             let response = self
@@ -252,32 +468,31 @@ impl UpstreamClient {
                         let json: Value = resp
                             .json()
                             .await
-                            .map_err(|e| format!("Parse json failed: {}", e))?;
+                            .map_err(|e| ProxyError::ParseError(e.to_string()))?;
                         return Ok(json);
                     }
 
                     // 如果有下一个端点且当前错误可重试，则切换
-                    let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+                    let has_next = idx + 1 < self.base_urls.len();
                     if has_next && Self::should_try_next_endpoint(status) {
                         tracing::warn!(
                             "fetchAvailableModels returned {} at {}, trying next endpoint",
                             status,
                             base_url
                         );
-                        last_err = Some(format!("Upstream error: {}", status));
+                        last_err = Some(ProxyError::from_response(resp).await);
                         continue;
                     }
 
                     // 不可重试的错误或已是最后一个端点
-                    return Err(format!("Upstream error: {}", status));
+                    return Err(ProxyError::from_response(resp).await);
                 }
                 Err(e) => {
-                    let msg = format!("Request failed at {}: {}", base_url, e);
-                    tracing::debug!("{}", msg);
-                    last_err = Some(msg);
+                    tracing::debug!("Request failed at {}: {}", base_url, e);
+                    last_err = Some(ProxyError::from(e));
 
                     // 如果是最后一个端点，退出循环
-                    if idx + 1 >= V1_INTERNAL_BASE_URL_FALLBACKS.len() {
+                    if idx + 1 >= self.base_urls.len() {
                         break;
                     }
                     continue;
@@ -285,13 +500,14 @@ impl UpstreamClient {
             }
         }
 
-        Err(last_err.unwrap_or_else(|| "All endpoints failed".to_string()))
+        Err(last_err.unwrap_or_else(|| ProxyError::NetworkError("All endpoints failed".to_string())))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proxy::tests::support::spawn_mock_upstream;
 
     #[test]
     fn test_build_url() {
@@ -310,4 +526,258 @@ mod tests {
         );
     }
 
+    fn count_request() -> crate::proxy::mappers::openai::OpenAIRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "How many tokens is this?"}]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_count_tokens_returns_total() {
+        use axum::{http::HeaderMap, routing::post, Json};
+
+        let captured = std::sync::Arc::new(tokio::sync::Mutex::new(None::<Value>));
+        let captured_clone = captured.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:countTokens",
+            post(move |headers: HeaderMap, Json(body): Json<Value>| {
+                let captured = captured_clone.clone();
+                async move {
+                    assert_eq!(headers["authorization"], "Bearer test-token");
+                    *captured.lock().await = Some(body);
+                    Json(serde_json::json!({"totalTokens": 1234}))
+                }
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None).with_base_urls(vec![base]);
+
+        let total = client
+            .count_tokens(&count_request(), "test-token", "test-project")
+            .await
+            .unwrap();
+        assert_eq!(total, 1234);
+
+        let body = captured.lock().await.clone().unwrap();
+        assert_eq!(body["request"]["model"], "models/gemini-2.5-flash");
+        assert_eq!(
+            body["request"]["contents"][0]["parts"][0]["text"],
+            "How many tokens is this?"
+        );
+    }
+
+    fn gunzip(bytes: &[u8]) -> Vec<u8> {
+        use std::io::Read;
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(bytes).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_encode_body_gzip_round_trip() {
+        let body = serde_json::json!({"contents": [{"parts": [{"text": "x".repeat(4096)}]}]});
+        let compressed = UpstreamClient::encode_body(&body, true).unwrap();
+        assert!(compressed.len() < 4096);
+        assert_eq!(serde_json::from_slice::<Value>(&gunzip(&compressed)).unwrap(), body);
+
+        let plain = UpstreamClient::encode_body(&body, false).unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&plain).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_request_sent_with_gzip_encoding() {
+        use axum::{body::Bytes, http::HeaderMap, routing::post};
+
+        let router = axum::Router::new().route(
+            "/v1internal:generateContent",
+            post(|headers: HeaderMap, body: Bytes| async move {
+                assert_eq!(headers["content-encoding"], "gzip");
+                assert_eq!(headers["content-type"], "application/json");
+                let json: Value = serde_json::from_slice(&gunzip(&body)).unwrap();
+                assert_eq!(json["request"]["model"], "gemini-2.5-flash");
+                "{}"
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None)
+            .with_base_urls(vec![base])
+            .with_compression(true);
+
+        let resp = client
+            .call_v1_internal(
+                "generateContent",
+                "test-token",
+                serde_json::json!({"request": {"model": "gemini-2.5-flash"}}),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_per_request_timeout_applied() {
+        use axum::routing::post;
+
+        let router = axum::Router::new().route(
+            "/v1internal:generateContent",
+            post(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "{}"
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None).with_base_urls(vec![base]);
+        let body = serde_json::json!({});
+
+        let err = client
+            .call_v1_internal("generateContent", "t", body.clone(), None, Some(Duration::from_millis(100)))
+            .await
+            .unwrap_err();
+        assert_eq!(err, ProxyError::Timeout);
+
+        let ok = client
+            .call_v1_internal("generateContent", "t", body, None, Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+        assert!(ok.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_create_cache_returns_name() {
+        use axum::{routing::post, Json};
+
+        let captured = std::sync::Arc::new(tokio::sync::Mutex::new(None::<Value>));
+        let captured_clone = captured.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:createCachedContent",
+            post(move |Json(body): Json<Value>| {
+                let captured = captured_clone.clone();
+                async move {
+                    *captured.lock().await = Some(body);
+                    Json(serde_json::json!({"name": "cachedContents/abc123"}))
+                }
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None).with_base_urls(vec![base]);
+
+        let name = client
+            .create_cache("You are a helpful assistant.", "gemini-2.5-flash", 600, "t", "test-project")
+            .await
+            .unwrap();
+        assert_eq!(name, "cachedContents/abc123");
+
+        let body = captured.lock().await.clone().unwrap();
+        assert_eq!(body["project"], "test-project");
+        assert_eq!(body["request"]["model"], "models/gemini-2.5-flash");
+        assert_eq!(body["request"]["ttl"], "600s");
+        assert_eq!(
+            body["request"]["systemInstruction"]["parts"][0]["text"],
+            "You are a helpful assistant."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_user_agent_sent_upstream() {
+        use axum::{http::HeaderMap, routing::post};
+
+        let captured = std::sync::Arc::new(tokio::sync::Mutex::new(None::<String>));
+        let captured_clone = captured.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:generateContent",
+            post(move |headers: HeaderMap| {
+                let captured = captured_clone.clone();
+                async move {
+                    *captured.lock().await = headers
+                        .get("user-agent")
+                        .and_then(|v| v.to_str().ok())
+                        .map(|s| s.to_string());
+                    "{}"
+                }
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::with_options(
+            None,
+            20,
+            Duration::from_secs(90),
+            "antigravity/1.12.0 linux/amd64",
+        )
+        .with_base_urls(vec![base]);
+
+        client
+            .call_v1_internal("generateContent", "t", serde_json::json!({}), None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            captured.lock().await.as_deref(),
+            Some("antigravity/1.12.0 linux/amd64")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_headers_reach_upstream() {
+        use axum::{http::HeaderMap, routing::post};
+
+        let captured = std::sync::Arc::new(tokio::sync::Mutex::new(None::<HeaderMap>));
+        let captured_clone = captured.clone();
+        let router = axum::Router::new().route(
+            "/v1internal:streamGenerateContent",
+            post(move |headers: HeaderMap| {
+                let captured = captured_clone.clone();
+                async move {
+                    *captured.lock().await = Some(headers);
+                    "data: {}\n\n"
+                }
+            }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None).with_base_urls(vec![base]);
+
+        let extra = std::collections::HashMap::from([(
+            "x-custom-b3-traceid".to_string(),
+            "463ac35c9f6413ad".to_string(),
+        )]);
+        client
+            .call_v1_internal_with_headers(
+                "streamGenerateContent",
+                "t",
+                serde_json::json!({}),
+                Some("alt=sse"),
+                extra,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let headers = captured.lock().await.clone().unwrap();
+        assert_eq!(headers["x-custom-b3-traceid"], "463ac35c9f6413ad");
+        assert_eq!(headers["authorization"], "Bearer t");
+    }
+
+    #[tokio::test]
+    async fn test_count_tokens_surfaces_upstream_error() {
+        use axum::{http::StatusCode, routing::post};
+
+        let router = axum::Router::new().route(
+            "/v1internal:countTokens",
+            post(|| async { (StatusCode::BAD_REQUEST, "invalid model") }),
+        );
+        let base = spawn_mock_upstream(router).await;
+        let client = UpstreamClient::new(None).with_base_urls(vec![base]);
+
+        let err = client
+            .count_tokens(&count_request(), "test-token", "test-project")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ProxyError::ApiError { status: 400, body: "invalid model".to_string() }
+        );
+    }
+
 }
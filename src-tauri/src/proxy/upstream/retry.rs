@@ -66,10 +66,22 @@ pub fn parse_retry_delay(error_text: &str) -> Option<u64> {
     None
 }
 
+/// 解析 Retry-After 响应头 (秒数)，返回毫秒；HTTP 日期格式不支持，返回 None
+pub fn parse_retry_after_header(value: &str) -> Option<u64> {
+    value.trim().parse::<u64>().ok().map(|secs| secs.saturating_mul(1000))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_retry_after_header() {
+        assert_eq!(parse_retry_after_header("2"), Some(2000));
+        assert_eq!(parse_retry_after_header(" 0 "), Some(0));
+        assert_eq!(parse_retry_after_header("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
     #[test]
     fn test_parse_duration_ms() {
         assert_eq!(parse_duration_ms("1.5s"), Some(1500));
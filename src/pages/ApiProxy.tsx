@@ -166,6 +166,8 @@ export default function ApiProxy() {
     // API Key editing states
     const [isEditingApiKey, setIsEditingApiKey] = useState(false);
     const [tempApiKey, setTempApiKey] = useState('');
+    // 轮换后返回的 API Key 明文，仅在本次会话中展示 (配置中只保存哈希)
+    const [revealedApiKey, setRevealedApiKey] = useState<string | null>(null);
 
     // Admin Password editing states
     const [isEditingAdminPassword, setIsEditingAdminPassword] = useState(false);
@@ -685,12 +687,19 @@ export default function ApiProxy() {
         setIsRegenerateKeyConfirmOpen(true);
     };
 
+    // 轮换 API Key：后端只保存哈希，返回的明文只展示这一次
+    const rotateApiKey = async (newKey?: string) => {
+        const key = await invoke<string>('rotate_api_key', { newKey });
+        setRevealedApiKey(key);
+        await loadConfig();
+        return key;
+    };
+
     const executeGenerateApiKey = async () => {
         setIsRegenerateKeyConfirmOpen(false);
         try {
-            const newKey = await invoke<string>('generate_api_key');
-            updateProxyConfig({ api_key: newKey });
-            showToast(t('common.success'), 'success');
+            await rotateApiKey();
+            showToast(t('proxy.config.api_key_show_once', { defaultValue: 'New API key generated. Copy it now, it will not be shown again.' }), 'success');
         } catch (error: any) {
             console.error('生成 API Key 失败:', error);
             showToast(t('proxy.dialog.operate_failed', { error: error.toString() }), 'error');
@@ -713,18 +722,22 @@ export default function ApiProxy() {
     };
 
     const handleEditApiKey = () => {
-        setTempApiKey(appConfig?.proxy.api_key || '');
+        setTempApiKey('');
         setIsEditingApiKey(true);
     };
 
-    const handleSaveApiKey = () => {
+    const handleSaveApiKey = async () => {
         if (!validateApiKey(tempApiKey)) {
             showToast(t('proxy.config.api_key_invalid'), 'error');
             return;
         }
-        updateProxyConfig({ api_key: tempApiKey });
-        setIsEditingApiKey(false);
-        showToast(t('proxy.config.api_key_updated'), 'success');
+        try {
+            await rotateApiKey(tempApiKey);
+            setIsEditingApiKey(false);
+            showToast(t('proxy.config.api_key_updated'), 'success');
+        } catch (error: any) {
+            showToast(t('proxy.dialog.operate_failed', { error: error.toString() }), 'error');
+        }
     };
 
     const handleCancelEditApiKey = () => {
@@ -759,7 +772,7 @@ export default function ApiProxy() {
         const port = status.running ? status.port : (appConfig?.proxy.port || 8045);
         // 推荐使用 127.0.0.1 以避免部分环境 IPv6 解析延迟问题
         const baseUrl = `http://127.0.0.1:${port}/v1`;
-        const apiKey = appConfig?.proxy.api_key || 'YOUR_API_KEY';
+        const apiKey = revealedApiKey || 'YOUR_API_KEY';
 
         // 1. Anthropic Protocol
         if (selectedProtocol === 'anthropic') {
@@ -1126,9 +1139,12 @@ print(response.text)`;
                                 <div className="flex gap-2">
                                     <input
                                         type="text"
-                                        value={isEditingApiKey ? tempApiKey : (appConfig.proxy.api_key)}
+                                        value={isEditingApiKey ? tempApiKey : (revealedApiKey || '')}
                                         onChange={(e) => isEditingApiKey && setTempApiKey(e.target.value)}
                                         readOnly={!isEditingApiKey}
+                                        placeholder={appConfig.proxy.api_key_hash
+                                            ? t('proxy.config.api_key_hidden', { defaultValue: '•••••••• (stored as a hash, regenerate to get a new key)' })
+                                            : t('proxy.config.api_key_unset', { defaultValue: 'No API key set' })}
                                         className={`flex-1 px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg text-xs font-mono ${isEditingApiKey
                                             ? 'bg-white dark:bg-base-200 text-gray-900 dark:text-base-content'
                                             : 'bg-gray-50 dark:bg-base-300 text-gray-600 dark:text-gray-400'
@@ -1168,7 +1184,8 @@ print(response.text)`;
                                                 <RefreshCw size={14} />
                                             </button>
                                             <button
-                                                onClick={() => copyToClipboardHandler(appConfig.proxy.api_key, 'api_key')}
+                                                onClick={() => revealedApiKey && copyToClipboardHandler(revealedApiKey, 'api_key')}
+                                                disabled={!revealedApiKey}
                                                 className="px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg bg-white dark:bg-base-200 hover:bg-gray-50 dark:hover:bg-base-300 transition-colors"
                                                 title={t('proxy.config.btn_copy')}
                                             >
@@ -1182,7 +1199,9 @@ print(response.text)`;
                                     )}
                                 </div>
                                 <p className="mt-0.5 text-[10px] text-amber-600 dark:text-amber-500">
-                                    {t('proxy.config.warning_key')}
+                                    {revealedApiKey
+                                        ? t('proxy.config.api_key_show_once', { defaultValue: 'New API key generated. Copy it now, it will not be shown again.' })
+                                        : t('proxy.config.warning_key')}
                                 </p>
                             </div>
 
@@ -1237,7 +1256,7 @@ print(response.text)`;
                                                 <Edit2 size={14} />
                                             </button>
                                             <button
-                                                onClick={() => copyToClipboardHandler(appConfig.proxy.admin_password || appConfig.proxy.api_key, 'admin_password')}
+                                                onClick={() => copyToClipboardHandler(appConfig.proxy.admin_password || revealedApiKey || '', 'admin_password')}
                                                 className="px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg bg-white dark:bg-base-200 hover:bg-gray-50 dark:hover:bg-base-300 transition-colors"
                                                 title={t('proxy.config.btn_copy')}
                                             >
@@ -1274,7 +1293,7 @@ print(response.text)`;
                             {/* CLI 同步卡片 - 支持桌面端与 Web 端 */}
                             <CliSyncCard
                                 proxyUrl={status.running ? status.base_url : `http://127.0.0.1:${appConfig.proxy.port || 8045}`}
-                                apiKey={appConfig.proxy.api_key}
+                                apiKey={revealedApiKey || ''}
                             />
 
                             {/* z.ai (GLM) Dispatcher */}
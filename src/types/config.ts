@@ -9,7 +9,12 @@ export interface ProxyConfig {
     auth_mode?: 'off' | 'strict' | 'all_except_health' | 'auto';
     port: number;
     api_key: string;
+    /** API Key 的 Argon2 哈希；明文只在轮换时返回一次 */
+    api_key_hash?: string;
     admin_password?: string;
+    admin_api_key?: string;
+    /** /admin 运行时管理接口密钥的 Argon2 哈希 */
+    admin_api_key_hash?: string;
     auto_start: boolean;
     custom_mapping?: Record<string, string>;
     request_timeout: number;
@@ -72,6 +77,9 @@ export interface ExperimentalConfig {
     context_compression_threshold_l1?: number;
     context_compression_threshold_l2?: number;
     context_compression_threshold_l3?: number;
+    min_chunk_bytes?: number;
+    enable_prompt_cache?: boolean;
+    prompt_cache_ttl_secs?: number;
 }
 
 export interface CircuitBreakerConfig {
@@ -36,6 +36,7 @@ const COMMAND_MAPPING: Record<string, { url: string; method: 'GET' | 'POST' | 'D
   'stop_proxy_service': { url: '/api/proxy/stop', method: 'POST' },
   'update_model_mapping': { url: '/api/proxy/mapping', method: 'POST' },
   'generate_api_key': { url: '/api/proxy/api-key/generate', method: 'POST' },
+  'rotate_api_key': { url: '/api/proxy/api-key/rotate', method: 'POST' },
   'clear_proxy_session_bindings': { url: '/api/proxy/session-bindings/clear', method: 'POST' },
   'clear_proxy_rate_limit': { url: '/api/proxy/rate-limits/:accountId', method: 'DELETE' },
   'clear_all_proxy_rate_limits': { url: '/api/proxy/rate-limits', method: 'DELETE' },
@@ -177,7 +178,8 @@ export async function request<T>(cmd: string, args?: any): Promise<T> {
         }
       }
       const errorData = await response.json().catch(() => ({}));
-      throw errorData.error || `HTTP Error ${response.status}`;
+      // 错误体为 OpenAI 格式 {"error": {"message", "type", ...}}
+      throw errorData.error?.message || errorData.error || `HTTP Error ${response.status}`;
     }
 
     // 如果是 204 No Content，直接返回 null